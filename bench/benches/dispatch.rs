@@ -0,0 +1,74 @@
+//! Benchmarks a command from the raw request to the encoded reply, the same steps the
+//! connection loop takes: decode, dispatch, middlewares, parse, execute and encode.
+//!
+//! Run with `cargo bench` from this directory. Criterion keeps the previous results in
+//! `target/criterion` and reports the change against them.
+
+use std::{
+    collections::VecDeque,
+    hint::black_box,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use redis_starter_rust::{
+    commands::{command::UnparsedCommandType, context::ConnectionContext, middleware},
+    db::data_store::{init_db, DbConfig},
+    parser::messages::RedisMessageType,
+};
+
+/// Amount of PINGs sent in a single pipelined request.
+const PIPELINE_DEPTH: usize = 16;
+
+fn request(args: &[&str]) -> String {
+    return RedisMessageType::bulk_string_array(args.to_vec()).encode();
+}
+
+/// Handles every message of the input and returns the encoded replies.
+fn handle(ctx: &ConnectionContext, mut input: &str) -> String {
+    let mut replies = String::new();
+    while !input.is_empty() {
+        let (message, length) = RedisMessageType::decode(input).expect("valid benchmark input");
+        input = &input[length..];
+
+        let args = match message {
+            RedisMessageType::Array(args) => args,
+            _ => VecDeque::new(),
+        };
+        let reply = UnparsedCommandType::new(args).and_then(|command| {
+            middleware::before_execute(ctx, &command)?;
+            return command.parse()?.execute(ctx);
+        });
+        replies.push_str(&reply.unwrap_or_else(|err| err).encode());
+    }
+    return replies;
+}
+
+fn bench_commands(c: &mut Criterion) {
+    init_db(DbConfig::new(PathBuf::new(), String::new(), None, 6379));
+    let ctx = ConnectionContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1));
+    handle(&ctx, &request(&["SET", "bench:hit", "value"]));
+
+    let cases = [
+        ("get_hit", request(&["GET", "bench:hit"])),
+        ("get_miss", request(&["GET", "bench:miss"])),
+        ("set", request(&["SET", "bench:set", "value"])),
+        (
+            "set_with_options",
+            request(&["SET", "bench:set", "value", "PX", "100000", "GET"]),
+        ),
+    ];
+    for (name, input) in cases {
+        c.bench_function(name, |b| b.iter(|| handle(&ctx, black_box(&input))));
+    }
+
+    let pipeline = request(&["PING"]).repeat(PIPELINE_DEPTH);
+    let mut group = c.benchmark_group("pipeline");
+    group.throughput(Throughput::Elements(PIPELINE_DEPTH as u64));
+    group.bench_function("ping", |b| b.iter(|| handle(&ctx, black_box(&pipeline))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_commands);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use redis_starter_rust::parser::db_file::RdbFile;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(rdb_file) = RdbFile::decode(data.to_vec()) {
+        rdb_file.get_database().to_dashmap();
+    }
+});
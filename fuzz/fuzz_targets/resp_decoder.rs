@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use redis_starter_rust::parser::messages::RedisMessageType;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    // decode every message of the input like the connection loop does
+    let mut input = input;
+    while let Ok((message, length)) = RedisMessageType::decode(input) {
+        assert!(length > 0 && length <= input.len());
+        assert_eq!(
+            message,
+            RedisMessageType::decode(message.encode()).unwrap().0
+        );
+        input = &input[length..];
+    }
+});
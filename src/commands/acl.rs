@@ -0,0 +1,240 @@
+use once_cell::sync::OnceCell;
+
+use crate::{
+    commands::{
+        command::UnparsedCommandType,
+        context::{ConnectionContext, ConnectionKind},
+        middleware::Middleware,
+        reply::{self, ErrorCode},
+    },
+    parser::messages::RedisMessageType,
+};
+
+static DEFAULT_USER_RULES: OnceCell<AclRules> = OnceCell::new();
+
+/// Categories commands are tagged with, see https://redis.io/docs/latest/operate/oss_and_stack/management/security/acl/#command-categories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclCategory {
+    Keyspace,
+    Read,
+    Write,
+    String,
+    Admin,
+    Fast,
+    Slow,
+    Dangerous,
+    Connection,
+}
+
+impl AclCategory {
+    const ALL: [AclCategory; 9] = [
+        Self::Keyspace,
+        Self::Read,
+        Self::Write,
+        Self::String,
+        Self::Admin,
+        Self::Fast,
+        Self::Slow,
+        Self::Dangerous,
+        Self::Connection,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL
+            .into_iter()
+            .find(|category| category.name().eq_ignore_ascii_case(name));
+    }
+
+    pub const fn name(&self) -> &'static str {
+        return match self {
+            Self::Keyspace => "keyspace",
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::String => "string",
+            Self::Admin => "admin",
+            Self::Fast => "fast",
+            Self::Slow => "slow",
+            Self::Dangerous => "dangerous",
+            Self::Connection => "connection",
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AclRule {
+    allow: bool,
+    /// `None` matches every command (`@all`).
+    category: Option<AclCategory>,
+}
+
+/// Category grants and denials like `+@read -@dangerous`.
+///
+/// Rules are applied from left to right and a later rule overrides an earlier one, so
+/// `+@all -@dangerous` allows everything except dangerous commands. Without any matching
+/// rule a command is denied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclRules {
+    rules: Vec<AclRule>,
+}
+
+impl AclRules {
+    /// The rules of the default user, which may run every command.
+    pub fn allow_all() -> Self {
+        return Self {
+            rules: vec![AclRule {
+                allow: true,
+                category: None,
+            }],
+        };
+    }
+
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+
+        for raw in input.split_whitespace() {
+            let rule = match raw.to_ascii_lowercase().as_str() {
+                "allcommands" => AclRule {
+                    allow: true,
+                    category: None,
+                },
+                "nocommands" => AclRule {
+                    allow: false,
+                    category: None,
+                },
+                lower => {
+                    let (allow, category) = match lower.split_at_checked(2) {
+                        Some(("+@", category)) => (true, category),
+                        Some(("-@", category)) => (false, category),
+                        _ => return Err(format!("Syntax error in ACL rule '{}'", raw)),
+                    };
+
+                    let category = match category {
+                        "all" => None,
+                        name => Some(
+                            AclCategory::from_name(name)
+                                .ok_or(format!("Unknown command category '{}'", name))?,
+                        ),
+                    };
+                    AclRule { allow, category }
+                }
+            };
+            rules.push(rule);
+        }
+
+        return Ok(Self { rules });
+    }
+
+    /// Whether a command tagged with the given categories may be run.
+    pub fn allows(&self, categories: &[AclCategory]) -> bool {
+        let mut allowed = false;
+        for rule in self.rules.iter() {
+            let matches = match rule.category {
+                None => true,
+                Some(category) => categories.contains(&category),
+            };
+
+            if matches {
+                allowed = rule.allow;
+            }
+        }
+        return allowed;
+    }
+}
+
+/// Sets the rules of the default user. Must be called before the first client connects.
+pub fn init_default_user_rules(rules: AclRules) {
+    DEFAULT_USER_RULES
+        .set(rules)
+        .expect("ACL rules of the default user are already set. Should never happen!");
+}
+
+fn get_default_user_rules() -> &'static AclRules {
+    return DEFAULT_USER_RULES.get_or_init(AclRules::allow_all);
+}
+
+/// Rejects commands the default user has no permissions for. There is no AUTH yet, so every
+/// client is the default user.
+pub struct AclCheck;
+
+impl Middleware for AclCheck {
+    fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType> {
+        // like redis, the master is not bound to any user
+        if ctx.client().kind() == ConnectionKind::MasterLink {
+            return Ok(());
+        }
+
+        if !get_default_user_rules().allows(command.acl_categories()) {
+            return Err(reply::error(
+                ErrorCode::NoPerm,
+                format!(
+                    "User default has no permissions to run the '{}' command",
+                    command.name()
+                ),
+            ));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::acl::{AclCategory, AclRules};
+
+    const GET: [AclCategory; 3] = [AclCategory::Read, AclCategory::String, AclCategory::Fast];
+    const KEYS: [AclCategory; 4] = [
+        AclCategory::Keyspace,
+        AclCategory::Read,
+        AclCategory::Slow,
+        AclCategory::Dangerous,
+    ];
+    const SET: [AclCategory; 3] = [AclCategory::Write, AclCategory::String, AclCategory::Slow];
+
+    #[test]
+    fn test_allow_all() {
+        let rules = AclRules::allow_all();
+
+        assert!(rules.allows(&GET));
+        assert!(rules.allows(&KEYS));
+        assert!(rules.allows(&[]));
+    }
+
+    #[test]
+    fn test_no_rules_deny_everything() {
+        let rules = AclRules::parse("").unwrap();
+
+        assert!(!rules.allows(&GET));
+        assert!(!rules.allows(&SET));
+    }
+
+    #[test]
+    fn test_grant_and_deny_categories() {
+        let rules = AclRules::parse("+@read -@dangerous").unwrap();
+
+        assert!(rules.allows(&GET));
+        assert!(!rules.allows(&KEYS));
+        assert!(!rules.allows(&SET));
+    }
+
+    #[test]
+    fn test_later_rules_override() {
+        let all_but_dangerous = AclRules::parse("+@all -@dangerous").unwrap();
+        let dangerous_again = AclRules::parse("+@all -@dangerous +@keyspace").unwrap();
+        let nothing = AclRules::parse("allcommands NOCOMMANDS").unwrap();
+
+        assert!(all_but_dangerous.allows(&SET));
+        assert!(!all_but_dangerous.allows(&KEYS));
+        assert!(dangerous_again.allows(&KEYS));
+        assert!(!nothing.allows(&GET));
+    }
+
+    #[test]
+    fn test_parse_invalid_rules() {
+        assert!(AclRules::parse("+@unknown").is_err());
+        assert!(AclRules::parse("+get").is_err());
+        assert!(AclRules::parse("@read").is_err());
+    }
+}
@@ -0,0 +1,564 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        context::{get_clients, Client, ConnectionContext},
+        reply::{self, ErrorCode},
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    parser::messages::RedisMessageType,
+};
+
+enum LibInfo {
+    Name(String),
+    Version(String),
+}
+
+/// Filters of CLIENT LIST and CLIENT KILL, a client has to match all of them.
+#[derive(Default)]
+struct ClientFilter {
+    ids: Option<Vec<u64>>,
+    addr: Option<String>,
+    /// A client type like `normal`, there are no pubsub clients so `pubsub` matches none.
+    client_type: Option<String>,
+    /// Only clients connected for longer, in seconds.
+    max_age: Option<u64>,
+}
+
+impl ClientFilter {
+    fn matches(&self, client: &Client) -> bool {
+        return self
+            .ids
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&client.id()))
+            && self
+                .addr
+                .as_ref()
+                .is_none_or(|addr| *addr == client.addr().to_string())
+            && self
+                .client_type
+                .as_ref()
+                .is_none_or(|client_type| client_type == client.kind().type_name())
+            && self
+                .max_age
+                .is_none_or(|max_age| client.age().as_secs() > max_age);
+    }
+}
+
+fn parse_client_type(value: &str) -> Result<String, RedisMessageType> {
+    return match value.to_ascii_lowercase().as_str() {
+        "normal" | "master" | "replica" | "pubsub" => Ok(value.to_ascii_lowercase()),
+        "slave" => Ok("replica".to_string()),
+        _ => Err(reply::error(
+            ErrorCode::Err,
+            format!("Unknown client type '{}'", value),
+        )),
+    };
+}
+
+struct KillFilter {
+    filter: ClientFilter,
+    skip_me: bool,
+    /// `CLIENT KILL <addr>`, which replies with OK or an error instead of a count.
+    legacy: bool,
+}
+
+enum Action {
+    Id,
+    Info,
+    List(ClientFilter),
+    Kill(KillFilter),
+    GetName,
+    SetName(String),
+    SetInfo(LibInfo),
+    Help,
+}
+
+pub struct ClientCommand {
+    action: Action,
+}
+
+impl ClientCommand {
+    fn new(action: Action) -> Self {
+        return Self { action };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for ClientCommand {
+    fn command_name() -> &'static str {
+        return "client";
+    }
+}
+impl ArgErrorMessageGenerator<ClientCommand> for ClientCommand {}
+
+/// Names and library infos are shown space separated in CLIENT LIST, so only printable
+/// chars without spaces are allowed.
+fn is_valid_info(value: &str) -> bool {
+    return value.chars().all(|c| ('!'..='~').contains(&c));
+}
+
+fn parse_set_info(mut args: VecDeque<RedisMessageType>) -> Result<Action, RedisMessageType> {
+    let (attribute, value) = match (args.pop_front(), args.pop_front(), args.is_empty()) {
+        (Some(attribute), Some(value), true) => {
+            (attribute.bulk_string_value()?, value.bulk_string_value()?)
+        }
+        _ => return Err(ClientCommand::sub_arg_count_error("setinfo")),
+    };
+
+    let attribute = attribute.to_ascii_lowercase();
+    if !is_valid_info(&value) {
+        return Err(reply::error(
+            ErrorCode::Err,
+            format!(
+                "{} cannot contain spaces, newlines or special characters.",
+                attribute
+            ),
+        ));
+    }
+
+    let info = match attribute.as_str() {
+        "lib-name" => LibInfo::Name(value),
+        "lib-ver" => LibInfo::Version(value),
+        _ => {
+            return Err(reply::error(
+                ErrorCode::Err,
+                format!("Unrecognized option '{}'", attribute),
+            ))
+        }
+    };
+
+    return Ok(Action::SetInfo(info));
+}
+
+fn parse_list(mut args: VecDeque<RedisMessageType>) -> Result<Action, RedisMessageType> {
+    let mut filter = ClientFilter::default();
+    let option = match args.pop_front() {
+        Some(option) => option.bulk_string_value()?,
+        None => return Ok(Action::List(filter)),
+    };
+
+    match (option.to_ascii_uppercase().as_str(), args.len()) {
+        ("TYPE", 1) => {
+            let value = args.pop_front().unwrap().bulk_string_value()?;
+            filter.client_type = Some(parse_client_type(&value)?);
+        }
+        ("ID", 1..) => {
+            let mut ids = Vec::with_capacity(args.len());
+            for id in args {
+                match id.bulk_string_value()?.parse::<u64>() {
+                    Ok(id) if id > 0 => ids.push(id),
+                    _ => return Err(reply::error(ErrorCode::Err, "Invalid client ID")),
+                }
+            }
+            filter.ids = Some(ids);
+        }
+        _ => return Err(reply::syntax_error()),
+    }
+
+    return Ok(Action::List(filter));
+}
+
+fn parse_kill(mut args: VecDeque<RedisMessageType>) -> Result<Action, RedisMessageType> {
+    if args.is_empty() {
+        return Err(ClientCommand::sub_arg_count_error("kill"));
+    }
+
+    // the old form only names the address and never skips the calling client
+    if args.len() == 1 {
+        let addr = args.pop_front().unwrap().bulk_string_value()?;
+        let filter = ClientFilter {
+            addr: Some(addr),
+            ..Default::default()
+        };
+        return Ok(Action::Kill(KillFilter {
+            filter,
+            skip_me: false,
+            legacy: true,
+        }));
+    }
+
+    let mut kill = KillFilter {
+        filter: ClientFilter::default(),
+        skip_me: true,
+        legacy: false,
+    };
+    while let Some(option) = args.pop_front() {
+        let option = option.bulk_string_value()?;
+        let value = args
+            .pop_front()
+            .ok_or(reply::syntax_error())?
+            .bulk_string_value()?;
+
+        match option.to_ascii_uppercase().as_str() {
+            "ID" => match value.parse::<u64>() {
+                Ok(id) if id > 0 => kill.filter.ids = Some(vec![id]),
+                _ => {
+                    return Err(reply::error(
+                        ErrorCode::Err,
+                        "client-id should be greater than 0",
+                    ))
+                }
+            },
+            "ADDR" => kill.filter.addr = Some(value),
+            "TYPE" => kill.filter.client_type = Some(parse_client_type(&value)?),
+            "MAXAGE" => match value.parse::<i64>() {
+                Ok(max_age) if max_age >= 0 => kill.filter.max_age = Some(max_age as u64),
+                _ => return Err(reply::not_an_integer()),
+            },
+            "SKIPME" => match value.to_ascii_lowercase().as_str() {
+                "yes" => kill.skip_me = true,
+                "no" => kill.skip_me = false,
+                _ => return Err(reply::syntax_error()),
+            },
+            _ => return Err(reply::syntax_error()),
+        }
+    }
+
+    return Ok(Action::Kill(kill));
+}
+
+/// Kills the matching clients and returns how many there were.
+fn kill_clients(ctx: &ConnectionContext, kill: &KillFilter) -> usize {
+    let own_id = ctx.client().id();
+    let mut killed = 0;
+    for client in get_clients() {
+        if !kill.filter.matches(&client) || (kill.skip_me && client.id() == own_id) {
+            continue;
+        }
+        client.kill(client.id() == own_id);
+        killed += 1;
+    }
+    return killed;
+}
+
+impl Parse for ClientCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let sub_command = args
+            .pop_front()
+            .ok_or(Self::arg_count_error())?
+            .bulk_string_value()?;
+
+        let action = match sub_command.to_ascii_uppercase().as_str() {
+            "SETINFO" => return Ok(Self::new(parse_set_info(args)?)),
+            "LIST" => return Ok(Self::new(parse_list(args)?)),
+            "KILL" => return Ok(Self::new(parse_kill(args)?)),
+            "SETNAME" => match (args.pop_front(), args.is_empty()) {
+                (Some(name), true) => {
+                    let name = name.bulk_string_value()?;
+                    if !is_valid_info(&name) {
+                        return Err(reply::error(
+                            ErrorCode::Err,
+                            "Client names cannot contain spaces, newlines or special characters.",
+                        ));
+                    }
+                    Action::SetName(name)
+                }
+                _ => return Err(Self::sub_arg_count_error(&sub_command)),
+            },
+            "ID" => Action::Id,
+            "INFO" => Action::Info,
+            "GETNAME" => Action::GetName,
+            "HELP" => Action::Help,
+            _ => return Err(Self::unknown_subcommand_error(&sub_command)),
+        };
+
+        // the other subcommands take no arguments
+        if !args.is_empty() {
+            return Err(Self::sub_arg_count_error(&sub_command));
+        }
+
+        return Ok(Self::new(action));
+    }
+}
+
+impl Execute for ClientCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        return Err(reply::error(
+            ErrorCode::Err,
+            "CLIENT can only be used on a client connection",
+        ));
+    }
+
+    fn execute_with_context(
+        self,
+        ctx: &ConnectionContext,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        let client = ctx.client();
+
+        let response = match self.action {
+            Action::Id => reply::integer(client.id() as i64),
+            Action::Info => reply::bulk(format!("{}\n", client.describe())),
+            Action::List(filter) => reply::bulk(
+                get_clients()
+                    .iter()
+                    .filter(|client| filter.matches(client))
+                    .map(|client| format!("{}\n", client.describe()))
+                    .collect::<String>(),
+            ),
+            Action::Kill(kill) => match (kill_clients(ctx, &kill), kill.legacy) {
+                (0, true) => return Err(reply::error(ErrorCode::Err, "No such client")),
+                (_, true) => reply::ok(),
+                (killed, false) => reply::integer(killed as i64),
+            },
+            Action::GetName => match client.state().name {
+                Some(name) => reply::bulk(name),
+                None => reply::null(),
+            },
+            Action::SetName(name) => {
+                // an empty name removes the name
+                client.update_state(|state| state.name = Some(name).filter(|n| !n.is_empty()));
+                reply::ok()
+            }
+            Action::SetInfo(info) => {
+                client.update_state(|state| match info {
+                    LibInfo::Name(name) => state.lib_name = Some(name),
+                    LibInfo::Version(version) => state.lib_ver = Some(version),
+                });
+                reply::ok()
+            }
+            Action::Help => reply::bulk_array(vec![
+                "CLIENT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                "GETNAME",
+                "    Return the name of the current connection.",
+                "ID",
+                "    Return the ID of the current connection.",
+                "INFO",
+                "    Return information about the current client connection.",
+                "KILL <ip:port>",
+                "    Kill connection made from <ip:port>.",
+                "KILL <option> <value> [<option> <value> [...]]",
+                "    Kill connections. Options are:",
+                "    * ADDR <ip:port>",
+                "      Kill connections made from the specified address",
+                "    * TYPE (NORMAL|MASTER|REPLICA|PUBSUB)",
+                "      Kill connections by type.",
+                "    * ID <client-id>",
+                "      Kill connections by client id.",
+                "    * MAXAGE <maxage>",
+                "      Kill connections older than the specified age.",
+                "    * SKIPME (YES|NO)",
+                "      Skip killing current connection (default: yes).",
+                "LIST [options ...]",
+                "    Return information about client connections. Options:",
+                "    * TYPE (NORMAL|MASTER|REPLICA|PUBSUB)",
+                "      Return clients of specified type.",
+                "    * ID <client-id> [<client-id> ...]",
+                "      Return clients of specified IDs only.",
+                "SETINFO <option> <value>",
+                "    Set client meta attr. Options are:",
+                "    * LIB-NAME: the client lib name.",
+                "    * LIB-VER: the client lib version.",
+                "SETNAME <name>",
+                "    Assign the name <name> to the current connection.",
+                "HELP",
+                "    Print this help.",
+            ]),
+        };
+
+        return Ok(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+    };
+
+    use crate::{
+        commands::{
+            client::ClientCommand,
+            context::ConnectionContext,
+            traits::{Execute, Parse},
+        },
+        parser::messages::RedisMessageType,
+    };
+
+    fn ctx() -> ConnectionContext {
+        return ConnectionContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1));
+    }
+
+    fn client(
+        ctx: &ConnectionContext,
+        args: Vec<&str>,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return ClientCommand::parse(VecDeque::from_iter(args))?.execute_with_context(ctx);
+    }
+
+    #[test]
+    fn test_set_info_is_shown_in_client_info() {
+        let ctx = ctx();
+        let ok = Ok(RedisMessageType::simple_string("OK"));
+
+        assert_eq!(ok, client(&ctx, vec!["SETINFO", "LIB-NAME", "redis-py"]));
+        assert_eq!(ok, client(&ctx, vec!["setinfo", "lib-ver", "5.0.1"]));
+
+        let info = client(&ctx, vec!["INFO"])
+            .unwrap()
+            .bulk_string_value()
+            .unwrap();
+        assert!(info.contains(" lib-name=redis-py lib-ver=5.0.1 "));
+    }
+
+    #[test]
+    fn test_set_info_invalid() {
+        let ctx = ctx();
+
+        assert!(client(&ctx, vec!["SETINFO", "lib-name"]).is_err());
+        assert!(client(&ctx, vec!["SETINFO", "lib-name", "redis py"]).is_err());
+        assert_eq!(
+            Err(RedisMessageType::error("ERR Unrecognized option 'lib-foo'")),
+            client(&ctx, vec!["SETINFO", "lib-foo", "bar"])
+        );
+    }
+
+    #[test]
+    fn test_client_list_contains_all_clients() {
+        let first = ctx();
+        let second = ctx();
+        client(&second, vec!["SETNAME", "second"]).unwrap();
+
+        let list = client(&first, vec!["LIST"])
+            .unwrap()
+            .bulk_string_value()
+            .unwrap();
+
+        assert!(list.contains(&format!("id={} ", first.client().id())));
+        assert!(list.contains(&format!("id={} ", second.client().id())));
+        assert!(list.contains(" name=second "));
+    }
+
+    #[test]
+    fn test_client_name() {
+        let ctx = ctx();
+
+        assert_eq!(
+            Ok(RedisMessageType::NullBulkString),
+            client(&ctx, vec!["GETNAME"])
+        );
+        client(&ctx, vec!["SETNAME", "conn"]).unwrap();
+        assert_eq!(
+            Ok(RedisMessageType::bulk_string("conn")),
+            client(&ctx, vec!["GETNAME"])
+        );
+        client(&ctx, vec!["SETNAME", ""]).unwrap();
+        assert_eq!(
+            Ok(RedisMessageType::NullBulkString),
+            client(&ctx, vec!["GETNAME"])
+        );
+    }
+
+    #[test]
+    fn test_client_list_filters() {
+        let ctx = ctx();
+        let id = ctx.client().id();
+        let list = |args: Vec<&str>| {
+            let args = ["LIST"].into_iter().chain(args).collect();
+            return client(&ctx, args).unwrap().bulk_string_value().unwrap();
+        };
+
+        assert!(list(vec!["TYPE", "normal"]).contains(&format!("id={} ", id)));
+        assert!(!list(vec!["TYPE", "master"]).contains(&format!("id={} ", id)));
+        assert_eq!("", list(vec!["TYPE", "pubsub"]));
+        assert_eq!(1, list(vec!["ID", &id.to_string()]).lines().count());
+
+        assert_eq!(
+            Err(RedisMessageType::error("ERR Unknown client type 'tree'")),
+            client(&ctx, vec!["LIST", "TYPE", "tree"])
+        );
+        assert_eq!(
+            Err(RedisMessageType::error("ERR Invalid client ID")),
+            client(&ctx, vec!["LIST", "ID", "abc"])
+        );
+    }
+
+    #[test]
+    fn test_client_kill() {
+        let other = ctx();
+        let ctx = ctx();
+        let own_id = ctx.client().id().to_string();
+        let other_id = other.client().id().to_string();
+
+        // the calling client is skipped by default
+        assert_eq!(
+            Ok(RedisMessageType::Integer(0)),
+            client(&ctx, vec!["KILL", "ID", &own_id])
+        );
+        // no client is connected for that long
+        assert_eq!(
+            Ok(RedisMessageType::Integer(0)),
+            client(&ctx, vec!["KILL", "ID", &other_id, "MAXAGE", "1000"])
+        );
+        assert!(!other.client().is_killed());
+
+        assert_eq!(
+            Ok(RedisMessageType::Integer(1)),
+            client(&ctx, vec!["KILL", "ID", &other_id, "TYPE", "normal"])
+        );
+        assert!(other.client().is_killed());
+
+        assert_eq!(
+            Ok(RedisMessageType::Integer(1)),
+            client(&ctx, vec!["KILL", "ID", &own_id, "SKIPME", "no"])
+        );
+        assert!(ctx.client().is_killed());
+    }
+
+    #[test]
+    fn test_client_kill_by_address() {
+        let peer = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 6001);
+        let ctx = ConnectionContext::new(peer);
+
+        assert_eq!(
+            Err(RedisMessageType::error("ERR No such client")),
+            client(&ctx, vec!["KILL", "127.0.0.1:6002"])
+        );
+        // the old form does not skip the calling client
+        assert_eq!(
+            Ok(RedisMessageType::simple_string("OK")),
+            client(&ctx, vec!["KILL", "127.0.0.1:6001"])
+        );
+        assert!(ctx.client().is_killed());
+    }
+
+    #[test]
+    fn test_client_kill_invalid() {
+        let ctx = ctx();
+
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "ERR wrong number of arguments for 'client|kill' command"
+            )),
+            client(&ctx, vec!["KILL"])
+        );
+        assert_eq!(
+            Err(RedisMessageType::error("ERR syntax error")),
+            client(&ctx, vec!["KILL", "ID", "1", "SKIPME"])
+        );
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "ERR client-id should be greater than 0"
+            )),
+            client(&ctx, vec!["KILL", "ID", "0"])
+        );
+        assert_eq!(
+            Err(RedisMessageType::error("ERR Unknown client type 'tree'")),
+            client(&ctx, vec!["KILL", "TYPE", "tree"])
+        );
+        assert!(!ctx.client().is_killed());
+    }
+
+    #[test]
+    fn test_client_id() {
+        let ctx = ctx();
+
+        assert_eq!(
+            Ok(RedisMessageType::Integer(ctx.client().id() as i64)),
+            client(&ctx, vec!["ID"])
+        );
+        assert!(client(&ctx, vec!["ID", "1"]).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    consts::CRLF,
+    parser::messages::RedisMessageType,
+};
+
+/// Hash slots of a cluster, all owned by this node.
+const CLUSTER_SLOTS: u16 = 16384;
+
+enum Action {
+    Info,
+    KeySlot(String),
+    Help,
+}
+
+/// CLUSTER of a single node cluster, only available with `--cluster-enabled yes`.
+pub struct ClusterCommand {
+    action: Action,
+}
+
+impl ClusterCommand {
+    fn new(action: Action) -> Self {
+        return Self { action };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for ClusterCommand {
+    fn command_name() -> &'static str {
+        return "cluster";
+    }
+}
+impl ArgErrorMessageGenerator<ClusterCommand> for ClusterCommand {}
+
+impl Parse for ClusterCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let sub_command = args
+            .pop_front()
+            .ok_or(Self::arg_count_error())?
+            .bulk_string_value()?;
+
+        let action = match sub_command.to_ascii_uppercase().as_str() {
+            "HELP" if args.is_empty() => Action::Help,
+            "INFO" if args.is_empty() => Action::Info,
+            "KEYSLOT" => match (args.pop_front(), args.is_empty()) {
+                (Some(key), true) => Action::KeySlot(key.bulk_string_value()?),
+                _ => return Err(Self::sub_arg_count_error(&sub_command)),
+            },
+            "INFO" => return Err(Self::sub_arg_count_error(&sub_command)),
+            _ => return Err(Self::unknown_subcommand_error(&sub_command)),
+        };
+
+        return Ok(Self::new(action));
+    }
+}
+
+impl Execute for ClusterCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        return match self.action {
+            Action::Help => Ok(reply::bulk_array(vec![
+                "CLUSTER <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                "INFO",
+                "    Return information about the cluster.",
+                "KEYSLOT <key>",
+                "    Return the hash slot for <key>.",
+                "HELP",
+                "    Print this help.",
+            ])),
+            Action::Info => {
+                let fields = [
+                    ("cluster_enabled", 1),
+                    ("cluster_slots_assigned", CLUSTER_SLOTS),
+                    ("cluster_slots_ok", CLUSTER_SLOTS),
+                    ("cluster_slots_pfail", 0),
+                    ("cluster_slots_fail", 0),
+                    ("cluster_known_nodes", 1),
+                    ("cluster_size", 1),
+                    ("cluster_current_epoch", 0),
+                    ("cluster_my_epoch", 0),
+                ];
+                let mut info = format!("cluster_state:ok{CRLF}");
+                for (key, value) in fields {
+                    info += &format!("{}:{}{CRLF}", key, value);
+                }
+                Ok(reply::bulk(info))
+            }
+            Action::KeySlot(key) => Ok(reply::integer(key_slot(key.as_bytes()) as i64)),
+        };
+    }
+}
+
+/// The hash slot of a key. Only the hash tag is hashed if the key has one, e.g. `user` of
+/// `{user}.name`, so related keys end up in the same slot.
+pub fn key_slot(key: &[u8]) -> u16 {
+    let hashed = match key.iter().position(|byte| *byte == b'{') {
+        Some(open) => match key[open + 1..].iter().position(|byte| *byte == b'}') {
+            // an empty tag like `{}` does not count
+            Some(length) if length > 0 => &key[open + 1..open + 1 + length],
+            _ => key,
+        },
+        None => key,
+    };
+    return crc16(hashed) % CLUSTER_SLOTS;
+}
+
+/// CRC16-CCITT (XMODEM) as used by redis cluster.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    return crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            cluster::{crc16, key_slot, ClusterCommand},
+            traits::{Execute, Parse},
+        },
+        parser::messages::RedisMessageType,
+    };
+
+    fn cluster(args: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return ClusterCommand::parse(VecDeque::from_iter(args))?.execute();
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(0x31C3, crc16(b"123456789"));
+    }
+
+    #[test]
+    fn test_key_slot() {
+        assert_eq!(12182, key_slot(b"foo"));
+        assert_eq!(11058, key_slot(b"somekey"));
+        assert_eq!(key_slot(b"user"), key_slot(b"{user}.name"));
+        assert_eq!(key_slot(b"{user}.name"), key_slot(b"x{user}{other}"));
+        assert_eq!(crc16(b"{}user") % 16384, key_slot(b"{}user"));
+    }
+
+    #[test]
+    fn test_cluster_command() {
+        assert_eq!(
+            Ok(RedisMessageType::Integer(12182)),
+            cluster(vec!["keyslot", "foo"])
+        );
+
+        let info = cluster(vec!["INFO"]).unwrap().bulk_string_value().unwrap();
+        assert!(info.starts_with("cluster_state:ok\r\ncluster_enabled:1\r\n"));
+
+        assert!(cluster(vec![]).is_err());
+        assert!(cluster(vec!["KEYSLOT"]).is_err());
+        assert!(cluster(vec!["INFO", "a"]).is_err());
+        assert!(cluster(vec!["NODES"]).is_err());
+    }
+}
@@ -5,67 +5,303 @@ use log::trace;
 
 use crate::{
     commands::{
+        acl::AclCategory,
+        client::ClientCommand,
+        cluster::ClusterCommand,
+        command_info::CommandCommand,
         config::ConfigCommand,
+        context::ConnectionContext,
+        copy::CopyCommand,
+        debug::DebugCommand,
+        del::DelCommand,
         echo::EchoCommand,
+        flush::FlushCommand,
         get::GetCommand,
+        getrange::GetRangeCommand,
         info::InfoCommand,
         keys::KeysCommand,
+        mode::{server_mode, ServerMode},
+        object::ObjectCommand,
         ping::PingCommand,
         psync::PsyncCommand,
+        rename::RenameCommand,
         replconf::ReplConfCommand,
+        reply::{self, ErrorCode},
+        scan::ScanCommand,
         set::SetCommand,
+        shutdown::ShutdownCommand,
         traits::{Command, Parsed, Unparsed},
+        wait::WaitCommand,
     },
     parser::messages::RedisMessageType,
     redis_commands,
 };
 
+// the modes default to standalone and cluster, monitoring commands are also available in
+// sentinel mode
 redis_commands! {
-    Ping => PingCommand,
-    Echo => EchoCommand,
-    Set => SetCommand,
-    Get => GetCommand,
-    Config => ConfigCommand,
-    Keys => KeysCommand,
-    Info => InfoCommand,
-    ReplConf => ReplConfCommand,
-    Psync => PsyncCommand
+    Ping => PingCommand [Fast, Connection] in [Standalone, Cluster, Sentinel],
+    Echo => EchoCommand [Fast, Connection] in [Standalone, Cluster, Sentinel],
+    Set => SetCommand [Write, String, Slow],
+    Get => GetCommand [Read, String, Fast],
+    GetRange => GetRangeCommand [Read, String, Slow],
+    Config => ConfigCommand [Admin, Slow, Dangerous] in [Standalone, Cluster, Sentinel],
+    Keys => KeysCommand [Keyspace, Read, Slow, Dangerous],
+    Scan => ScanCommand [Keyspace, Read, Slow],
+    Info => InfoCommand [Slow, Dangerous] in [Standalone, Cluster, Sentinel],
+    ReplConf => ReplConfCommand [Admin, Slow, Dangerous],
+    Psync => PsyncCommand [Admin, Slow, Dangerous],
+    Debug => DebugCommand [Admin, Slow, Dangerous],
+    Wait => WaitCommand [Slow, Connection],
+    Rename => RenameCommand [Keyspace, Write, Slow],
+    Copy => CopyCommand [Keyspace, Write, Slow],
+    Del => DelCommand [Keyspace, Write, Slow],
+    FlushAll => FlushCommand [Keyspace, Write, Slow, Dangerous],
+    FlushDb => FlushCommand [Keyspace, Write, Slow, Dangerous],
+    Client => ClientCommand [Slow, Connection] in [Standalone, Cluster, Sentinel],
+    Object => ObjectCommand [Keyspace, Read, Slow],
+    Cluster => ClusterCommand [Slow] in [Cluster],
+    Command => CommandCommand [Slow, Connection] in [Standalone, Cluster, Sentinel],
+    Shutdown => ShutdownCommand [Admin, Slow, Dangerous] in [Standalone, Cluster, Sentinel]
+}
+
+/// A command as it is declared in the command table, see [`COMMAND_TABLE`].
+#[derive(Debug)]
+pub struct CommandMetadata {
+    table_name: &'static str,
+    pub acl_categories: &'static [AclCategory],
+    pub modes: &'static [ServerMode],
+}
+
+impl CommandMetadata {
+    pub fn name(&self) -> String {
+        return self.table_name.to_lowercase();
+    }
+}
+
+/// The names of the commands available in the mode of the server, including aliases, with
+/// the metadata of the command implementing them.
+pub fn available_commands() -> Vec<(String, &'static CommandMetadata)> {
+    let find = |name: &str| {
+        return COMMAND_TABLE
+            .iter()
+            .find(|command| command.table_name.eq_ignore_ascii_case(name))
+            .expect("Aliases point to commands of the table. Should never happen!");
+    };
+
+    return COMMAND_TABLE
+        .iter()
+        .map(|command| (command.name(), command))
+        .chain(
+            COMMAND_ALIASES
+                .iter()
+                .map(|(alias, name)| (alias.to_lowercase(), find(name))),
+        )
+        .filter(|(_, command)| command.modes.contains(&server_mode()))
+        .collect();
+}
+
+/// Legacy names of commands, redis keeps accepting them so old clients still work. Using
+/// them is counted as deprecated call, see [`crate::commands::middleware::DeprecatedAlias`].
+pub const COMMAND_ALIASES: [(&str, &str); 1] = [("SUBSTR", "GETRANGE")];
+
+/// The alias and the command implementing it, if `name` is an alias.
+fn find_alias(name: &str) -> Option<(&'static str, &'static str)> {
+    return COMMAND_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .copied();
+}
+
+fn unknown_command(command_arg: &str, args: &VecDeque<RedisMessageType>) -> RedisMessageType {
+    // like redis, the error names the command as it was send
+    let args: String = args
+        .iter()
+        .filter_map(|arg| arg.as_string())
+        .map(|arg| format!("'{}' ", arg))
+        .collect();
+    return reply::error(
+        ErrorCode::Err,
+        format!(
+            "unknown command '{}', with args beginning with: {}",
+            command_arg, args
+        ),
+    );
 }
 
 impl UnparsedCommandType {
     pub fn new(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
         let command_arg = match args
             .pop_front()
-            .ok_or(RedisMessageType::error("No argument passed to redis!"))?
+            .ok_or(reply::error(ErrorCode::Err, "No argument passed to redis!"))?
         {
             RedisMessageType::BulkString(val) => val,
             _ => {
-                return Err(RedisMessageType::error(
+                return Err(reply::error(
+                    ErrorCode::Err,
                     "Command must be encoded as a bulk string!",
                 ))
             }
         };
 
-        let command = match command_arg.to_uppercase().as_str() {
+        let name = command_arg.to_uppercase();
+        let alias = find_alias(&name);
+        let mut command = match alias.map_or(name.as_str(), |(_, command)| command) {
             "PING" => Self::Ping(Command::<Unparsed, PingCommand>::new(args)),
             "GET" => Self::Get(Command::<Unparsed, GetCommand>::new(args)),
+            "GETRANGE" => Self::GetRange(Command::<Unparsed, GetRangeCommand>::new(args)),
             "SET" => Self::Set(Command::<Unparsed, SetCommand>::new(args)),
             "ECHO" => Self::Echo(Command::<Unparsed, EchoCommand>::new(args)),
             "CONFIG" => Self::Config(Command::<Unparsed, ConfigCommand>::new(args)),
             "KEYS" => Self::Keys(Command::<Unparsed, KeysCommand>::new(args)),
+            "SCAN" => Self::Scan(Command::<Unparsed, ScanCommand>::new(args)),
             "INFO" => Self::Info(Command::<Unparsed, InfoCommand>::new(args)),
             "REPLCONF" => Self::ReplConf(Command::<Unparsed, ReplConfCommand>::new(args)),
             "PSYNC" => Self::Psync(Command::<Unparsed, PsyncCommand>::new(args)),
+            "DEBUG" => Self::Debug(Command::<Unparsed, DebugCommand>::new(args)),
+            "WAIT" => Self::Wait(Command::<Unparsed, WaitCommand>::new(args)),
+            "RENAME" => Self::Rename(Command::<Unparsed, RenameCommand>::new(args)),
+            "COPY" => Self::Copy(Command::<Unparsed, CopyCommand>::new(args)),
+            "DEL" => Self::Del(Command::<Unparsed, DelCommand>::new(args)),
+            "FLUSHALL" => Self::FlushAll(Command::<Unparsed, FlushCommand>::new(args)),
+            "FLUSHDB" => Self::FlushDb(Command::<Unparsed, FlushCommand>::new(args)),
+            "OBJECT" => Self::Object(Command::<Unparsed, ObjectCommand>::new(args)),
+            "CLIENT" => Self::Client(Command::<Unparsed, ClientCommand>::new(args)),
+            "CLUSTER" => Self::Cluster(Command::<Unparsed, ClusterCommand>::new(args)),
+            "COMMAND" => Self::Command(Command::<Unparsed, CommandCommand>::new(args)),
+            "SHUTDOWN" => Self::Shutdown(Command::<Unparsed, ShutdownCommand>::new(args)),
             // "SAVE" => Self::SAVE(SaveCommand::new(args)),
-            _other => {
-                return Err(RedisMessageType::error(format!(
-                    "Unknown command name: '{}'",
-                    _other
-                )))
-            }
+            _other => return Err(unknown_command(&command_arg, &args)),
         };
+        // commands of other modes don't exist in this one
+        if !command.modes().contains(&server_mode()) {
+            return Err(unknown_command(&command_arg, command.args()));
+        }
+        command.set_alias(alias.map(|(alias, _)| alias));
         trace!("Parsed command {}", command.name().to_ascii_uppercase());
 
         return Ok(command);
     }
+
+    /// The name shown in CLIENT LIST. Container commands include their subcommand, like
+    /// `client|list` or `config|get`.
+    pub fn full_name(&self) -> String {
+        let subcommand = match self {
+            Self::Client(_) | Self::Config(_) | Self::Command(_) => self.args().front(),
+            _ => None,
+        };
+
+        return match subcommand.and_then(|subcommand| subcommand.as_string()) {
+            Some(subcommand) => format!("{}|{}", self.name(), subcommand.to_lowercase()),
+            None => self.name(),
+        };
+    }
+
+    /// The command as it is fed to replicas. Only built for writes, so reads don't pay for
+    /// a copy of their arguments.
+    pub fn to_message(&self) -> RedisMessageType {
+        let mut message = VecDeque::with_capacity(self.args().len() + 1);
+        message.push_back(RedisMessageType::bulk_string(self.name().to_uppercase()));
+        message.extend(self.args().iter().cloned());
+        return RedisMessageType::Array(message);
+    }
+
+    /// Commands altering the dataset. These are fed to connected replicas.
+    pub fn is_write(&self) -> bool {
+        return self.acl_categories().contains(&AclCategory::Write);
+    }
+
+    /// The keys a write command changes, locked until it is fed to the replicas. Missing
+    /// arguments are ignored, the command fails parsing anyway.
+    pub fn write_keys(&self) -> Vec<String> {
+        let keys = self.args().iter().filter_map(|arg| arg.as_string());
+        return match self {
+            Self::Set(_) => keys.take(1).collect(),
+            Self::Rename(_) | Self::Copy(_) => keys.take(2).collect(),
+            Self::Del(_) => keys.collect(),
+            _ => Vec::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{command::UnparsedCommandType, mode::ServerMode},
+        parser::messages::RedisMessageType,
+    };
+
+    fn command(args: Vec<&str>) -> UnparsedCommandType {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return UnparsedCommandType::new(VecDeque::from_iter(args))
+            .ok()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_full_name() {
+        assert_eq!("get", command(vec!["GET", "key"]).full_name());
+        assert_eq!("client|list", command(vec!["CLIENT", "LIST"]).full_name());
+        assert_eq!(
+            "config|get",
+            command(vec!["config", "Get", "dir"]).full_name()
+        );
+        assert_eq!("client", command(vec!["CLIENT"]).full_name());
+    }
+
+    #[test]
+    fn test_write_keys() {
+        assert_eq!(
+            vec!["key"],
+            command(vec!["SET", "key", "value"]).write_keys()
+        );
+        assert_eq!(
+            vec!["a", "b"],
+            command(vec!["COPY", "a", "b", "REPLACE"]).write_keys()
+        );
+        assert_eq!(
+            vec!["a", "b", "c"],
+            command(vec!["DEL", "a", "b", "c"]).write_keys()
+        );
+        assert!(command(vec!["GET", "key"]).write_keys().is_empty());
+    }
+
+    #[test]
+    fn test_alias() {
+        assert_eq!("getrange", command(vec!["substr", "key", "0", "1"]).name());
+        assert_eq!(
+            "getrange",
+            command(vec!["GETRANGE", "key", "0", "1"]).name()
+        );
+    }
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(&ServerMode::DATA, command(vec!["GET", "key"]).modes());
+        assert_eq!(&ServerMode::ALL, command(vec!["PING"]).modes());
+        assert_eq!(&ServerMode::ALL, command(vec!["INFO"]).modes());
+
+        // the tests run in standalone mode
+        let args = ["CLUSTER", "INFO"].map(RedisMessageType::bulk_string);
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "ERR unknown command 'CLUSTER', with args beginning with: 'INFO' "
+            )),
+            UnparsedCommandType::new(VecDeque::from(args)).map(|command| command.name())
+        );
+    }
+
+    #[test]
+    fn test_to_message() {
+        assert_eq!(
+            RedisMessageType::bulk_string_array(vec!["SET", "key", "value"]),
+            command(vec!["set", "key", "value"]).to_message()
+        );
+        assert_eq!(
+            RedisMessageType::bulk_string_array(vec!["FLUSHDB", "async"]),
+            command(vec!["flushdb", "async"]).to_message()
+        );
+    }
 }
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        acl::AclCategory,
+        command::{available_commands, CommandMetadata},
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    parser::messages::RedisMessageType,
+    utils::glob::glob_match,
+};
+
+/// Filter of COMMAND LIST FILTERBY.
+enum Filter {
+    /// There are no modules, so no command matches.
+    Module(String),
+    /// An unknown category matches no command, like in redis.
+    AclCategory(Option<AclCategory>),
+    Pattern(String),
+}
+
+impl Filter {
+    fn matches(&self, name: &str, command: &CommandMetadata) -> bool {
+        return match self {
+            Self::Module(_) => false,
+            Self::AclCategory(category) => {
+                category.is_some_and(|category| command.acl_categories.contains(&category))
+            }
+            // like redis, the pattern ignores the case
+            Self::Pattern(pattern) => glob_match(&pattern.to_lowercase(), name),
+        };
+    }
+}
+
+enum Action {
+    Count,
+    List(Option<Filter>),
+    Help,
+}
+
+/// COMMAND, lets tooling discover which commands of redis this server implements.
+pub struct CommandCommand {
+    action: Action,
+}
+
+impl CommandCommand {
+    fn new(action: Action) -> Self {
+        return Self { action };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for CommandCommand {
+    fn command_name() -> &'static str {
+        return "command";
+    }
+}
+impl ArgErrorMessageGenerator<CommandCommand> for CommandCommand {}
+
+impl Parse for CommandCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let sub_command = args
+            .pop_front()
+            .ok_or(Self::arg_count_error())?
+            .bulk_string_value()?;
+
+        let action = match sub_command.to_ascii_uppercase().as_str() {
+            "HELP" if args.is_empty() => Action::Help,
+            "COUNT" if args.is_empty() => Action::Count,
+            "LIST" if args.is_empty() => Action::List(None),
+            "LIST" => {
+                let (filterby, kind, value) =
+                    match (args.pop_front(), args.pop_front(), args.pop_front()) {
+                        (Some(filterby), Some(kind), Some(value)) if args.is_empty() => (
+                            filterby.bulk_string_value()?,
+                            kind.bulk_string_value()?,
+                            value.bulk_string_value()?,
+                        ),
+                        _ => return Err(reply::syntax_error()),
+                    };
+                if !filterby.eq_ignore_ascii_case("FILTERBY") {
+                    return Err(reply::syntax_error());
+                }
+
+                let filter = match kind.to_ascii_uppercase().as_str() {
+                    "MODULE" => Filter::Module(value),
+                    "ACLCAT" => Filter::AclCategory(AclCategory::from_name(&value)),
+                    "PATTERN" => Filter::Pattern(value),
+                    _ => return Err(reply::syntax_error()),
+                };
+                Action::List(Some(filter))
+            }
+            "HELP" | "COUNT" => return Err(Self::sub_arg_count_error(&sub_command)),
+            _ => return Err(Self::unknown_subcommand_error(&sub_command)),
+        };
+
+        return Ok(Self::new(action));
+    }
+}
+
+impl Execute for CommandCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        return match self.action {
+            Action::Help => Ok(reply::bulk_array(vec![
+                "COMMAND <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                "COUNT",
+                "    Return the total number of commands in this Redis server.",
+                "LIST",
+                "    Return a list of all commands in this Redis server.",
+                "LIST FILTERBY (MODULE <module-name>|ACLCAT <category>|PATTERN <pattern>)",
+                "    Return a list of the commands matching the filter.",
+                "HELP",
+                "    Print this help.",
+            ])),
+            Action::Count => Ok(reply::integer(available_commands().len() as i64)),
+            Action::List(filter) => {
+                let names = available_commands()
+                    .into_iter()
+                    .filter(|(name, command)| {
+                        filter
+                            .as_ref()
+                            .is_none_or(|filter| filter.matches(name, command))
+                    })
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>();
+                Ok(reply::bulk_array(names))
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            command_info::CommandCommand,
+            traits::{Execute, Parse},
+        },
+        parser::messages::RedisMessageType,
+    };
+
+    fn command(args: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return CommandCommand::parse(VecDeque::from_iter(args))?.execute();
+    }
+
+    fn list(args: Vec<&str>) -> Vec<String> {
+        return match command(args).unwrap() {
+            RedisMessageType::Array(names) => {
+                names.iter().filter_map(|name| name.as_string()).collect()
+            }
+            other => panic!("COMMAND LIST replied with {:?}", other),
+        };
+    }
+
+    #[test]
+    fn test_command_list() {
+        let all = list(vec!["LIST"]);
+        assert!(all.contains(&"get".to_string()));
+        assert!(all.contains(&"substr".to_string()));
+        assert!(all.contains(&"command".to_string()));
+        // the tests run in standalone mode
+        assert!(!all.contains(&"cluster".to_string()));
+        assert_eq!(
+            Ok(RedisMessageType::Integer(all.len() as i64)),
+            command(vec!["COUNT"])
+        );
+    }
+
+    #[test]
+    fn test_command_list_filterby() {
+        assert_eq!(
+            vec!["get", "getrange"],
+            list(vec!["LIST", "FILTERBY", "PATTERN", "GET*"])
+        );
+        assert_eq!(
+            vec!["substr"],
+            list(vec!["LIST", "FILTERBY", "PATTERN", "s?bstr"])
+        );
+        assert_eq!(
+            vec!["set", "get", "getrange", "substr"],
+            list(vec!["list", "filterby", "aclcat", "string"])
+        );
+        assert!(list(vec!["LIST", "FILTERBY", "ACLCAT", "nope"]).is_empty());
+        assert!(list(vec!["LIST", "FILTERBY", "MODULE", "json"]).is_empty());
+    }
+
+    #[test]
+    fn test_command_invalid_args() {
+        assert!(command(vec![]).is_err());
+        assert!(command(vec!["COUNT", "a"]).is_err());
+        assert!(command(vec!["LIST", "FILTERBY", "PATTERN"]).is_err());
+        assert!(command(vec!["LIST", "FILTER", "PATTERN", "*"]).is_err());
+        assert!(command(vec!["LIST", "FILTERBY", "TYPE", "*"]).is_err());
+    }
+}
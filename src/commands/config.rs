@@ -1,32 +1,232 @@
-use std::collections::VecDeque;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
-    db::data_store::get_db,
-    parser::messages::RedisMessageType,
+    commands::{
+        reply::{self, ErrorCode},
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::{
+        data_store::{get_db, DbConfig, ServerRole},
+        eviction::EvictionPolicy,
+    },
+    parser::messages::{protocol_limits, RedisMessageType},
+    utils::units::{format_bool, parse_bool, parse_memory},
 };
 
 // more items could be implemented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ConfigItem {
     Dir,
-    DbFile,
+    DbFilename,
+    ReplicaOf,
+    ReplicaReadOnly,
+    ProtoMaxBulkLen,
+    ProtoMaxMultibulkLen,
+    ProtoMaxInlineLen,
+    ClientQueryBufferLimit,
+    Maxmemory,
+    MaxmemoryPolicy,
+    MaxmemorySamples,
+    Masterauth,
+    ReplCompression,
+    CommandTimeLimit,
+    ShutdownTimeout,
+}
+
+/// A parsed value for CONFIG SET.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ConfigValue {
+    Path(PathBuf),
+    FileName(String),
+    Memory(u64),
+    Policy(EvictionPolicy),
+    Samples(usize),
+    ReadOnly(bool),
+    /// An empty password removes it.
+    Password(Option<String>),
+    Compression(bool),
+    TimeLimit(Duration),
+    ShutdownTimeout(Duration),
+}
+
+impl ConfigItem {
+    const ALL: [ConfigItem; 15] = [
+        Self::Dir,
+        Self::DbFilename,
+        Self::ReplicaOf,
+        Self::ReplicaReadOnly,
+        Self::ProtoMaxBulkLen,
+        Self::ProtoMaxMultibulkLen,
+        Self::ProtoMaxInlineLen,
+        Self::ClientQueryBufferLimit,
+        Self::Maxmemory,
+        Self::MaxmemoryPolicy,
+        Self::MaxmemorySamples,
+        Self::Masterauth,
+        Self::ReplCompression,
+        Self::CommandTimeLimit,
+        Self::ShutdownTimeout,
+    ];
+
+    const fn name(&self) -> &'static str {
+        return match self {
+            Self::Dir => "dir",
+            Self::DbFilename => "dbfilename",
+            Self::ReplicaOf => "replicaof",
+            Self::ReplicaReadOnly => "replica-read-only",
+            Self::ProtoMaxBulkLen => "proto-max-bulk-len",
+            Self::ProtoMaxMultibulkLen => "proto-max-multibulk-len",
+            Self::ProtoMaxInlineLen => "proto-max-inline-len",
+            Self::ClientQueryBufferLimit => "client-query-buffer-limit",
+            Self::Maxmemory => "maxmemory",
+            Self::MaxmemoryPolicy => "maxmemory-policy",
+            Self::MaxmemorySamples => "maxmemory-samples",
+            Self::Masterauth => "masterauth",
+            Self::ReplCompression => "repl-compression",
+            Self::CommandTimeLimit => "command-time-limit",
+            Self::ShutdownTimeout => "shutdown-timeout",
+        };
+    }
+
+    /// Deprecated names, still accepted so old tooling and conf files keep working.
+    const fn aliases(&self) -> &'static [&'static str] {
+        return match self {
+            Self::Dir => &[],
+            Self::DbFilename => &["dbfile"],
+            Self::ReplicaOf => &["slaveof"],
+            Self::ReplicaReadOnly => &["slave-read-only"],
+            Self::ProtoMaxBulkLen
+            | Self::ProtoMaxMultibulkLen
+            | Self::ProtoMaxInlineLen
+            | Self::ClientQueryBufferLimit
+            | Self::Maxmemory
+            | Self::MaxmemoryPolicy
+            | Self::MaxmemorySamples
+            | Self::Masterauth
+            | Self::ReplCompression
+            | Self::CommandTimeLimit
+            | Self::ShutdownTimeout => &[],
+        };
+    }
+
+    fn value(&self, config: &DbConfig) -> String {
+        return match self {
+            Self::Dir => config
+                .db_dir
+                .to_str()
+                .expect("ERR Unable to get the dir due to technikal reason. Should never happen!")
+                .to_string(),
+            Self::DbFilename => config.db_filename.clone(),
+            Self::ReplicaOf => match &config.replication_data.role {
+                ServerRole::Master => String::new(),
+                ServerRole::Slave((host, port)) => format!("{} {}", host, port),
+            },
+            Self::ReplicaReadOnly => format_bool(config.replica_read_only).to_string(),
+            Self::ProtoMaxBulkLen => protocol_limits().max_bulk_len.to_string(),
+            Self::ProtoMaxMultibulkLen => protocol_limits().max_multibulk_len.to_string(),
+            Self::ProtoMaxInlineLen => protocol_limits().max_inline_len.to_string(),
+            Self::ClientQueryBufferLimit => protocol_limits().max_query_buffer_len.to_string(),
+            Self::Maxmemory => config.maxmemory.to_string(),
+            Self::MaxmemoryPolicy => config.maxmemory_policy.name().to_string(),
+            Self::MaxmemorySamples => config.maxmemory_samples.to_string(),
+            Self::Masterauth => config.masterauth.clone().unwrap_or_default(),
+            Self::ReplCompression => format_bool(config.repl_compression).to_string(),
+            Self::CommandTimeLimit => config.command_time_limit.as_millis().to_string(),
+            Self::ShutdownTimeout => config.shutdown_timeout.as_secs().to_string(),
+        };
+    }
+
+    /// Parses the value like redis does for the type of the parameter. Parameters that are
+    /// only read at startup can not be set.
+    fn parse_value(&self, value: &str) -> Result<ConfigValue, String> {
+        return match self {
+            Self::Dir => match Path::new(value).is_dir() {
+                true => Ok(ConfigValue::Path(PathBuf::from(value))),
+                false => Err("No such file or directory".to_string()),
+            },
+            Self::DbFilename => match value.contains('/') {
+                false => Ok(ConfigValue::FileName(value.to_string())),
+                true => Err("dbfilename can't be a path, just a filename".to_string()),
+            },
+            Self::Maxmemory => parse_memory(value).map(ConfigValue::Memory),
+            Self::MaxmemoryPolicy => EvictionPolicy::from_name(value).map(ConfigValue::Policy),
+            Self::MaxmemorySamples => match value.parse::<usize>() {
+                Ok(samples) if samples > 0 => Ok(ConfigValue::Samples(samples)),
+                _ => Err("argument must be a positive integer".to_string()),
+            },
+            Self::ReplicaReadOnly => parse_bool(value).map(ConfigValue::ReadOnly),
+            Self::ReplCompression => parse_bool(value).map(ConfigValue::Compression),
+            Self::CommandTimeLimit => match value.parse::<u64>() {
+                Ok(millis) => Ok(ConfigValue::TimeLimit(Duration::from_millis(millis))),
+                Err(_) => Err("argument couldn't be parsed into an integer".to_string()),
+            },
+            Self::ShutdownTimeout => match value.parse::<u64>() {
+                Ok(secs) => Ok(ConfigValue::ShutdownTimeout(Duration::from_secs(secs))),
+                Err(_) => Err("argument couldn't be parsed into an integer".to_string()),
+            },
+            Self::Masterauth => Ok(ConfigValue::Password(
+                (!value.is_empty()).then(|| value.to_string()),
+            )),
+            Self::ReplicaOf
+            | Self::ProtoMaxBulkLen
+            | Self::ProtoMaxMultibulkLen
+            | Self::ProtoMaxInlineLen
+            | Self::ClientQueryBufferLimit => Err("can't set immutable config".to_string()),
+        };
+    }
+}
+
+impl ConfigValue {
+    fn apply(self, config: &mut DbConfig) {
+        match self {
+            Self::Path(dir) => config.db_dir = dir,
+            Self::FileName(name) => config.db_filename = name,
+            Self::Memory(bytes) => config.maxmemory = bytes,
+            Self::Policy(policy) => config.maxmemory_policy = policy,
+            Self::Samples(samples) => config.maxmemory_samples = samples,
+            Self::ReadOnly(read_only) => config.replica_read_only = read_only,
+            // read on every handshake, so it applies once the link is established again
+            Self::Password(password) => config.masterauth = password,
+            Self::Compression(compression) => config.repl_compression = compression,
+            Self::TimeLimit(limit) => config.command_time_limit = limit,
+            Self::ShutdownTimeout(timeout) => config.shutdown_timeout = timeout,
+        }
+    }
 }
 
 impl TryFrom<String> for ConfigItem {
     type Error = String;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        match value.to_ascii_uppercase().as_str() {
-            "DIR" => Ok(Self::Dir),
-            "DBFILE" => Ok(Self::DbFile),
-            _ => Err(value),
-        }
+        return Self::ALL
+            .into_iter()
+            .find(|item| {
+                item.name().eq_ignore_ascii_case(&value)
+                    || item
+                        .aliases()
+                        .iter()
+                        .any(|alias| alias.eq_ignore_ascii_case(&value))
+            })
+            .ok_or(value);
     }
 }
 
+/// The name and value of every config parameter.
+pub fn all_values() -> Vec<(&'static str, String)> {
+    let config = get_db().get_config();
+    return ConfigItem::ALL
+        .iter()
+        .map(|item| (item.name(), item.value(&config)))
+        .collect();
+}
+
 enum Action {
-    // Get should support many entries.
-    Get(Vec<ConfigItem>),
+    // Get should support many entries. Items are replied with the name they were requested by.
+    Get(Vec<(String, ConfigItem)>),
     Set((ConfigItem, String)),
     Help,
     Rewrite,
@@ -52,17 +252,25 @@ impl CommandName for ConfigCommand {
 impl ArgErrorMessageGenerator<ConfigCommand> for ConfigCommand {}
 
 fn parse_get_command(args: VecDeque<RedisMessageType>) -> Result<Action, RedisMessageType> {
+    if args.is_empty() {
+        return Err(ConfigCommand::sub_arg_count_error("get"));
+    }
+
     let mut items = Vec::with_capacity(args.len());
 
     for arg in args.iter() {
-        let item = ConfigItem::try_from(arg.bulk_string_value()?).map_err(|err| {
-            RedisMessageType::error(format!(
-                "ERR Unknown option or number of arguments for CONFIG GET - '{}'",
-                err
-            ))
+        let name = arg.bulk_string_value()?.to_ascii_lowercase();
+        let item = ConfigItem::try_from(name.clone()).map_err(|err| {
+            reply::error(
+                ErrorCode::Err,
+                format!(
+                    "Unknown option or number of arguments for CONFIG GET - '{}'",
+                    err
+                ),
+            )
         })?;
 
-        items.push(item);
+        items.push((name, item));
     }
 
     return Ok(Action::Get(items));
@@ -71,24 +279,28 @@ fn parse_get_command(args: VecDeque<RedisMessageType>) -> Result<Action, RedisMe
 fn parse_set_command(mut args: VecDeque<RedisMessageType>) -> Result<Action, RedisMessageType> {
     let arg = args
         .pop_front()
-        .ok_or_else(ConfigCommand::arg_count_error)?
+        .ok_or_else(|| ConfigCommand::sub_arg_count_error("set"))?
         .bulk_string_value()?;
 
     let config_item = ConfigItem::try_from(arg).map_err(|err| {
-        RedisMessageType::error(format!(
-            "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
-            err
-        ))
+        reply::error(
+            ErrorCode::Err,
+            format!(
+                "Unknown option or number of arguments for CONFIG SET - '{}'",
+                err
+            ),
+        )
     })?;
 
     let value = args
         .pop_front()
-        .ok_or_else(ConfigCommand::arg_count_error)?
+        .ok_or_else(|| ConfigCommand::sub_arg_count_error("set"))?
         .bulk_string_value()?;
 
     if !args.is_empty() {
-        return Err(RedisMessageType::error(
-            "ERR As of now only a single value may be set for the CONFIG SET command!",
+        return Err(reply::error(
+            ErrorCode::Err,
+            "As of now only a single value may be set for the CONFIG SET command!",
         ));
     }
 
@@ -108,12 +320,7 @@ impl Parse for ConfigCommand {
             "RESETSTAT" => Action::ResetStat,
             "GET" => parse_get_command(args)?,
             "SET" => parse_set_command(args)?,
-            _val => {
-                return Err(RedisMessageType::error(format!(
-                    "ERR unkown subcommand '{}'. Try CONFIG HELP",
-                    _val
-                )))
-            }
+            _ => return Err(Self::unknown_subcommand_error(&key)),
         };
 
         return Ok(Self::new(action));
@@ -121,7 +328,7 @@ impl Parse for ConfigCommand {
 }
 
 fn execute_help() -> RedisMessageType {
-    return RedisMessageType::bulk_string_array(vec![
+    return reply::bulk_array(vec![
         "CONFIG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
         "GET <pattern>",
         "    Return parameters matching the <pattern> and their values.",
@@ -132,31 +339,29 @@ fn execute_help() -> RedisMessageType {
     ]);
 }
 
-fn execute_get(items: Vec<ConfigItem>) -> Result<RedisMessageType, RedisMessageType> {
+fn execute_get(items: Vec<(String, ConfigItem)>) -> Result<RedisMessageType, RedisMessageType> {
     let config = get_db().get_config();
-    let result: VecDeque<RedisMessageType> = items
-        .iter()
-        .map(|item| match item {
-            ConfigItem::DbFile => vec!["dbfilename", &config.db_filename],
-            ConfigItem::Dir => vec![
-                "dir",
-                config.db_dir.to_str().expect(
-                    "ERR Unable to get the dir due to technikal reason. Should never happen!",
-                ),
-            ],
-        })
-        .flat_map(|inner| {
-            inner
-                .into_iter()
-                .map(|val| RedisMessageType::bulk_string(val))
-        })
-        .collect();
+    let entries = items
+        .into_iter()
+        .map(|(name, item)| (name, reply::bulk(item.value(&config))));
 
-    return Ok(RedisMessageType::Array(result));
+    return Ok(reply::map(entries));
 }
 
-fn _execute_set(_item: ConfigItem, _value: String) -> Result<RedisMessageType, RedisMessageType> {
-    return Ok(RedisMessageType::NullBulkString);
+fn execute_set(item: ConfigItem, value: String) -> Result<RedisMessageType, RedisMessageType> {
+    let value = item.parse_value(&value).map_err(|reason| {
+        reply::error(
+            ErrorCode::Err,
+            format!(
+                "CONFIG SET failed (possibly related to argument '{}') - {}",
+                item.name(),
+                reason
+            ),
+        )
+    })?;
+
+    get_db().update_config(|config| value.apply(config));
+    return Ok(reply::ok());
 }
 
 impl Execute for ConfigCommand {
@@ -164,11 +369,171 @@ impl Execute for ConfigCommand {
         let result = match self.action {
             Action::Help => execute_help(),
             Action::Get(action) => execute_get(action)?,
-            Action::Set(val) => unimplemented!(),
-            Action::ResetStat => unimplemented!(),
-            Action::Rewrite => unimplemented!(),
+            Action::Set((item, value)) => execute_set(item, value)?,
+            Action::ResetStat => {
+                return Err(reply::error(
+                    ErrorCode::Err,
+                    "CONFIG RESETSTAT is not supported",
+                ))
+            }
+            Action::Rewrite => {
+                return Err(reply::error(
+                    ErrorCode::Err,
+                    "CONFIG REWRITE is not supported, the config is not stored in a file",
+                ))
+            }
         };
 
         return Ok(result);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            config::{ConfigCommand, ConfigItem},
+            reply,
+            traits::{Execute, Parse},
+        },
+        db::data_store::init_test_db,
+        parser::messages::RedisMessageType,
+    };
+
+    fn config_get(names: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = ["GET"].into_iter().chain(names);
+        let args = VecDeque::from_iter(args.map(RedisMessageType::bulk_string));
+        return ConfigCommand::parse(args)?.execute();
+    }
+
+    #[test]
+    fn test_config_item_aliases() {
+        assert_eq!(
+            Ok(ConfigItem::ReplicaReadOnly),
+            ConfigItem::try_from("slave-read-only".to_string())
+        );
+        assert_eq!(
+            Ok(ConfigItem::ReplicaOf),
+            ConfigItem::try_from("SLAVEOF".to_string())
+        );
+        assert_eq!(
+            Ok(ConfigItem::DbFilename),
+            ConfigItem::try_from("dbfilename".to_string())
+        );
+        assert_eq!(
+            Err("slave-unknown".to_string()),
+            ConfigItem::try_from("slave-unknown".to_string())
+        );
+    }
+
+    #[test]
+    fn test_config_get_replies_with_requested_name() {
+        init_test_db();
+
+        let response = config_get(vec!["SLAVE-READ-ONLY", "replica-read-only"]).unwrap();
+
+        assert_eq!(
+            RedisMessageType::bulk_string_array(vec![
+                "slave-read-only",
+                "yes",
+                "replica-read-only",
+                "yes"
+            ]),
+            response
+        );
+    }
+
+    #[test]
+    fn test_config_get_unknown_item() {
+        assert!(config_get(vec!["hash-max-ziplist-entries"]).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_actions_reply_with_error() {
+        for action in ["RESETSTAT", "REWRITE"] {
+            let args = VecDeque::from([RedisMessageType::bulk_string(action)]);
+            assert!(ConfigCommand::parse(args).unwrap().execute().is_err());
+        }
+    }
+
+    fn config_set(name: &str, value: &str) -> Result<RedisMessageType, RedisMessageType> {
+        let args = ["SET", name, value].map(RedisMessageType::bulk_string);
+        return ConfigCommand::parse(VecDeque::from(args))?.execute();
+    }
+
+    #[test]
+    fn test_config_set_memory_value() {
+        init_test_db();
+
+        assert_eq!(Ok(reply::ok()), config_set("maxmemory", "2gb"));
+        assert_eq!(
+            RedisMessageType::bulk_string_array(vec!["maxmemory", "2147483648"]),
+            config_get(vec!["maxmemory"]).unwrap()
+        );
+
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "ERR CONFIG SET failed (possibly related to argument 'maxmemory') - argument must be a memory value"
+            )),
+            config_set("maxmemory", "2 gb")
+        );
+        assert_eq!(
+            RedisMessageType::bulk_string_array(vec!["maxmemory", "2147483648"]),
+            config_get(vec!["maxmemory"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_set_masterauth() {
+        init_test_db();
+
+        assert_eq!(Ok(reply::ok()), config_set("masterauth", "secret"));
+        assert_eq!(
+            Some("secret".to_string()),
+            init_test_db().get_config().masterauth
+        );
+
+        assert_eq!(Ok(reply::ok()), config_set("masterauth", ""));
+        assert_eq!(None, init_test_db().get_config().masterauth);
+    }
+
+    #[test]
+    fn test_config_set_invalid_values() {
+        init_test_db();
+
+        for (name, value, reason) in [
+            (
+                "replica-read-only",
+                "maybe",
+                "argument must be 'yes' or 'no'",
+            ),
+            (
+                "dbfilename",
+                "dir/dump.rdb",
+                "dbfilename can't be a path, just a filename",
+            ),
+            ("proto-max-bulk-len", "1mb", "can't set immutable config"),
+            (
+                "maxmemory-policy",
+                "allkeys-lfu",
+                "LFU policies are not supported, access frequencies are not tracked",
+            ),
+            (
+                "maxmemory-samples",
+                "0",
+                "argument must be a positive integer",
+            ),
+        ] {
+            let expected = format!(
+                "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                name, reason
+            );
+            assert_eq!(
+                Err(RedisMessageType::error(expected)),
+                config_set(name, value)
+            );
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Command conformance suite.
+//!
+//! Every scenario is a list of commands with the RESP encoded reply real redis gives for
+//! them. The commands run through the same steps as a client command (dispatch, middlewares,
+//! parse and execute) on a single connection, so a scenario can build on the previous
+//! commands. New commands add a scenario here instead of ad-hoc reply tests.
+//!
+//! All tests share one data store, so keys are prefixed with the name of the scenario.
+
+use std::{
+    collections::VecDeque,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+use crate::{
+    commands::{command::UnparsedCommandType, context::ConnectionContext, middleware},
+    db::data_store::init_test_db,
+    parser::messages::RedisMessageType,
+};
+
+type Scenario = &'static [(&'static [&'static str], &'static str)];
+
+const PING: Scenario = &[
+    (&["PING"], "+PONG\r\n"),
+    (&["ping", "hello"], "$5\r\nhello\r\n"),
+    (
+        &["PING", "a", "b"],
+        "-ERR wrong number of arguments for 'ping' command\r\n",
+    ),
+];
+
+const ECHO: Scenario = &[
+    (&["ECHO", "hey"], "$3\r\nhey\r\n"),
+    (&["ECHO", ""], "$0\r\n\r\n"),
+    (
+        &["ECHO"],
+        "-ERR wrong number of arguments for 'echo' command\r\n",
+    ),
+];
+
+const SET_GET: Scenario = &[
+    (&["GET", "set_get:key"], "$-1\r\n"),
+    (&["SET", "set_get:key", "value"], "+OK\r\n"),
+    (&["GET", "set_get:key"], "$5\r\nvalue\r\n"),
+    (&["SET", "set_get:key", "other", "GET"], "$5\r\nvalue\r\n"),
+    (&["SET", "set_get:new", "value", "GET"], "$-1\r\n"),
+    (&["SET", "set_get:key", "ttl", "PX", "100000"], "+OK\r\n"),
+    (&["SET", "set_get:key", "kept", "KEEPTTL"], "+OK\r\n"),
+    (&["GET", "set_get:key"], "$4\r\nkept\r\n"),
+    (
+        &["SET", "set_get:key", "value", "EX", "soon"],
+        "-ERR value is not an integer or out of range\r\n",
+    ),
+    (
+        &["SET", "set_get:key", "value", "EX", "0"],
+        "-ERR invalid expire time in 'set' command\r\n",
+    ),
+    (
+        &["SET", "set_get:key", "value", "PXAT", "-5"],
+        "-ERR invalid expire time in 'set' command\r\n",
+    ),
+    (
+        &["SET", "set_get:key", "value", "EX"],
+        "-ERR syntax error\r\n",
+    ),
+    (
+        &["SET", "set_get:key", "value", "FOREVER"],
+        "-ERR syntax error\r\n",
+    ),
+    (
+        &["GET"],
+        "-ERR wrong number of arguments for 'get' command\r\n",
+    ),
+    (
+        &["SET", "set_get:key"],
+        "-ERR wrong number of arguments for 'set' command\r\n",
+    ),
+];
+
+const GETRANGE: Scenario = &[
+    (&["GETRANGE", "getrange:missing", "0", "-1"], "$0\r\n\r\n"),
+    (&["SET", "getrange:key", "This is a string"], "+OK\r\n"),
+    (&["GETRANGE", "getrange:key", "0", "3"], "$4\r\nThis\r\n"),
+    (&["GETRANGE", "getrange:key", "-3", "-1"], "$3\r\ning\r\n"),
+    (
+        &["GETRANGE", "getrange:key", "10", "100"],
+        "$6\r\nstring\r\n",
+    ),
+    (&["GETRANGE", "getrange:key", "5", "3"], "$0\r\n\r\n"),
+    (&["SUBSTR", "getrange:key", "0", "3"], "$4\r\nThis\r\n"),
+    (
+        &["GETRANGE", "getrange:key", "start", "3"],
+        "-ERR value is not an integer or out of range\r\n",
+    ),
+    (
+        &["GETRANGE", "getrange:key", "0"],
+        "-ERR wrong number of arguments for 'getrange' command\r\n",
+    ),
+];
+
+const SCAN: Scenario = &[
+    (&["SET", "scan:only", "value"], "+OK\r\n"),
+    // a count above the amount of keys finishes the scan in one step
+    (
+        &["SCAN", "0", "MATCH", "scan:*", "COUNT", "1000000000"],
+        "*2\r\n$1\r\n0\r\n*1\r\n$9\r\nscan:only\r\n",
+    ),
+    (
+        &[
+            "SCAN",
+            "0",
+            "MATCH",
+            "scan:*",
+            "COUNT",
+            "1000000000",
+            "TYPE",
+            "hash",
+        ],
+        "*2\r\n$1\r\n0\r\n*0\r\n",
+    ),
+    (&["SCAN", "-1"], "-ERR invalid cursor\r\n"),
+    (&["SCAN", "0", "COUNT", "0"], "-ERR syntax error\r\n"),
+    (&["SCAN", "0", "MATCH"], "-ERR syntax error\r\n"),
+    (
+        &["SCAN", "0", "TYPE", "tree"],
+        "-ERR unknown type name 'tree'\r\n",
+    ),
+    (
+        &["SCAN"],
+        "-ERR wrong number of arguments for 'scan' command\r\n",
+    ),
+];
+
+const RENAME: Scenario = &[
+    (
+        &["RENAME", "rename:missing", "rename:dst"],
+        "-ERR no such key\r\n",
+    ),
+    (&["SET", "rename:src", "value"], "+OK\r\n"),
+    (&["RENAME", "rename:src", "rename:dst"], "+OK\r\n"),
+    (&["GET", "rename:src"], "$-1\r\n"),
+    (&["GET", "rename:dst"], "$5\r\nvalue\r\n"),
+    (&["RENAME", "rename:dst", "rename:dst"], "+OK\r\n"),
+    (
+        &["RENAME", "rename:dst"],
+        "-ERR wrong number of arguments for 'rename' command\r\n",
+    ),
+];
+
+const DEL: Scenario = &[
+    (&["SET", "del:first", "value"], "+OK\r\n"),
+    (&["SET", "del:second", "value"], "+OK\r\n"),
+    (&["DEL", "del:first", "del:second", "del:missing"], ":2\r\n"),
+    (&["GET", "del:first"], "$-1\r\n"),
+    (&["DEL", "del:first"], ":0\r\n"),
+    (
+        &["DEL"],
+        "-ERR wrong number of arguments for 'del' command\r\n",
+    ),
+];
+
+const COPY: Scenario = &[
+    (&["COPY", "copy:missing", "copy:dst"], ":0\r\n"),
+    (&["SET", "copy:src", "value"], "+OK\r\n"),
+    (&["COPY", "copy:src", "copy:dst"], ":1\r\n"),
+    (&["COPY", "copy:src", "copy:dst"], ":0\r\n"),
+    (&["COPY", "copy:src", "copy:dst", "REPLACE"], ":1\r\n"),
+    (&["GET", "copy:dst"], "$5\r\nvalue\r\n"),
+    (
+        &["COPY", "copy:src", "copy:dst", "FORCE"],
+        "-ERR syntax error\r\n",
+    ),
+];
+
+const KEYS: Scenario = &[
+    (&["KEYS", "keys:*"], "*0\r\n"),
+    (&["SET", "keys:a", "value"], "+OK\r\n"),
+    (&["KEYS", "keys:[a-c]"], "*1\r\n$6\r\nkeys:a\r\n"),
+    (&["KEYS", "keys:?b"], "*0\r\n"),
+];
+
+const CLIENT: Scenario = &[
+    (&["CLIENT", "GETNAME"], "$-1\r\n"),
+    (&["CLIENT", "SETNAME", "conformance"], "+OK\r\n"),
+    (&["CLIENT", "GETNAME"], "$11\r\nconformance\r\n"),
+    (
+        &["CLIENT", "SETNAME", "with space"],
+        "-ERR Client names cannot contain spaces, newlines or special characters.\r\n",
+    ),
+    (
+        &["CLIENT", "NOPE"],
+        "-ERR unknown subcommand 'NOPE'. Try CLIENT HELP.\r\n",
+    ),
+];
+
+const WAIT: Scenario = &[
+    (&["WAIT", "0", "0"], ":0\r\n"),
+    (&["WAIT", "1", "-1"], "-ERR timeout is negative\r\n"),
+    (
+        &["WAIT", "1", "soon"],
+        "-ERR timeout is not an integer or out of range\r\n",
+    ),
+];
+
+const OBJECT: Scenario = &[
+    (&["OBJECT", "IDLETIME", "object:missing"], "$-1\r\n"),
+    (&["SET", "object:key", "value"], "+OK\r\n"),
+    (&["OBJECT", "IDLETIME", "object:key"], ":0\r\n"),
+    (&["OBJECT", "REFCOUNT", "object:key"], ":1\r\n"),
+    (&["OBJECT", "ENCODING", "object:key"], "$6\r\nembstr\r\n"),
+    (&["SET", "object:int", "12"], "+OK\r\n"),
+    (&["OBJECT", "ENCODING", "object:int"], "$3\r\nint\r\n"),
+    // integers of the shared pool are referenced by every key holding them
+    (&["OBJECT", "REFCOUNT", "object:int"], ":2147483647\r\n"),
+    (&["SET", "object:int", "10000"], "+OK\r\n"),
+    (&["OBJECT", "REFCOUNT", "object:int"], ":1\r\n"),
+    (
+        &["OBJECT", "idletime"],
+        "-ERR wrong number of arguments for 'object|idletime' command\r\n",
+    ),
+    (
+        &["OBJECT", "NOPE"],
+        "-ERR unknown subcommand 'NOPE'. Try OBJECT HELP.\r\n",
+    ),
+];
+
+const SUBCOMMAND_ERRORS: Scenario = &[
+    (
+        &["CONFIG", "GET"],
+        "-ERR wrong number of arguments for 'config|get' command\r\n",
+    ),
+    (
+        &["CONFIG", "Set", "dir"],
+        "-ERR wrong number of arguments for 'config|set' command\r\n",
+    ),
+    (
+        &["CONFIG", "nope"],
+        "-ERR unknown subcommand 'nope'. Try CONFIG HELP.\r\n",
+    ),
+    (
+        &["DEBUG", "SHARDS", "extra"],
+        "-ERR Unknown subcommand or wrong number of arguments for 'SHARDS'. Try DEBUG HELP.\r\n",
+    ),
+    (
+        &["CLIENT", "SETINFO", "lib-name"],
+        "-ERR wrong number of arguments for 'client|setinfo' command\r\n",
+    ),
+];
+
+const COMMAND: Scenario = &[
+    (&["COMMAND", "LIST", "FILTERBY", "MODULE", "nope"], "*0\r\n"),
+    (&["command", "list", "filterby", "aclcat", "nope"], "*0\r\n"),
+    (
+        &["COMMAND", "LIST", "FILTERBY", "PATTERN"],
+        "-ERR syntax error\r\n",
+    ),
+    (
+        &["COMMAND", "COUNT", "a"],
+        "-ERR wrong number of arguments for 'command|count' command\r\n",
+    ),
+    (
+        &["COMMAND", "NOPE"],
+        "-ERR unknown subcommand 'NOPE'. Try COMMAND HELP.\r\n",
+    ),
+];
+
+const UNKNOWN_COMMAND: Scenario = &[(
+    &["NOPE", "a", "b"],
+    "-ERR unknown command 'NOPE', with args beginning with: 'a' 'b' \r\n",
+)];
+
+/// Runs a command like the connection loop does and returns its reply.
+fn run(ctx: &ConnectionContext, args: &[&str]) -> RedisMessageType {
+    let args = args.iter().map(|arg| RedisMessageType::bulk_string(*arg));
+
+    let reply = UnparsedCommandType::new(VecDeque::from_iter(args)).and_then(|command| {
+        middleware::before_execute(ctx, &command)?;
+        return command.parse()?.execute(ctx);
+    });
+    return match reply {
+        Ok(reply) => reply,
+        Err(reply) => reply,
+    };
+}
+
+fn check(scenario: Scenario) {
+    init_test_db();
+    let ctx = ConnectionContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1));
+
+    for (args, expected) in scenario {
+        assert_eq!(
+            expected.as_bytes(),
+            run(&ctx, args).encode(),
+            "unexpected reply for {:?}",
+            args
+        );
+    }
+}
+
+#[test]
+fn test_ping() {
+    check(PING);
+}
+
+#[test]
+fn test_echo() {
+    check(ECHO);
+}
+
+#[test]
+fn test_set_get() {
+    check(SET_GET);
+}
+
+#[test]
+fn test_getrange() {
+    check(GETRANGE);
+}
+
+#[test]
+fn test_scan() {
+    check(SCAN);
+}
+
+#[test]
+fn test_rename() {
+    check(RENAME);
+}
+
+#[test]
+fn test_del() {
+    check(DEL);
+}
+
+#[test]
+fn test_copy() {
+    check(COPY);
+}
+
+#[test]
+fn test_keys() {
+    check(KEYS);
+}
+
+#[test]
+fn test_client() {
+    check(CLIENT);
+}
+
+#[test]
+fn test_wait() {
+    check(WAIT);
+}
+
+#[test]
+fn test_object() {
+    check(OBJECT);
+}
+
+#[test]
+fn test_subcommand_errors() {
+    check(SUBCOMMAND_ERRORS);
+}
+
+#[test]
+fn test_command() {
+    check(COMMAND);
+}
+
+#[test]
+fn test_unknown_command() {
+    check(UNKNOWN_COMMAND);
+}
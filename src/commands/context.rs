@@ -0,0 +1,359 @@
+use std::{
+    collections::BTreeMap,
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use once_cell::sync::{Lazy, OnceCell};
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+static CLIENTS: Lazy<RwLock<BTreeMap<u64, Arc<Client>>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// State of a single client connection, shared by all commands executed on it.
+///
+/// The client is listed in the client registry (CLIENT LIST) until the context is dropped.
+#[derive(Debug)]
+pub struct ConnectionContext {
+    pub peer: SocketAddr,
+    client: Arc<Client>,
+}
+
+impl ConnectionContext {
+    pub fn new(peer: SocketAddr) -> Self {
+        return Self::with_kind(peer, ConnectionKind::Client);
+    }
+
+    pub fn with_kind(peer: SocketAddr, kind: ConnectionKind) -> Self {
+        let client = Arc::new(Client::new(peer, kind));
+        CLIENTS
+            .write()
+            .expect("Unable to write the client registry. Should never happen!")
+            .insert(client.id, Arc::clone(&client));
+
+        return Self { peer, client };
+    }
+
+    pub fn client(&self) -> &Client {
+        return &self.client;
+    }
+}
+
+impl Drop for ConnectionContext {
+    fn drop(&mut self) {
+        CLIENTS
+            .write()
+            .expect("Unable to write the client registry. Should never happen!")
+            .remove(&self.client.id);
+    }
+}
+
+/// Returns all connected clients ordered by their id.
+pub fn get_clients() -> Vec<Arc<Client>> {
+    return CLIENTS
+        .read()
+        .expect("Unable to read the client registry. Should never happen!")
+        .values()
+        .cloned()
+        .collect();
+}
+
+/// What a connection is used for. The connections of the replication link are no regular
+/// clients: commands are applied without replies and checks meant for clients are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionKind {
+    Client,
+    /// On a replica, the connection to its master. Only `REPLCONF GETACK` is answered.
+    MasterLink,
+    /// On a master, the connection of a replica after PSYNC. Only acks are read from it.
+    ReplicaLink,
+}
+
+impl ConnectionKind {
+    /// The flag shown in CLIENT LIST, like redis.
+    pub fn flag(&self) -> char {
+        return match self {
+            Self::Client => 'N',
+            Self::MasterLink => 'M',
+            Self::ReplicaLink => 'S',
+        };
+    }
+
+    /// The client type of redis, used by the TYPE filters of CLIENT LIST and CLIENT KILL.
+    pub const fn type_name(&self) -> &'static str {
+        return match self {
+            Self::Client => "normal",
+            Self::MasterLink => "master",
+            Self::ReplicaLink => "replica",
+        };
+    }
+
+    /// Whether the result of a command is send back on this connection.
+    pub fn sends_replies(&self) -> bool {
+        return *self == Self::Client;
+    }
+}
+
+/// A client connection as listed by CLIENT LIST.
+#[derive(Debug)]
+pub struct Client {
+    id: u64,
+    addr: SocketAddr,
+    created_at: Instant,
+    state: Mutex<ClientState>,
+    /// A handle of the connection to close it from other threads, see [`Client::kill`].
+    socket: OnceCell<TcpStream>,
+    killed: AtomicBool,
+}
+
+/// The parts of a client that change while it is connected.
+#[derive(Debug, Clone)]
+pub struct ClientState {
+    /// Changes once a client becomes a replica with PSYNC.
+    pub kind: ConnectionKind,
+    pub name: Option<String>,
+    /// Set by client libraries with `CLIENT SETINFO LIB-NAME`.
+    pub lib_name: Option<String>,
+    /// Set by client libraries with `CLIENT SETINFO LIB-VER`.
+    pub lib_ver: Option<String>,
+    /// Name of the last command, container commands include the subcommand (`client|list`).
+    pub last_command: Option<String>,
+    pub last_interaction: Instant,
+    pub total_commands: u64,
+    /// Bytes read from the client.
+    pub total_net_in: u64,
+    /// Bytes of replies written to the client.
+    pub total_net_out: u64,
+    /// Replication offset right after the last write of the client, WAIT waits for it.
+    pub last_write_offset: u64,
+    /// Offered by a replica with `REPLCONF capa lz4`, its replication stream is compressed.
+    pub repl_compression: bool,
+}
+
+impl Client {
+    fn new(addr: SocketAddr, kind: ConnectionKind) -> Self {
+        let now = Instant::now();
+        return Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst),
+            addr,
+            created_at: now,
+            state: Mutex::new(ClientState {
+                kind,
+                name: None,
+                lib_name: None,
+                lib_ver: None,
+                last_command: None,
+                last_interaction: now,
+                total_commands: 0,
+                total_net_in: 0,
+                total_net_out: 0,
+                last_write_offset: 0,
+                repl_compression: false,
+            }),
+            socket: OnceCell::new(),
+            killed: AtomicBool::new(false),
+        };
+    }
+
+    pub fn id(&self) -> u64 {
+        return self.id;
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        return self.addr;
+    }
+
+    /// Time since the client connected.
+    pub fn age(&self) -> Duration {
+        return self.created_at.elapsed();
+    }
+
+    /// Lets [`Client::kill`] close the connection, `socket` is a clone of its stream.
+    pub fn attach_socket(&self, socket: TcpStream) {
+        let _ = self.socket.set(socket);
+    }
+
+    /// Closes the connection of the client. The thread serving it notices once its read
+    /// fails. `closes_itself` is for a client killing its own connection: the reply still
+    /// has to be written, the connection is closed after it, see [`Client::is_killed`].
+    pub fn kill(&self, closes_itself: bool) {
+        self.killed.store(true, Ordering::SeqCst);
+        if closes_itself {
+            return;
+        }
+        if let Some(Err(err)) = self
+            .socket
+            .get()
+            .map(|socket| socket.shutdown(Shutdown::Both))
+        {
+            debug!(
+                "Unable to shut down the connection of client {}: {}",
+                self.id, err
+            );
+        }
+    }
+
+    /// Closes the reading half of the connection, e.g. on shutdown. The thread serving it
+    /// notices once its read fails, a reply can still be written.
+    pub fn stop_reading(&self) {
+        if let Some(Err(err)) = self
+            .socket
+            .get()
+            .map(|socket| socket.shutdown(Shutdown::Read))
+        {
+            debug!("Unable to stop reading from client {}: {}", self.id, err);
+        }
+    }
+
+    pub fn is_killed(&self) -> bool {
+        return self.killed.load(Ordering::SeqCst);
+    }
+
+    pub fn kind(&self) -> ConnectionKind {
+        return self
+            .state
+            .lock()
+            .expect("Client state lock poisoned. Should never happen!")
+            .kind;
+    }
+
+    pub fn state(&self) -> ClientState {
+        return self
+            .state
+            .lock()
+            .expect("Client state lock poisoned. Should never happen!")
+            .clone();
+    }
+
+    pub fn update_state<F: FnOnce(&mut ClientState)>(&self, f: F) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Client state lock poisoned. Should never happen!");
+        f(&mut state);
+    }
+
+    /// Remembers the command as the last one the client executed.
+    pub fn record_command(&self, command: String) {
+        self.update_state(|state| {
+            state.last_command = Some(command);
+            state.last_interaction = Instant::now();
+            state.total_commands += 1;
+        });
+    }
+
+    pub fn record_net_in(&self, bytes: usize) {
+        self.update_state(|state| state.total_net_in += bytes as u64);
+    }
+
+    pub fn record_net_out(&self, bytes: usize) {
+        self.update_state(|state| state.total_net_out += bytes as u64);
+    }
+
+    pub fn record_write_offset(&self, offset: u64) {
+        self.update_state(|state| state.last_write_offset = offset);
+    }
+
+    pub fn last_write_offset(&self) -> u64 {
+        return self
+            .state
+            .lock()
+            .expect("Client state lock poisoned. Should never happen!")
+            .last_write_offset;
+    }
+
+    /// A single line of the CLIENT LIST / CLIENT INFO output, without the trailing newline.
+    pub fn describe(&self) -> String {
+        let state = self.state();
+        return format!(
+            "id={} addr={} name={} age={} idle={} flags={} db=0 cmd={} lib-name={} lib-ver={} tot-net-in={} tot-net-out={} tot-cmds={}",
+            self.id,
+            self.addr,
+            state.name.unwrap_or_default(),
+            self.created_at.elapsed().as_secs(),
+            state.last_interaction.elapsed().as_secs(),
+            state.kind.flag(),
+            state.last_command.unwrap_or("NULL".to_string()),
+            state.lib_name.unwrap_or_default(),
+            state.lib_ver.unwrap_or_default(),
+            state.total_net_in,
+            state.total_net_out,
+            state.total_commands,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use crate::commands::context::{get_clients, ConnectionContext, ConnectionKind};
+
+    fn ctx() -> ConnectionContext {
+        return ConnectionContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1));
+    }
+
+    #[test]
+    fn test_client_registry() {
+        let first = ctx();
+        let second = ctx();
+        let is_registered = |id: u64| get_clients().iter().any(|client| client.id() == id);
+
+        assert!(first.client().id() < second.client().id());
+        assert!(is_registered(first.client().id()));
+
+        let id = second.client().id();
+        drop(second);
+        assert!(!is_registered(id));
+    }
+
+    #[test]
+    fn test_describe_client() {
+        let ctx = ctx();
+        ctx.client().record_command("get".to_string());
+        ctx.client().record_command("client|list".to_string());
+        ctx.client().record_net_in(14);
+        ctx.client().record_net_out(5);
+        ctx.client()
+            .update_state(|state| state.lib_name = Some("redis-py".to_string()));
+
+        let description = ctx.client().describe();
+
+        assert!(description.starts_with(&format!("id={} addr=127.0.0.1:1 ", ctx.client().id())));
+        assert!(description.contains(" cmd=client|list "));
+        assert!(description.contains(" lib-name=redis-py lib-ver= "));
+        assert!(description.ends_with(" tot-net-in=14 tot-net-out=5 tot-cmds=2"));
+    }
+
+    #[test]
+    fn test_last_write_offset() {
+        let writer = ctx();
+        let other = ctx();
+        assert_eq!(0, writer.client().last_write_offset());
+
+        writer.client().record_write_offset(42);
+
+        assert_eq!(42, writer.client().last_write_offset());
+        assert_eq!(0, other.client().last_write_offset());
+    }
+
+    #[test]
+    fn test_connection_kind_flags() {
+        let ctx = ctx();
+        assert!(ctx.client().describe().contains(" flags=N "));
+
+        ctx.client()
+            .update_state(|state| state.kind = ConnectionKind::ReplicaLink);
+        assert_eq!(ConnectionKind::ReplicaLink, ctx.client().kind());
+        assert!(ctx.client().describe().contains(" flags=S "));
+
+        let master = ConnectionContext::with_kind(ctx.peer, ConnectionKind::MasterLink);
+        assert!(master.client().describe().contains(" flags=M "));
+        assert!(!master.client().kind().sends_replies());
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+};
+
+pub struct CopyCommand {
+    source: String,
+    destination: String,
+    replace: bool,
+}
+
+impl CopyCommand {
+    fn new(source: String, destination: String, replace: bool) -> Self {
+        return Self {
+            source,
+            destination,
+            replace,
+        };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for CopyCommand {
+    fn command_name() -> &'static str {
+        return "copy";
+    }
+}
+impl ArgErrorMessageGenerator<CopyCommand> for CopyCommand {}
+
+impl Parse for CopyCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let (source, destination) = match (args.pop_front(), args.pop_front()) {
+            (Some(source), Some(destination)) => (
+                source.bulk_string_value()?,
+                destination.bulk_string_value()?,
+            ),
+            _ => return Err(Self::arg_count_error()),
+        };
+
+        // there is only a single database, so DB is not supported
+        let replace = match args.pop_front() {
+            None => false,
+            Some(arg) if arg.bulk_string_value()?.eq_ignore_ascii_case("REPLACE") => true,
+            Some(_) => return Err(reply::syntax_error()),
+        };
+
+        if !args.is_empty() {
+            return Err(reply::syntax_error());
+        }
+
+        return Ok(Self::new(source, destination, replace));
+    }
+}
+
+impl Execute for CopyCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let copied = get_db().copy(&self.source, &self.destination, self.replace);
+        return Ok(reply::integer(copied as i64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            copy::CopyCommand,
+            traits::{Execute, Parse},
+        },
+        db::data_store::{init_test_db, DataUnit},
+        parser::messages::RedisMessageType,
+    };
+
+    fn copy(args: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return CopyCommand::parse(VecDeque::from_iter(args))?.execute();
+    }
+
+    #[test]
+    fn test_copy() {
+        init_test_db().set("copy_source", DataUnit::new("copy_source", "value", None));
+
+        assert_eq!(
+            Ok(RedisMessageType::Integer(1)),
+            copy(vec!["copy_source", "copy_destination"])
+        );
+        assert_eq!(
+            Ok(RedisMessageType::Integer(0)),
+            copy(vec!["copy_source", "copy_destination"])
+        );
+        assert_eq!(
+            Ok(RedisMessageType::Integer(1)),
+            copy(vec!["copy_source", "copy_destination", "replace"])
+        );
+        assert_eq!(
+            Ok(RedisMessageType::Integer(0)),
+            copy(vec!["copy_missing", "copy_destination"])
+        );
+    }
+
+    #[test]
+    fn test_copy_invalid_args() {
+        assert!(copy(vec!["a"]).is_err());
+        assert!(copy(vec!["a", "b", "DB", "1"]).is_err());
+        assert!(copy(vec!["a", "b", "REPLACE", "REPLACE"]).is_err());
+    }
+}
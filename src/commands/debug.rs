@@ -0,0 +1,478 @@
+use std::{collections::VecDeque, time::Duration};
+
+use rand::Rng;
+
+use crate::{
+    commands::{
+        reply::{self, ErrorCode},
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::{
+        data_store::get_db,
+        persistence,
+        ttl_stats::{TtlHistogram, TTL_SAMPLE_SIZE},
+        write_pause,
+    },
+    parser::{db_file::RdbValue, messages::RedisMessageType},
+    utils::{
+        buffer_pool::{self, PoolStats},
+        deadline,
+        glob::glob_match,
+    },
+};
+
+/// Longest string redis stores as `embstr`.
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// Iterations of DEBUG STRINGMATCH-LEN.
+const STRINGMATCH_FUZZ_ITERATIONS: usize = 100_000;
+
+enum Action {
+    Object(String),
+    StringMatchLen,
+    Shards,
+    TtlStats,
+    BufferPool,
+    PauseWrites(bool),
+    Sleep(Duration),
+    SleepAfterFork(Duration),
+    FailSaves(bool),
+    /// Panics, with the key locked if one is given.
+    Panic(Option<String>),
+    Help,
+}
+
+pub struct DebugCommand {
+    action: Action,
+}
+
+impl DebugCommand {
+    fn new(action: Action) -> Self {
+        return Self { action };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for DebugCommand {
+    fn command_name() -> &'static str {
+        return "debug";
+    }
+}
+impl ArgErrorMessageGenerator<DebugCommand> for DebugCommand {}
+
+impl Parse for DebugCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let sub_command = args
+            .pop_front()
+            .ok_or(Self::arg_count_error())?
+            .bulk_string_value()?;
+
+        let action = match sub_command.to_ascii_uppercase().as_str() {
+            "HELP" => Action::Help,
+            "STRINGMATCH-LEN" if args.is_empty() => Action::StringMatchLen,
+            "SHARDS" if args.is_empty() => Action::Shards,
+            "TTL-STATS" if args.is_empty() => Action::TtlStats,
+            "BUFFER-POOL" if args.is_empty() => Action::BufferPool,
+            "PAUSE-WRITES" => match (args.pop_front(), args.is_empty()) {
+                (Some(flag), true) => match flag.bulk_string_value()?.as_str() {
+                    "1" => Action::PauseWrites(true),
+                    "0" => Action::PauseWrites(false),
+                    _ => return Err(reply::syntax_error()),
+                },
+                _ => return Err(Self::sub_syntax_error(&sub_command)),
+            },
+            "SLEEP" => match (args.pop_front(), args.is_empty()) {
+                (Some(seconds), true) => Action::Sleep(parse_seconds(seconds)?),
+                _ => return Err(Self::sub_syntax_error(&sub_command)),
+            },
+            "SLEEP-AFTER-FORK-SECONDS" => match (args.pop_front(), args.is_empty()) {
+                (Some(seconds), true) => Action::SleepAfterFork(parse_seconds(seconds)?),
+                _ => return Err(Self::sub_syntax_error(&sub_command)),
+            },
+            "FAIL-SAVES" => match (args.pop_front(), args.is_empty()) {
+                (Some(flag), true) => match flag.bulk_string_value()?.as_str() {
+                    "1" => Action::FailSaves(true),
+                    "0" => Action::FailSaves(false),
+                    _ => return Err(reply::syntax_error()),
+                },
+                _ => return Err(Self::sub_syntax_error(&sub_command)),
+            },
+            "PANIC" => match (args.pop_front(), args.is_empty()) {
+                (key, true) => Action::Panic(key.map(|key| key.bulk_string_value()).transpose()?),
+                _ => return Err(Self::sub_syntax_error(&sub_command)),
+            },
+            "OBJECT" => match (args.pop_front(), args.is_empty()) {
+                (Some(key), true) => Action::Object(key.bulk_string_value()?),
+                _ => return Err(Self::sub_syntax_error(&sub_command)),
+            },
+            _ => return Err(Self::sub_syntax_error(&sub_command)),
+        };
+
+        return Ok(Self::new(action));
+    }
+}
+
+/// Seconds with decimals, e.g. `0.5`.
+fn parse_seconds(seconds: RedisMessageType) -> Result<Duration, RedisMessageType> {
+    return seconds
+        .bulk_string_value()?
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or(reply::error(ErrorCode::Err, "value is not a valid float"));
+}
+
+impl Execute for DebugCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        return match self.action {
+            Action::Help => Ok(reply::bulk_array(vec![
+                "DEBUG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                "OBJECT <key>",
+                "    Show low-level info about the key and associated value.",
+                "STRINGMATCH-LEN",
+                "    Run a fuzz tester against the glob matcher used by KEYS.",
+                "SHARDS",
+                "    Show the amount of keys and the capacity of each keyspace shard.",
+                "TTL-STATS",
+                "    Show a histogram of the remaining ttl of sampled keys with an expiry.",
+                "BUFFER-POOL",
+                "    Show the hits and misses of the pool of large buffers and the pooled",
+                "    buffers of each size class.",
+                "PAUSE-WRITES <0|1>",
+                "    Pause (1) or resume (0) the execution of all write commands. Returns once",
+                "    the writes in flight are done. Client writes wait for at most a second,",
+                "    then fail with an error.",
+                "SLEEP <seconds>",
+                "    Stop the server for <seconds>. Decimals allowed. Cancelled once the",
+                "    command-time-limit is reached.",
+                "SLEEP-AFTER-FORK-SECONDS <seconds>",
+                "    Delay every snapshot after it started, e.g. the one of a full resync, so",
+                "    the save can be observed in progress. Decimals allowed, 0 disables it.",
+                "FAIL-SAVES <0|1>",
+                "    Let every snapshot fail (1) or work again (0). A replica retries its",
+                "    full resync until snapshots work again.",
+                "PANIC [<key>]",
+                "    Panic while executing the command, with <key> locked if given. Only the",
+                "    connection of the client is closed, the server keeps running.",
+                "HELP",
+                "    Print this help.",
+            ])),
+            Action::StringMatchLen => {
+                stringmatch_fuzz_test(STRINGMATCH_FUZZ_ITERATIONS);
+                Ok(reply::status("Apparently Redis did not crash: test passed"))
+            }
+            Action::Shards => Ok(reply::bulk(describe_shards(&get_db().shard_stats()))),
+            Action::TtlStats => Ok(reply::bulk(describe_ttls(
+                &get_db().sample_ttls(TTL_SAMPLE_SIZE),
+            ))),
+            Action::BufferPool => Ok(reply::bulk(describe_buffer_pool(&buffer_pool::stats()))),
+            Action::PauseWrites(pause) => {
+                if pause {
+                    write_pause::pause();
+                } else {
+                    write_pause::resume();
+                }
+                Ok(reply::ok())
+            }
+            Action::Sleep(duration) => {
+                deadline::sleep(duration)?;
+                Ok(reply::ok())
+            }
+            Action::SleepAfterFork(delay) => {
+                persistence::set_sleep_after_fork(delay);
+                Ok(reply::ok())
+            }
+            Action::FailSaves(fail) => {
+                persistence::set_fail_saves(fail);
+                Ok(reply::ok())
+            }
+            Action::Panic(None) => panic!("DEBUG PANIC"),
+            Action::Panic(Some(key)) => {
+                get_db().with_locked_keys(&[&key], |_| panic!("DEBUG PANIC with '{}' locked", key))
+            }
+            Action::Object(key) => {
+                let data = get_db().peek(key).ok_or(reply::no_such_key())?;
+
+                Ok(reply::status(format!(
+                    "refcount:{} encoding:{} serializedlength:{}",
+                    data.value.ref_count(),
+                    string_encoding(&data.value),
+                    RdbValue::String(data.value.to_string()).encode().len()
+                )))
+            }
+        };
+    }
+}
+
+/// Feeds random patterns made of the special chars of the glob matcher and random values
+/// into it. Passes as long as nothing panics or hangs.
+fn stringmatch_fuzz_test(iterations: usize) {
+    const CHARS: &[u8] = b"*?[]^-\\ab";
+    let mut rng = rand::rng();
+    let mut random_string = |max_length: usize| -> String {
+        let length = rng.random_range(0..=max_length);
+        return (0..length)
+            .map(|_| CHARS[rng.random_range(0..CHARS.len())] as char)
+            .collect();
+    };
+
+    for _ in 0..iterations {
+        let pattern = random_string(32);
+        let value = random_string(32);
+        glob_match(&pattern, &value);
+    }
+}
+
+/// Summary line followed by one line per shard, a shard holding far more keys than the
+/// average points to hot keys contending on the same lock.
+fn describe_shards(shards: &[(usize, usize)]) -> String {
+    let keys = shards.iter().map(|(keys, _)| keys).sum::<usize>();
+    let min = shards.iter().map(|(keys, _)| *keys).min().unwrap_or(0);
+    let max = shards.iter().map(|(keys, _)| *keys).max().unwrap_or(0);
+    let avg = keys as f64 / shards.len().max(1) as f64;
+
+    let mut output = format!(
+        "shards:{} keys:{} min:{} max:{} avg:{:.2}\n",
+        shards.len(),
+        keys,
+        min,
+        max,
+        avg
+    );
+    for (index, (keys, capacity)) in shards.iter().enumerate() {
+        output.push_str(&format!(
+            "shard:{} keys:{} capacity:{}\n",
+            index, keys, capacity
+        ));
+    }
+    return output;
+}
+
+fn describe_ttls(histogram: &TtlHistogram) -> String {
+    let mut output = format!(
+        "samples:{} avg_ttl:{}\n",
+        histogram.samples(),
+        histogram.avg_ttl_millis()
+    );
+    for (bucket, count) in histogram.buckets() {
+        output.push_str(&format!("{}:{}\n", bucket, count));
+    }
+    return output;
+}
+
+fn describe_buffer_pool(stats: &PoolStats) -> String {
+    let mut output = format!(
+        "hits:{} misses:{} returned:{} discarded:{} pooled_bytes:{}\n",
+        stats.hits, stats.misses, stats.returned, stats.discarded, stats.pooled_bytes
+    );
+    for (size, buffers) in &stats.classes {
+        output.push_str(&format!("class:{} buffers:{}\n", size, buffers));
+    }
+    return output;
+}
+
+/// The encoding redis would pick for a string value.
+pub fn string_encoding(value: &str) -> &'static str {
+    // only canonical integers are stored as int, e.g. "01" or "+1" are not
+    let is_int = value
+        .parse::<i64>()
+        .is_ok_and(|int| int.to_string() == value);
+
+    if is_int {
+        return "int";
+    } else if value.len() <= EMBSTR_SIZE_LIMIT {
+        return "embstr";
+    }
+    return "raw";
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use crate::{
+        commands::{
+            debug::{
+                describe_buffer_pool, describe_shards, describe_ttls, string_encoding, DebugCommand,
+            },
+            reply,
+            traits::{Execute, Parse},
+        },
+        db::{
+            data_store::{init_test_db, DataUnit, Expiry},
+            ttl_stats::TtlHistogram,
+            value::SHARED_REF_COUNT,
+        },
+        parser::messages::RedisMessageType,
+        utils::{buffer_pool::PoolStats, deadline},
+    };
+
+    fn debug(args: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return DebugCommand::parse(VecDeque::from_iter(args))?.execute();
+    }
+
+    #[test]
+    fn test_debug_sleep_is_cancelled() {
+        assert_eq!(Ok(reply::ok()), debug(vec!["SLEEP", "0.01"]));
+        assert_eq!(
+            Err(RedisMessageType::error("ERR value is not a valid float")),
+            debug(vec!["SLEEP", "-1"])
+        );
+
+        let result = deadline::with_budget(Some(Duration::from_millis(10)), || {
+            return debug(vec!["SLEEP", "5"]);
+        });
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "ERR command cancelled after exceeding the time limit of 10 ms"
+            )),
+            result
+        );
+    }
+
+    #[test]
+    fn test_debug_object_serialized_length() {
+        let value = "x".repeat(100);
+        init_test_db().set("debug_object", DataUnit::new("debug_object", &value, None));
+
+        let response = debug(vec!["OBJECT", "debug_object"]).unwrap();
+
+        assert_eq!(
+            RedisMessageType::simple_string("refcount:1 encoding:raw serializedlength:102"),
+            response
+        );
+    }
+
+    #[test]
+    fn test_debug_object_missing_key() {
+        init_test_db();
+        let response = debug(vec!["object", "debug_object_missing"]);
+
+        assert_eq!(Err(RedisMessageType::error("ERR no such key")), response);
+    }
+
+    #[test]
+    fn test_debug_stringmatch_len() {
+        assert_eq!(
+            Ok(RedisMessageType::simple_string(
+                "Apparently Redis did not crash: test passed"
+            )),
+            debug(vec!["STRINGMATCH-LEN"])
+        );
+    }
+
+    #[test]
+    fn test_debug_invalid_args() {
+        assert!(debug(vec![]).is_err());
+        assert!(debug(vec!["OBJECT"]).is_err());
+        assert!(debug(vec!["OBJECT", "a", "b"]).is_err());
+        assert!(debug(vec!["unknown"]).is_err());
+        assert!(debug(vec!["STRINGMATCH-LEN", "a"]).is_err());
+        assert!(debug(vec!["PAUSE-WRITES"]).is_err());
+        assert!(debug(vec!["PAUSE-WRITES", "yes"]).is_err());
+    }
+
+    #[test]
+    fn test_debug_object_int() {
+        init_test_db().set(
+            "debug_object_int",
+            DataUnit::new("debug_object_int", "1000", None),
+        );
+
+        let response = debug(vec!["OBJECT", "debug_object_int"]).unwrap();
+
+        assert_eq!(
+            RedisMessageType::simple_string(format!(
+                "refcount:{} encoding:int serializedlength:3",
+                SHARED_REF_COUNT
+            )),
+            response
+        );
+    }
+
+    #[test]
+    fn test_describe_shards() {
+        assert_eq!(
+            "shards:2 keys:3 min:1 max:2 avg:1.50\nshard:0 keys:2 capacity:3\nshard:1 keys:1 capacity:3\n",
+            describe_shards(&[(2, 3), (1, 3)])
+        );
+    }
+
+    #[test]
+    fn test_debug_shards() {
+        init_test_db().set("debug_shards", DataUnit::new("debug_shards", "value", None));
+
+        let response = debug(vec!["SHARDS"]).unwrap().bulk_string_value().unwrap();
+
+        assert!(response.starts_with("shards:"));
+        assert!(response
+            .lines()
+            .skip(1)
+            .all(|line| line.starts_with("shard:")));
+    }
+
+    #[test]
+    fn test_describe_ttls() {
+        let mut histogram = TtlHistogram::default();
+        histogram.record(Duration::from_secs(2));
+        histogram.record(Duration::from_secs(4));
+
+        assert_eq!(
+            "samples:2 avg_ttl:3000\n<1s:0\n<10s:2\n<1m:0\n<10m:0\n<1h:0\n<1d:0\n>=1d:0\n",
+            describe_ttls(&histogram)
+        );
+    }
+
+    #[test]
+    fn test_debug_ttl_stats() {
+        init_test_db().set(
+            "debug_ttl_stats",
+            DataUnit::new(
+                "debug_ttl_stats",
+                "value",
+                Some(Expiry::Ttl(Duration::from_secs(100))),
+            ),
+        );
+
+        let response = debug(vec!["TTL-STATS"])
+            .unwrap()
+            .bulk_string_value()
+            .unwrap();
+
+        assert!(response.starts_with("samples:"));
+        assert!(!response.starts_with("samples:0 "));
+    }
+
+    #[test]
+    fn test_describe_buffer_pool() {
+        let stats = PoolStats {
+            hits: 3,
+            misses: 2,
+            returned: 4,
+            discarded: 1,
+            pooled_bytes: 16384,
+            classes: vec![(16384, 1), (32768, 0)],
+        };
+
+        assert_eq!(
+            "hits:3 misses:2 returned:4 discarded:1 pooled_bytes:16384\nclass:16384 buffers:1\nclass:32768 buffers:0\n",
+            describe_buffer_pool(&stats)
+        );
+        assert!(debug(vec!["BUFFER-POOL"])
+            .unwrap()
+            .bulk_string_value()
+            .unwrap()
+            .starts_with("hits:"));
+        assert!(debug(vec!["BUFFER-POOL", "a"]).is_err());
+    }
+
+    #[test]
+    fn test_string_encoding() {
+        assert_eq!("int", string_encoding("-123"));
+        assert_eq!("embstr", string_encoding("0123"));
+        assert_eq!("embstr", string_encoding("+1"));
+        assert_eq!("embstr", string_encoding(&"x".repeat(44)));
+        assert_eq!("raw", string_encoding(&"x".repeat(45)));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+};
+
+pub struct DelCommand {
+    keys: Vec<String>,
+}
+
+impl DelCommand {
+    fn new(keys: Vec<String>) -> Self {
+        return Self { keys };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for DelCommand {
+    fn command_name() -> &'static str {
+        return "del";
+    }
+}
+impl ArgErrorMessageGenerator<DelCommand> for DelCommand {}
+
+impl Parse for DelCommand {
+    fn parse(args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        if args.is_empty() {
+            return Err(Self::arg_count_error());
+        }
+
+        let keys = args
+            .into_iter()
+            .map(|key| key.bulk_string_value())
+            .collect::<Result<Vec<String>, RedisMessageType>>()?;
+        return Ok(Self::new(keys));
+    }
+}
+
+impl Execute for DelCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let keys: Vec<&str> = self.keys.iter().map(String::as_str).collect();
+        let removed = get_db().delete(&keys);
+        return Ok(reply::integer(removed as i64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            del::DelCommand,
+            traits::{Execute, Parse},
+        },
+        db::data_store::{init_test_db, DataUnit},
+        parser::messages::RedisMessageType,
+    };
+
+    fn del(args: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return DelCommand::parse(VecDeque::from_iter(args))?.execute();
+    }
+
+    #[test]
+    fn test_del() {
+        let db = init_test_db();
+        db.set("del_first", DataUnit::new("del_first", "value", None));
+        db.set("del_second", DataUnit::new("del_second", "value", None));
+
+        assert_eq!(
+            Ok(RedisMessageType::Integer(2)),
+            del(vec!["del_first", "del_second", "del_missing", "del_first"])
+        );
+        assert!(db.peek("del_first").is_none());
+        assert_eq!(Ok(RedisMessageType::Integer(0)), del(vec!["del_first"]));
+    }
+
+    #[test]
+    fn test_del_invalid_args() {
+        assert!(del(vec![]).is_err());
+    }
+}
@@ -38,3 +38,29 @@ impl Execute for EchoCommand {
         return Ok(self.echo_value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            echo::EchoCommand,
+            traits::{Execute, Parse},
+        },
+        parser::messages::RedisMessageType,
+    };
+
+    #[test]
+    fn test_echo_binary_payload() {
+        let payload = "line\r\nbreak\0nul";
+        let args = VecDeque::from([RedisMessageType::bulk_string(payload)]);
+
+        let response = EchoCommand::parse(args).unwrap().execute().unwrap();
+
+        assert_eq!(
+            format!("${}\r\n{}\r\n", payload.len(), payload).as_bytes(),
+            response.encode()
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+};
+
+/// FLUSHALL and FLUSHDB, the server has a single database so both remove all keys.
+pub struct FlushCommand {
+    lazy: bool,
+}
+
+impl FlushCommand {
+    fn new(lazy: bool) -> Self {
+        return Self { lazy };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for FlushCommand {
+    fn command_name() -> &'static str {
+        return "flushall";
+    }
+}
+impl ArgErrorMessageGenerator<FlushCommand> for FlushCommand {}
+
+impl Parse for FlushCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let lazy = match (args.pop_front(), args.is_empty()) {
+            (None, _) => false,
+            (Some(mode), true) => match mode.bulk_string_value()?.to_ascii_uppercase().as_str() {
+                "SYNC" => false,
+                "ASYNC" => true,
+                _ => return Err(reply::syntax_error()),
+            },
+            (Some(_), false) => return Err(reply::syntax_error()),
+        };
+
+        return Ok(Self::new(lazy));
+    }
+}
+
+impl Execute for FlushCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        get_db().flush(self.lazy);
+        return Ok(reply::ok());
+    }
+}
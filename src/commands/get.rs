@@ -1,7 +1,10 @@
 use std::collections::VecDeque;
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
     db::data_store::get_db,
     parser::messages::RedisMessageType,
 };
@@ -19,7 +22,7 @@ impl GetCommand {
 // could be moved into a procedural macro in the future
 impl CommandName for GetCommand {
     fn command_name() -> &'static str {
-        return "echo";
+        return "get";
     }
 }
 impl ArgErrorMessageGenerator<GetCommand> for GetCommand {}
@@ -37,10 +40,35 @@ impl Parse for GetCommand {
 impl Execute for GetCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
         let response = match get_db().get(self.key) {
-            None => RedisMessageType::NullBulkString,
-            Some(val) => RedisMessageType::bulk_string(val.value),
+            None => reply::null(),
+            Some(val) => reply::bulk(val.value),
         };
 
         return Ok(response);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            get::GetCommand,
+            traits::{Execute, Parse},
+        },
+        db::data_store::{init_test_db, DataUnit},
+        parser::messages::RedisMessageType,
+    };
+
+    #[test]
+    fn test_get_binary_payload() {
+        let payload = "\0\r\n\0\r\n";
+        init_test_db().set("get_binary", DataUnit::new("get_binary", payload, None));
+        let args = VecDeque::from([RedisMessageType::bulk_string("get_binary")]);
+
+        let response = GetCommand::parse(args).unwrap().execute().unwrap();
+
+        assert_eq!("$6\r\n\0\r\n\0\r\n\r\n".as_bytes(), response.encode());
+    }
+}
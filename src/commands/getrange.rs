@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+};
+
+pub struct GetRangeCommand {
+    key: String,
+    start: i64,
+    end: i64,
+}
+
+impl GetRangeCommand {
+    fn new(key: String, start: i64, end: i64) -> Self {
+        return Self { key, start, end };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for GetRangeCommand {
+    fn command_name() -> &'static str {
+        return "getrange";
+    }
+}
+impl ArgErrorMessageGenerator<GetRangeCommand> for GetRangeCommand {}
+
+impl Parse for GetRangeCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let (key, start, end) = match (
+            args.pop_front(),
+            args.pop_front(),
+            args.pop_front(),
+            args.is_empty(),
+        ) {
+            (Some(key), Some(start), Some(end), true) => (
+                key.bulk_string_value()?,
+                start.bulk_string_value()?,
+                end.bulk_string_value()?,
+            ),
+            _ => return Err(Self::arg_count_error()),
+        };
+
+        let start = start.parse().map_err(|_| reply::not_an_integer())?;
+        let end = end.parse().map_err(|_| reply::not_an_integer())?;
+
+        return Ok(Self::new(key, start, end));
+    }
+}
+
+/// The byte range `start..=end` of a value with `len` bytes. Negative offsets count from
+/// the end and both ends are clamped to the value, None if the range is empty.
+fn byte_range(len: usize, start: i64, end: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    if len == 0 || (start < 0 && end < 0 && start > end) {
+        return None;
+    }
+
+    let from_end = |offset: i64| if offset < 0 { len + offset } else { offset };
+    let start = from_end(start).max(0);
+    let end = from_end(end).max(0).min(len - 1);
+
+    return (start <= end).then_some((start as usize, end as usize));
+}
+
+impl Execute for GetRangeCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let value = match get_db().get(self.key) {
+            Some(data) => data.value,
+            None => return Ok(reply::bulk("")),
+        };
+
+        let response = match byte_range(value.len(), self.start, self.end) {
+            // the range may split a char, redis returns the bytes as they are
+            Some((start, end)) => {
+                reply::bulk(String::from_utf8_lossy(&value.as_bytes()[start..=end]))
+            }
+            None => reply::bulk(""),
+        };
+
+        return Ok(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::getrange::byte_range;
+
+    #[test]
+    fn test_byte_range() {
+        // "This is a string"
+        let len = 16;
+        assert_eq!(Some((0, 3)), byte_range(len, 0, 3));
+        assert_eq!(Some((13, 15)), byte_range(len, -3, -1));
+        assert_eq!(Some((0, 15)), byte_range(len, 0, -1));
+        assert_eq!(Some((10, 15)), byte_range(len, 10, 100));
+        assert_eq!(Some((0, 0)), byte_range(len, -100, 0));
+        assert_eq!(None, byte_range(len, 5, 3));
+        assert_eq!(None, byte_range(len, -1, -5));
+        assert_eq!(None, byte_range(len, 20, 30));
+        assert_eq!(None, byte_range(0, 0, -1));
+    }
+}
@@ -1,18 +1,326 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, time::Duration};
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
-    consts::LF,
-    db::data_store::get_db,
+    commands::{
+        middleware,
+        mode::server_mode,
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    consts::CRLF,
+    db::{
+        data_store::{get_db, ServerRole},
+        defrag, eviction, persistence,
+        ttl_stats::TTL_SAMPLE_SIZE,
+    },
     parser::messages::RedisMessageType,
+    replication::slave::get_slave_state,
+    utils::platform,
 };
 
-// no arg support needed
-pub struct InfoCommand;
+const REDIS_VERSION: &str = "7.2.0";
+
+/// Sections in the order they are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InfoSection {
+    Server,
+    Memory,
+    Persistence,
+    Stats,
+    Replication,
+    Cpu,
+    Keyspace,
+}
+
+impl InfoSection {
+    const ALL: [InfoSection; 7] = [
+        Self::Server,
+        Self::Memory,
+        Self::Persistence,
+        Self::Stats,
+        Self::Replication,
+        Self::Cpu,
+        Self::Keyspace,
+    ];
+
+    /// Sections returned by `INFO` and `INFO default`.
+    const DEFAULT: [InfoSection; 7] = Self::ALL;
+
+    fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL
+            .into_iter()
+            .find(|section| section.name().eq_ignore_ascii_case(name));
+    }
+
+    const fn name(&self) -> &'static str {
+        return match self {
+            Self::Server => "server",
+            Self::Memory => "memory",
+            Self::Persistence => "persistence",
+            Self::Stats => "stats",
+            Self::Replication => "replication",
+            Self::Cpu => "cpu",
+            Self::Keyspace => "keyspace",
+        };
+    }
+
+    const fn title(&self) -> &'static str {
+        return match self {
+            Self::Server => "Server",
+            Self::Memory => "Memory",
+            Self::Persistence => "Persistence",
+            Self::Stats => "Stats",
+            Self::Replication => "Replication",
+            Self::Cpu => "CPU",
+            Self::Keyspace => "Keyspace",
+        };
+    }
+
+    fn fields(&self) -> Vec<(String, String)> {
+        let fields = match self {
+            Self::Server => server_fields(),
+            Self::Memory => memory_fields(),
+            Self::Persistence => persistence_fields(),
+            // the only section with field names built at runtime
+            Self::Stats => return stats_fields(),
+            Self::Replication => replication_fields(),
+            Self::Cpu => cpu_fields(),
+            Self::Keyspace => keyspace_fields(),
+        };
+        return fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+    }
+
+    fn render(&self) -> String {
+        let mut section = format!("# {}{CRLF}", self.title());
+        for (key, value) in self.fields() {
+            section += &format!("{}:{}{CRLF}", key, value);
+        }
+        return section;
+    }
+}
+
+fn server_fields() -> Vec<(&'static str, String)> {
+    let config = get_db().get_config();
+    let uptime = get_db().uptime();
+
+    return vec![
+        ("redis_version", REDIS_VERSION.to_string()),
+        ("redis_mode", server_mode().name().to_string()),
+        ("process_id", std::process::id().to_string()),
+        ("tcp_port", config.current_listening_port.to_string()),
+        ("storage_backend", config.storage_backend.name().to_string()),
+        ("uptime_in_seconds", uptime.as_secs().to_string()),
+        ("uptime_in_days", (uptime.as_secs() / 86400).to_string()),
+    ];
+}
+
+/// Empty on platforms without process metrics, see [`platform::cpu_usage`].
+fn cpu_fields() -> Vec<(&'static str, String)> {
+    let Some(usage) = platform::cpu_usage() else {
+        return Vec::new();
+    };
+    let seconds = |time: Duration| format!("{:.6}", time.as_secs_f64());
+
+    return vec![
+        ("used_cpu_sys", seconds(usage.process.sys)),
+        ("used_cpu_user", seconds(usage.process.user)),
+        ("used_cpu_sys_children", seconds(usage.children.sys)),
+        ("used_cpu_user_children", seconds(usage.children.user)),
+    ];
+}
+
+fn memory_fields() -> Vec<(&'static str, String)> {
+    let defrag = defrag::stats();
+    let config = get_db().get_config();
+    return vec![
+        ("used_memory", get_db().used_memory().to_string()),
+        ("maxmemory", config.maxmemory.to_string()),
+        (
+            "maxmemory_policy",
+            config.maxmemory_policy.name().to_string(),
+        ),
+        ("active_defrag_cycles", defrag.cycles.to_string()),
+        (
+            "active_defrag_last_cycle_bytes_before",
+            defrag.last_cycle_bytes_before.to_string(),
+        ),
+        (
+            "active_defrag_last_cycle_bytes_after",
+            defrag.last_cycle_bytes_after.to_string(),
+        ),
+        (
+            "active_defrag_reclaimed_bytes",
+            defrag.reclaimed_bytes.to_string(),
+        ),
+        (
+            "active_defrag_skipped_shards",
+            defrag.skipped_shards.to_string(),
+        ),
+    ];
+}
+
+/// Evicted keys and calls of legacy aliases, every alias has a field like
+/// `deprecated_cmd_substr:calls=1,replacement=getrange`.
+fn stats_fields() -> Vec<(String, String)> {
+    let deprecated_calls = middleware::deprecated_calls();
+    let total: u64 = deprecated_calls.iter().map(|(_, _, calls)| calls).sum();
+
+    let mut fields = vec![
+        (
+            "evicted_keys".to_string(),
+            eviction::evicted_keys().to_string(),
+        ),
+        ("total_deprecated_calls".to_string(), total.to_string()),
+    ];
+    for (alias, command, calls) in deprecated_calls {
+        fields.push((
+            format!("deprecated_cmd_{}", alias.to_lowercase()),
+            format!("calls={},replacement={}", calls, command.to_lowercase()),
+        ));
+    }
+    return fields;
+}
+
+fn replication_fields() -> Vec<(&'static str, String)> {
+    let config = get_db().get_config();
+    let repl_data = config.replication_data;
+    let mut fields = vec![("role", repl_data.role.name().to_string())];
+
+    if let ServerRole::Slave((host, port)) = &repl_data.role {
+        let slave_state = get_slave_state();
+        fields.extend([
+            ("master_host", host.clone()),
+            ("master_port", port.to_string()),
+            (
+                "master_link_status",
+                slave_state.link_status.name().to_string(),
+            ),
+            (
+                "master_last_io_seconds_ago",
+                slave_state.last_io_seconds_ago().to_string(),
+            ),
+            ("slave_repl_offset", slave_state.repl_offset.to_string()),
+            (
+                "slave_read_only",
+                (config.replica_read_only as u8).to_string(),
+            ),
+        ]);
+    }
+
+    fields.extend([
+        ("master_replid", repl_data.master_repl_id),
+        (
+            "master_repl_offset",
+            repl_data.master_repl_offset.to_string(),
+        ),
+    ]);
+    return fields;
+}
+
+/// The save fields of redis and the write history of the dataset, which survives restarts
+/// through the rdb file.
+fn persistence_fields() -> Vec<(&'static str, String)> {
+    let stats = persistence::stats();
+    let seconds = |duration: Option<Duration>| match duration {
+        Some(duration) => duration.as_secs().to_string(),
+        None => "-1".to_string(),
+    };
+    let status = |ok: bool| if ok { "ok" } else { "err" }.to_string();
+
+    let mut fields = vec![
+        ("loading", u8::from(stats.loading).to_string()),
+        ("async_loading", "0".to_string()),
+        (
+            "rdb_changes_since_last_save",
+            stats.changes_since_last_save.to_string(),
+        ),
+        (
+            "rdb_bgsave_in_progress",
+            u8::from(stats.saves_in_progress > 0).to_string(),
+        ),
+        (
+            "rdb_last_save_time",
+            persistence::unix_secs(stats.last_save).to_string(),
+        ),
+        ("rdb_last_bgsave_status", status(stats.last_save_ok)),
+        (
+            "rdb_last_bgsave_time_sec",
+            seconds(stats.last_save_duration),
+        ),
+        (
+            "rdb_current_bgsave_time_sec",
+            seconds(stats.save_started.map(|started| started.elapsed())),
+        ),
+        ("rdb_saves", stats.saves.to_string()),
+        ("rdb_last_cow_size", stats.last_cow_size.to_string()),
+        (
+            "rdb_last_load_keys_expired",
+            stats.last_load_keys_expired.to_string(),
+        ),
+        (
+            "rdb_last_load_keys_loaded",
+            stats.last_load_keys_loaded.to_string(),
+        ),
+        // there is no AOF, the fields are reported like by redis with appendonly off
+        ("aof_enabled", "0".to_string()),
+        ("aof_rewrite_in_progress", "0".to_string()),
+        ("aof_rewrite_scheduled", "0".to_string()),
+        ("aof_last_rewrite_time_sec", "-1".to_string()),
+        ("aof_current_rewrite_time_sec", "-1".to_string()),
+        ("aof_last_bgrewrite_status", status(true)),
+        ("aof_rewrites", "0".to_string()),
+        ("aof_last_write_status", status(true)),
+        ("aof_last_cow_size", "0".to_string()),
+        ("total_writes", stats.total_writes.to_string()),
+        (
+            "dataset_created_time",
+            persistence::unix_secs(stats.dataset_created).to_string(),
+        ),
+    ];
+
+    // like redis, the progress is only shown while loading
+    if let Some(started) = stats.loading_started {
+        let progress = stats.loading_progress();
+        fields.extend([
+            (
+                "loading_start_time",
+                persistence::unix_secs(started).to_string(),
+            ),
+            ("loading_total_bytes", stats.loading_total_bytes.to_string()),
+            (
+                "loading_loaded_bytes",
+                ((stats.loading_total_bytes as f64 * progress) as u64).to_string(),
+            ),
+            ("loading_loaded_perc", format!("{:.2}", progress * 100.0)),
+            ("loading_eta_seconds", stats.loading_eta().to_string()),
+        ]);
+    }
+    return fields;
+}
+
+fn keyspace_fields() -> Vec<(&'static str, String)> {
+    let (keys, expires) = get_db().get_keyspace_stats();
+    if keys == 0 {
+        return Vec::new();
+    }
+
+    let avg_ttl = get_db().sample_ttls(TTL_SAMPLE_SIZE).avg_ttl_millis();
+    return vec![(
+        "db0",
+        format!("keys={},expires={},avg_ttl={}", keys, expires, avg_ttl),
+    )];
+}
+
+pub struct InfoCommand {
+    sections: Vec<InfoSection>,
+}
 
 impl InfoCommand {
-    fn new() -> Self {
-        return Self;
+    fn new(sections: Vec<InfoSection>) -> Self {
+        return Self { sections };
     }
 }
 
@@ -25,20 +333,107 @@ impl CommandName for InfoCommand {
 impl ArgErrorMessageGenerator<InfoCommand> for InfoCommand {}
 
 impl Parse for InfoCommand {
-    fn parse(_args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
-        return Ok(Self::new());
+    fn parse(args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        if args.is_empty() {
+            return Ok(Self::new(InfoSection::DEFAULT.to_vec()));
+        }
+
+        let mut selected = Vec::with_capacity(InfoSection::ALL.len());
+        for arg in args.iter() {
+            let arg = arg.bulk_string_value()?;
+
+            match arg.to_ascii_lowercase().as_str() {
+                "default" => selected.extend(InfoSection::DEFAULT),
+                // there are no module sections, so all and everything are the same
+                "all" | "everything" => selected.extend(InfoSection::ALL),
+                // unknown sections are ignored
+                name => selected.extend(InfoSection::from_name(name)),
+            }
+        }
+
+        // sections are always rendered in the same order and only once
+        let sections = InfoSection::ALL
+            .into_iter()
+            .filter(|section| selected.contains(section))
+            .collect();
+
+        return Ok(Self::new(sections));
     }
 }
 
+/// All sections, as returned by `INFO all`.
+pub fn render_all() -> String {
+    return InfoSection::ALL
+        .iter()
+        .map(|section| section.render())
+        .collect::<Vec<String>>()
+        .join(CRLF);
+}
+
 impl Execute for InfoCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
-        let repl_data = get_db().get_config().replication_data;
-
-        return Ok(RedisMessageType::BulkString(format!(
-            "role:{}{LF}master_replid:{}{LF}master_repl_offset:{}{LF}",
-            repl_data.role.name(),
-            repl_data.master_repl_id,
-            repl_data.master_repl_offset
-        )));
+        let info = self
+            .sections
+            .iter()
+            .map(|section| section.render())
+            .collect::<Vec<String>>()
+            .join(CRLF);
+
+        return Ok(reply::bulk(info));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            info::{InfoCommand, InfoSection},
+            traits::Parse,
+        },
+        parser::messages::RedisMessageType,
+    };
+
+    fn parse_sections(args: Vec<&str>) -> Vec<InfoSection> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return InfoCommand::parse(VecDeque::from_iter(args))
+            .unwrap()
+            .sections;
+    }
+
+    #[test]
+    fn test_parse_no_args_is_default() {
+        assert_eq!(InfoSection::DEFAULT.to_vec(), parse_sections(vec![]));
+        assert_eq!(
+            InfoSection::DEFAULT.to_vec(),
+            parse_sections(vec!["default"])
+        );
+    }
+
+    #[test]
+    fn test_parse_all_and_everything() {
+        assert_eq!(InfoSection::ALL.to_vec(), parse_sections(vec!["all"]));
+        assert_eq!(
+            InfoSection::ALL.to_vec(),
+            parse_sections(vec!["EVERYTHING"])
+        );
+    }
+
+    #[test]
+    fn test_parse_sections_are_ordered_and_unique() {
+        assert_eq!(
+            vec![InfoSection::Server, InfoSection::Keyspace],
+            parse_sections(vec!["keyspace", "Server", "keyspace"])
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_section_is_empty() {
+        assert!(parse_sections(vec!["unknown"]).is_empty());
+        assert_eq!(
+            vec![InfoSection::Replication],
+            parse_sections(vec!["unknown", "replication"])
+        );
     }
 }
@@ -1,9 +1,13 @@
 use std::collections::VecDeque;
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
     db::data_store::get_db,
     parser::messages::RedisMessageType,
+    utils::{deadline::Budget, glob::glob_match},
 };
 
 pub struct KeysCommand {
@@ -25,13 +29,10 @@ impl ArgErrorMessageGenerator<KeysCommand> for KeysCommand {}
 
 impl Parse for KeysCommand {
     fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
-        let mut arg = args
+        let arg = args
             .pop_front()
             .ok_or(Self::arg_count_error())?
             .bulk_string_value()?;
-        if arg.ends_with("*") {
-            let _ = arg.pop();
-        }
 
         if !args.is_empty() {
             return Err(Self::arg_count_error());
@@ -43,6 +44,68 @@ impl Parse for KeysCommand {
 
 impl Execute for KeysCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
-        return Ok(RedisMessageType::bulk_string_array(get_db().get_all_keys()));
+        // a huge keyspace may take long, so the time budget is checked on the way
+        let mut budget = Budget::default();
+        let mut keys = Vec::new();
+        get_db().try_for_each_key(|key| {
+            if glob_match(&self.pattern, key) {
+                keys.push(key.to_string());
+            }
+            return budget.tick();
+        })?;
+
+        return Ok(reply::bulk_array(keys));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use crate::{
+        commands::{
+            keys::KeysCommand,
+            traits::{Execute, Parse},
+        },
+        db::data_store::{init_test_db, DataUnit},
+        parser::messages::RedisMessageType,
+        utils::deadline,
+    };
+
+    #[test]
+    fn test_keys_pattern() {
+        for key in ["keys_test:1", "keys_test:2", "keys_test:10"] {
+            init_test_db().set(key, DataUnit::new(key, "value", None));
+        }
+        let args = VecDeque::from([RedisMessageType::bulk_string("keys_test:?")]);
+
+        let response = KeysCommand::parse(args).unwrap().execute().unwrap();
+
+        let mut keys: Vec<String> = match response {
+            RedisMessageType::Array(keys) => keys
+                .iter()
+                .map(|key| key.bulk_string_value().unwrap())
+                .collect(),
+            other => panic!("Expected an array, got {}", other),
+        };
+        keys.sort();
+        assert_eq!(vec!["keys_test:1", "keys_test:2"], keys);
+    }
+
+    #[test]
+    fn test_keys_cancelled_by_time_limit() {
+        for i in 0..2048 {
+            let key = format!("keys_budget:{}", i);
+            init_test_db().set(key.clone(), DataUnit::new(key.as_str(), "value", None));
+        }
+        let args = VecDeque::from([RedisMessageType::bulk_string("*")]);
+        let command = KeysCommand::parse(args).unwrap();
+
+        let result = deadline::with_budget(Some(Duration::ZERO), || command.execute());
+
+        assert!(
+            result.is_err(),
+            "KEYS has to stop once the budget is used up"
+        );
     }
 }
@@ -1,12 +1,35 @@
+/// The modes a command of the `redis_commands!` table is available in. Without `in [...]`
+/// it is a data command.
+#[macro_export]
+macro_rules! command_modes {
+    () => {
+        &ServerMode::DATA
+    };
+    ([$($mode:ident),*]) => {
+        &[$(ServerMode::$mode),*]
+    };
+}
+
 #[macro_export]
 macro_rules! redis_commands {
-    ($($name:ident => $cmd:ty),+ $(,)?) => {
+    ($($name:ident => $cmd:ty [$($category:ident),*] $(in [$($mode:ident),*])?),+ $(,)?) => {
         pub enum UnparsedCommandType {
             $(
                 $name(Command<Unparsed, $cmd>),
             )+
         }
 
+        /// Metadata of every command of the table, in the order of the table.
+        pub const COMMAND_TABLE: &[CommandMetadata] = &[
+            $(
+                CommandMetadata {
+                    table_name: stringify!($name),
+                    acl_categories: &[$(AclCategory::$category),*],
+                    modes: $crate::command_modes!($([$($mode),*])?),
+                },
+            )+
+        ];
+
         pub enum ParsedCommandType {
             $(
                 $name(Command<Parsed, $cmd>),
@@ -22,6 +45,50 @@ macro_rules! redis_commands {
                 }
             }
 
+            /// The arguments without the command name.
+            pub fn args(&self) -> &VecDeque<RedisMessageType> {
+                match self {
+                    $(
+                        UnparsedCommandType::$name(cmd) => &cmd.item.args,
+                    )+
+                }
+            }
+
+            /// The legacy name the command was called by, if it was called by an alias.
+            pub fn alias(&self) -> Option<&'static str> {
+                match self {
+                    $(
+                        UnparsedCommandType::$name(cmd) => cmd.item.alias,
+                    )+
+                }
+            }
+
+            fn set_alias(&mut self, alias: Option<&'static str>) {
+                match self {
+                    $(
+                        UnparsedCommandType::$name(cmd) => cmd.item.alias = alias,
+                    )+
+                }
+            }
+
+            /// The ACL categories the command belongs to.
+            pub fn acl_categories(&self) -> &'static [AclCategory] {
+                match self {
+                    $(
+                        UnparsedCommandType::$name(_) => &[$(AclCategory::$category),*],
+                    )+
+                }
+            }
+
+            /// The server modes the command is available in.
+            pub fn modes(&self) -> &'static [ServerMode] {
+                match self {
+                    $(
+                        UnparsedCommandType::$name(_) => $crate::command_modes!($([$($mode),*])?),
+                    )+
+                }
+            }
+
             pub fn parse(self) -> Result<ParsedCommandType, RedisMessageType> {
                 match self {
                     $(
@@ -33,10 +100,10 @@ macro_rules! redis_commands {
         }
 
         impl ParsedCommandType {
-            pub fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+            pub fn execute(self, ctx: &ConnectionContext) -> Result<RedisMessageType, RedisMessageType> {
                 match self {
                     $(
-                        ParsedCommandType::$name(cmd) => cmd.execute(),
+                        ParsedCommandType::$name(cmd) => cmd.execute_with_context(ctx),
                     )+
                 }
             }
@@ -0,0 +1,368 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+
+use crate::{
+    commands::{
+        acl::AclCheck,
+        command::{UnparsedCommandType, COMMAND_ALIASES},
+        context::{ConnectionContext, ConnectionKind},
+        reply::{self, ErrorCode},
+    },
+    db::{
+        data_store::{get_db, ServerRole},
+        eviction, persistence,
+    },
+    parser::messages::RedisMessageType,
+};
+
+static MIDDLEWARES: Lazy<MiddlewareChain> = Lazy::new(MiddlewareChain::default);
+
+/// Calls of every legacy alias, for the stats section of INFO.
+static DEPRECATED_CALLS: Lazy<Mutex<BTreeMap<&'static str, u64>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// A check applied to every client command before it is executed.
+///
+/// Cross cutting features (read only replicas, paused writes, ...) are implemented as a
+/// middleware instead of being checked inside every single command.
+pub trait Middleware: Send + Sync {
+    /// Returns the reply send to the client instead of executing the command.
+    fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType>;
+}
+
+pub struct MiddlewareChain {
+    middlewares: Vec<Box<dyn Middleware>>,
+}
+
+impl MiddlewareChain {
+    pub fn new(middlewares: Vec<Box<dyn Middleware>>) -> Self {
+        return Self { middlewares };
+    }
+
+    /// Runs all middlewares in order, the first rejection wins.
+    pub fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType> {
+        for middleware in self.middlewares.iter() {
+            middleware.before(ctx, command)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Default for MiddlewareChain {
+    fn default() -> Self {
+        return Self::new(vec![
+            Box::new(AclCheck),
+            Box::new(RejectWhileLoading::new(persistence::is_loading)),
+            Box::new(ReadOnlyReplica),
+            Box::new(Evict::new(|| eviction::free_memory(get_db()))),
+            Box::new(DeprecatedAlias),
+        ]);
+    }
+}
+
+/// Runs the global middleware chain for a client command.
+pub fn before_execute(
+    ctx: &ConnectionContext,
+    command: &UnparsedCommandType,
+) -> Result<(), RedisMessageType> {
+    return MIDDLEWARES.before(ctx, command);
+}
+
+/// Rejects writes of clients on a replica, unless replica-read-only is disabled. Writes of
+/// the master reach the replica through the master link and are always applied.
+pub struct ReadOnlyReplica;
+
+impl Middleware for ReadOnlyReplica {
+    fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType> {
+        if ctx.client().kind() == ConnectionKind::MasterLink {
+            return Ok(());
+        }
+
+        let config = get_db().get_config();
+        let is_slave = matches!(config.replication_data.role, ServerRole::Slave(_));
+
+        if is_slave && config.replica_read_only && command.is_write() {
+            return Err(reply::error(
+                ErrorCode::ReadOnly,
+                "You can't write against a read only replica.",
+            ));
+        }
+        return Ok(());
+    }
+}
+
+/// Evicts keys before a write of a client once the used memory exceeds `maxmemory`. The
+/// write is rejected with `-OOM` if no memory could be freed, e.g. with the noeviction
+/// policy. Reads are always answered, writes of the master are always applied.
+pub struct Evict {
+    free_memory: fn() -> bool,
+}
+
+impl Evict {
+    pub fn new(free_memory: fn() -> bool) -> Self {
+        return Self { free_memory };
+    }
+}
+
+impl Middleware for Evict {
+    fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType> {
+        if ctx.client().kind() == ConnectionKind::MasterLink || !command.is_write() {
+            return Ok(());
+        }
+
+        if !(self.free_memory)() {
+            return Err(reply::error(
+                ErrorCode::Oom,
+                "command not allowed when used memory > 'maxmemory'.",
+            ));
+        }
+        return Ok(());
+    }
+}
+
+/// Commands answered while a dataset is loaded, like the commands redis flags with `loading`.
+/// They don't touch the keyspace.
+const LOADING_OK: [&str; 7] = [
+    "info", "config", "client", "command", "debug", "replconf", "shutdown",
+];
+
+/// Answers commands with `-LOADING` while a dataset is loaded, e.g. on startup or on a full
+/// resync of a replica. Only inspecting and configuring the server is possible meanwhile.
+pub struct RejectWhileLoading {
+    is_loading: fn() -> bool,
+}
+
+impl RejectWhileLoading {
+    pub fn new(is_loading: fn() -> bool) -> Self {
+        return Self { is_loading };
+    }
+}
+
+impl Middleware for RejectWhileLoading {
+    fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType> {
+        if ctx.client().kind() == ConnectionKind::MasterLink || !(self.is_loading)() {
+            return Ok(());
+        }
+
+        if !LOADING_OK.contains(&command.name().as_str()) {
+            return Err(reply::error(
+                ErrorCode::Loading,
+                "Redis is loading the dataset in memory",
+            ));
+        }
+        return Ok(());
+    }
+}
+
+/// Counts the calls of legacy aliases like SUBSTR, so old clients can be found before the
+/// aliases are removed. The first call of an alias is logged as warning, later ones only
+/// with debug logging.
+pub struct DeprecatedAlias;
+
+impl Middleware for DeprecatedAlias {
+    fn before(
+        &self,
+        ctx: &ConnectionContext,
+        command: &UnparsedCommandType,
+    ) -> Result<(), RedisMessageType> {
+        let Some(alias) = command.alias() else {
+            return Ok(());
+        };
+
+        let calls = {
+            let mut deprecated_calls = DEPRECATED_CALLS
+                .lock()
+                .expect("Deprecated calls lock poisoned. Should never happen!");
+            let calls = deprecated_calls.entry(alias).or_insert(0);
+            *calls += 1;
+            *calls
+        };
+
+        let replacement = command.name().to_uppercase();
+        if calls == 1 {
+            warn!(
+                "Client {} used the deprecated command {}, use {} instead",
+                ctx.peer, alias, replacement
+            );
+        } else {
+            debug!(
+                "Client {} used the deprecated command {}, use {} instead",
+                ctx.peer, alias, replacement
+            );
+        }
+        return Ok(());
+    }
+}
+
+/// Calls of every legacy alias and the command replacing it, including aliases not used yet.
+pub fn deprecated_calls() -> Vec<(&'static str, &'static str, u64)> {
+    let deprecated_calls = DEPRECATED_CALLS
+        .lock()
+        .expect("Deprecated calls lock poisoned. Should never happen!");
+    return COMMAND_ALIASES
+        .iter()
+        .map(|(alias, command)| {
+            let calls = deprecated_calls.get(alias).copied().unwrap_or(0);
+            return (*alias, *command, calls);
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{
+        commands::{
+            command::UnparsedCommandType,
+            context::ConnectionContext,
+            middleware::{
+                deprecated_calls, DeprecatedAlias, Evict, Middleware, MiddlewareChain,
+                RejectWhileLoading,
+            },
+        },
+        parser::messages::RedisMessageType,
+    };
+
+    struct Counting(&'static AtomicUsize);
+
+    impl Middleware for Counting {
+        fn before(
+            &self,
+            _ctx: &ConnectionContext,
+            _command: &UnparsedCommandType,
+        ) -> Result<(), RedisMessageType> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            return Ok(());
+        }
+    }
+
+    struct Reject;
+
+    impl Middleware for Reject {
+        fn before(
+            &self,
+            _ctx: &ConnectionContext,
+            _command: &UnparsedCommandType,
+        ) -> Result<(), RedisMessageType> {
+            return Err(RedisMessageType::error("ERR rejected"));
+        }
+    }
+
+    fn ping() -> UnparsedCommandType {
+        let args = VecDeque::from([RedisMessageType::bulk_string("PING")]);
+        return UnparsedCommandType::new(args).ok().unwrap();
+    }
+
+    fn ctx() -> ConnectionContext {
+        return ConnectionContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1));
+    }
+
+    #[test]
+    fn test_chain_runs_all_middlewares() {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let chain = MiddlewareChain::new(vec![
+            Box::new(Counting(&COUNTER)),
+            Box::new(Counting(&COUNTER)),
+        ]);
+
+        assert!(chain.before(&ctx(), &ping()).is_ok());
+        assert_eq!(2, COUNTER.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_chain_stops_at_first_rejection() {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let chain = MiddlewareChain::new(vec![Box::new(Reject), Box::new(Counting(&COUNTER))]);
+
+        assert_eq!(
+            Err(RedisMessageType::error("ERR rejected")),
+            chain.before(&ctx(), &ping())
+        );
+        assert_eq!(0, COUNTER.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_deprecated_alias_is_counted() {
+        let substr_calls = || deprecated_calls()[0];
+        let (alias, replacement, before) = substr_calls();
+        assert_eq!(("SUBSTR", "GETRANGE"), (alias, replacement));
+
+        let args = ["substr", "key", "0", "1"].map(RedisMessageType::bulk_string);
+        let command = UnparsedCommandType::new(VecDeque::from(args)).ok().unwrap();
+        assert_eq!(Some("SUBSTR"), command.alias());
+        assert_eq!(None, ping().alias());
+
+        assert!(DeprecatedAlias.before(&ctx(), &command).is_ok());
+        // other tests may call SUBSTR at the same time
+        assert!(substr_calls().2 > before);
+    }
+
+    #[test]
+    fn test_reject_while_loading() {
+        let command = |args: &[&str]| {
+            let args = args.iter().map(|arg| RedisMessageType::bulk_string(*arg));
+            return UnparsedCommandType::new(VecDeque::from_iter(args))
+                .ok()
+                .unwrap();
+        };
+        let loading = RejectWhileLoading::new(|| true);
+
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "LOADING Redis is loading the dataset in memory"
+            )),
+            loading.before(&ctx(), &command(&["GET", "key"]))
+        );
+        assert!(loading.before(&ctx(), &ping()).is_err());
+        assert!(loading.before(&ctx(), &command(&["INFO"])).is_ok());
+        assert!(loading
+            .before(&ctx(), &command(&["CONFIG", "GET", "dir"]))
+            .is_ok());
+
+        let loaded = RejectWhileLoading::new(|| false);
+        assert!(loaded.before(&ctx(), &command(&["GET", "key"])).is_ok());
+    }
+
+    #[test]
+    fn test_evict_rejects_writes_without_memory() {
+        let set = ["SET", "key", "value"].map(RedisMessageType::bulk_string);
+        let set = UnparsedCommandType::new(VecDeque::from(set)).ok().unwrap();
+        let full = Evict::new(|| false);
+
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "OOM command not allowed when used memory > 'maxmemory'."
+            )),
+            full.before(&ctx(), &set)
+        );
+        assert!(full.before(&ctx(), &ping()).is_ok());
+        assert!(Evict::new(|| true).before(&ctx(), &set).is_ok());
+    }
+}
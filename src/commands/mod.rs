@@ -1,12 +1,32 @@
+pub mod acl;
+pub mod client;
+pub mod cluster;
 pub mod command;
+pub mod command_info;
 pub mod config;
+#[cfg(test)]
+mod conformance;
+pub mod context;
+pub mod copy;
+pub mod debug;
+pub mod del;
 pub mod echo;
+pub mod flush;
 pub mod get;
+pub mod getrange;
 pub mod info;
 pub mod keys;
 pub mod macros;
+pub mod middleware;
+pub mod mode;
+pub mod object;
 pub mod ping;
 pub mod psync;
+pub mod rename;
 pub mod replconf;
+pub mod reply;
+pub mod scan;
 pub mod set;
+pub mod shutdown;
 pub mod traits;
+pub mod wait;
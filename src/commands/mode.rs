@@ -0,0 +1,44 @@
+use once_cell::sync::OnceCell;
+
+static SERVER_MODE: OnceCell<ServerMode> = OnceCell::new();
+
+/// The mode the server was started in, chosen with `--cluster-enabled` or `--sentinel`.
+///
+/// The mode decides which command families exist. Every command lists the modes it is
+/// available in, see the `redis_commands!` table, anything else is an unknown command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerMode {
+    Standalone,
+    /// A cluster of a single node that owns every hash slot.
+    Cluster,
+    /// Sentinel-lite, the server only answers monitoring commands and holds no data.
+    Sentinel,
+}
+
+impl ServerMode {
+    /// Modes of the commands working on the dataset, the default of the command table.
+    pub const DATA: [ServerMode; 2] = [Self::Standalone, Self::Cluster];
+
+    /// Modes of the connection and monitoring commands.
+    pub const ALL: [ServerMode; 3] = [Self::Standalone, Self::Cluster, Self::Sentinel];
+
+    /// The name shown as `redis_mode` in INFO.
+    pub const fn name(&self) -> &'static str {
+        return match self {
+            Self::Standalone => "standalone",
+            Self::Cluster => "cluster",
+            Self::Sentinel => "sentinel",
+        };
+    }
+}
+
+/// Sets the mode of the server. Must be called before the first client connects.
+pub fn init_server_mode(mode: ServerMode) {
+    SERVER_MODE
+        .set(mode)
+        .expect("Server mode is already set. Should never happen!");
+}
+
+pub fn server_mode() -> ServerMode {
+    return *SERVER_MODE.get_or_init(|| ServerMode::Standalone);
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        debug::string_encoding,
+        reply::{self, ErrorCode},
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+};
+
+enum Action {
+    Encoding(String),
+    Freq(String),
+    IdleTime(String),
+    RefCount(String),
+    Help,
+}
+
+pub struct ObjectCommand {
+    action: Action,
+}
+
+impl ObjectCommand {
+    fn new(action: Action) -> Self {
+        return Self { action };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for ObjectCommand {
+    fn command_name() -> &'static str {
+        return "object";
+    }
+}
+impl ArgErrorMessageGenerator<ObjectCommand> for ObjectCommand {}
+
+impl Parse for ObjectCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let sub_command = args
+            .pop_front()
+            .ok_or(Self::arg_count_error())?
+            .bulk_string_value()?;
+
+        let action: fn(String) -> Action = match sub_command.to_ascii_uppercase().as_str() {
+            "HELP" if args.is_empty() => return Ok(Self::new(Action::Help)),
+            "ENCODING" => Action::Encoding,
+            "FREQ" => Action::Freq,
+            "IDLETIME" => Action::IdleTime,
+            "REFCOUNT" => Action::RefCount,
+            _ => return Err(Self::unknown_subcommand_error(&sub_command)),
+        };
+
+        let action = match (args.pop_front(), args.is_empty()) {
+            (Some(key), true) => action(key.bulk_string_value()?),
+            _ => return Err(Self::sub_arg_count_error(&sub_command)),
+        };
+
+        return Ok(Self::new(action));
+    }
+}
+
+/// Reply of OBJECT FREQ, access frequencies are only tracked with an LFU eviction policy.
+const LFU_NOT_SELECTED: &str = "An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.";
+
+fn execute_help() -> RedisMessageType {
+    return reply::bulk_array(vec![
+        "OBJECT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+        "ENCODING <key>",
+        "    Return the kind of internal representation used in order to store the value",
+        "    associated with a <key>.",
+        "FREQ <key>",
+        "    Return the access frequency index of the <key>. The returned integer is",
+        "    proportional to the logarithm of the recent access frequency of the key.",
+        "IDLETIME <key>",
+        "    Return the idle time of the <key>, that is the approximated number of",
+        "    seconds elapsed since the last access to the key.",
+        "REFCOUNT <key>",
+        "    Return the number of references of the value associated with the specified",
+        "    <key>.",
+        "HELP",
+        "    Print this help.",
+    ]);
+}
+
+impl Execute for ObjectCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let key = match self.action {
+            Action::Help => return Ok(execute_help()),
+            Action::Encoding(ref key)
+            | Action::Freq(ref key)
+            | Action::IdleTime(ref key)
+            | Action::RefCount(ref key) => key,
+        };
+
+        // inspecting a key is no access, else IDLETIME would always be 0
+        let data = match get_db().peek(key) {
+            Some(data) => data,
+            None => return Ok(reply::null()),
+        };
+
+        let response = match self.action {
+            Action::Encoding(_) => reply::bulk(string_encoding(&data.value)),
+            Action::IdleTime(_) => reply::integer(data.idle_time().as_secs() as i64),
+            Action::RefCount(_) => reply::integer(data.value.ref_count()),
+            // LFU policies are not supported, so the access frequency is never tracked
+            Action::Freq(_) => return Err(reply::error(ErrorCode::Err, LFU_NOT_SELECTED)),
+            Action::Help => unreachable!("HELP is answered before looking up the key"),
+        };
+
+        return Ok(response);
+    }
+}
@@ -1,15 +1,20 @@
 use std::collections::VecDeque;
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
     parser::messages::RedisMessageType,
 };
 
-pub struct PingCommand;
+pub struct PingCommand {
+    message: Option<String>,
+}
 
 impl PingCommand {
-    pub fn new() -> Self {
-        return Self {};
+    pub fn new(message: Option<String>) -> Self {
+        return Self { message };
     }
 }
 
@@ -21,16 +26,22 @@ impl CommandName for PingCommand {
 impl ArgErrorMessageGenerator<PingCommand> for PingCommand {}
 
 impl Parse for PingCommand {
-    fn parse(args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
-        if !args.is_empty() {
-            return Err(Self::arg_count_error());
-        }
-        return Ok(PingCommand::new());
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        // ping takes at most 1 argument
+        let message = match (args.pop_front(), args.is_empty()) {
+            (None, _) => None,
+            (Some(arg), true) => Some(arg.bulk_string_value()?),
+            (Some(_), false) => return Err(Self::arg_count_error()),
+        };
+        return Ok(PingCommand::new(message));
     }
 }
 
 impl Execute for PingCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
-        return Ok(RedisMessageType::simple_string("PONG"));
+        return match self.message {
+            None => Ok(reply::status("PONG")),
+            Some(message) => Ok(reply::bulk(message)),
+        };
     }
 }
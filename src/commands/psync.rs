@@ -1,9 +1,14 @@
 use std::collections::VecDeque;
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    commands::{
+        context::ConnectionContext,
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
     db::data_store::get_db,
     parser::messages::RedisMessageType,
+    replication::compression,
 };
 
 #[allow(dead_code)]
@@ -47,9 +52,28 @@ impl Parse for PsyncCommand {
 impl Execute for PsyncCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
         let data = get_db().get_config().replication_data;
-        return Ok(RedisMessageType::simple_string(format!(
+        return Ok(reply::status(format!(
             "FULLRESYNC {} {}",
             data.master_repl_id, data.master_repl_offset
         )));
     }
+
+    /// A replica that offered `REPLCONF capa lz4` is told the stream will be compressed.
+    fn execute_with_context(
+        self,
+        ctx: &ConnectionContext,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        let response = self.execute()?;
+        if !ctx.client().state().repl_compression {
+            return Ok(response);
+        }
+        return match response {
+            RedisMessageType::SimpleString(reply) => Ok(reply::status(format!(
+                "{} {}",
+                reply,
+                compression::CAPABILITY
+            ))),
+            response => Ok(response),
+        };
+    }
 }
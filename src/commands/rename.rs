@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+};
+
+pub struct RenameCommand {
+    source: String,
+    destination: String,
+}
+
+impl RenameCommand {
+    fn new(source: String, destination: String) -> Self {
+        return Self {
+            source,
+            destination,
+        };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for RenameCommand {
+    fn command_name() -> &'static str {
+        return "rename";
+    }
+}
+impl ArgErrorMessageGenerator<RenameCommand> for RenameCommand {}
+
+impl Parse for RenameCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        return match (args.pop_front(), args.pop_front(), args.is_empty()) {
+            (Some(source), Some(destination), true) => Ok(Self::new(
+                source.bulk_string_value()?,
+                destination.bulk_string_value()?,
+            )),
+            _ => Err(Self::arg_count_error()),
+        };
+    }
+}
+
+impl Execute for RenameCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        if !get_db().rename(&self.source, &self.destination) {
+            return Err(reply::no_such_key());
+        }
+        return Ok(reply::ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{
+            rename::RenameCommand,
+            traits::{Execute, Parse},
+        },
+        db::data_store::{init_test_db, DataUnit},
+        parser::messages::RedisMessageType,
+    };
+
+    fn rename(args: Vec<&str>) -> Result<RedisMessageType, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return RenameCommand::parse(VecDeque::from_iter(args))?.execute();
+    }
+
+    #[test]
+    fn test_rename() {
+        init_test_db().set(
+            "rename_source",
+            DataUnit::new("rename_source", "value", None),
+        );
+
+        assert_eq!(
+            Ok(RedisMessageType::simple_string("OK")),
+            rename(vec!["rename_source", "rename_destination"])
+        );
+        assert_eq!(
+            Err(RedisMessageType::error("ERR no such key")),
+            rename(vec!["rename_source", "rename_destination"])
+        );
+    }
+
+    #[test]
+    fn test_rename_invalid_args() {
+        assert!(rename(vec!["a"]).is_err());
+        assert!(rename(vec!["a", "b", "c"]).is_err());
+    }
+}
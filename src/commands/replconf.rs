@@ -1,15 +1,23 @@
 use std::collections::VecDeque;
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    commands::{
+        context::ConnectionContext,
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
     parser::messages::RedisMessageType,
+    replication::compression,
 };
 
-pub struct ReplConfCommand;
+pub struct ReplConfCommand {
+    /// Values of the `capa` options, the capabilities the replica supports.
+    capabilities: Vec<String>,
+}
 
 impl ReplConfCommand {
-    fn new() -> Self {
-        return Self {};
+    fn new(capabilities: Vec<String>) -> Self {
+        return Self { capabilities };
     }
 }
 
@@ -22,13 +30,66 @@ impl CommandName for ReplConfCommand {
 impl ArgErrorMessageGenerator<ReplConfCommand> for ReplConfCommand {}
 
 impl Parse for ReplConfCommand {
-    fn parse(_args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
-        return Ok(Self::new());
+    /// Options other than `capa` are accepted and ignored.
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let mut capabilities = Vec::new();
+        while let Some(option) = args.pop_front() {
+            let value = args.pop_front();
+            if option.bulk_string_value()?.eq_ignore_ascii_case("capa") {
+                let value = value.ok_or(Self::arg_count_error())?;
+                capabilities.push(value.bulk_string_value()?.to_lowercase());
+            }
+        }
+        return Ok(Self::new(capabilities));
     }
 }
 
 impl Execute for ReplConfCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
-        return Ok(RedisMessageType::simple_string("OK"));
+        return Ok(reply::ok());
+    }
+
+    /// Remembers whether the replica takes a compressed replication stream, see PSYNC.
+    fn execute_with_context(
+        self,
+        ctx: &ConnectionContext,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        if self
+            .capabilities
+            .iter()
+            .any(|capability| capability == compression::CAPABILITY)
+        {
+            ctx.client()
+                .update_state(|state| state.repl_compression = true);
+        }
+        return self.execute();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{replconf::ReplConfCommand, traits::Parse},
+        parser::messages::RedisMessageType,
+    };
+
+    fn parse(args: Vec<&str>) -> Result<ReplConfCommand, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return ReplConfCommand::parse(VecDeque::from_iter(args));
+    }
+
+    #[test]
+    fn test_parse_capabilities() {
+        let command = parse(vec!["capa", "psync2", "CAPA", "LZ4"]).ok().unwrap();
+        assert_eq!(vec!["psync2", "lz4"], command.capabilities);
+
+        let command = parse(vec!["listening-port", "6380"]).ok().unwrap();
+        assert!(command.capabilities.is_empty());
+        let command = parse(vec!["GETACK", "*"]).ok().unwrap();
+        assert!(command.capabilities.is_empty());
+
+        assert!(parse(vec!["capa"]).is_err());
     }
 }
@@ -0,0 +1,123 @@
+//! Builds the replies of commands.
+//!
+//! Commands use these functions instead of constructing [`RedisMessageType`] variants by
+//! hand, so the encoding of replies (e.g. maps, which RESP2 sends as flat arrays) and the
+//! format of errors are decided in one place.
+
+use std::{collections::VecDeque, fmt::Display};
+
+use crate::parser::messages::RedisMessageType;
+
+/// The first word of an error reply, clients match on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Err,
+    NoPerm,
+    ReadOnly,
+    Loading,
+    Oom,
+}
+
+impl ErrorCode {
+    pub const fn name(&self) -> &'static str {
+        return match self {
+            Self::Err => "ERR",
+            Self::NoPerm => "NOPERM",
+            Self::ReadOnly => "READONLY",
+            Self::Loading => "LOADING",
+            Self::Oom => "OOM",
+        };
+    }
+}
+
+pub fn ok() -> RedisMessageType {
+    return RedisMessageType::simple_string("OK");
+}
+
+/// A simple string like `PONG`, only for short status replies without line breaks.
+pub fn status<S: Into<String>>(status: S) -> RedisMessageType {
+    return RedisMessageType::simple_string(status);
+}
+
+pub fn integer(value: i64) -> RedisMessageType {
+    return RedisMessageType::Integer(value);
+}
+
+pub fn bulk<S: Into<String>>(value: S) -> RedisMessageType {
+    return RedisMessageType::bulk_string(value);
+}
+
+/// The reply for a missing value.
+pub fn null() -> RedisMessageType {
+    return RedisMessageType::NullBulkString;
+}
+
+pub fn array<I: IntoIterator<Item = RedisMessageType>>(items: I) -> RedisMessageType {
+    return RedisMessageType::Array(items.into_iter().collect());
+}
+
+/// An array of bulk strings.
+pub fn bulk_array<S: Into<String>, I: IntoIterator<Item = S>>(values: I) -> RedisMessageType {
+    return array(values.into_iter().map(bulk));
+}
+
+/// RESP2 has no map type, so the entries are send as a flat array of keys and values.
+pub fn map<K: Into<String>, I: IntoIterator<Item = (K, RedisMessageType)>>(
+    entries: I,
+) -> RedisMessageType {
+    let mut items = VecDeque::new();
+    for (key, value) in entries {
+        items.push_back(bulk(key));
+        items.push_back(value);
+    }
+    return RedisMessageType::Array(items);
+}
+
+pub fn error<S: Display>(code: ErrorCode, message: S) -> RedisMessageType {
+    return RedisMessageType::error(format!("{} {}", code.name(), message));
+}
+
+pub fn syntax_error() -> RedisMessageType {
+    return error(ErrorCode::Err, "syntax error");
+}
+
+pub fn not_an_integer() -> RedisMessageType {
+    return error(ErrorCode::Err, "value is not an integer or out of range");
+}
+
+pub fn no_such_key() -> RedisMessageType {
+    return error(ErrorCode::Err, "no such key");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        commands::reply::{self, ErrorCode},
+        parser::messages::RedisMessageType,
+    };
+
+    #[test]
+    fn test_map_is_a_flat_array() {
+        let map = reply::map([
+            ("role", reply::bulk("master")),
+            ("port", reply::integer(6379)),
+        ]);
+
+        assert_eq!(
+            "*4\r\n$4\r\nrole\r\n$6\r\nmaster\r\n$4\r\nport\r\n:6379\r\n".as_bytes(),
+            map.encode()
+        );
+    }
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(
+            RedisMessageType::error("NOPERM no permissions"),
+            reply::error(ErrorCode::NoPerm, "no permissions")
+        );
+        assert_eq!(
+            b"-ERR syntax error\r\n".to_vec(),
+            reply::syntax_error().encode()
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+use crate::{
+    commands::{
+        reply::{self, ErrorCode},
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+    utils::glob::glob_match,
+};
+
+const DEFAULT_COUNT: usize = 10;
+
+/// Types a TYPE filter may name, only strings are stored so far.
+const KNOWN_TYPES: [&str; 6] = ["string", "list", "set", "zset", "hash", "stream"];
+
+pub struct ScanCommand {
+    cursor: u64,
+    pattern: Option<String>,
+    count: usize,
+    key_type: Option<String>,
+}
+
+impl ScanCommand {
+    fn new(cursor: u64, pattern: Option<String>, count: usize, key_type: Option<String>) -> Self {
+        return Self {
+            cursor,
+            pattern,
+            count,
+            key_type,
+        };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for ScanCommand {
+    fn command_name() -> &'static str {
+        return "scan";
+    }
+}
+impl ArgErrorMessageGenerator<ScanCommand> for ScanCommand {}
+
+impl Parse for ScanCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let cursor = args
+            .pop_front()
+            .ok_or(Self::arg_count_error())?
+            .bulk_string_value()?
+            .parse::<u64>()
+            .map_err(|_| reply::error(ErrorCode::Err, "invalid cursor"))?;
+
+        let mut pattern = None;
+        let mut count = DEFAULT_COUNT;
+        let mut key_type = None;
+        while let Some(option) = args.pop_front() {
+            let option = option.bulk_string_value()?;
+            let value = args
+                .pop_front()
+                .ok_or(reply::syntax_error())?
+                .bulk_string_value()?;
+
+            match option.to_ascii_uppercase().as_str() {
+                "MATCH" => pattern = Some(value),
+                "COUNT" => {
+                    count = match value.parse::<i64>() {
+                        Ok(count) if count >= 1 => count as usize,
+                        Ok(_) => return Err(reply::syntax_error()),
+                        Err(_) => return Err(reply::not_an_integer()),
+                    }
+                }
+                "TYPE" => match KNOWN_TYPES.contains(&value.to_ascii_lowercase().as_str()) {
+                    true => key_type = Some(value.to_ascii_lowercase()),
+                    false => {
+                        let message = format!("unknown type name '{}'", value);
+                        return Err(reply::error(ErrorCode::Err, message));
+                    }
+                },
+                _ => return Err(reply::syntax_error()),
+            }
+        }
+
+        return Ok(Self::new(cursor, pattern, count, key_type));
+    }
+}
+
+impl Execute for ScanCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let (cursor, keys) = get_db().scan(self.cursor, self.count);
+
+        // like redis the filters apply after the step, so a step may return no keys at all
+        let keys = keys
+            .into_iter()
+            .filter(|key| match &self.pattern {
+                Some(pattern) => glob_match(pattern, key),
+                None => true,
+            })
+            .filter(|_| self.key_type.as_deref().is_none_or(|t| t == "string"));
+
+        return Ok(reply::array([
+            reply::bulk(cursor.to_string()),
+            reply::bulk_array(keys),
+        ]));
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 use log::trace;
 
 use crate::{
-    commands::traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    commands::{
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
     db::data_store::{get_db, DataUnit, Expiry},
     parser::messages::RedisMessageType,
 };
@@ -62,11 +65,32 @@ impl SetCommand {
 
 impl CommandName for SetCommand {
     fn command_name() -> &'static str {
-        return "ping";
+        return "set";
     }
 }
 impl ArgErrorMessageGenerator<SetCommand> for SetCommand {}
 
+/// Takes the value of an expire option. Like redis, only positive values are valid.
+fn parse_expire_time(args: &mut VecDeque<RedisMessageType>) -> Result<u64, RedisMessageType> {
+    let value = args
+        .pop_front()
+        .ok_or(reply::syntax_error())?
+        .bulk_string_value()?
+        .parse::<i64>()
+        .map_err(|_| reply::not_an_integer())?;
+
+    if value <= 0 {
+        return Err(SetCommand::invalid_value_error("expire time"));
+    }
+    return Ok(value as u64);
+}
+
+fn unix_time(since_epoch: Duration) -> Result<SystemTime, RedisMessageType> {
+    return SystemTime::UNIX_EPOCH
+        .checked_add(since_epoch)
+        .ok_or(SetCommand::invalid_value_error("expire time"));
+}
+
 impl Parse for SetCommand {
     fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
         let key = args
@@ -94,46 +118,27 @@ impl Parse for SetCommand {
                     return_old_value = true;
                 }
                 "EX" => {
-                    // next argument must exist
-                    let arg = args.pop_front().ok_or(Self::arg_count_error())?;
-                    let secs = arg.bulk_string_value()?.parse::<u64>().map_err(|_| {
-                        RedisMessageType::error("ERR value is not an integer or out of range")
-                    })?;
+                    let secs = parse_expire_time(&mut args)?;
                     expiry_condition = Some(ExpiryCondition::EX(Duration::from_secs(secs)));
                 }
                 "PX" => {
-                    let arg = args.pop_front().ok_or(Self::arg_count_error())?;
-                    let ms = arg.bulk_string_value()?.parse::<u64>().map_err(|_| {
-                        RedisMessageType::error("ERR value is not an integer or out of range")
-                    })?;
+                    let ms = parse_expire_time(&mut args)?;
                     expiry_condition = Some(ExpiryCondition::PX(Duration::from_millis(ms)));
                 }
                 "EXAT" => {
-                    let arg = args.pop_front().ok_or(Self::arg_count_error())?;
-                    let ts = arg.bulk_string_value()?.parse::<u64>().map_err(|_| {
-                        RedisMessageType::error("ERR value is not an integer or out of range")
-                    })?;
-                    expiry_condition = Some(ExpiryCondition::EXAT(
-                        SystemTime::UNIX_EPOCH + Duration::from_secs(ts),
-                    ));
+                    let ts = parse_expire_time(&mut args)?;
+                    expiry_condition =
+                        Some(ExpiryCondition::EXAT(unix_time(Duration::from_secs(ts))?));
                 }
                 "PXAT" => {
-                    let arg = args.pop_front().ok_or(Self::arg_count_error())?;
-                    let ts = arg.bulk_string_value()?.parse::<u64>().map_err(|_| {
-                        RedisMessageType::error("ERR value is not an integer or out of range")
-                    })?;
-                    expiry_condition = Some(ExpiryCondition::PXAT(
-                        SystemTime::UNIX_EPOCH + Duration::from_millis(ts),
-                    ));
+                    let ts = parse_expire_time(&mut args)?;
+                    expiry_condition =
+                        Some(ExpiryCondition::PXAT(unix_time(Duration::from_millis(ts))?));
                 }
                 "KEEPTTL" => {
                     expiry_condition = Some(ExpiryCondition::KEEPTTL);
                 }
-                _ => {
-                    return Err(RedisMessageType::error(format!(
-                        "ERR value is not a valid argument for command 'set'."
-                    )));
-                }
+                _ => return Err(reply::syntax_error()),
             }
         }
 
@@ -149,47 +154,48 @@ impl Parse for SetCommand {
 
 impl Execute for SetCommand {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
-        let old_value = get_db().get(&self.key);
-
-        let expiry = self.expiry_condition.and_then(|condition| match condition {
-            ExpiryCondition::EX(dur) | ExpiryCondition::PX(dur) => Some(Expiry::Ttl(dur)),
-            ExpiryCondition::EXAT(st) | ExpiryCondition::PXAT(st) => Some(Expiry::Deadline(st)),
-            ExpiryCondition::KEEPTTL => old_value
-                .clone()
-                .and_then(|v| v.get_expiry_deadline())
-                .map(Expiry::Instant),
-        });
-
-        let data = DataUnit::new(self.key.clone(), self.value, expiry);
-
-        match self.set_condition {
-            None => get_db().set(self.key, data),
-            Some(condition) => match condition {
-                SetCondition::NX => {
-                    if old_value.is_some() {
-                        let error = format!("Not setting value for key: '{}' due to 'NX' argument (create only command) and exsisting value.", self.key);
-                        trace!("{}", error);
-                        return Err(RedisMessageType::error(error));
-                    }
-                    get_db().set(self.key, data);
+        let key = self.key;
+        let value = self.value;
+        let set_condition = self.set_condition;
+        let expiry_condition = self.expiry_condition;
+
+        // condition check and write happen under the same lock
+        let old_value = get_db().update(key.clone(), |current| {
+            match set_condition {
+                Some(SetCondition::NX) if current.is_some() => {
+                    let error = format!("Not setting value for key: '{}' due to 'NX' argument (create only command) and exsisting value.", key);
+                    trace!("{}", error);
+                    return Err(RedisMessageType::error(error));
                 }
-                SetCondition::XX => {
-                    if old_value.is_none() {
-                        let error = format!("Not setting value for key: '{}' due to 'XX' argument (update only command) and non exsisting value.", self.key);
-                        trace!("{}", error);
-                        return Err(RedisMessageType::error(error));
-                    }
-                    get_db().set(self.key, data);
+                Some(SetCondition::XX) if current.is_none() => {
+                    let error = format!("Not setting value for key: '{}' due to 'XX' argument (update only command) and non exsisting value.", key);
+                    trace!("{}", error);
+                    return Err(RedisMessageType::error(error));
                 }
-            },
-        };
+                _ => (),
+            }
+
+            let expiry = expiry_condition.and_then(|condition| match condition {
+                ExpiryCondition::EX(dur) | ExpiryCondition::PX(dur) => Some(Expiry::Ttl(dur)),
+                ExpiryCondition::EXAT(st) | ExpiryCondition::PXAT(st) => {
+                    Some(Expiry::Deadline(st))
+                }
+                ExpiryCondition::KEEPTTL => current
+                    .as_ref()
+                    .and_then(|v| v.get_expiry_deadline())
+                    .map(Expiry::Instant),
+            });
+
+            let data = DataUnit::new(key.clone(), value, expiry);
+            return Ok(current.replace(data));
+        })?;
 
         if self.return_old_value {
             return Ok(old_value
-                .map(|v| RedisMessageType::bulk_string(v.value))
-                .unwrap_or(RedisMessageType::NullBulkString));
+                .map(|v| reply::bulk(v.value))
+                .unwrap_or(reply::null()));
         } else {
-            return Ok(RedisMessageType::simple_string("OK"));
+            return Ok(reply::ok());
         }
     }
 }
@@ -0,0 +1,101 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    commands::{
+        reply::{self, ErrorCode},
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::get_db,
+    parser::messages::RedisMessageType,
+    utils::shutdown,
+};
+
+/// SHUTDOWN [NOSAVE] [NOW] [FORCE]. The dataset is never saved, so SAVE is refused and
+/// NOSAVE and FORCE change nothing. NOW closes every connection without waiting for the
+/// `shutdown-timeout`.
+///
+/// The caller gets `+OK` before its connection is closed, the process exits once the other
+/// connections are closed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShutdownCommand {
+    now: bool,
+}
+
+impl ShutdownCommand {
+    fn new(now: bool) -> Self {
+        return Self { now };
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for ShutdownCommand {
+    fn command_name() -> &'static str {
+        return "shutdown";
+    }
+}
+impl ArgErrorMessageGenerator<ShutdownCommand> for ShutdownCommand {}
+
+impl Parse for ShutdownCommand {
+    fn parse(args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let mut now = false;
+        for arg in args {
+            match arg.bulk_string_value()?.to_ascii_uppercase().as_str() {
+                "NOSAVE" | "FORCE" => {}
+                "NOW" => now = true,
+                "SAVE" => {
+                    return Err(reply::error(
+                        ErrorCode::Err,
+                        "SAVE is not supported, the dataset is not persisted on shutdown",
+                    ))
+                }
+                _ => return Err(reply::syntax_error()),
+            }
+        }
+
+        return Ok(Self::new(now));
+    }
+}
+
+impl Execute for ShutdownCommand {
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let timeout = match self.now {
+            true => Duration::ZERO,
+            false => get_db().get_config().shutdown_timeout,
+        };
+
+        return match shutdown::request(timeout) {
+            Ok(()) => Ok(reply::ok()),
+            Err(reason) => Err(reply::error(
+                ErrorCode::Err,
+                format!("Errors trying to SHUTDOWN: {}", reason),
+            )),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        commands::{shutdown::ShutdownCommand, traits::Parse},
+        parser::messages::RedisMessageType,
+    };
+
+    fn parse(args: Vec<&str>) -> Result<ShutdownCommand, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return ShutdownCommand::parse(VecDeque::from_iter(args));
+    }
+
+    // executing would exit the test process, see the integration tests instead
+    #[test]
+    fn test_parse_shutdown() {
+        assert_eq!(Ok(ShutdownCommand { now: false }), parse(vec![]));
+        assert_eq!(
+            Ok(ShutdownCommand { now: true }),
+            parse(vec!["nosave", "NOW", "force"])
+        );
+        assert!(parse(vec!["SAVE"]).is_err());
+        assert!(parse(vec!["ABORT"]).is_err());
+    }
+}
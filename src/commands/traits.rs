@@ -1,6 +1,12 @@
 use std::collections::VecDeque;
 
-use crate::parser::messages::RedisMessageType;
+use crate::{
+    commands::{
+        context::ConnectionContext,
+        reply::{self, ErrorCode},
+    },
+    parser::messages::RedisMessageType,
+};
 
 pub struct Unparsed;
 pub struct Parsed;
@@ -13,6 +19,8 @@ mod sealed {
 
 pub struct UnparsedCommand {
     pub args: VecDeque<RedisMessageType>,
+    /// The legacy name the command was called by, see `COMMAND_ALIASES`.
+    pub alias: Option<&'static str>,
 }
 
 pub struct Command<State, ParsedPayload>
@@ -39,7 +47,7 @@ pub type StateItem<S, P> = <S as StateItemType<P>>::Item;
 impl<P> Command<Unparsed, P> {
     pub fn new(args: VecDeque<RedisMessageType>) -> Self {
         Self {
-            item: UnparsedCommand { args },
+            item: UnparsedCommand { args, alias: None },
         }
     }
 }
@@ -58,8 +66,17 @@ where
     }
 }
 
-pub trait Execute {
+pub trait Execute: Sized {
     fn execute(self) -> Result<RedisMessageType, RedisMessageType>;
+
+    /// Executes the command for a client connection. Only commands depending on the
+    /// connection (e.g. CLIENT) need to override this.
+    fn execute_with_context(
+        self,
+        _ctx: &ConnectionContext,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        return self.execute();
+    }
 }
 
 impl<P> Command<Parsed, P>
@@ -69,28 +86,88 @@ where
     pub fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
         return self.item.execute();
     }
+
+    pub fn execute_with_context(
+        self,
+        ctx: &ConnectionContext,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        return self.item.execute_with_context(ctx);
+    }
 }
 
 pub trait CommandName {
     fn command_name() -> &'static str;
 }
 
+/// Errors for arguments that can not be parsed. They name the command, and the subcommand
+/// or option at fault, in the formats redis uses.
 pub trait ArgErrorMessageGenerator<P>
 where
     P: CommandName,
 {
     fn arg_count_error() -> RedisMessageType {
-        RedisMessageType::error(format!(
-            "ERR wrong number of arguments for '{}' command",
-            P::command_name()
-        ))
+        reply::error(
+            ErrorCode::Err,
+            format!(
+                "wrong number of arguments for '{}' command",
+                P::command_name()
+            ),
+        )
+    }
+
+    fn sub_arg_count_error(sub_command: &str) -> RedisMessageType {
+        reply::error(
+            ErrorCode::Err,
+            format!(
+                "wrong number of arguments for '{}|{}' command",
+                P::command_name(),
+                sub_command.to_ascii_lowercase()
+            ),
+        )
+    }
+
+    fn unknown_subcommand_error(sub_command: &str) -> RedisMessageType {
+        reply::error(
+            ErrorCode::Err,
+            format!(
+                "unknown subcommand '{}'. Try {} HELP.",
+                sub_command,
+                P::command_name().to_ascii_uppercase()
+            ),
+        )
+    }
+
+    /// For commands that can not tell an unknown subcommand from wrong arguments of a known
+    /// one, e.g. because subcommands are only matched together with their arguments.
+    fn sub_syntax_error(sub_command: &str) -> RedisMessageType {
+        reply::error(
+            ErrorCode::Err,
+            format!(
+                "Unknown subcommand or wrong number of arguments for '{}'. Try {} HELP.",
+                sub_command,
+                P::command_name().to_ascii_uppercase()
+            ),
+        )
+    }
+
+    /// The value of the named argument or option is not a number, e.g. 'timeout'.
+    fn not_an_integer_error(name: &str) -> RedisMessageType {
+        reply::error(
+            ErrorCode::Err,
+            format!("{} is not an integer or out of range", name),
+        )
+    }
+
+    fn negative_value_error(name: &str) -> RedisMessageType {
+        reply::error(ErrorCode::Err, format!("{} is negative", name))
     }
 
-    fn sub_arg_count_error(key: String) -> RedisMessageType {
-        RedisMessageType::error(format!(
-            "ERR wrong number of arguments for '{}|{}' command",
-            P::command_name(),
-            key
-        ))
+    /// The value is a number but outside of the range the command accepts, e.g. an expire
+    /// time of 0.
+    fn invalid_value_error(name: &str) -> RedisMessageType {
+        reply::error(
+            ErrorCode::Err,
+            format!("invalid {} in '{}' command", name, P::command_name()),
+        )
     }
 }
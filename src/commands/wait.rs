@@ -0,0 +1,131 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    commands::{
+        context::ConnectionContext,
+        reply,
+        traits::{ArgErrorMessageGenerator, CommandName, Execute, Parse},
+    },
+    db::data_store::{get_db, ServerRole},
+    parser::messages::RedisMessageType,
+    replication::master,
+};
+
+pub struct WaitCommand {
+    num_replicas: usize,
+    /// `None` blocks until enough replicas acknowledged.
+    timeout: Option<Duration>,
+}
+
+impl WaitCommand {
+    fn new(num_replicas: usize, timeout: Option<Duration>) -> Self {
+        return Self {
+            num_replicas,
+            timeout,
+        };
+    }
+
+    /// Waits until enough replicas acknowledged the given replication offset.
+    fn wait_for(self, offset: u64) -> Result<RedisMessageType, RedisMessageType> {
+        // a replica has no replicas of its own, so there is nothing to wait for
+        if let ServerRole::Slave(_) = get_db().get_config().replication_data.role {
+            return Ok(reply::integer(0));
+        }
+
+        let acked = master::wait_for_acks(self.num_replicas, offset, self.timeout);
+        return Ok(reply::integer(acked as i64));
+    }
+}
+
+// could be moved into a procedural macro in the future
+impl CommandName for WaitCommand {
+    fn command_name() -> &'static str {
+        return "wait";
+    }
+}
+impl ArgErrorMessageGenerator<WaitCommand> for WaitCommand {}
+
+impl Parse for WaitCommand {
+    fn parse(mut args: VecDeque<RedisMessageType>) -> Result<Self, RedisMessageType> {
+        let (num_replicas, timeout) = match (args.pop_front(), args.pop_front(), args.is_empty()) {
+            (Some(num_replicas), Some(timeout), true) => (
+                num_replicas.bulk_string_value()?,
+                timeout.bulk_string_value()?,
+            ),
+            _ => return Err(Self::arg_count_error()),
+        };
+
+        let num_replicas = num_replicas
+            .parse::<usize>()
+            .map_err(|_| reply::not_an_integer())?;
+        let timeout = timeout
+            .parse::<i64>()
+            .map_err(|_| Self::not_an_integer_error("timeout"))?;
+
+        let timeout = match timeout {
+            ..0 => return Err(Self::negative_value_error("timeout")),
+            0 => None,
+            millis => Some(Duration::from_millis(millis as u64)),
+        };
+
+        return Ok(Self::new(num_replicas, timeout));
+    }
+}
+
+impl Execute for WaitCommand {
+    /// Without a client every write so far is waited for.
+    fn execute(self) -> Result<RedisMessageType, RedisMessageType> {
+        let offset = get_db().get_config().replication_data.master_repl_offset as u64;
+        return self.wait_for(offset);
+    }
+
+    /// Only the writes of the client are waited for, not the ones of other clients
+    /// propagated since.
+    fn execute_with_context(
+        self,
+        ctx: &ConnectionContext,
+    ) -> Result<RedisMessageType, RedisMessageType> {
+        return self.wait_for(ctx.client().last_write_offset());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use crate::{
+        commands::{traits::Parse, wait::WaitCommand},
+        parser::messages::RedisMessageType,
+    };
+
+    fn parse(args: Vec<&str>) -> Result<WaitCommand, RedisMessageType> {
+        let args = args.into_iter().map(RedisMessageType::bulk_string);
+        return WaitCommand::parse(VecDeque::from_iter(args));
+    }
+
+    #[test]
+    fn test_parse_wait() {
+        let command = parse(vec!["2", "500"]).ok().unwrap();
+
+        assert_eq!(2, command.num_replicas);
+        assert_eq!(Some(Duration::from_millis(500)), command.timeout);
+    }
+
+    #[test]
+    fn test_parse_wait_zero_timeout_blocks() {
+        let command = parse(vec!["1", "0"]).ok().unwrap();
+
+        assert_eq!(None, command.timeout);
+    }
+
+    #[test]
+    fn test_parse_wait_invalid_args() {
+        assert!(parse(vec!["1"]).is_err());
+        assert!(parse(vec!["1", "100", "1"]).is_err());
+        assert!(parse(vec!["one", "100"]).is_err());
+        assert_eq!(
+            Err(RedisMessageType::error("ERR timeout is negative")),
+            parse(vec!["1", "-1"]).map(|_| ())
+        );
+    }
+}
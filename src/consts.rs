@@ -2,6 +2,7 @@ use core::str;
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use once_cell::sync::Lazy;
@@ -10,5 +11,12 @@ pub const CR: u8 = b'\r';
 pub const LF: u8 = b'\n';
 pub const CRLF: &str = "\r\n";
 
+/// Clients and replicas not reading what is send to them for this long are disconnected, so a
+/// slow reader can not block a worker thread forever.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default of `shutdown-timeout`, like redis.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub static GLOBAL_MAP: Lazy<Arc<RwLock<HashMap<String, String>>>> =
     Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
@@ -1,20 +1,39 @@
-use rand::Rng;
 use std::{
+    cell::RefCell,
     fs,
+    hash::{BuildHasher, RandomState},
+    io::Read,
     path::PathBuf,
-    sync::{Arc, RwLock},
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread,
     time::{Duration, Instant, SystemTime},
 };
 
-use dashmap::DashMap;
-
 use anyhow::{anyhow, Result};
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
 use once_cell::sync::OnceCell;
 
-use crate::parser::db_file::RdbFile;
+use crate::{
+    consts::DEFAULT_SHUTDOWN_TIMEOUT,
+    db::{
+        eviction::{EvictionPolicy, DEFAULT_MAXMEMORY_SAMPLES},
+        persistence,
+        storage::{MemoryTracked, Storage, StorageBackend},
+        ttl_stats::TtlHistogram,
+        value::Value,
+    },
+    parser::db_file::RdbFile,
+    utils::{buffer_pool, random::RandomSource},
+};
 
-const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+const REPL_ID_LEN: usize = 40;
+const KEY_LOCK_STRIPES: usize = 256;
+/// Resolution of the last access time of keys, like the LRU clock of redis. Reads only
+/// update the access time once it is older than this.
+pub const LRU_CLOCK_RESOLUTION: Duration = Duration::from_secs(1);
+/// Keys and values are only shrunk if that frees at least this many bytes, smaller slack
+/// is not worth the reallocation.
+pub const MIN_WASTED_BYTES: usize = 1024;
 static DB: OnceCell<DataStore> = OnceCell::new();
 pub fn get_db() -> &'static DataStore {
     return DB
@@ -30,6 +49,18 @@ pub fn init_db(db_config: DbConfig) {
     trace!("Config has been initialized!")
 }
 
+/// Initializes the global db with an empty config if no other test did so yet.
+#[cfg(test)]
+pub fn init_test_db() -> &'static DataStore {
+    return DB.get_or_init(|| DataStore::init(DbConfig::new(PathBuf::new(), "".into(), None, 1)));
+}
+
+/// A data store of its own with an empty config, for tests that must not share keys.
+#[cfg(test)]
+pub fn new_test_db() -> DataStore {
+    return DataStore::init(DbConfig::new(PathBuf::new(), "".into(), None, 1));
+}
+
 #[derive(Debug, Clone)]
 pub enum ServerRole {
     Master,
@@ -65,24 +96,14 @@ impl ReplicationData {
         return Self::new(ServerRole::Slave((host, port)));
     }
 
+    /// The replication id is assigned by the data store, drawn from its random source.
     fn new(role: ServerRole) -> Self {
-        let master_repl_id = Self::generate_master_repl_id();
-
         return Self {
             role,
-            master_repl_id,
+            master_repl_id: String::new(),
             master_repl_offset: 0,
         };
     }
-
-    fn generate_master_repl_id() -> String {
-        return (0..40)
-            .map(|_| {
-                let idx = rand::rng().random_range(0..CHARSET.len());
-                CHARSET[idx] as char
-            })
-            .collect();
-    }
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +112,33 @@ pub struct DbConfig {
     pub db_filename: String,
     pub replication_data: ReplicationData,
     pub current_listening_port: u16,
+    /// Amount of keyspace shards, a power of two. None uses the DashMap default which
+    /// depends on the amount of cpus.
+    pub shard_amount: Option<usize>,
+    /// The map holding the keys, only chosen at startup.
+    pub storage_backend: StorageBackend,
+    /// Memory limit in bytes, 0 is no limit. Writes evict keys by the `maxmemory_policy`
+    /// once the used memory exceeds it.
+    pub maxmemory: u64,
+    /// Which keys are evicted to stay within `maxmemory`.
+    pub maxmemory_policy: EvictionPolicy,
+    /// Keys sampled per eviction, more approximate a true LRU better.
+    pub maxmemory_samples: usize,
+    /// Whether a replica rejects writes of its clients.
+    pub replica_read_only: bool,
+    /// Seed of the randomness of the server. None seeds from the operating system, a fixed
+    /// seed makes e.g. the replication id deterministic.
+    pub random_seed: Option<u64>,
+    /// Password a replica authenticates with at its master during the handshake.
+    pub masterauth: Option<String>,
+    /// Whether a replica asks its master for an lz4 compressed replication stream.
+    pub repl_compression: bool,
+    /// Time budget of a client command, zero is no limit. Only commands able to stop
+    /// cleanly check it, see [`crate::utils::deadline`].
+    pub command_time_limit: Duration,
+    /// How long commands in flight may take to finish on shutdown, before their
+    /// connections are closed by force.
+    pub shutdown_timeout: Duration,
 }
 
 impl DbConfig {
@@ -109,6 +157,17 @@ impl DbConfig {
             db_filename,
             replication_data,
             current_listening_port,
+            shard_amount: None,
+            storage_backend: StorageBackend::DashMap,
+            maxmemory: 0,
+            maxmemory_policy: EvictionPolicy::NoEviction,
+            maxmemory_samples: DEFAULT_MAXMEMORY_SAMPLES,
+            replica_read_only: true,
+            random_seed: None,
+            masterauth: None,
+            repl_compression: false,
+            command_time_limit: Duration::ZERO,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
         };
     }
 
@@ -117,22 +176,235 @@ impl DbConfig {
     }
 }
 
+thread_local! {
+    /// Stripes the current thread holds the write lock of, as the address of their
+    /// [`KeyLocks`] and the index of the stripe.
+    static HELD_STRIPES: RefCell<Vec<(usize, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Striped locks for keys. A key is always guarded by the same stripe, no matter which
+/// shard of the storage it lives in.
+///
+/// Lock order: stripes are taken before any shard lock of the storage and, if several are
+/// needed, in ascending index order. Following this order two threads can never deadlock.
+///
+/// A stripe the current thread already holds the write lock of is not locked again, so a
+/// write command can hold the locks of its keys until it is propagated, see
+/// [`DataStore::with_locked_keys`].
+///
+/// The stripes guard no data, so a stripe poisoned by a panicking command is used as is.
+/// Else a single panic would lock its keys away until the server restarts.
+#[derive(Debug)]
+struct KeyLocks {
+    hasher: RandomState,
+    stripes: Box<[RwLock<()>]>,
+}
+
+/// The lock of a stripe, released on drop.
+enum StripeGuard<'a> {
+    Read(RwLockReadGuard<'a, ()>),
+    Write(RwLockWriteGuard<'a, ()>, (usize, usize)),
+    /// The current thread already held the write lock of the stripe.
+    Reentered,
+}
+
+impl Drop for StripeGuard<'_> {
+    fn drop(&mut self) {
+        if let Self::Write(_, held) = self {
+            HELD_STRIPES.with(|stripes| stripes.borrow_mut().retain(|stripe| stripe != held));
+        }
+    }
+}
+
+impl KeyLocks {
+    fn new() -> Self {
+        return Self {
+            hasher: RandomState::new(),
+            stripes: (0..KEY_LOCK_STRIPES).map(|_| RwLock::new(())).collect(),
+        };
+    }
+
+    fn stripe(&self, key: &str) -> usize {
+        return self.hasher.hash_one(key) as usize % self.stripes.len();
+    }
+
+    /// Identifies a stripe of these locks among the ones of other stores.
+    fn held_id(&self, stripe: usize) -> (usize, usize) {
+        return (self as *const Self as usize, stripe);
+    }
+
+    fn is_held(&self, stripe: usize) -> bool {
+        let id = self.held_id(stripe);
+        return HELD_STRIPES.with(|stripes| stripes.borrow().contains(&id));
+    }
+
+    fn read(&self, key: &str) -> StripeGuard<'_> {
+        let stripe = self.stripe(key);
+        if self.is_held(stripe) {
+            return StripeGuard::Reentered;
+        }
+        return StripeGuard::Read(
+            self.stripes[stripe]
+                .read()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+
+    fn write(&self, key: &str) -> StripeGuard<'_> {
+        return self.lock_stripe(self.stripe(key));
+    }
+
+    fn lock_stripe(&self, stripe: usize) -> StripeGuard<'_> {
+        if self.is_held(stripe) {
+            return StripeGuard::Reentered;
+        }
+        let guard = self.stripes[stripe]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let id = self.held_id(stripe);
+        HELD_STRIPES.with(|stripes| stripes.borrow_mut().push(id));
+        return StripeGuard::Write(guard, id);
+    }
+
+    /// Locks the stripes of all keys in ascending order. Keys sharing a stripe lock it once.
+    fn write_many(&self, keys: &[&str]) -> Vec<StripeGuard<'_>> {
+        let mut stripes: Vec<usize> = keys.iter().map(|key| self.stripe(key)).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+
+        return stripes
+            .into_iter()
+            .map(|stripe| self.lock_stripe(stripe))
+            .collect();
+    }
+}
+
 #[derive(Debug)]
 pub struct DataStore {
-    db: Arc<DashMap<String, DataUnit>>,
+    db: MemoryTracked,
     config: Arc<RwLock<DbConfig>>,
+    started_at: Instant,
+    key_locks: KeyLocks,
+    random: RandomSource,
+    /// Orders the keys for SCAN, independent of the hashing of the storage.
+    scan_hasher: RandomState,
+}
+
+/// Iterator returned by [`DataStore::snapshot_iter`].
+pub struct SnapshotIter<'a> {
+    db: &'a dyn Storage,
+    next_shard: usize,
+    current: std::vec::IntoIter<DataUnit>,
+    peak_copy_bytes: usize,
+}
+
+impl SnapshotIter<'_> {
+    fn clone_shard(&mut self, index: usize) -> Vec<DataUnit> {
+        let mut values = Vec::new();
+        let mut bytes = 0;
+        self.db.for_each_in_shard(index, &mut |_, value| {
+            if !value.is_expired() {
+                bytes +=
+                    std::mem::size_of::<DataUnit>() + value.key.capacity() + value.value.capacity();
+                values.push(value.clone());
+            }
+        });
+        self.peak_copy_bytes = self.peak_copy_bytes.max(bytes);
+        return values;
+    }
+
+    /// Bytes of the largest shard copied so far, the extra memory the snapshot needs.
+    pub fn peak_copy_bytes(&self) -> usize {
+        return self.peak_copy_bytes;
+    }
+}
+
+impl Iterator for SnapshotIter<'_> {
+    type Item = DataUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.current.next() {
+                return Some(value);
+            }
+
+            if self.next_shard >= self.db.shard_count() {
+                return None;
+            }
+            self.current = self.clone_shard(self.next_shard).into_iter();
+            self.next_shard += 1;
+        }
+    }
+}
+
+/// A key sampled by [`DataStore::sample_keys`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySample {
+    pub key: String,
+    pub idle_time: Duration,
+    pub expiry_deadline: Option<Instant>,
+}
+
+impl KeySample {
+    fn new(key: &str, value: &DataUnit) -> Self {
+        return Self {
+            key: key.to_string(),
+            idle_time: value.idle_time(),
+            expiry_deadline: value.expiry_deadline,
+        };
+    }
+}
+
+/// Access to a fixed set of keys locked by [`DataStore::with_locked_keys`].
+pub struct LockedKeys<'a> {
+    store: &'a DataStore,
+    keys: &'a [&'a str],
+}
+
+impl LockedKeys<'_> {
+    fn check_locked(&self, key: &str) {
+        if !self.keys.contains(&key) {
+            panic!("Key '{}' is accessed without being locked!", key);
+        }
+    }
+
+    /// Returns the value of the key, None if it is missing or expired.
+    pub fn get(&self, key: &str) -> Option<DataUnit> {
+        self.check_locked(key);
+        let value = self.store.db.get(key)?;
+        return (!value.is_expired()).then_some(value);
+    }
+
+    pub fn set(&self, key: &str, value: DataUnit) {
+        self.check_locked(key);
+        self.store.db.set(key.to_string(), value);
+    }
+
+    /// Removes the key and returns its value, None if it was missing or expired.
+    pub fn remove(&self, key: &str) -> Option<DataUnit> {
+        self.check_locked(key);
+        let value = self.store.db.remove(key)?;
+        return (!value.is_expired()).then_some(value);
+    }
 }
 
 impl DataStore {
-    fn init(db_config: DbConfig) -> Self {
-        let map = Self::load_data_from_dbfile(&db_config).unwrap_or(DashMap::new());
+    fn init(mut db_config: DbConfig) -> Self {
+        let random = RandomSource::new(db_config.random_seed);
+        db_config.replication_data.master_repl_id = random.id(REPL_ID_LEN);
+
+        let storage = db_config.storage_backend.create(db_config.shard_amount);
         return Self {
-            db: Arc::new(map),
+            db: MemoryTracked::new(storage),
             config: Arc::new(RwLock::new(db_config)),
+            started_at: Instant::now(),
+            key_locks: KeyLocks::new(),
+            random,
+            scan_hasher: RandomState::new(),
         };
     }
 
-    fn load_data_from_dbfile(db_config: &DbConfig) -> Result<DashMap<String, DataUnit>> {
+    fn load_data_from_dbfile(db_config: &DbConfig) -> Result<RdbFile> {
         let path = db_config.get_full_db_file_path();
         if !path.is_file() {
             return Err(anyhow!(
@@ -141,22 +413,244 @@ impl DataStore {
             ));
         }
         debug!("Loading db file");
-        let raw_data: Vec<u8> = fs::read(path)?;
+        let mut file = fs::File::open(path)?;
+        let mut raw_data = buffer_pool::take(file.metadata()?.len() as usize);
+        file.read_to_end(&mut raw_data)?;
         trace!("Loaded db file");
-        let rdb_file = RdbFile::decode(raw_data)?;
+        let rdb_file = RdbFile::decode(&raw_data);
+        buffer_pool::give_back(raw_data);
+        let rdb_file = rdb_file?;
         debug!("Parsed db file contents into memory");
-        let dash_map = rdb_file.get_database().to_dashmap();
-        info!("Successfully loaded db file contents into in memory database!");
-        return Ok(dash_map);
+        return Ok(rdb_file);
+    }
+
+    /// Loads the db file of the config, if there is one. Clients are answered with
+    /// `-LOADING` until it is done, so large files are loaded in the background on startup.
+    pub fn load_db_file(&self) {
+        let db_config = self.get_config();
+        let Ok(metadata) = fs::metadata(db_config.get_full_db_file_path()) else {
+            debug!("There is no db file to load");
+            return;
+        };
+
+        let _loading = persistence::start_loading(metadata.len());
+        match Self::load_data_from_dbfile(&db_config) {
+            Ok(rdb_file) => {
+                persistence::load(&rdb_file, |value| self.set(value.key.clone(), value));
+                info!("Successfully loaded db file contents into in memory database!");
+            }
+            Err(err) => warn!("Unable to load the db file: {}", err),
+        }
+    }
+
+    /// Replaces the whole keyspace with the contents of the rdb file, e.g. after a full resync.
+    /// Clients are only answered with `-LOADING` meanwhile if the caller started loading, see
+    /// [`persistence::start_loading`].
+    pub fn load_rdb_file(&self, rdb_file: &RdbFile) {
+        drop(self.db.take());
+        persistence::load(rdb_file, |value| self.set(value.key.clone(), value));
+        info!("Loaded {} keys from rdb file", self.db.len());
     }
 
-    pub fn get_all_keys(&self) -> Vec<String> {
-        let mut keys = Vec::with_capacity(self.db.capacity());
-        for entry in self.db.iter() {
-            keys.push(entry.key.clone());
+    /// Calls `f` with the key of every non expired value, shard by shard, and stops at the
+    /// first error.
+    pub fn try_for_each_key<E, F>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(&str) -> Result<(), E>,
+    {
+        for index in 0..self.db.shard_count() {
+            let mut result = Ok(());
+            self.db.for_each_in_shard(index, &mut |key, value| {
+                if result.is_ok() && !value.is_expired() {
+                    result = f(key);
+                }
+            });
+            result?;
+        }
+        return Ok(());
+    }
+
+    /// One step of SCAN: up to about `count` keys after `cursor` and the cursor to continue
+    /// with, 0 once all shards are done.
+    ///
+    /// Within a shard keys are visited in the order of their reversed hash, which is the
+    /// reverse binary cursor of redis without its table size. The position of a key never
+    /// changes, no matter how the tables grow or shrink, so every key present for the whole
+    /// scan is returned at least once. Each step hashes all keys of the shards it visits.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<String>) {
+        let shard_count = self.db.shard_count();
+        // every storage has a power of two of at least 2 shards, their index is the top of
+        // the cursor and the position in the shard the rest
+        let shard_bits = shard_count.trailing_zeros();
+        let position_bits = u64::BITS - shard_bits;
+        let mut index = (cursor >> position_bits) as usize;
+        let mut position = cursor & (u64::MAX >> shard_bits);
+
+        let mut keys = Vec::new();
+        while index < shard_count && keys.len() < count {
+            let mut remaining: Vec<(u64, String)> = Vec::new();
+            self.db.for_each_in_shard(index, &mut |key, value| {
+                let key_position = self.scan_hasher.hash_one(key).reverse_bits() >> shard_bits;
+                if key_position >= position && !value.is_expired() {
+                    remaining.push((key_position, key.to_string()));
+                }
+            });
+
+            let wanted = count - keys.len();
+            if remaining.len() <= wanted {
+                keys.extend(remaining.into_iter().map(|(_, key)| key));
+                index += 1;
+                position = 0;
+                continue;
+            }
+
+            remaining.select_nth_unstable(wanted - 1);
+            let last = remaining[..wanted]
+                .iter()
+                .map(|(key_position, _)| *key_position)
+                .max();
+            let last = last.expect("At least one key is wanted. Should never happen!");
+            // keys sharing the position of the last one can not be told apart by the cursor
+            keys.extend(
+                remaining
+                    .into_iter()
+                    .filter(|(key_position, _)| *key_position <= last)
+                    .map(|(_, key)| key),
+            );
+            // a key after the last one exists, so the position can not overflow
+            position = last + 1;
+            break;
         }
-        keys.shrink_to_fit();
-        return keys;
+
+        if index == shard_count {
+            return (0, keys);
+        }
+        return (((index as u64) << position_bits) | position, keys);
+    }
+
+    /// Iterates over a copy of all non expired values, taken shard by shard.
+    ///
+    /// Each shard is cloned while holding its read lock, so every shard is consistent in
+    /// itself and writes to all other shards continue while the snapshot is consumed.
+    pub fn snapshot_iter(&self) -> SnapshotIter<'_> {
+        return SnapshotIter {
+            db: &self.db,
+            next_shard: 0,
+            current: Vec::new().into_iter(),
+            peak_copy_bytes: 0,
+        };
+    }
+
+    /// Returns the amount of keys and the capacity of each shard.
+    pub fn shard_stats(&self) -> Vec<(usize, usize)> {
+        return (0..self.db.shard_count())
+            .map(|index| self.db.shard_stats(index))
+            .collect();
+    }
+
+    pub fn shard_count(&self) -> usize {
+        return self.db.shard_count();
+    }
+
+    /// See [`Storage::shrink_shard`].
+    pub fn shrink_shard(&self, index: usize) -> Option<(usize, usize)> {
+        return self.db.shrink_shard(index);
+    }
+
+    /// Estimated bytes used by the keys, see [`DataUnit::memory_usage`].
+    pub fn used_memory(&self) -> u64 {
+        return self.db.used_memory();
+    }
+
+    /// Samples up to `count` keys for the eviction, only keys with an expiry if
+    /// `volatile_only` is set. The samples are neighbours in a shard starting at a random
+    /// position, the hashing of the keys spreads them over the keyspace.
+    pub fn sample_keys(&self, count: usize, volatile_only: bool) -> Vec<KeySample> {
+        let shard_count = self.db.shard_count();
+        let start = self.random.range(0..shard_count);
+        let mut samples = Vec::with_capacity(count);
+
+        for index in (start..shard_count).chain(0..start) {
+            let (len, _) = self.db.shard_stats(index);
+            if len == 0 {
+                continue;
+            }
+            let skip = self.random.range(0..len);
+            let wanted = count - samples.len();
+            // keys before the start position are only taken if the shard wraps around
+            let mut wrapped = Vec::new();
+            let mut position = 0;
+            self.db.for_each_in_shard(index, &mut |key, value| {
+                let eligible = !volatile_only || value.expiry_deadline.is_some();
+                if eligible && position >= skip && samples.len() < count {
+                    samples.push(KeySample::new(key, value));
+                } else if eligible && position < skip && wrapped.len() < wanted {
+                    wrapped.push(KeySample::new(key, value));
+                }
+                position += 1;
+            });
+            let missing = count - samples.len();
+            samples.extend(wrapped.into_iter().take(missing));
+
+            if samples.len() >= count {
+                break;
+            }
+        }
+        return samples;
+    }
+
+    /// Builds a histogram of the remaining ttl of up to `max_samples` volatile keys. The
+    /// sampling starts at a random shard, so repeated calls look at different keys.
+    pub fn sample_ttls(&self, max_samples: usize) -> TtlHistogram {
+        let mut histogram = TtlHistogram::default();
+        let shard_count = self.db.shard_count();
+        let start = self.random.range(0..shard_count);
+        let now = Instant::now();
+
+        for index in (start..shard_count).chain(0..start) {
+            self.db.for_each_in_shard(index, &mut |_, value| {
+                let deadline = value.expiry_deadline.filter(|deadline| *deadline > now);
+                if let Some(deadline) = deadline {
+                    if histogram.samples() < max_samples {
+                        histogram.record(deadline - now);
+                    }
+                }
+            });
+
+            if histogram.samples() >= max_samples {
+                break;
+            }
+        }
+        return histogram;
+    }
+
+    /// Time passed since the data store was initialized.
+    pub fn uptime(&self) -> Duration {
+        return self.started_at.elapsed();
+    }
+
+    /// Removes all keys. With `lazy` the removed keys are freed on a background thread, so
+    /// flushing a large keyspace does not block the caller.
+    pub fn flush(&self, lazy: bool) {
+        let tables = self.db.take();
+
+        if lazy {
+            thread::spawn(move || drop(tables));
+        }
+        info!("Flushed all keys");
+    }
+
+    /// Returns the amount of keys and the amount of keys with an expiry.
+    pub fn get_keyspace_stats(&self) -> (usize, usize) {
+        let mut keys = 0;
+        let mut expires = 0;
+        self.db.for_each(&mut |_, value| {
+            keys += 1;
+            if value.expiry_deadline.is_some() {
+                expires += 1;
+            }
+        });
+        return (keys, expires);
     }
 
     pub fn get_config(&self) -> DbConfig {
@@ -167,15 +661,56 @@ impl DataStore {
         return config.clone();
     }
 
+    /// Changes the config at runtime, e.g. by CONFIG SET.
+    pub fn update_config<F: FnOnce(&mut DbConfig)>(&self, f: F) {
+        let mut config = self
+            .config
+            .write()
+            .expect("Unable to get global config. Should never happen");
+        f(&mut config);
+    }
+
+    /// Advances the replication offset by the bytes fed to the replicas, returns the new one.
+    pub fn add_master_repl_offset(&self, bytes: u128) -> u128 {
+        let mut config = self
+            .config
+            .write()
+            .expect("Unable to get global config. Should never happen");
+        config.replication_data.master_repl_offset += bytes;
+        return config.replication_data.master_repl_offset;
+    }
+
     /// gets the key, if it has expired return None and remove the key from the db.
     pub fn get<S: Into<String>>(&self, key: S) -> Option<DataUnit> {
-        let key = key.into();
+        return self.lookup(key.into(), true);
+    }
+
+    /// Returns the value like [`DataStore::get`], but does not count as an access of the
+    /// key. Used by commands inspecting keys, e.g. OBJECT IDLETIME.
+    pub fn peek<S: Into<String>>(&self, key: S) -> Option<DataUnit> {
+        return self.lookup(key.into(), false);
+    }
+
+    fn lookup(&self, key: String, touch: bool) -> Option<DataUnit> {
         // needs limited scope, else it will threadlock
-        let value = self.db.get(&key)?.clone();
+        let value = {
+            let _lock = self.key_locks.read(&key);
+            self.db.get(&key)?
+        };
+
+        // the access time has a resolution of LRU_CLOCK_RESOLUTION, so most reads only need
+        // the read lock of the shard
+        if touch && !value.is_expired() && value.last_access.elapsed() >= LRU_CLOCK_RESOLUTION {
+            let _lock = self.key_locks.read(&key);
+            self.db
+                .modify(&key, &mut |current| current.last_access = Instant::now());
+        }
 
         if value.is_expired() {
-            self.remove_key(&key);
-            info!("Key '{}' - is expired and has been removed!", &key);
+            // the key may have been overwritten in the meantime, so only remove it if still expired
+            let _lock = self.key_locks.write(&key);
+            self.db.remove_if(&key, &|value| value.is_expired());
+            debug!("Key '{}' - is expired and has been removed!", &key);
             return None;
         }
 
@@ -183,44 +718,130 @@ impl DataStore {
         return Some(value);
     }
 
-    fn remove_key<S: Into<String>>(&self, key: S) {
+    /// Removes the key, expired or not. Returns false if it was missing.
+    pub fn remove_key<S: Into<String>>(&self, key: S) -> bool {
         let key = key.into();
-        self.db.remove(&key);
+        let _lock = self.key_locks.write(&key);
         trace!("Removing value for key: '{}'", &key);
+        return self.db.remove(&key).is_some();
+    }
+
+    /// Runs `f` with exclusive access to all given keys, for commands spanning several keys.
+    ///
+    /// Either all changes made by `f` are visible to other threads or none of them. The keys
+    /// are locked in a deterministic order, so concurrent calls with overlapping keys in any
+    /// order do not deadlock. `f` may only access the given keys, also through the other
+    /// methods of the store, as the keys are already locked by the current thread.
+    pub fn with_locked_keys<R, F>(&self, keys: &[&str], f: F) -> R
+    where
+        F: FnOnce(&LockedKeys) -> R,
+    {
+        let _locks = self.key_locks.write_many(keys);
+        return f(&LockedKeys { store: self, keys });
+    }
+
+    /// Moves the value of `source` to `destination`, an existing destination is overwritten.
+    /// The absolute expiry moves with the value. Returns false if the source is missing.
+    pub fn rename(&self, source: &str, destination: &str) -> bool {
+        return self.with_locked_keys(&[source, destination], |keys| {
+            let value = match keys.remove(source) {
+                Some(value) => value,
+                None => return false,
+            };
+
+            keys.set(destination, value.with_key(destination));
+            return true;
+        });
+    }
+
+    /// Removes all given keys. Returns the amount of keys removed, expired keys don't count.
+    pub fn delete(&self, keys: &[&str]) -> usize {
+        return self.with_locked_keys(keys, |locked| {
+            return keys
+                .iter()
+                .filter(|key| locked.remove(key).is_some())
+                .count();
+        });
+    }
+
+    /// Copies the value of `source` to `destination` with the same absolute expiry.
+    ///
+    /// Returns false if the source is missing, or if the destination exists and `replace`
+    /// is not set.
+    pub fn copy(&self, source: &str, destination: &str, replace: bool) -> bool {
+        return self.with_locked_keys(&[source, destination], |keys| {
+            let value = match keys.get(source) {
+                Some(value) => value,
+                None => return false,
+            };
+            if !replace && keys.get(destination).is_some() {
+                return false;
+            }
+
+            keys.set(destination, value.with_key(destination));
+            return true;
+        });
+    }
+
+    /// Read-modify-write of a single key while holding the lock of its shard.
+    ///
+    /// `f` recieves the current value (None if missing or expired) and may change it in place.
+    /// Setting it to None removes the key. No other thread can access the key in between.
+    pub fn update<S, R, F>(&self, key: S, f: F) -> R
+    where
+        S: Into<String>,
+        F: FnOnce(&mut Option<DataUnit>) -> R,
+    {
+        let key = key.into();
+        let _lock = self.key_locks.write(&key);
+
+        let mut f = Some(f);
+        let mut result = None;
+        self.db.update(key, &mut |value| {
+            // an expired value is handed out as missing and dropped unless it is replaced
+            if value.as_ref().is_some_and(|value| value.is_expired()) {
+                *value = None;
+            }
+            let f = f
+                .take()
+                .expect("Storage calls update once. Should never happen!");
+            result = Some(f(value));
+        });
+        return result.expect("Storage calls update once. Should never happen!");
     }
 
     /// Upsets the current HashSet
-    pub fn set<S: Into<String>>(&self, key: S, mut value: DataUnit) {
-        // Do not change without carefully reading the comments!!!
+    pub fn set<S: Into<String>>(&self, key: S, value: DataUnit) {
         let key = key.into();
 
         trace!("Setting value for {}, {:#?}", &key, &value);
+        let _lock = self.key_locks.write(&key);
 
-        self.db
-            .entry(key.clone())
-            .and_modify(|existing_value| {
-                // Update existing value - We "Swap" the old and the new value for performance reasons. Do NOT use value after the .and_modify call!
-                // This will use the old_value and not the expected new value
-                trace!("Old value of key: '{}', {:#?}", &key, &existing_value);
-                std::mem::swap(existing_value, &mut value);
-                trace!("Updated value for key: '{}'", &key);
-            })
-            // .or_insert only is called if the key does not exsist. The usage of value is acceptable here since the values arent swapped
-            .or_insert_with(|| {
-                trace!("Created new value for key: '{}'", &key);
-                value
-            });
+        // the old value is dropped after the shard lock is released
+        let old_value = self.db.set(key, value);
+        trace!("Old value of key: {:#?}", &old_value);
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct DataUnit {
     pub key: String,
-    pub value: String,
+    pub value: Value,
     // todo: change to Expiry object
     expiry_deadline: Option<Instant>,
+    last_access: Instant,
 }
 
+// the access time is bookkeeping and not part of the value
+impl PartialEq for DataUnit {
+    fn eq(&self, other: &Self) -> bool {
+        return self.key == other.key
+            && self.value == other.value
+            && self.expiry_deadline == other.expiry_deadline;
+    }
+}
+impl Eq for DataUnit {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expiry {
     Ttl(Duration),
@@ -248,11 +869,45 @@ impl DataUnit {
 
         return Self {
             key: key.into(),
-            value: value.into(),
+            value: Value::from(value.into()),
             expiry_deadline: expiry_deadline,
+            last_access: Instant::now(),
+        };
+    }
+
+    /// Temporary value while the real one is updated. It is expired, so it is never
+    /// returned even if an update does not complete.
+    pub fn placeholder() -> Self {
+        return Self {
+            key: String::new(),
+            value: Value::Owned(String::new()),
+            expiry_deadline: Some(Instant::now()),
+            last_access: Instant::now(),
+        };
+    }
+
+    /// The same value and expiry deadline stored under another key. Commands moving values
+    /// between keys must use this instead of building a new value from the remaining ttl.
+    pub fn with_key<S: Into<String>>(&self, key: S) -> Self {
+        return Self {
+            key: key.into(),
+            value: self.value.clone(),
+            expiry_deadline: self.expiry_deadline,
+            last_access: self.last_access,
         };
     }
 
+    /// Estimated bytes the key takes in the keyspace, the key is stored twice.
+    pub fn memory_usage(&self) -> usize {
+        return std::mem::size_of::<DataUnit>() + 2 * self.key.len() + self.value.len();
+    }
+
+    /// Time since the value was last read or written, with a resolution of
+    /// [`LRU_CLOCK_RESOLUTION`].
+    pub fn idle_time(&self) -> Duration {
+        return self.last_access.elapsed();
+    }
+
     pub fn is_expired(&self) -> bool {
         return self
             .expiry_deadline
@@ -263,6 +918,14 @@ impl DataUnit {
     pub fn get_expiry_deadline(&self) -> Option<Instant> {
         return self.expiry_deadline;
     }
+
+    /// The expiry deadline as a unix timestamp, as it is persisted in rdb files.
+    pub fn get_expiry_timestamp(&self) -> Option<SystemTime> {
+        let now = Instant::now();
+        return self
+            .expiry_deadline
+            .map(|deadline| SystemTime::now() + deadline.saturating_duration_since(now));
+    }
 }
 
 #[cfg(test)]
@@ -279,8 +942,33 @@ mod tests {
     #[cfg(test)]
     mod test_data_store {
 
-        use crate::db::data_store::{tests::empty_db_config, DataStore, DataUnit, Expiry};
-        use std::time::{Duration, Instant};
+        use crate::db::{
+            data_store::{tests::empty_db_config, DataStore, DataUnit, Expiry},
+            storage::{Storage, StorageBackend},
+        };
+        use std::{
+            collections::HashSet,
+            time::{Duration, Instant},
+        };
+
+        #[test]
+        fn test_seeded_repl_id() {
+            let seeded = || {
+                let mut config = empty_db_config();
+                config.random_seed = Some(7);
+                return DataStore::init(config)
+                    .get_config()
+                    .replication_data
+                    .master_repl_id;
+            };
+
+            let repl_id = seeded();
+            assert_eq!(40, repl_id.len());
+            assert_eq!(repl_id, seeded());
+
+            let random = DataStore::init(empty_db_config());
+            assert_ne!(repl_id, random.get_config().replication_data.master_repl_id);
+        }
 
         #[test]
         fn test_set_get_remove() {
@@ -288,7 +976,7 @@ mod tests {
             data_store.set("key", DataUnit::new("key", "value", None));
 
             assert!(
-                data_store.db.contains_key("key"),
+                data_store.db.get("key").is_some(),
                 "DataStore must contain the key after setting it"
             );
             assert_eq!(
@@ -306,7 +994,7 @@ mod tests {
 
             data_store.remove_key("key");
             assert!(
-                !data_store.db.contains_key("key"),
+                !data_store.db.get("key").is_some(),
                 "DataStore must not contain the key after removing it"
             );
         }
@@ -349,8 +1037,391 @@ mod tests {
                 "Value should be expired!"
             );
 
-            assert!(!data_store.db.contains_key("key"));
-            assert!(!data_store.db.contains_key("key2"));
+            assert!(data_store.db.get("key").is_none());
+            assert!(data_store.db.get("key2").is_none());
+        }
+
+        #[test]
+        fn test_flush() {
+            let data_store = DataStore::init(empty_db_config());
+            for lazy in [false, true] {
+                data_store.set("a", DataUnit::new("a", "1", None));
+                data_store.set("b", DataUnit::new("b", "2", None));
+
+                data_store.flush(lazy);
+                assert!(data_store.db.is_empty());
+                assert_eq!(None, data_store.get("a"));
+            }
+
+            data_store.set("a", DataUnit::new("a", "1", None));
+            assert_eq!("1", data_store.get("a").unwrap().value);
+        }
+
+        #[test]
+        fn test_scan_returns_every_key() {
+            let data_store = DataStore::init(empty_db_config());
+            for i in 0..1000 {
+                let key = format!("key:{}", i);
+                data_store.set(&key, DataUnit::new(key.clone(), "value".into(), None));
+            }
+
+            let mut cursor = 0;
+            let mut seen = HashSet::new();
+            let mut steps = 0;
+            loop {
+                let (next, keys) = data_store.scan(cursor, 10);
+                assert!(keys.len() <= 11, "Only ties exceed the count");
+                seen.extend(keys);
+                steps += 1;
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+
+            assert_eq!(1000, seen.len());
+            assert!(steps >= 100);
+        }
+
+        #[test]
+        fn test_scan_hashmap_backend() {
+            let mut config = empty_db_config();
+            config.storage_backend = StorageBackend::ShardedHashMap;
+            config.shard_amount = Some(8);
+            let data_store = DataStore::init(config);
+            for i in 0..100 {
+                let key = format!("key:{}", i);
+                data_store.set(&key, DataUnit::new(key.clone(), "value".into(), None));
+            }
+
+            let (mut cursor, keys) = data_store.scan(0, 10);
+            let mut seen = HashSet::<String>::from_iter(keys);
+            while cursor != 0 {
+                let (next, keys) = data_store.scan(cursor, 10);
+                seen.extend(keys);
+                cursor = next;
+            }
+
+            assert_eq!(100, seen.len());
+            assert_eq!(8, data_store.shard_count());
+        }
+
+        #[test]
+        fn test_scan_while_resizing() {
+            let data_store = DataStore::init(empty_db_config());
+            for i in 0..100 {
+                let key = format!("stable:{}", i);
+                data_store.set(&key, DataUnit::new(key.clone(), "value".into(), None));
+            }
+
+            // the tables grow and shrink between the steps
+            let (mut cursor, keys) = data_store.scan(0, 10);
+            let mut seen = HashSet::<String>::from_iter(keys);
+            let mut round = 0;
+            while cursor != 0 {
+                for i in 0..500 {
+                    let key = format!("churn:{}:{}", round, i);
+                    data_store.set(&key, DataUnit::new(key.clone(), "value".into(), None));
+                }
+                if round % 2 == 1 {
+                    for i in 0..500 {
+                        data_store.update(format!("churn:{}:{}", round - 1, i), |v| *v = None);
+                        data_store.update(format!("churn:{}:{}", round, i), |v| *v = None);
+                    }
+                    for index in 0..data_store.shard_count() {
+                        data_store.shrink_shard(index);
+                    }
+                }
+                round += 1;
+
+                let (next, keys) = data_store.scan(cursor, 10);
+                seen.extend(keys);
+                cursor = next;
+            }
+
+            for i in 0..100 {
+                assert!(seen.contains(&format!("stable:{}", i)));
+            }
+        }
+
+        #[test]
+        fn test_shrink_shard() {
+            let data_store = DataStore::init(empty_db_config());
+            for i in 0..1000 {
+                let key = i.to_string();
+                data_store.set(key.clone(), DataUnit::new(key.as_str(), "value", None));
+            }
+            for i in 0..1000 {
+                data_store.db.remove(&i.to_string());
+            }
+            let mut value = String::with_capacity(4096);
+            value.push_str("small");
+            data_store.set("key", DataUnit::new("key".to_string(), value, None));
+
+            let capacity: usize = data_store.shard_stats().iter().map(|(_, c)| c).sum();
+            let (before, after) = (0..data_store.shard_count())
+                .filter_map(|index| data_store.shrink_shard(index))
+                .fold((0, 0), |(b, a), (before, after)| (b + before, a + after));
+
+            assert!(before >= after + 4000, "{} -> {}", before, after);
+            assert!(
+                data_store
+                    .shard_stats()
+                    .iter()
+                    .map(|(_, c)| c)
+                    .sum::<usize>()
+                    < capacity
+            );
+            assert_eq!(5, data_store.db.get("key").unwrap().value.capacity());
+            assert_eq!("small", data_store.get("key").unwrap().value);
+        }
+
+        #[test]
+        fn test_last_access() {
+            let data_store = DataStore::init(empty_db_config());
+            data_store.set("key", DataUnit::new("key", "value", None));
+            data_store.db.modify("key", &mut |value| {
+                value.last_access = Instant::now() - Duration::from_secs(10)
+            });
+
+            let idle = data_store.peek("key").unwrap().idle_time();
+            assert!(
+                idle >= Duration::from_secs(10),
+                "peek must not touch the key"
+            );
+            assert!(data_store.peek("key").unwrap().idle_time() >= idle);
+
+            data_store.get("key");
+            assert!(data_store.peek("key").unwrap().idle_time() < Duration::from_secs(10));
+
+            // renamed values keep their access time
+            let mut value = data_store.peek("key").unwrap();
+            value.last_access = Instant::now() - Duration::from_secs(10);
+            assert!(value.with_key("other").idle_time() >= Duration::from_secs(10));
+        }
+    }
+
+    #[cfg(test)]
+    mod test_snapshot_data_store {
+        use std::{
+            sync::{
+                atomic::{AtomicBool, Ordering},
+                Arc,
+            },
+            thread,
+            time::Instant,
+        };
+
+        use crate::db::data_store::{tests::empty_db_config, DataStore, DataUnit};
+
+        #[test]
+        fn test_snapshot_contains_all_values() {
+            let data_store = DataStore::init(empty_db_config());
+            for i in 0..100 {
+                let key = format!("key{}", i);
+                data_store.set(&key, DataUnit::new(&key, &key, None));
+            }
+
+            let mut keys: Vec<String> = data_store.snapshot_iter().map(|v| v.key).collect();
+            keys.sort();
+
+            let mut expected: Vec<String> = (0..100).map(|i| format!("key{}", i)).collect();
+            expected.sort();
+            assert_eq!(expected, keys);
+        }
+
+        #[test]
+        fn test_snapshot_skips_expired_values() {
+            let data_store = DataStore::init(empty_db_config());
+            let mut expired = DataUnit::new("expired", "value", None);
+            expired.expiry_deadline = Some(Instant::now());
+            data_store.set("expired", expired);
+            data_store.set("key", DataUnit::new("key", "value", None));
+
+            let keys: Vec<String> = data_store.snapshot_iter().map(|v| v.key).collect();
+
+            assert_eq!(vec!["key".to_string()], keys);
+        }
+
+        #[test]
+        fn test_snapshot_while_writing() {
+            let store = Arc::new(DataStore::init(empty_db_config()));
+            for i in 0..1000 {
+                let key = format!("key{}", i);
+                store.set(&key, DataUnit::new(key.as_str(), "0", None));
+            }
+            let done = Arc::new(AtomicBool::new(false));
+
+            let writer = {
+                let store = Arc::clone(&store);
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    let mut i = 0;
+                    while !done.load(Ordering::SeqCst) {
+                        let key = format!("key{}", i % 1000);
+                        store.set(&key, DataUnit::new(key.as_str(), "1", None));
+                        i += 1;
+                    }
+                })
+            };
+
+            // keys are only overwritten, never added or removed, so each snapshot sees all of them
+            for _ in 0..20 {
+                assert_eq!(1000, store.snapshot_iter().count());
+            }
+
+            done.store(true, Ordering::SeqCst);
+            writer.join().expect("Thread panicked");
+        }
+    }
+
+    #[cfg(test)]
+    mod test_update_data_store {
+        use std::time::{Duration, Instant};
+
+        use crate::db::{
+            data_store::{tests::empty_db_config, DataStore, DataUnit, Expiry},
+            storage::Storage,
+        };
+
+        #[test]
+        fn test_update_missing_key() {
+            let data_store = DataStore::init(empty_db_config());
+
+            let was_missing = data_store.update("key", |value| {
+                let was_missing = value.is_none();
+                *value = Some(DataUnit::new("key", "value", None));
+                was_missing
+            });
+
+            assert!(was_missing);
+            assert_eq!("value", data_store.get("key").unwrap().value);
+        }
+
+        #[test]
+        fn test_update_existing_key() {
+            let data_store = DataStore::init(empty_db_config());
+            data_store.set("key", DataUnit::new("key", "value", None));
+
+            data_store.update("key", |value| {
+                value.as_mut().unwrap().value.to_mut().push_str("2");
+            });
+
+            assert_eq!("value2", data_store.get("key").unwrap().value);
+        }
+
+        #[test]
+        fn test_update_remove_key() {
+            let data_store = DataStore::init(empty_db_config());
+            data_store.set("key", DataUnit::new("key", "value", None));
+
+            data_store.update("key", |value| *value = None);
+
+            assert!(data_store.db.get("key").is_none());
+        }
+
+        #[test]
+        fn test_update_expired_key_is_missing() {
+            let data_store = DataStore::init(empty_db_config());
+            let mut data =
+                DataUnit::new("key", "value", Some(Expiry::Ttl(Duration::from_secs(10))));
+            data.expiry_deadline = Some(Instant::now());
+            data_store.set("key", data);
+
+            let was_missing = data_store.update("key", |value| value.is_none());
+
+            assert!(
+                was_missing,
+                "Expired values must not be passed to the update"
+            );
+            assert!(data_store.db.get("key").is_none());
+        }
+    }
+
+    #[cfg(test)]
+    mod test_move_data_store {
+        use std::time::{Duration, Instant};
+
+        use crate::db::data_store::{tests::empty_db_config, DataStore, DataUnit, Expiry};
+
+        fn expiring(key: &str, value: &str) -> DataUnit {
+            return DataUnit::new(key, value, Some(Expiry::Ttl(Duration::from_secs(100))));
+        }
+
+        #[test]
+        fn test_rename_keeps_expiry() {
+            let data_store = DataStore::init(empty_db_config());
+            let data = expiring("source", "value");
+            data_store.set("source", data.clone());
+
+            assert!(data_store.rename("source", "destination"));
+
+            let moved = data_store.get("destination").unwrap();
+            assert_eq!("destination", moved.key);
+            assert_eq!("value", moved.value);
+            assert_eq!(data.get_expiry_deadline(), moved.get_expiry_deadline());
+            assert!(data_store.get("source").is_none());
+        }
+
+        #[test]
+        fn test_rename_overwrites_destination() {
+            let data_store = DataStore::init(empty_db_config());
+            data_store.set("source", DataUnit::new("source", "new", None));
+            data_store.set("destination", expiring("destination", "old"));
+
+            assert!(data_store.rename("source", "destination"));
+
+            let moved = data_store.get("destination").unwrap();
+            assert_eq!("new", moved.value);
+            assert_eq!(None, moved.get_expiry_deadline());
+        }
+
+        #[test]
+        fn test_rename_to_itself() {
+            let data_store = DataStore::init(empty_db_config());
+            data_store.set("key", DataUnit::new("key", "value", None));
+
+            assert!(data_store.rename("key", "key"));
+            assert_eq!("value", data_store.get("key").unwrap().value);
+        }
+
+        #[test]
+        fn test_rename_missing_or_expired_source() {
+            let data_store = DataStore::init(empty_db_config());
+            let mut data = expiring("expired", "value");
+            data.expiry_deadline = Some(Instant::now());
+            data_store.set("expired", data);
+
+            assert!(!data_store.rename("missing", "destination"));
+            assert!(!data_store.rename("expired", "destination"));
+            assert!(data_store.get("destination").is_none());
+        }
+
+        #[test]
+        fn test_copy_keeps_expiry() {
+            let data_store = DataStore::init(empty_db_config());
+            let data = expiring("source", "value");
+            data_store.set("source", data.clone());
+
+            assert!(data_store.copy("source", "destination", false));
+
+            let copied = data_store.get("destination").unwrap();
+            assert_eq!("destination", copied.key);
+            assert_eq!(data.get_expiry_deadline(), copied.get_expiry_deadline());
+            assert_eq!(Some(data), data_store.get("source"));
+        }
+
+        #[test]
+        fn test_copy_replace() {
+            let data_store = DataStore::init(empty_db_config());
+            data_store.set("source", DataUnit::new("source", "new", None));
+            data_store.set("destination", DataUnit::new("destination", "old", None));
+
+            assert!(!data_store.copy("source", "destination", false));
+            assert_eq!("old", data_store.get("destination").unwrap().value);
+
+            assert!(data_store.copy("source", "destination", true));
+            assert_eq!("new", data_store.get("destination").unwrap().value);
         }
     }
 
@@ -358,6 +1429,7 @@ mod tests {
     mod test_concurrency_data_store {
         use crate::db::data_store::tests::empty_db_config;
         use crate::db::data_store::{DataStore, DataUnit};
+        use crate::db::storage::Storage;
 
         use std::sync::Arc;
         use std::thread;
@@ -394,8 +1466,203 @@ mod tests {
             // Verify that all keys are present
             for i in 0..100 {
                 let key = format!("key{}", i);
-                assert!(store.db.contains_key(&key));
+                assert!(store.db.get(&key).is_some());
+            }
+        }
+
+        #[test]
+        fn test_concurrent_update_counter() {
+            let store = Arc::new(DataStore::init(empty_db_config()));
+
+            let mut handles = Vec::new();
+            for _ in 0..8 {
+                let store_clone = Arc::clone(&store);
+                handles.push(thread::spawn(move || {
+                    for _ in 0..500 {
+                        store_clone.update("counter", |value| {
+                            let counter = value
+                                .as_ref()
+                                .map(|v| v.value.parse::<u64>().unwrap())
+                                .unwrap_or(0);
+                            *value = Some(DataUnit::new(
+                                "counter".to_string(),
+                                (counter + 1).to_string(),
+                                None,
+                            ));
+                        });
+                    }
+                }));
+            }
+
+            for handle in handles {
+                handle.join().expect("Thread panicked");
+            }
+
+            assert_eq!("4000", store.get("counter").unwrap().value);
+        }
+    }
+
+    #[cfg(test)]
+    mod test_locked_keys_data_store {
+        use std::{
+            panic::{self, AssertUnwindSafe},
+            sync::{
+                atomic::{AtomicBool, Ordering},
+                Arc,
+            },
+            thread,
+        };
+
+        use rand::Rng;
+
+        use crate::db::data_store::{tests::empty_db_config, DataStore, DataUnit};
+
+        const ACCOUNTS: usize = 16;
+
+        fn account(i: usize) -> String {
+            return format!("account{}", i);
+        }
+
+        fn balance(data: &Option<DataUnit>) -> i64 {
+            return data.as_ref().unwrap().value.parse().unwrap();
+        }
+
+        #[test]
+        fn test_move_value() {
+            let store = DataStore::init(empty_db_config());
+            store.set("src", DataUnit::new("src", "value", None));
+
+            store.with_locked_keys(&["src", "dst"], |locked| {
+                let value = locked.remove("src").unwrap();
+                locked.set(
+                    "dst",
+                    DataUnit::new("dst".to_string(), value.value.into(), None),
+                );
+            });
+
+            assert!(store.get("src").is_none());
+            assert_eq!("value", store.get("dst").unwrap().value);
+        }
+
+        #[test]
+        fn test_same_key_twice() {
+            let store = DataStore::init(empty_db_config());
+
+            // must not deadlock on a stripe that is already locked
+            store.with_locked_keys(&["key", "key"], |locked| {
+                locked.set("key", DataUnit::new("key", "value", None));
+            });
+
+            assert_eq!("value", store.get("key").unwrap().value);
+        }
+
+        #[test]
+        fn test_store_methods_within_locked_keys() {
+            let store = DataStore::init(empty_db_config());
+
+            // the keys are locked by the current thread already, so this must not deadlock
+            store.with_locked_keys(&["src", "dst"], |_| {
+                store.set("src", DataUnit::new("src", "value", None));
+                assert!(store.rename("src", "dst"));
+                assert_eq!("value", store.get("dst").unwrap().value);
+            });
+
+            // the locks are released again
+            let store = Arc::new(store);
+            let other = Arc::clone(&store);
+            thread::spawn(move || other.remove_key("dst"))
+                .join()
+                .unwrap();
+            assert!(store.get("dst").is_none());
+        }
+
+        #[test]
+        fn test_panic_within_locked_keys() {
+            let store = DataStore::init(empty_db_config());
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                store.with_locked_keys(&["key"], |_| panic!("command panicked"));
+            }));
+            assert!(result.is_err());
+
+            // the poisoned stripe of the key is still usable
+            store.with_locked_keys(&["key"], |locked| {
+                locked.set("key", DataUnit::new("key", "value", None));
+            });
+            assert_eq!("value", store.get("key").unwrap().value);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_access_unlocked_key() {
+            let store = DataStore::init(empty_db_config());
+
+            store.with_locked_keys(&["key"], |locked| locked.get("other"));
+        }
+
+        /// Transfers between random accounts, in random key order, while readers check that no
+        /// partial transfer is ever visible. Also fails by hanging if the lock order deadlocks.
+        #[test]
+        fn test_concurrent_transfers_keep_total() {
+            let store = Arc::new(DataStore::init(empty_db_config()));
+            for i in 0..ACCOUNTS {
+                store.set(
+                    account(i),
+                    DataUnit::new(account(i), "100".to_string(), None),
+                );
+            }
+            let done = Arc::new(AtomicBool::new(false));
+
+            let mut writers = Vec::new();
+            for _ in 0..8 {
+                let store = Arc::clone(&store);
+                writers.push(thread::spawn(move || {
+                    let mut rng = rand::rng();
+                    for _ in 0..2000 {
+                        let from = account(rng.random_range(0..ACCOUNTS));
+                        let to = account(rng.random_range(0..ACCOUNTS));
+                        let keys = [from.as_str(), to.as_str()];
+
+                        store.with_locked_keys(&keys, |locked| {
+                            let from_balance = balance(&locked.get(&from)) - 1;
+                            locked.set(
+                                &from,
+                                DataUnit::new(from.clone(), from_balance.to_string(), None),
+                            );
+                            let to_balance = balance(&locked.get(&to)) + 1;
+                            locked
+                                .set(&to, DataUnit::new(to.clone(), to_balance.to_string(), None));
+                        });
+                    }
+                }));
             }
+
+            let reader = {
+                let store = Arc::clone(&store);
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    while !done.load(Ordering::SeqCst) {
+                        let keys: Vec<String> = (0..ACCOUNTS).map(account).collect();
+                        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+                        let total: i64 = store.with_locked_keys(&keys, |locked| {
+                            keys.iter().map(|key| balance(&locked.get(key))).sum()
+                        });
+                        assert_eq!(100 * ACCOUNTS as i64, total);
+                    }
+                })
+            };
+
+            for writer in writers {
+                writer.join().expect("Thread panicked");
+            }
+            done.store(true, Ordering::SeqCst);
+            reader.join().expect("Reader saw a partial transfer");
+
+            let total: i64 = (0..ACCOUNTS)
+                .map(|i| store.get(account(i)).unwrap().value.parse::<i64>().unwrap())
+                .sum();
+            assert_eq!(100 * ACCOUNTS as i64, total);
         }
     }
 
@@ -411,6 +1678,7 @@ mod tests {
                 key: "key".into(),
                 value: "data value".into(),
                 expiry_deadline: None,
+                last_access: Instant::now(),
             };
 
             assert!(
@@ -426,6 +1694,7 @@ mod tests {
                 key: "key".into(),
                 value: "data value".into(),
                 expiry_deadline: Some(now + Duration::from_millis(50)),
+                last_access: now,
             };
             assert!(!data.is_expired(), "Data should not expire instantly!");
             data.expiry_deadline = Some(now - Duration::from_millis(1));
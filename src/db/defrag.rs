@@ -0,0 +1,118 @@
+//! Background maintenance shrinking over-allocated memory of the keyspace, a light version
+//! of the active defragmentation of redis.
+//!
+//! Deleted keys leave the tables of the shards at their peak size and values may keep more
+//! capacity than they use. Every [`DEFRAG_INTERVAL`] the task shrinks both, one shard at a
+//! time. A shard used by a client at that moment is skipped until the next cycle, so the
+//! task only works on idle shards and never makes clients wait.
+
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error};
+use once_cell::sync::Lazy;
+
+use crate::db::data_store::{get_db, DataStore};
+
+pub const DEFRAG_INTERVAL: Duration = Duration::from_secs(10);
+
+static STATS: Lazy<Mutex<DefragStats>> = Lazy::new(|| Mutex::new(DefragStats::default()));
+
+/// Shown in the memory section of INFO. The byte counts are estimates of the allocated
+/// tables, keys and values of the shards shrunk in the last cycle.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DefragStats {
+    pub cycles: u64,
+    pub last_cycle_bytes_before: usize,
+    pub last_cycle_bytes_after: usize,
+    pub reclaimed_bytes: usize,
+    pub skipped_shards: u64,
+}
+
+pub fn stats() -> DefragStats {
+    return *STATS
+        .lock()
+        .expect("Defrag stats lock poisoned. Should never happen!");
+}
+
+/// Shrinks every idle shard of the data store once and adds the result to the stats.
+pub fn run_cycle(db: &DataStore) -> DefragStats {
+    let start = Instant::now();
+    let mut cycle = DefragStats {
+        cycles: 1,
+        ..Default::default()
+    };
+
+    for index in 0..db.shard_count() {
+        match db.shrink_shard(index) {
+            Some((before, after)) => {
+                cycle.last_cycle_bytes_before += before;
+                cycle.last_cycle_bytes_after += after;
+            }
+            None => cycle.skipped_shards += 1,
+        }
+    }
+    cycle.reclaimed_bytes = cycle.last_cycle_bytes_before - cycle.last_cycle_bytes_after;
+    debug!(
+        "Defrag cycle reclaimed {} bytes in {:?}, {} shards were busy",
+        cycle.reclaimed_bytes,
+        start.elapsed(),
+        cycle.skipped_shards
+    );
+
+    let mut stats = STATS
+        .lock()
+        .expect("Defrag stats lock poisoned. Should never happen!");
+    stats.cycles += 1;
+    stats.last_cycle_bytes_before = cycle.last_cycle_bytes_before;
+    stats.last_cycle_bytes_after = cycle.last_cycle_bytes_after;
+    stats.reclaimed_bytes += cycle.reclaimed_bytes;
+    stats.skipped_shards += cycle.skipped_shards;
+
+    return cycle;
+}
+
+/// Runs a cycle every [`DEFRAG_INTERVAL`] on its own thread.
+pub fn start() {
+    let spawned = thread::Builder::new()
+        .name("defrag".to_string())
+        .spawn(|| loop {
+            thread::sleep(DEFRAG_INTERVAL);
+            run_cycle(get_db());
+        });
+
+    if let Err(err) = spawned {
+        error!("Unable to start the defrag task: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{
+        data_store::{init_test_db, DataUnit, MIN_WASTED_BYTES},
+        defrag::{run_cycle, stats},
+    };
+
+    #[test]
+    fn test_cycle_records_stats() {
+        let db = init_test_db();
+        let mut value = String::with_capacity(4 * MIN_WASTED_BYTES);
+        value.push_str("small");
+        db.set(
+            "defrag:key",
+            DataUnit::new("defrag:key".to_string(), value, None),
+        );
+
+        // other tests may hold the shard of the key, it is shrunk by a later cycle then
+        let shrunk = (0..10)
+            .map(|_| run_cycle(db))
+            .any(|cycle| cycle.reclaimed_bytes >= 3 * MIN_WASTED_BYTES);
+
+        assert!(shrunk, "The slack of the value must be reclaimed");
+        assert_eq!("small", db.get("defrag:key").unwrap().value);
+        assert!(stats().reclaimed_bytes >= 3 * MIN_WASTED_BYTES);
+    }
+}
@@ -0,0 +1,373 @@
+//! Eviction of keys once the used memory exceeds `maxmemory`, like redis.
+//!
+//! The keys are not ordered by their access time. Instead `maxmemory-samples` keys are
+//! sampled and the best candidates are kept in a pool of [`EVICTION_POOL_SIZE`] keys, the
+//! best candidate of the pool is evicted. More samples approximate a true LRU better, at
+//! the cost of cpu time on every eviction.
+//!
+//! The used memory is an estimate of the keys and values, see
+//! [`crate::db::storage::MemoryTracked`]. Every evicted key is propagated as `DEL key`, so
+//! replicas drop it too. Replicas don't evict on their own, they follow their master like
+//! with `replica-ignore-maxmemory yes`.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use log::{debug, trace};
+use once_cell::sync::Lazy;
+
+use crate::{
+    db::data_store::{DataStore, KeySample},
+    parser::messages::RedisMessageType,
+    replication::master,
+};
+
+/// Candidates kept between evictions, like the eviction pool of redis.
+pub const EVICTION_POOL_SIZE: usize = 16;
+
+pub const DEFAULT_MAXMEMORY_SAMPLES: usize = 5;
+
+static POOL: Lazy<Mutex<EvictionPool>> = Lazy::new(|| Mutex::new(EvictionPool::new()));
+
+static EVICTED_KEYS: AtomicU64 = AtomicU64::new(0);
+
+/// Which keys are evicted, chosen with `maxmemory-policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Writes are rejected once the memory is used up.
+    NoEviction,
+    AllKeysLru,
+    VolatileLru,
+    AllKeysRandom,
+    VolatileRandom,
+    /// The keys expiring next.
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    const ALL: [EvictionPolicy; 6] = [
+        Self::NoEviction,
+        Self::AllKeysLru,
+        Self::VolatileLru,
+        Self::AllKeysRandom,
+        Self::VolatileRandom,
+        Self::VolatileTtl,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        return match self {
+            Self::NoEviction => "noeviction",
+            Self::AllKeysLru => "allkeys-lru",
+            Self::VolatileLru => "volatile-lru",
+            Self::AllKeysRandom => "allkeys-random",
+            Self::VolatileRandom => "volatile-random",
+            Self::VolatileTtl => "volatile-ttl",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        if name.to_ascii_lowercase().ends_with("-lfu") {
+            return Err(
+                "LFU policies are not supported, access frequencies are not tracked".into(),
+            );
+        }
+        return Self::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(name))
+            .ok_or(format!("'{}' is no maxmemory policy", name));
+    }
+
+    /// Whether only keys with an expiry are evicted.
+    const fn volatile_only(&self) -> bool {
+        return matches!(
+            self,
+            Self::VolatileLru | Self::VolatileRandom | Self::VolatileTtl
+        );
+    }
+
+    /// How good a sample is evicted, the higher the better. None if the policy does not
+    /// rank the keys.
+    fn score(&self, sample: &KeySample, now: Instant) -> Option<u64> {
+        return match self {
+            Self::AllKeysLru | Self::VolatileLru => Some(sample.idle_time.as_millis() as u64),
+            // the sooner a key expires the better
+            Self::VolatileTtl => {
+                let ttl = sample.expiry_deadline?.saturating_duration_since(now);
+                Some(u64::MAX - ttl.as_millis() as u64)
+            }
+            Self::NoEviction | Self::AllKeysRandom | Self::VolatileRandom => None,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Candidate {
+    key: String,
+    score: u64,
+}
+
+/// The best candidates of all samples so far, ordered by their score.
+#[derive(Debug, Default)]
+pub struct EvictionPool {
+    /// Ascending by score, the best candidate is the last one.
+    candidates: Vec<Candidate>,
+}
+
+impl EvictionPool {
+    pub fn new() -> Self {
+        return Self {
+            candidates: Vec::with_capacity(EVICTION_POOL_SIZE),
+        };
+    }
+
+    /// Adds a sampled key, a key already in the pool gets its new score. Returns false if
+    /// the pool is full of better candidates.
+    pub fn insert(&mut self, key: String, score: u64) -> bool {
+        self.candidates.retain(|candidate| candidate.key != key);
+
+        let position = self
+            .candidates
+            .partition_point(|candidate| candidate.score < score);
+        if self.candidates.len() >= EVICTION_POOL_SIZE {
+            if position == 0 {
+                return false;
+            }
+            // the worst candidate makes room
+            self.candidates.remove(0);
+            self.candidates
+                .insert(position - 1, Candidate { key, score });
+            return true;
+        }
+        self.candidates.insert(position, Candidate { key, score });
+        return true;
+    }
+
+    /// Removes the best candidate and returns it with its score.
+    pub fn pop_best(&mut self) -> Option<(String, u64)> {
+        return self
+            .candidates
+            .pop()
+            .map(|candidate| (candidate.key, candidate.score));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.candidates.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.candidates.is_empty();
+    }
+}
+
+/// Keys evicted since the server started, shown in the stats section of INFO.
+pub fn evicted_keys() -> u64 {
+    return EVICTED_KEYS.load(Ordering::Relaxed);
+}
+
+/// Evicts keys until the used memory is within `maxmemory` of the config. Returns false if
+/// that is not possible, e.g. with the noeviction policy or without keys left to evict.
+pub fn free_memory(db: &DataStore) -> bool {
+    let config = db.get_config();
+    if config.maxmemory == 0 || db.used_memory() <= config.maxmemory {
+        return true;
+    }
+
+    let mut pool = POOL
+        .lock()
+        .expect("Eviction pool lock poisoned. Should never happen!");
+    return evict(
+        db,
+        &mut pool,
+        config.maxmemory,
+        config.maxmemory_policy,
+        config.maxmemory_samples,
+        propagate_eviction,
+    );
+}
+
+/// Feeds an evicted key to the replicas, like redis as `DEL key`.
+fn propagate_eviction(key: &str) {
+    master::propagate(&RedisMessageType::bulk_string_array(vec!["DEL", key]));
+}
+
+/// See [`free_memory`], with an explicit pool and settings. `on_evict` is called for every
+/// evicted key while the key is still locked, so no write to it comes in between.
+fn evict(
+    db: &DataStore,
+    pool: &mut EvictionPool,
+    maxmemory: u64,
+    policy: EvictionPolicy,
+    samples: usize,
+    on_evict: fn(&str),
+) -> bool {
+    while db.used_memory() > maxmemory {
+        if policy == EvictionPolicy::NoEviction {
+            return false;
+        }
+
+        let Some((key, score)) = next_victim(db, pool, policy, samples) else {
+            debug!(
+                "Unable to evict with {}, no key left to evict",
+                policy.name()
+            );
+            return false;
+        };
+        // a key of the pool may have been removed in the meantime
+        let evicted = db.with_locked_keys(&[&key], |locked| {
+            let evicted = locked.remove(&key).is_some();
+            if evicted {
+                on_evict(&key);
+            }
+            return evicted;
+        });
+        if evicted {
+            EVICTED_KEYS.fetch_add(1, Ordering::Relaxed);
+            trace!(
+                "Evicted key '{}' by {} with score {:?}, {} of {} bytes used",
+                key,
+                policy.name(),
+                score,
+                db.used_memory(),
+                maxmemory
+            );
+        }
+    }
+    return true;
+}
+
+/// The key to evict next and its score, None if there is none.
+fn next_victim(
+    db: &DataStore,
+    pool: &mut EvictionPool,
+    policy: EvictionPolicy,
+    samples: usize,
+) -> Option<(String, Option<u64>)> {
+    let now = Instant::now();
+    let sampled = db.sample_keys(samples, policy.volatile_only());
+
+    if matches!(
+        policy,
+        EvictionPolicy::AllKeysRandom | EvictionPolicy::VolatileRandom
+    ) {
+        return sampled.into_iter().next().map(|sample| (sample.key, None));
+    }
+
+    for sample in sampled {
+        let Some(score) = policy.score(&sample, now) else {
+            continue;
+        };
+        if pool.insert(sample.key.clone(), score) {
+            trace!(
+                "Eviction candidate '{}' with score {} joined the pool",
+                sample.key,
+                score
+            );
+        }
+    }
+    return pool.pop_best().map(|(key, score)| (key, Some(score)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::db::{
+        data_store::{new_test_db, DataUnit, Expiry},
+        eviction::{evict, EvictionPolicy, EvictionPool, EVICTION_POOL_SIZE},
+    };
+
+    #[test]
+    fn test_policy_names() {
+        assert_eq!(
+            Ok(EvictionPolicy::AllKeysLru),
+            EvictionPolicy::from_name("ALLKEYS-LRU")
+        );
+        assert_eq!("volatile-ttl", EvictionPolicy::VolatileTtl.name());
+        assert!(EvictionPolicy::from_name("allkeys-lfu").is_err());
+        assert!(EvictionPolicy::from_name("lru").is_err());
+    }
+
+    #[test]
+    fn test_pool_keeps_best_candidates() {
+        let mut pool = EvictionPool::new();
+        for score in 0..EVICTION_POOL_SIZE as u64 + 4 {
+            pool.insert(format!("key:{}", score), score);
+        }
+        assert_eq!(EVICTION_POOL_SIZE, pool.len());
+        assert!(!pool.insert("worse".to_string(), 1));
+
+        // a key sampled again gets its new score
+        assert!(pool.insert("key:10".to_string(), 100));
+        assert_eq!(Some(("key:10".to_string(), 100)), pool.pop_best());
+        assert_eq!(Some(("key:19".to_string(), 19)), pool.pop_best());
+        assert_eq!(EVICTION_POOL_SIZE - 2, pool.len());
+    }
+
+    #[test]
+    fn test_evict_until_memory_is_freed() {
+        let db = new_test_db();
+        for index in 0..100 {
+            let key = format!("key:{}", index);
+            db.set(key.clone(), DataUnit::new(key, "value".to_string(), None));
+        }
+        let limit = db.used_memory() / 2;
+
+        let mut pool = EvictionPool::new();
+        assert!(!evict(
+            &db,
+            &mut pool,
+            limit,
+            EvictionPolicy::NoEviction,
+            5,
+            |_| ()
+        ));
+        assert!(!evict(
+            &db,
+            &mut pool,
+            limit,
+            EvictionPolicy::VolatileLru,
+            5,
+            |_| ()
+        ));
+        assert_eq!(100, db.get_keyspace_stats().0);
+
+        assert!(evict(
+            &db,
+            &mut pool,
+            limit,
+            EvictionPolicy::AllKeysLru,
+            5,
+            |_| ()
+        ));
+        assert!(db.used_memory() <= limit);
+        assert!(db.get_keyspace_stats().0 >= 49);
+    }
+
+    #[test]
+    fn test_volatile_ttl_evicts_keys_expiring_next() {
+        let db = new_test_db();
+        let ttl = |secs| Some(Expiry::Ttl(Duration::from_secs(secs)));
+        db.set("soon", DataUnit::new("soon", "value", ttl(10)));
+        db.set("later", DataUnit::new("later", "value", ttl(1000)));
+        db.set("never", DataUnit::new("never", "value", None));
+        let limit = db.used_memory() - 1;
+
+        let mut pool = EvictionPool::new();
+        assert!(evict(
+            &db,
+            &mut pool,
+            limit,
+            EvictionPolicy::VolatileTtl,
+            5,
+            |_| ()
+        ));
+        assert!(db.peek("soon").is_none());
+        assert!(db.peek("later").is_some());
+        assert!(db.peek("never").is_some());
+    }
+}
@@ -1,2 +1,9 @@
 pub mod data_store;
-pub mod replication_data;
\ No newline at end of file
+pub mod defrag;
+pub mod eviction;
+pub mod persistence;
+pub mod replication_data;
+pub mod storage;
+pub mod ttl_stats;
+pub mod value;
+pub mod write_pause;
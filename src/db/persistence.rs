@@ -0,0 +1,395 @@
+//! Statistics of the dataset that survive restarts, shown in the persistence section of
+//! INFO. Every rdb file written carries them as aux fields and loading one restores them,
+//! so a restart does not reset the history of the dataset.
+//!
+//! There is no fork and no AOF. A snapshot is copied shard by shard, the largest copy is
+//! reported as the copy-on-write size a fork would have.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use once_cell::sync::Lazy;
+
+use crate::{
+    db::data_store::{DataStore, DataUnit},
+    parser::db_file::RdbFile,
+};
+
+/// Save time of the rdb file, written by redis as well.
+const AUX_CTIME: &str = "ctime";
+const AUX_TOTAL_WRITES: &str = "total-writes";
+const AUX_DATASET_CTIME: &str = "dataset-ctime";
+
+/// Loaded keys between two updates of the loading progress.
+const LOADING_PROGRESS_INTERVAL: u64 = 1024;
+
+/// Whether a dataset is being loaded, checked before every command, see [`is_loading`].
+static LOADING: AtomicBool = AtomicBool::new(false);
+
+/// Faults injected into snapshots by DEBUG, so the handling of slow and failing saves can
+/// be tested. See [`set_sleep_after_fork`] and [`set_fail_saves`].
+static SLEEP_AFTER_FORK_MICROS: AtomicU64 = AtomicU64::new(0);
+static FAIL_SAVES: AtomicBool = AtomicBool::new(false);
+
+static STATS: Lazy<Mutex<PersistenceStats>> = Lazy::new(|| {
+    let now = SystemTime::now();
+    return Mutex::new(PersistenceStats {
+        total_writes: 0,
+        changes_since_last_save: 0,
+        saves: 0,
+        last_save: now,
+        dataset_created: now,
+        saves_in_progress: 0,
+        save_started: None,
+        last_save_duration: None,
+        last_save_ok: true,
+        last_cow_size: 0,
+        loading: false,
+        loading_started: None,
+        loading_total_bytes: 0,
+        loading_total_keys: 0,
+        loading_loaded_keys: 0,
+        last_load_keys_loaded: 0,
+        last_load_keys_expired: 0,
+    });
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PersistenceStats {
+    /// Write commands executed on the dataset since it was created.
+    pub total_writes: u64,
+    pub changes_since_last_save: u64,
+    /// Rdb files written since the server started.
+    pub saves: u64,
+    /// Like redis, the start of the server until the first save.
+    pub last_save: SystemTime,
+    /// When the first server holding the dataset started.
+    pub dataset_created: SystemTime,
+    /// Snapshots being written, e.g. for several replicas at once.
+    pub saves_in_progress: usize,
+    /// Start of the oldest save in progress.
+    pub save_started: Option<Instant>,
+    pub last_save_duration: Option<Duration>,
+    /// Whether the last snapshot was written out, e.g. by [`crate::server::Server::export_rdb`].
+    pub last_save_ok: bool,
+    /// Bytes of the largest shard copied by the last snapshot.
+    pub last_cow_size: u64,
+    /// Whether an rdb file is being loaded, e.g. on startup or on a full resync.
+    pub loading: bool,
+    pub loading_started: Option<SystemTime>,
+    /// Size of the file being loaded, 0 if unknown.
+    pub loading_total_bytes: u64,
+    /// Keys of the file being loaded, 0 until it is decoded.
+    pub loading_total_keys: u64,
+    pub loading_loaded_keys: u64,
+    pub last_load_keys_loaded: u64,
+    /// Keys of the last loaded file that were already expired and therefore skipped.
+    pub last_load_keys_expired: u64,
+}
+
+fn lock() -> std::sync::MutexGuard<'static, PersistenceStats> {
+    return STATS
+        .lock()
+        .expect("Persistence stats lock poisoned. Should never happen!");
+}
+
+pub fn stats() -> PersistenceStats {
+    return *lock();
+}
+
+pub fn record_write() {
+    let mut stats = lock();
+    stats.total_writes += 1;
+    stats.changes_since_last_save += 1;
+}
+
+pub fn unix_secs(time: SystemTime) -> u64 {
+    return time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+}
+
+/// Delays every snapshot after it started, where redis would fork, so the save can be
+/// observed in progress. Zero disables the delay.
+pub fn set_sleep_after_fork(delay: Duration) {
+    SLEEP_AFTER_FORK_MICROS.store(delay.as_micros() as u64, Ordering::SeqCst);
+}
+
+/// Lets every snapshot fail until it is disabled again.
+pub fn set_fail_saves(fail: bool) {
+    FAIL_SAVES.store(fail, Ordering::SeqCst);
+}
+
+/// Writes a snapshot of the data store as rdb file, with the stats as aux fields, and
+/// counts it as a save. Only fails if a failure is injected, see [`set_fail_saves`].
+pub fn save_snapshot(db: &DataStore) -> io::Result<Vec<u8>> {
+    let started = Instant::now();
+    {
+        let mut stats = lock();
+        stats.saves_in_progress += 1;
+        stats.save_started = stats.save_started.or(Some(started));
+    }
+
+    let delay = Duration::from_micros(SLEEP_AFTER_FORK_MICROS.load(Ordering::SeqCst));
+    if !delay.is_zero() {
+        thread::sleep(delay);
+    }
+    if FAIL_SAVES.load(Ordering::SeqCst) {
+        warn!("Snapshot failed, failures are injected by DEBUG FAIL-SAVES");
+        let mut stats = lock();
+        stats.last_save_ok = false;
+        finish_save(&mut stats);
+        return Err(io::Error::other("injected snapshot failure"));
+    }
+
+    let now = SystemTime::now();
+    let current = stats();
+    let aux_fields = [
+        (AUX_CTIME, unix_secs(now).to_string()),
+        (AUX_TOTAL_WRITES, current.total_writes.to_string()),
+        (
+            AUX_DATASET_CTIME,
+            unix_secs(current.dataset_created).to_string(),
+        ),
+    ];
+    let mut values = db.snapshot_iter();
+    let rdb_file = RdbFile::encode_with_aux(&mut values, &aux_fields);
+
+    let mut stats = lock();
+    // writes during the snapshot may be part of it or not, like with a fork in redis
+    stats.changes_since_last_save = stats
+        .changes_since_last_save
+        .saturating_sub(current.changes_since_last_save);
+    stats.saves += 1;
+    stats.last_save = now;
+    stats.last_save_duration = Some(started.elapsed());
+    stats.last_save_ok = true;
+    stats.last_cow_size = values.peak_copy_bytes() as u64;
+    finish_save(&mut stats);
+    return Ok(rdb_file);
+}
+
+fn finish_save(stats: &mut PersistenceStats) {
+    stats.saves_in_progress -= 1;
+    if stats.saves_in_progress == 0 {
+        stats.save_started = None;
+    }
+}
+
+/// Marks the last save as failed, the snapshot could not be written out.
+pub fn record_failed_save() {
+    lock().last_save_ok = false;
+}
+
+/// Marks a dataset as being loaded until the returned guard is dropped. Clients are
+/// answered with `-LOADING` meanwhile.
+pub fn start_loading(total_bytes: u64) -> Loading {
+    let mut stats = lock();
+    stats.loading = true;
+    stats.loading_started = Some(SystemTime::now());
+    stats.loading_total_bytes = total_bytes;
+    stats.loading_total_keys = 0;
+    stats.loading_loaded_keys = 0;
+    LOADING.store(true, Ordering::Release);
+    return Loading { _private: () };
+}
+
+/// See [`start_loading`].
+#[derive(Debug)]
+pub struct Loading {
+    _private: (),
+}
+
+impl Drop for Loading {
+    fn drop(&mut self) {
+        let mut stats = lock();
+        stats.loading = false;
+        stats.loading_started = None;
+        LOADING.store(false, Ordering::Release);
+    }
+}
+
+/// Cheaper than [`stats`], as it is checked for every command.
+pub fn is_loading() -> bool {
+    return LOADING.load(Ordering::Acquire);
+}
+
+/// Loads the values of an rdb file with `insert` and takes over its stats, see [`restore`].
+/// Expired values are skipped.
+pub fn load<F: FnMut(DataUnit)>(rdb_file: &RdbFile, mut insert: F) {
+    let values = rdb_file.get_database().to_dashmap();
+    lock().loading_total_keys = values.len() as u64;
+
+    let mut loaded = 0;
+    let mut expired = 0;
+    for (index, (_, value)) in values.into_iter().enumerate() {
+        if index as u64 % LOADING_PROGRESS_INTERVAL == 0 {
+            lock().loading_loaded_keys = index as u64;
+        }
+        if value.is_expired() {
+            expired += 1;
+            continue;
+        }
+        insert(value);
+        loaded += 1;
+    }
+
+    restore(rdb_file);
+    let mut stats = lock();
+    stats.loading_loaded_keys = stats.loading_total_keys;
+    stats.last_load_keys_loaded = loaded;
+    stats.last_load_keys_expired = expired;
+}
+
+impl PersistenceStats {
+    /// Share of the file loaded so far, from 0 to 1. Decoding the file counts as nothing
+    /// loaded, afterwards the share of inserted keys is reported.
+    pub fn loading_progress(&self) -> f64 {
+        if self.loading_total_keys == 0 {
+            return 0.0;
+        }
+        return self.loading_loaded_keys as f64 / self.loading_total_keys as f64;
+    }
+
+    /// Estimated seconds until the load is done, 1 while there is no progress to estimate
+    /// from, like redis.
+    pub fn loading_eta(&self) -> u64 {
+        let progress = self.loading_progress();
+        let elapsed = self
+            .loading_started
+            .and_then(|started| started.elapsed().ok())
+            .unwrap_or(Duration::ZERO);
+        if progress == 0.0 {
+            return 1;
+        }
+        return (elapsed.as_secs_f64() * (1.0 - progress) / progress) as u64;
+    }
+}
+
+fn parse_aux_field(key: &str, value: &str) -> Option<u64> {
+    let parsed = value.parse::<u64>();
+    if parsed.is_err() {
+        warn!(
+            "Ignoring invalid aux field {} '{}' of the rdb file",
+            key, value
+        );
+    }
+    return parsed.ok();
+}
+
+/// Takes over the stats of a loaded rdb file. Files without them, e.g. of redis, leave the
+/// stats unchanged.
+pub fn restore(rdb_file: &RdbFile) {
+    let mut stats = lock();
+    for (key, value) in rdb_file.aux_fields() {
+        if ![AUX_CTIME, AUX_TOTAL_WRITES, AUX_DATASET_CTIME].contains(&key) {
+            continue;
+        }
+        let Some(value) = parse_aux_field(key, value) else {
+            continue;
+        };
+
+        match key {
+            AUX_CTIME => stats.last_save = UNIX_EPOCH + Duration::from_secs(value),
+            AUX_TOTAL_WRITES => stats.total_writes = value,
+            _ => stats.dataset_created = UNIX_EPOCH + Duration::from_secs(value),
+        }
+    }
+    stats.changes_since_last_save = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{
+        db::{
+            data_store::{init_test_db, DataUnit, Expiry},
+            persistence::{load, record_write, restore, save_snapshot, stats, unix_secs},
+        },
+        parser::db_file::RdbFile,
+    };
+
+    // the stats are global, so save and restore are tested together
+    #[test]
+    fn test_save_and_restore() {
+        record_write();
+        let rdb_file = RdbFile::decode(save_snapshot(init_test_db()).unwrap()).unwrap();
+        let saved = stats();
+        assert!(saved.saves >= 1);
+        assert!(saved.total_writes >= 1);
+        assert!(saved.last_save_ok);
+        assert!(saved.last_save_duration.is_some());
+
+        let fields: Vec<_> = rdb_file.aux_fields().map(|(key, _)| key).collect();
+        assert_eq!(
+            vec!["redis-ver", "ctime", "total-writes", "dataset-ctime"],
+            fields
+        );
+
+        let old_file = RdbFile::decode(RdbFile::encode_with_aux(
+            vec![],
+            &[
+                ("ctime", "1700000000".to_string()),
+                ("total-writes", "12345".to_string()),
+                ("dataset-ctime", "1600000000".to_string()),
+                ("used-mem", "1024".to_string()),
+            ],
+        ))
+        .unwrap();
+        restore(&old_file);
+
+        let restored = stats();
+        assert_eq!(12345, restored.total_writes);
+        assert_eq!(0, restored.changes_since_last_save);
+        assert_eq!(1700000000, unix_secs(restored.last_save));
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(1600000000),
+            restored.dataset_created
+        );
+    }
+
+    #[test]
+    fn test_loading_progress() {
+        let mut loading = stats();
+        loading.loading_started = Some(SystemTime::now() - Duration::from_secs(10));
+        loading.loading_total_keys = 0;
+        assert_eq!(0.0, loading.loading_progress());
+        assert_eq!(1, loading.loading_eta());
+
+        loading.loading_total_keys = 100;
+        loading.loading_loaded_keys = 25;
+        assert_eq!(0.25, loading.loading_progress());
+        assert_eq!(30, loading.loading_eta());
+    }
+
+    #[test]
+    fn test_load_skips_expired_values() {
+        let expired = Some(Expiry::Ttl(Duration::ZERO));
+        let rdb_file = RdbFile::decode(RdbFile::encode(vec![
+            DataUnit::new("a", "1", None),
+            DataUnit::new("b", "2", None),
+            DataUnit::new("expired", "3", expired),
+        ]))
+        .unwrap();
+
+        let mut keys = Vec::new();
+        load(&rdb_file, |value| keys.push(value.key));
+        keys.sort();
+
+        assert_eq!(vec!["a", "b"], keys);
+        let loaded = stats();
+        assert!(!loaded.loading);
+        assert_eq!(2, loaded.last_load_keys_loaded);
+        assert_eq!(1, loaded.last_load_keys_expired);
+    }
+}
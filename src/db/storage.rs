@@ -0,0 +1,641 @@
+//! The keyspace behind the data store.
+//!
+//! The data store keeps the key locks, expiry and stats, a [`Storage`] only maps keys to
+//! values. Backends can therefore be swapped, e.g. to benchmark them against each other,
+//! without touching the data store or the commands. Like DashMap, every backend splits the
+//! keys into shards, which SCAN, snapshots and the active defrag walk one at a time.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, RandomState},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread,
+};
+
+use dashmap::{mapref::entry::Entry, DashMap, SharedValue};
+
+use crate::db::{
+    data_store::{DataUnit, MIN_WASTED_BYTES},
+    value::Value,
+};
+
+/// A map of keys to values, split into shards. The values are returned as stored, expired
+/// values included, the data store decides what to do with them.
+pub trait Storage: Send + Sync + fmt::Debug {
+    /// Amount of shards, a power of two of at least 2. A key stays in its shard for good.
+    fn shard_count(&self) -> usize;
+
+    fn get(&self, key: &str) -> Option<DataUnit>;
+
+    /// Inserts the value and returns the one it replaced.
+    fn set(&self, key: String, value: DataUnit) -> Option<DataUnit>;
+
+    fn remove(&self, key: &str) -> Option<DataUnit>;
+
+    /// Removes the key only if its value fulfills `condition`, checked under the lock of
+    /// its shard.
+    fn remove_if(&self, key: &str, condition: &dyn Fn(&DataUnit) -> bool) -> Option<DataUnit>;
+
+    /// Changes the value of an existing key in place, returns false if it is missing.
+    fn modify(&self, key: &str, f: &mut dyn FnMut(&mut DataUnit)) -> bool;
+
+    /// Read-modify-write of the key while holding the lock of its shard. `f` recieves the
+    /// value, None if it is missing, and setting it to None removes the key.
+    fn update(&self, key: String, f: &mut dyn FnMut(&mut Option<DataUnit>));
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Calls `f` for every key of the shard while holding its read lock.
+    fn for_each_in_shard(&self, index: usize, f: &mut dyn FnMut(&str, &DataUnit));
+
+    /// Calls `f` for every key, shard by shard.
+    fn for_each(&self, f: &mut dyn FnMut(&str, &DataUnit)) {
+        for index in 0..self.shard_count() {
+            self.for_each_in_shard(index, f);
+        }
+    }
+
+    /// The amount of keys and the capacity of the table of the shard.
+    fn shard_stats(&self, index: usize) -> (usize, usize);
+
+    /// Shrinks the table of the shard and the keys and values in it wasting at least
+    /// [`MIN_WASTED_BYTES`]. Returns the estimated allocated bytes before and after, None if
+    /// the shard is in use, so the shrinking never waits for clients.
+    fn shrink_shard(&self, index: usize) -> Option<(usize, usize)>;
+
+    /// Removes all keys and returns them, so the caller decides where they are freed.
+    fn take(&self) -> Box<dyn Any + Send>;
+}
+
+/// The available backends, chosen by `--storage-backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    DashMap,
+    /// A std HashMap behind a RwLock per shard.
+    ShardedHashMap,
+}
+
+impl StorageBackend {
+    pub const ALL: [StorageBackend; 2] = [Self::DashMap, Self::ShardedHashMap];
+
+    pub const fn name(&self) -> &'static str {
+        return match self {
+            Self::DashMap => "dashmap",
+            Self::ShardedHashMap => "hashmap",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL
+            .into_iter()
+            .find(|backend| backend.name().eq_ignore_ascii_case(name));
+    }
+
+    /// Creates an empty storage. Without a shard amount the default of DashMap is used,
+    /// which depends on the amount of cpus.
+    pub fn create(&self, shard_amount: Option<usize>) -> Box<dyn Storage> {
+        return match self {
+            Self::DashMap => Box::new(DashMapStorage::new(shard_amount)),
+            Self::ShardedHashMap => Box::new(ShardedHashMapStorage::new(
+                shard_amount.unwrap_or_else(default_shard_amount),
+            )),
+        };
+    }
+}
+
+/// The shard amount DashMap picks by default.
+fn default_shard_amount() -> usize {
+    let cpus = thread::available_parallelism().map_or(1, usize::from);
+    return (cpus * 4).next_power_of_two();
+}
+
+fn shrink_string(string: &mut String) {
+    if string.capacity() - string.len() >= MIN_WASTED_BYTES {
+        string.shrink_to_fit();
+    }
+}
+
+/// Shrinks the strings of the value and returns their capacity afterwards. Shared values do
+/// not belong to the key and are left alone.
+fn shrink_value(value: &mut DataUnit) -> usize {
+    shrink_string(&mut value.key);
+    if let Value::Owned(string) = &mut value.value {
+        shrink_string(string);
+    }
+    return value.key.capacity() + value.value.capacity();
+}
+
+#[derive(Debug)]
+pub struct DashMapStorage {
+    map: DashMap<String, DataUnit>,
+}
+
+impl DashMapStorage {
+    pub fn new(shard_amount: Option<usize>) -> Self {
+        let map = match shard_amount {
+            Some(shard_amount) => DashMap::with_shard_amount(shard_amount),
+            None => DashMap::new(),
+        };
+        return Self { map };
+    }
+}
+
+impl Storage for DashMapStorage {
+    fn shard_count(&self) -> usize {
+        return self.map.shards().len();
+    }
+
+    fn get(&self, key: &str) -> Option<DataUnit> {
+        return self.map.get(key).map(|value| value.clone());
+    }
+
+    fn set(&self, key: String, value: DataUnit) -> Option<DataUnit> {
+        return self.map.insert(key, value);
+    }
+
+    fn remove(&self, key: &str) -> Option<DataUnit> {
+        return self.map.remove(key).map(|(_, value)| value);
+    }
+
+    fn remove_if(&self, key: &str, condition: &dyn Fn(&DataUnit) -> bool) -> Option<DataUnit> {
+        return self
+            .map
+            .remove_if(key, |_, value| condition(value))
+            .map(|(_, value)| value);
+    }
+
+    fn modify(&self, key: &str, f: &mut dyn FnMut(&mut DataUnit)) -> bool {
+        return match self.map.get_mut(key) {
+            Some(mut value) => {
+                f(&mut value);
+                true
+            }
+            None => false,
+        };
+    }
+
+    fn update(&self, key: String, f: &mut dyn FnMut(&mut Option<DataUnit>)) {
+        match self.map.entry(key) {
+            Entry::Occupied(mut entry) => {
+                // take the value out instead of cloning it, the placeholder counts as expired
+                let existing = std::mem::replace(entry.get_mut(), DataUnit::placeholder());
+                let mut value = Some(existing);
+
+                f(&mut value);
+
+                match value {
+                    Some(value) => *entry.get_mut() = value,
+                    None => {
+                        entry.remove();
+                    }
+                }
+            }
+            Entry::Vacant(entry) => {
+                let mut value = None;
+
+                f(&mut value);
+
+                if let Some(value) = value {
+                    entry.insert(value);
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        return self.map.len();
+    }
+
+    fn for_each_in_shard(&self, index: usize, f: &mut dyn FnMut(&str, &DataUnit)) {
+        let shard = self.map.shards()[index].read();
+
+        // SAFETY: the read guard of the shard is held while iterating, so no bucket can be
+        // removed or moved in the meantime.
+        unsafe {
+            for bucket in shard.iter() {
+                let (key, value) = bucket.as_ref();
+                f(key, value.get());
+            }
+        }
+    }
+
+    fn shard_stats(&self, index: usize) -> (usize, usize) {
+        let shard = self.map.shards()[index].read();
+        return (shard.len(), shard.capacity());
+    }
+
+    fn shrink_shard(&self, index: usize) -> Option<(usize, usize)> {
+        let mut shard = self.map.shards()[index].try_write()?;
+        let entry_size = std::mem::size_of::<(String, SharedValue<DataUnit>)>();
+
+        let mut before = shard.capacity() * entry_size;
+        let mut after = 0;
+        // SAFETY: the write guard of the shard is held, so no other thread accesses the
+        // buckets while they are changed.
+        unsafe {
+            for bucket in shard.iter() {
+                let (key, value) = bucket.as_mut();
+                let value = value.get_mut();
+                before += key.capacity() + value.key.capacity() + value.value.capacity();
+
+                shrink_string(key);
+                after += key.capacity() + shrink_value(value);
+            }
+        }
+
+        // a table shrunk to fit its keys has less than twice their capacity
+        if shard.capacity() > 2 * shard.len() {
+            let hasher = self.map.hasher();
+            let len = shard.len();
+            shard.shrink_to(len, |(key, _)| hasher.hash_one(key));
+        }
+        after += shard.capacity() * entry_size;
+
+        return Some((before, after));
+    }
+
+    fn take(&self) -> Box<dyn Any + Send> {
+        let tables: Vec<_> = self
+            .map
+            .shards()
+            .iter()
+            .map(|shard| std::mem::take(&mut *shard.write()))
+            .collect();
+        return Box::new(tables);
+    }
+}
+
+#[derive(Debug)]
+pub struct ShardedHashMapStorage {
+    hasher: RandomState,
+    shards: Box<[RwLock<HashMap<String, DataUnit>>]>,
+}
+
+impl ShardedHashMapStorage {
+    /// `shard_amount` must be a power of two of at least 2, like for DashMap.
+    pub fn new(shard_amount: usize) -> Self {
+        assert!(
+            shard_amount >= 2 && shard_amount.is_power_of_two(),
+            "Shard amount must be a power of two greater than 1"
+        );
+        return Self {
+            hasher: RandomState::new(),
+            shards: (0..shard_amount)
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+        };
+    }
+
+    fn shard(&self, key: &str) -> usize {
+        // the top bits like DashMap, the low bits select the bucket within the shard
+        let shard_bits = self.shards.len().trailing_zeros();
+        return (self.hasher.hash_one(key) >> (u64::BITS - shard_bits)) as usize;
+    }
+
+    fn read(&self, index: usize) -> RwLockReadGuard<'_, HashMap<String, DataUnit>> {
+        return self.shards[index]
+            .read()
+            .expect("Shard lock poisoned. Should never happen!");
+    }
+
+    fn write(&self, index: usize) -> RwLockWriteGuard<'_, HashMap<String, DataUnit>> {
+        return self.shards[index]
+            .write()
+            .expect("Shard lock poisoned. Should never happen!");
+    }
+}
+
+impl Storage for ShardedHashMapStorage {
+    fn shard_count(&self) -> usize {
+        return self.shards.len();
+    }
+
+    fn get(&self, key: &str) -> Option<DataUnit> {
+        return self.read(self.shard(key)).get(key).cloned();
+    }
+
+    fn set(&self, key: String, value: DataUnit) -> Option<DataUnit> {
+        return self.write(self.shard(&key)).insert(key, value);
+    }
+
+    fn remove(&self, key: &str) -> Option<DataUnit> {
+        return self.write(self.shard(key)).remove(key);
+    }
+
+    fn remove_if(&self, key: &str, condition: &dyn Fn(&DataUnit) -> bool) -> Option<DataUnit> {
+        let mut shard = self.write(self.shard(key));
+        if !shard.get(key).is_some_and(condition) {
+            return None;
+        }
+        return shard.remove(key);
+    }
+
+    fn modify(&self, key: &str, f: &mut dyn FnMut(&mut DataUnit)) -> bool {
+        return match self.write(self.shard(key)).get_mut(key) {
+            Some(value) => {
+                f(value);
+                true
+            }
+            None => false,
+        };
+    }
+
+    fn update(&self, key: String, f: &mut dyn FnMut(&mut Option<DataUnit>)) {
+        let mut shard = self.write(self.shard(&key));
+        match shard.get_mut(&key) {
+            Some(existing) => {
+                // take the value out instead of cloning it, the placeholder counts as expired
+                let mut value = Some(std::mem::replace(existing, DataUnit::placeholder()));
+
+                f(&mut value);
+
+                match value {
+                    Some(value) => *existing = value,
+                    None => {
+                        shard.remove(&key);
+                    }
+                }
+            }
+            None => {
+                let mut value = None;
+
+                f(&mut value);
+
+                if let Some(value) = value {
+                    shard.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        return (0..self.shards.len())
+            .map(|index| self.read(index).len())
+            .sum();
+    }
+
+    fn for_each_in_shard(&self, index: usize, f: &mut dyn FnMut(&str, &DataUnit)) {
+        for (key, value) in self.read(index).iter() {
+            f(key, value);
+        }
+    }
+
+    fn shard_stats(&self, index: usize) -> (usize, usize) {
+        let shard = self.read(index);
+        return (shard.len(), shard.capacity());
+    }
+
+    fn shrink_shard(&self, index: usize) -> Option<(usize, usize)> {
+        let mut shard = self.shards[index].try_write().ok()?;
+        let entry_size = std::mem::size_of::<(String, DataUnit)>();
+
+        // the keys of a HashMap can not be changed in place, only the values are shrunk
+        let mut before = shard.capacity() * entry_size;
+        let mut after = 0;
+        for (key, value) in shard.iter_mut() {
+            before += key.capacity() + value.key.capacity() + value.value.capacity();
+            after += key.capacity() + shrink_value(value);
+        }
+
+        if shard.capacity() > 2 * shard.len() {
+            shard.shrink_to_fit();
+        }
+        after += shard.capacity() * entry_size;
+
+        return Some((before, after));
+    }
+
+    fn take(&self) -> Box<dyn Any + Send> {
+        let tables: Vec<_> = (0..self.shards.len())
+            .map(|index| std::mem::take(&mut *self.write(index)))
+            .collect();
+        return Box::new(tables);
+    }
+}
+
+/// Counts the memory used by the keys of the wrapped storage, compared to `maxmemory` by
+/// the eviction.
+///
+/// The count is an estimate of the keys and values only, see [`DataUnit::memory_usage`],
+/// the tables and the allocator are not part of it.
+#[derive(Debug)]
+pub struct MemoryTracked {
+    storage: Box<dyn Storage>,
+    used_memory: AtomicI64,
+}
+
+impl MemoryTracked {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        return Self {
+            storage,
+            used_memory: AtomicI64::new(0),
+        };
+    }
+
+    pub fn used_memory(&self) -> u64 {
+        return self.used_memory.load(Ordering::Relaxed).max(0) as u64;
+    }
+
+    fn add(&self, delta: i64) {
+        if delta != 0 {
+            self.used_memory.fetch_add(delta, Ordering::Relaxed);
+        }
+    }
+}
+
+fn size(value: Option<&DataUnit>) -> i64 {
+    return value.map_or(0, |value| value.memory_usage() as i64);
+}
+
+impl Storage for MemoryTracked {
+    fn shard_count(&self) -> usize {
+        return self.storage.shard_count();
+    }
+
+    fn get(&self, key: &str) -> Option<DataUnit> {
+        return self.storage.get(key);
+    }
+
+    fn set(&self, key: String, value: DataUnit) -> Option<DataUnit> {
+        let added = size(Some(&value));
+        let old_value = self.storage.set(key, value);
+        self.add(added - size(old_value.as_ref()));
+        return old_value;
+    }
+
+    fn remove(&self, key: &str) -> Option<DataUnit> {
+        let value = self.storage.remove(key);
+        self.add(-size(value.as_ref()));
+        return value;
+    }
+
+    fn remove_if(&self, key: &str, condition: &dyn Fn(&DataUnit) -> bool) -> Option<DataUnit> {
+        let value = self.storage.remove_if(key, condition);
+        self.add(-size(value.as_ref()));
+        return value;
+    }
+
+    fn modify(&self, key: &str, f: &mut dyn FnMut(&mut DataUnit)) -> bool {
+        return self.storage.modify(key, &mut |value| {
+            let before = size(Some(value));
+            f(value);
+            self.add(size(Some(value)) - before);
+        });
+    }
+
+    fn update(&self, key: String, f: &mut dyn FnMut(&mut Option<DataUnit>)) {
+        self.storage.update(key, &mut |value| {
+            let before = size(value.as_ref());
+            f(value);
+            self.add(size(value.as_ref()) - before);
+        });
+    }
+
+    fn len(&self) -> usize {
+        return self.storage.len();
+    }
+
+    fn for_each_in_shard(&self, index: usize, f: &mut dyn FnMut(&str, &DataUnit)) {
+        self.storage.for_each_in_shard(index, f);
+    }
+
+    fn shard_stats(&self, index: usize) -> (usize, usize) {
+        return self.storage.shard_stats(index);
+    }
+
+    fn shrink_shard(&self, index: usize) -> Option<(usize, usize)> {
+        return self.storage.shrink_shard(index);
+    }
+
+    fn take(&self) -> Box<dyn Any + Send> {
+        let tables = self.storage.take();
+        // keys set while taking may be counted wrongly, the count is an estimate anyway
+        self.used_memory.store(0, Ordering::Relaxed);
+        return tables;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{
+        data_store::DataUnit,
+        storage::{MemoryTracked, Storage, StorageBackend},
+    };
+
+    fn backends() -> impl Iterator<Item = Box<dyn Storage>> {
+        return StorageBackend::ALL
+            .into_iter()
+            .map(|backend| backend.create(Some(4)));
+    }
+
+    fn value(key: &str, value: &str) -> DataUnit {
+        return DataUnit::new(key, value, None);
+    }
+
+    #[test]
+    fn test_backend_names() {
+        for backend in StorageBackend::ALL {
+            assert_eq!(Some(backend), StorageBackend::from_name(backend.name()));
+        }
+        assert_eq!(
+            Some(StorageBackend::ShardedHashMap),
+            StorageBackend::from_name("HashMap")
+        );
+        assert_eq!(None, StorageBackend::from_name("sled"));
+    }
+
+    #[test]
+    fn test_set_get_remove() {
+        for storage in backends() {
+            assert_eq!(None, storage.set("a".into(), value("a", "1")));
+            assert_eq!(
+                Some(value("a", "1")),
+                storage.set("a".into(), value("a", "2"))
+            );
+            assert_eq!(Some(value("a", "2")), storage.get("a"));
+            assert_eq!(1, storage.len());
+
+            assert_eq!(None, storage.remove_if("a", &|value| &*value.value == "1"));
+            assert_eq!(
+                Some(value("a", "2")),
+                storage.remove_if("a", &|value| &*value.value == "2")
+            );
+            assert_eq!(None, storage.remove("a"));
+            assert!(storage.is_empty(), "{:?}", storage);
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        for storage in backends() {
+            storage.update("a".into(), &mut |value| {
+                assert_eq!(None, *value);
+                *value = Some(DataUnit::new("a", "1", None));
+            });
+            storage.update("a".into(), &mut |value| {
+                value.as_mut().unwrap().value.to_mut().push('0');
+            });
+            assert_eq!(Some(value("a", "10")), storage.get("a"));
+
+            assert!(storage.modify("a", &mut |value| value.key.push('!')));
+            assert!(!storage.modify("b", &mut |_| ()));
+
+            storage.update("a".into(), &mut |value| *value = None);
+            assert_eq!(None, storage.get("a"));
+        }
+    }
+
+    #[test]
+    fn test_shards_hold_every_key() {
+        for storage in backends() {
+            for i in 0..100 {
+                storage.set(i.to_string(), value(&i.to_string(), "value"));
+            }
+
+            let mut keys = 0;
+            storage.for_each(&mut |key, value| {
+                assert_eq!(key, value.key);
+                keys += 1;
+            });
+            let stats: usize = (0..storage.shard_count())
+                .map(|index| storage.shard_stats(index).0)
+                .sum();
+            assert_eq!((100, 100), (keys, stats));
+
+            drop(storage.take());
+            assert!(storage.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_memory_tracked() {
+        let storage = MemoryTracked::new(StorageBackend::DashMap.create(Some(4)));
+        let size = |key: &str, value: &str| self::value(key, value).memory_usage() as u64;
+
+        storage.set("a".into(), value("a", "1"));
+        storage.set("b".into(), value("b", "22"));
+        storage.set("a".into(), value("a", "333"));
+        assert_eq!(size("a", "333") + size("b", "22"), storage.used_memory());
+
+        storage.update("b".into(), &mut |value| {
+            value.as_mut().unwrap().value.to_mut().push_str("22")
+        });
+        assert_eq!(size("a", "333") + size("b", "2222"), storage.used_memory());
+
+        storage.remove("a");
+        storage.update("c".into(), &mut |value| *value = Some(self::value("c", "")));
+        assert_eq!(size("b", "2222") + size("c", ""), storage.used_memory());
+
+        drop(storage.take());
+        assert_eq!(0, storage.used_memory());
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+/// Amount of volatile keys looked at for INFO keyspace and DEBUG TTL-STATS.
+pub const TTL_SAMPLE_SIZE: usize = 1000;
+
+/// Upper bounds of the histogram buckets, the last bucket holds all longer ttls.
+const BUCKETS: [(&str, Duration); 6] = [
+    ("<1s", Duration::from_secs(1)),
+    ("<10s", Duration::from_secs(10)),
+    ("<1m", Duration::from_secs(60)),
+    ("<10m", Duration::from_secs(10 * 60)),
+    ("<1h", Duration::from_secs(60 * 60)),
+    ("<1d", Duration::from_secs(24 * 60 * 60)),
+];
+const LAST_BUCKET: &str = ">=1d";
+
+/// Histogram of the remaining time to live of sampled keys. It is only an estimate of the
+/// whole keyspace, the sampled keys are not picked evenly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TtlHistogram {
+    counts: [usize; BUCKETS.len() + 1],
+    total: Duration,
+    samples: usize,
+}
+
+impl TtlHistogram {
+    pub fn record(&mut self, ttl: Duration) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|(_, bound)| ttl < *bound)
+            .unwrap_or(BUCKETS.len());
+
+        self.counts[bucket] += 1;
+        self.total = self.total.saturating_add(ttl);
+        self.samples += 1;
+    }
+
+    pub fn samples(&self) -> usize {
+        return self.samples;
+    }
+
+    /// The average ttl in milliseconds, 0 without any samples like redis.
+    pub fn avg_ttl_millis(&self) -> u128 {
+        if self.samples == 0 {
+            return 0;
+        }
+        return self.total.as_millis() / self.samples as u128;
+    }
+
+    /// Returns the name of every bucket with the amount of keys in it.
+    pub fn buckets(&self) -> Vec<(&'static str, usize)> {
+        return BUCKETS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain([LAST_BUCKET])
+            .zip(self.counts)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::db::ttl_stats::TtlHistogram;
+
+    #[test]
+    fn test_empty_histogram() {
+        let histogram = TtlHistogram::default();
+
+        assert_eq!(0, histogram.samples());
+        assert_eq!(0, histogram.avg_ttl_millis());
+        assert!(histogram.buckets().iter().all(|(_, count)| *count == 0));
+    }
+
+    #[test]
+    fn test_record_ttls() {
+        let mut histogram = TtlHistogram::default();
+        histogram.record(Duration::from_millis(500));
+        histogram.record(Duration::from_secs(1));
+        histogram.record(Duration::from_secs(90));
+        histogram.record(Duration::from_secs(7 * 24 * 60 * 60));
+
+        assert_eq!(4, histogram.samples());
+        assert_eq!(151_222_875, histogram.avg_ttl_millis());
+        assert_eq!(
+            vec![
+                ("<1s", 1),
+                ("<10s", 1),
+                ("<1m", 0),
+                ("<10m", 1),
+                ("<1h", 0),
+                ("<1d", 0),
+                (">=1d", 1)
+            ],
+            histogram.buckets()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! The value stored under a key.
+//!
+//! Like redis, the canonical integers `0..SHARED_INTEGERS` are shared objects: every key
+//! holding one of them references the same allocation of a global pool instead of owning a
+//! copy. Integer-heavy keyspaces, e.g. counters and flags, need no allocation per value.
+
+use std::{fmt, ops::Deref, sync::Arc};
+
+use once_cell::sync::Lazy;
+
+/// Integers below this are shared, the OBJ_SHARED_INTEGERS of redis.
+pub const SHARED_INTEGERS: usize = 10000;
+
+/// Reference count OBJECT REFCOUNT reports for shared objects, they are never freed.
+pub const SHARED_REF_COUNT: i64 = i32::MAX as i64;
+
+static SHARED_POOL: Lazy<Vec<Arc<str>>> = Lazy::new(|| {
+    return (0..SHARED_INTEGERS)
+        .map(|int| Arc::from(int.to_string()))
+        .collect();
+});
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Shared(Arc<str>),
+    Owned(String),
+}
+
+impl Value {
+    /// The shared object of the value if it is a canonical integer of the pool, e.g. "01"
+    /// or "+1" are not.
+    fn shared(value: &str) -> Option<Arc<str>> {
+        let int = value.parse::<usize>().ok()?;
+        let shared = SHARED_POOL.get(int)?;
+        return (**shared == *value).then(|| Arc::clone(shared));
+    }
+
+    pub fn is_shared(&self) -> bool {
+        return matches!(self, Self::Shared(_));
+    }
+
+    /// The reference count redis reports for the value, values are never shared between
+    /// keys except for the shared objects.
+    pub fn ref_count(&self) -> i64 {
+        return match self {
+            Self::Shared(_) => SHARED_REF_COUNT,
+            Self::Owned(_) => 1,
+        };
+    }
+
+    /// Bytes allocated for the value, shared objects do not belong to a single key.
+    pub fn capacity(&self) -> usize {
+        return match self {
+            Self::Shared(_) => 0,
+            Self::Owned(value) => value.capacity(),
+        };
+    }
+
+    /// The owned string of the value, to change it in place.
+    pub fn to_mut(&mut self) -> &mut String {
+        if let Self::Shared(shared) = self {
+            *self = Self::Owned(shared.to_string());
+        }
+        return match self {
+            Self::Owned(value) => value,
+            Self::Shared(_) => unreachable!("The value was made owned above"),
+        };
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        return match Self::shared(&value) {
+            Some(shared) => Self::Shared(shared),
+            None => Self::Owned(value),
+        };
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        return match Self::shared(value) {
+            Some(shared) => Self::Shared(shared),
+            None => Self::Owned(value.to_string()),
+        };
+    }
+}
+
+impl From<Value> for String {
+    fn from(value: Value) -> Self {
+        return match value {
+            Value::Shared(shared) => shared.to_string(),
+            Value::Owned(value) => value,
+        };
+    }
+}
+
+impl Deref for Value {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        return match self {
+            Self::Shared(shared) => shared,
+            Self::Owned(value) => value,
+        };
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(self);
+    }
+}
+
+// equal by content, whether a value is shared is an implementation detail
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        return **self == **other;
+    }
+}
+impl Eq for Value {}
+
+impl PartialEq<str> for Value {
+    fn eq(&self, other: &str) -> bool {
+        return **self == *other;
+    }
+}
+
+impl PartialEq<Value> for &str {
+    fn eq(&self, other: &Value) -> bool {
+        return **self == **other;
+    }
+}
+
+impl PartialEq<Value> for String {
+    fn eq(&self, other: &Value) -> bool {
+        return **self == **other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::db::value::{Value, SHARED_REF_COUNT};
+
+    #[test]
+    fn test_shared_integers() {
+        for shared in ["0", "42", "9999"] {
+            assert!(Value::from(shared).is_shared(), "'{}' is shared", shared);
+        }
+        for owned in ["10000", "-1", "01", "+1", " 1", "value", ""] {
+            assert!(!Value::from(owned).is_shared(), "'{}' is not shared", owned);
+        }
+
+        let (Value::Shared(first), Value::Shared(second)) = (Value::from("7"), Value::from("7"))
+        else {
+            panic!("Integers of the pool are shared");
+        };
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_ref_count() {
+        assert_eq!(SHARED_REF_COUNT, Value::from("100").ref_count());
+        assert_eq!(1, Value::from("100000").ref_count());
+    }
+
+    #[test]
+    fn test_to_mut() {
+        let mut value = Value::from("1");
+        value.to_mut().push('0');
+
+        assert_eq!("10", value);
+        assert!(!value.is_shared());
+        assert!(Value::from("1").is_shared(), "The pool is unchanged");
+    }
+}
@@ -0,0 +1,254 @@
+//! Pauses the execution of all write commands, e.g. to take a snapshot of a master and its
+//! replicas while nothing changes (DEBUG PAUSE-WRITES).
+//!
+//! Every write holds a [`WritePermit`] while it executes. [`pause`] returns once all writes
+//! in flight are done, so no write is half applied while the server is paused.
+//!
+//! The server pauses writes itself with [`hold`], e.g. for the snapshot of a full resync.
+//! Such a hold is independent of DEBUG PAUSE-WRITES, resuming does not end it.
+//!
+//! A waiting client write blocks the thread serving its connection. Client writes therefore
+//! wait for DEBUG PAUSE-WRITES at most [`CLIENT_WAIT_LIMIT`] and fail after, else more
+//! waiting clients than threads would leave no thread for the command resuming writes.
+//! Holds end on their own, they are waited for without a limit.
+
+use std::{
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use log::info;
+use once_cell::sync::Lazy;
+
+/// How long a client write waits for DEBUG PAUSE-WRITES to end, see [`acquire_client_write`].
+pub const CLIENT_WAIT_LIMIT: Duration = Duration::from_secs(1);
+
+static STATE: Lazy<(Mutex<PauseState>, Condvar)> =
+    Lazy::new(|| (Mutex::new(PauseState::default()), Condvar::new()));
+
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: bool,
+    /// Pauses of the server itself, see [`hold`].
+    holds: usize,
+    in_flight: usize,
+}
+
+impl PauseState {
+    fn blocks_writes(&self) -> bool {
+        return self.paused || self.holds > 0;
+    }
+}
+
+fn lock_state() -> MutexGuard<'static, PauseState> {
+    return STATE
+        .0
+        .lock()
+        .expect("Write pause lock poisoned. Should never happen!");
+}
+
+fn wait<'a>(state: MutexGuard<'a, PauseState>) -> MutexGuard<'a, PauseState> {
+    return STATE
+        .1
+        .wait(state)
+        .expect("Write pause lock poisoned. Should never happen!");
+}
+
+fn wait_timeout<'a>(
+    state: MutexGuard<'a, PauseState>,
+    timeout: Duration,
+) -> MutexGuard<'a, PauseState> {
+    return STATE
+        .1
+        .wait_timeout(state, timeout)
+        .expect("Write pause lock poisoned. Should never happen!")
+        .0;
+}
+
+/// Allows a single write to execute, writes are not paused until it is dropped.
+pub struct WritePermit {
+    _private: (),
+}
+
+impl Drop for WritePermit {
+    fn drop(&mut self) {
+        lock_state().in_flight -= 1;
+        STATE.1.notify_all();
+    }
+}
+
+/// Blocks while writes are paused.
+pub fn acquire_write() -> WritePermit {
+    let mut state = lock_state();
+    while state.blocks_writes() {
+        state = wait(state);
+    }
+    state.in_flight += 1;
+    return WritePermit { _private: () };
+}
+
+/// Blocks while writes are paused, but gives up once DEBUG PAUSE-WRITES lasts longer than
+/// [`CLIENT_WAIT_LIMIT`]. None tells the write has to be rejected.
+pub fn acquire_client_write() -> Option<WritePermit> {
+    return acquire_write_within(CLIENT_WAIT_LIMIT);
+}
+
+fn acquire_write_within(limit: Duration) -> Option<WritePermit> {
+    let deadline = Instant::now() + limit;
+    let mut state = lock_state();
+    while state.blocks_writes() {
+        if !state.paused {
+            state = wait(state);
+            continue;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+        state = wait_timeout(state, remaining);
+    }
+    state.in_flight += 1;
+    return Some(WritePermit { _private: () });
+}
+
+/// Pauses all writes and waits until the writes in flight are done.
+pub fn pause() {
+    let mut state = lock_state();
+    state.paused = true;
+    while state.in_flight > 0 {
+        state = wait(state);
+    }
+    info!("Writes are paused");
+}
+
+/// Pauses writes until the returned [`WriteHold`] is dropped, returns once the writes in
+/// flight are done. Must not be called while holding a [`WritePermit`].
+pub fn hold() -> WriteHold {
+    let mut state = lock_state();
+    state.holds += 1;
+    while state.in_flight > 0 {
+        state = wait(state);
+    }
+    return WriteHold { _private: () };
+}
+
+/// Writes are paused until it is dropped, see [`hold`].
+pub struct WriteHold {
+    _private: (),
+}
+
+impl Drop for WriteHold {
+    fn drop(&mut self) {
+        lock_state().holds -= 1;
+        STATE.1.notify_all();
+    }
+}
+
+pub fn resume() {
+    lock_state().paused = false;
+    STATE.1.notify_all();
+    info!("Writes are resumed");
+}
+
+pub fn is_paused() -> bool {
+    return lock_state().paused;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex, MutexGuard, PoisonError,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::db::write_pause::{
+        acquire_write, acquire_write_within, hold, is_paused, pause, resume,
+    };
+
+    /// The tests share the global pause state, so they run one after another.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn lock_serial() -> MutexGuard<'static, ()> {
+        return SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+    }
+
+    #[test]
+    fn test_pause_blocks_writes() {
+        let _serial = lock_serial();
+        pause();
+        assert!(is_paused());
+
+        let written = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let written = Arc::clone(&written);
+            thread::spawn(move || {
+                let _permit = acquire_write();
+                written.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!written.load(Ordering::SeqCst));
+
+        resume();
+        writer.join().unwrap();
+        assert!(written.load(Ordering::SeqCst));
+        assert!(!is_paused());
+    }
+
+    #[test]
+    fn test_hold_outlasts_resume() {
+        let _serial = lock_serial();
+        let hold = hold();
+        // DEBUG PAUSE-WRITES 0 does not end a hold of the server
+        resume();
+
+        let written = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let written = Arc::clone(&written);
+            thread::spawn(move || {
+                let _permit = acquire_write();
+                written.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!written.load(Ordering::SeqCst));
+
+        drop(hold);
+        writer.join().unwrap();
+        assert!(written.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_client_write_waits_for_pause_within_limit() {
+        let _serial = lock_serial();
+        pause();
+        assert!(acquire_write_within(Duration::from_millis(50)).is_none());
+
+        let resumer = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            resume();
+        });
+        assert!(acquire_write_within(Duration::from_secs(5)).is_some());
+        resumer.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_write_waits_for_hold_without_limit() {
+        let _serial = lock_serial();
+        let hold = hold();
+
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(hold);
+        });
+        assert!(acquire_write_within(Duration::from_millis(10)).is_some());
+        releaser.join().unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+#![allow(warnings)]
+
+use log::trace;
+use std::{
+    io::{self, Read},
+    net::TcpStream,
+    result::Result,
+};
+
+pub mod commands;
+pub mod consts;
+pub mod db;
+pub mod parser;
+pub mod replication;
+pub mod server;
+pub mod utils;
+
+/// Reads the data provided in a single TCP message.
+pub fn read_message(stream: &mut TcpStream) -> Result<Vec<u8>, io::Error> {
+    return read_message_up_to(stream, usize::MAX);
+}
+
+/// Like [`read_message`], but stops once at least `max_len` bytes were read, so a client
+/// sending without pause can not make the message grow without bounds.
+pub fn read_message_up_to(stream: &mut TcpStream, max_len: usize) -> Result<Vec<u8>, io::Error> {
+    const BUFFER_SIZE: usize = 1024;
+    let mut data = Vec::with_capacity(BUFFER_SIZE * 4); // pre-allocate
+    let mut buf = [0u8; BUFFER_SIZE];
+
+    loop {
+        let n = stream.read(&mut buf)?;
+        trace!("Bytes received: {}", n);
+
+        utils::buffer_pool::reserve(&mut data, n);
+        data.extend_from_slice(&buf[..n]);
+
+        if n < BUFFER_SIZE || data.len() >= max_len {
+            break; // no more data immediately available, EOF or enough data
+        }
+    }
+
+    Ok(data)
+}
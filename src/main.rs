@@ -1,39 +1,84 @@
 #![allow(warnings)]
 
 use core::str;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, log_enabled, warn, Level};
 use std::{
     io::{self, ErrorKind, Read, Write},
     net::{SocketAddr, TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
     result::Result,
+    thread,
 };
-use utils::{cli::Args, thread_pool::ThreadPool};
-
-pub mod commands;
-pub mod consts;
-pub mod db;
-pub mod parser;
-pub mod utils;
-
-use crate::{
-    commands::command::UnparsedCommandType,
-    db::data_store::{get_db, init_db, ServerRole},
-    parser::messages::RedisMessageType,
-    utils::logger::generate_hex_log,
+
+use redis_starter_rust::{
+    commands::{
+        acl,
+        command::UnparsedCommandType,
+        context::ConnectionContext,
+        middleware, mode,
+        reply::{self, ErrorCode},
+    },
+    consts::WRITE_TIMEOUT,
+    db::{
+        data_store::{get_db, init_db, ServerRole},
+        defrag, persistence, write_pause,
+    },
+    parser::messages::{init_protocol_limits, protocol_limits, Recovery, RedisMessageType},
+    read_message_up_to,
+    replication::{master, slave},
+    server::Server,
+    utils::{
+        buffer_pool,
+        cli::Args,
+        deadline, diagnostics,
+        ip_filter::{self, ConnectionPermit},
+        logger::generate_hex_log,
+        shutdown,
+        thread_pool::ThreadPool,
+    },
 };
 
 fn main() {
     let args: Args = Args::parse();
     init_db(args.get_db_config());
+    acl::init_default_user_rules(args.acl_rules.clone());
+    mode::init_server_mode(args.mode);
+    ip_filter::init_ip_filter(args.get_ip_filter());
+    init_protocol_limits(args.protocol_limits);
 
     let server_address = SocketAddr::new(args.host, args.port);
     let pool = ThreadPool::new(args.threads.into());
-
+    #[cfg(unix)]
+    diagnostics::dump_on_sigusr1(pool.usage());
+    #[cfg(unix)]
+    shutdown::shutdown_on_signals();
+    defrag::start();
+
+    // clients are answered with -LOADING until the db file is loaded, a replica connects
+    // to its master afterwards
     match get_db().get_config().replication_data.role {
-        ServerRole::Master => (),
-        ServerRole::Slave((host, port)) => {
-            pool.execute(move || connect_slave_to_master(host, port))
-        }
+        ServerRole::Master => match &args.preload {
+            // the fixtures apply on top of the db file, both before connections are accepted
+            Some(path) => {
+                get_db().load_db_file();
+                if let Err(err) = Server::get().preload_file(path) {
+                    error!("Unable to preload {:?}: {}", path, err);
+                    std::process::exit(1);
+                }
+            }
+            None => {
+                let spawned = thread::Builder::new()
+                    .name("rdb-load".to_string())
+                    .spawn(|| get_db().load_db_file());
+                if let Err(err) = spawned {
+                    error!("Unable to start loading the db file: {}", err);
+                }
+            }
+        },
+        ServerRole::Slave((host, port)) => pool.execute(move || {
+            get_db().load_db_file();
+            slave::connect_slave_to_master(host, port)
+        }),
     }
 
     info!(
@@ -52,7 +97,17 @@ fn main() {
 
     for stream in listener.incoming() {
         match stream {
-            Ok(stream) => pool.execute(|| recieve_message(stream)),
+            // the process exits soon, the connection is closed right away
+            Ok(_) if shutdown::is_shutting_down() => {}
+            Ok(stream) => {
+                if let Some(permit) = admit_connection(&stream) {
+                    pool.execute(move || {
+                        // counts against the limit of the address until the connection is closed
+                        let _permit = permit;
+                        recieve_message(stream)
+                    })
+                }
+            }
             Err(err) => {
                 error!("Error while recieving tcp message: {}", err)
             }
@@ -60,40 +115,63 @@ fn main() {
     }
 }
 
-/// Reads the data provided in a single TCP message.
-fn read_message(stream: &mut TcpStream) -> Result<Vec<u8>, io::Error> {
-    const BUFFER_SIZE: usize = 1024;
-    let mut data = Vec::with_capacity(BUFFER_SIZE * 4); // pre-allocate
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    loop {
-        let n = stream.read(&mut buf)?;
-        trace!("Bytes received: {}", n);
-
-        data.extend_from_slice(&buf[..n]);
-
-        if n < BUFFER_SIZE {
-            break; // no more data immediately available or EOF
+/// Checks the address of a new connection against the ip filter. A refused connection is
+/// told why and closed right away, before it occupies a thread.
+fn admit_connection(stream: &TcpStream) -> Option<ConnectionPermit> {
+    let peer = match stream.peer_addr() {
+        Ok(peer) => peer,
+        Err(err) => {
+            info!("Unable to get the address of a new connection: {}", err);
+            return None;
         }
-    }
+    };
 
-    Ok(data)
+    return match ip_filter::admit(peer.ip()) {
+        Ok(permit) => Some(permit),
+        Err(rejection) => {
+            warn!("Refused connection of {}: {}", peer, rejection);
+            let reply = reply::error(ErrorCode::Err, rejection.to_string()).encode();
+            // a fresh socket has room for the short reply, so this does not block
+            let _ = (&*stream).write_all(&reply);
+            None
+        }
+    };
 }
 
 fn recieve_message(mut stream: TcpStream) {
     let peer = stream.peer_addr().unwrap();
+    if let Err(err) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+        error!("Unable to set the write timeout for {}: {}", peer, err);
+        return;
+    }
+    let ctx = ConnectionContext::new(peer);
+    // lets CLIENT KILL of other clients close this connection
+    match stream.try_clone() {
+        Ok(socket) => ctx.client().attach_socket(socket),
+        Err(err) => warn!("Unable to clone the connection of {}: {}", peer, err),
+    }
+    // a single read may contain several commands or only the start of one, so the bytes
+    // not handled yet are kept until the next read
+    let mut buffer = Vec::new();
+    let max_buffer_len = protocol_limits().max_query_buffer_len;
     'connection: loop {
-        let raw_message = match read_message(&mut stream) {
+        // an idle connection does not keep the buffer of a large command
+        buffer_pool::release(&mut buffer);
+        // one byte more than the limit is enough to know it is exceeded
+        match read_message_up_to(&mut stream, max_buffer_len - buffer.len() + 1) {
             Ok(raw_message) => {
-                trace!(
-                    "Successfully read tcp message. {:?}",
-                    generate_hex_log(&raw_message)
-                );
+                // the hex dump is costly, only build it when it is actually logged
+                if log_enabled!(Level::Debug) {
+                    debug!("Message recieved: {:?}", generate_hex_log(&raw_message));
+                }
                 if raw_message.is_empty() {
                     info!("No bytes recieved. Closing connection");
                     return;
                 }
-                raw_message
+                ctx.client().record_net_in(raw_message.len());
+                buffer_pool::reserve(&mut buffer, raw_message.len());
+                buffer.extend_from_slice(&raw_message);
+                buffer_pool::give_back(raw_message);
             }
             Err(err) => {
                 match err.kind() {
@@ -104,122 +182,200 @@ fn recieve_message(mut stream: TcpStream) {
             }
         };
 
-        let message_input =
-            str::from_utf8(&raw_message).expect("Unable to parse input bytestream to str utf8");
-        debug!("Message recieved: {:?}", generate_hex_log(&raw_message));
+        // e.g. a client announcing a huge array and streaming its elements without end
+        if buffer.len() > max_buffer_len {
+            warn!(
+                "Closing client {} that reached the max query buffer length of {} bytes",
+                peer, max_buffer_len
+            );
+            let error = reply::error(
+                ErrorCode::Err,
+                format!(
+                    "Protocol error: query buffer limit of {} bytes exceeded",
+                    max_buffer_len
+                ),
+            );
+            write_reply(&mut stream, &ctx, &error);
+            break 'connection;
+        }
 
-        let response = match process_message(message_input) {
-            Ok(message) => message,
-            Err(message) => message,
-        };
+        'messages: loop {
+            let message_input = match str::from_utf8(&buffer) {
+                Ok(message_input) => message_input,
+                // the last char may be split between two reads
+                Err(err) if err.error_len().is_none() => {
+                    str::from_utf8(&buffer[..err.valid_up_to()])
+                        .expect("The prefix is valid utf8. Should never happen!")
+                }
+                Err(err) => {
+                    let error = reply::error(
+                        ErrorCode::Err,
+                        format!("Protocol error: only utf8 payloads are supported ({})", err),
+                    );
+                    buffer.clear();
+                    if !write_reply(&mut stream, &ctx, &error) {
+                        break 'connection;
+                    }
+                    continue 'connection;
+                }
+            };
+
+            let (message, length) = match RedisMessageType::decode(message_input) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    let recovery = RedisMessageType::recover(message_input, &err);
+                    let error = reply::error(ErrorCode::Err, format!("Protocol error: {}", err));
+                    match recovery {
+                        Recovery::NeedMoreData => continue 'connection,
+                        Recovery::Skip(length) => {
+                            warn!("Skipping {} malformed bytes send by {}", length, peer);
+                            buffer.drain(..length);
+                            if !write_reply(&mut stream, &ctx, &error) {
+                                break 'connection;
+                            }
+                            continue 'messages;
+                        }
+                        Recovery::Close => {
+                            write_reply(&mut stream, &ctx, &error);
+                            break 'connection;
+                        }
+                    }
+                }
+            };
+            buffer.drain(..length);
 
-        stream
-            .write_all(response.encode().as_bytes())
-            .expect("Failed to write to stream. Should never happen!");
+            let result = match to_command(message) {
+                Ok(UnparsedCommandType::Psync(psync)) => {
+                    // the connection is owned by the replication module from here on
+                    master::handle_psync(stream, &ctx, psync);
+                    return;
+                }
+                Ok(command) => match execute_catching_panics(&ctx, command) {
+                    Some(result) => result,
+                    None => {
+                        let error = reply::error(ErrorCode::Err, "internal error");
+                        write_reply(&mut stream, &ctx, &error);
+                        break 'connection;
+                    }
+                },
+                Err(err) => Err(err),
+            };
+
+            let response = match result {
+                Ok(message) => message,
+                Err(message) => message,
+            };
+
+            if !write_reply(&mut stream, &ctx, &response) {
+                break 'connection;
+            }
+            // the client killed its own connection, the reply to CLIENT KILL was its last
+            if ctx.client().is_killed() {
+                break 'connection;
+            }
+            // pipelined commands are not executed any more
+            if shutdown::is_shutting_down() {
+                break 'connection;
+            }
+        }
     }
 }
 
-fn read_simple_string_response(stream: &mut TcpStream) -> String {
-    let message = read_message(stream).unwrap();
-    let message_input =
-        str::from_utf8(&message).expect(format!("Unable to parse input bytestream to str utf8 -> {:?}", message).as_str());
-    let parsed_message = RedisMessageType::decode(message_input)
-        .expect("unable to parse RedisMessageType from input byte stream")
-        .0;
-
-    return match parsed_message {
-        RedisMessageType::SimpleString(val) => val,
-        _ => panic!("Expected a \"PONG\" response from the master server"),
+/// Writes a reply to the client. Returns false if the connection has to be closed, because
+/// the client is gone or did not read its replies within the write timeout. A reply may be
+/// written partially in that case, so the connection can not be used any more.
+fn write_reply(stream: &mut TcpStream, ctx: &ConnectionContext, reply: &RedisMessageType) -> bool {
+    let reply = reply.encode();
+    return match stream.write_all(&reply) {
+        Ok(()) => {
+            ctx.client().record_net_out(reply.len());
+            true
+        }
+        Err(err) => {
+            match err.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut => {
+                    warn!(
+                        "Client {} is not reading its replies, disconnecting",
+                        ctx.peer
+                    )
+                }
+                _ => info!("Unable to write the reply to client {}: {}", ctx.peer, err),
+            }
+            false
+        }
     };
 }
 
-fn process_message(message: &str) -> Result<RedisMessageType, RedisMessageType> {
-    let parsed_message = RedisMessageType::decode(message)
-        .expect("unable to parse RedisMessageType from input byte stream")
-        .0;
-
-    let command: UnparsedCommandType = match parsed_message {
-        RedisMessageType::Array(val) => UnparsedCommandType::new(val)?,
-        other => panic!(
-            "Expected an RedisMessageType::Array as a command input, but got: {}",
-            other.to_string()
-        ),
+fn to_command(message: RedisMessageType) -> Result<UnparsedCommandType, RedisMessageType> {
+    return match message {
+        RedisMessageType::Array(val) => UnparsedCommandType::new(val),
+        other => Err(reply::error(
+            ErrorCode::Err,
+            format!(
+                "Protocol error: expected an Array as a command input, but got: {}",
+                other.to_string()
+            ),
+        )),
     };
-
-    return command.parse()?.execute();
 }
 
-fn connect_slave_to_master(master_host: String, master_port: u16) {
-    info!("Starting slave to master connection");
-    let stream = TcpStream::connect(format!("{}:{}", master_host, master_port))
-        .expect("Failed to connect to master!");
-
-    repl_handshake(stream);
+/// Executes the command like [`execute_command`], but catches a panic while doing so. None
+/// if the command panicked, the state of the connection is unknown then and it has to be
+/// closed.
+fn execute_catching_panics(
+    ctx: &ConnectionContext,
+    command: UnparsedCommandType,
+) -> Option<Result<RedisMessageType, RedisMessageType>> {
+    return match panic::catch_unwind(AssertUnwindSafe(|| execute_command(ctx, command))) {
+        Ok(result) => Some(result),
+        Err(_) => {
+            error!(
+                "A command of client {} panicked, closing the connection",
+                ctx.peer
+            );
+            None
+        }
+    };
 }
 
-fn repl_handshake(mut stream: TcpStream) {
-    debug!("Handshake 1/3 Sending ping to master");
-    {
-        let ping = RedisMessageType::bulk_string_array(vec!["PING"]);
-        stream
-            .write_all(ping.encode().as_bytes())
-            .expect("Failed to write to stream. Should never happen!");
-
-        let val = read_simple_string_response(&mut stream);
-        if val != "PONG" {
-            panic!("Expected a \"PONG\" response from the master server")
-        }
-    }
-    debug!("Handshake 1/3 Successfully completed. PONG response recieved.");
-
-    debug!("Handshake 2/3 Sending replconf to master");
-    {
-        trace!("Sending replconf 1/2 listenport to master");
-        {
-            let listen_port = get_db().get_config().current_listening_port;
-            let replconf = RedisMessageType::bulk_string_array(vec![
-                "REPLCONF",
-                "listening-port",
-                format!("{}", listen_port).as_str(),
-            ]);
-
-            stream
-                .write_all(replconf.encode().as_bytes())
-                .expect("Failed to write to stream. Should never happen!");
-
-            let val = read_simple_string_response(&mut stream);
-            if val != "OK" {
-                panic!("Expected a \"OK\" response from the master server")
-            }
-        }
-        trace!("Sending replconf 2/2 capa to master");
-        {
-            let listen_port = get_db().get_config().current_listening_port;
-            let replconf = RedisMessageType::bulk_string_array(vec!["REPLCONF", "capa", "psync2"]);
-
-            stream
-                .write_all(replconf.encode().as_bytes())
-                .expect("Failed to write to stream. Should never happen!");
-
-            let val = read_simple_string_response(&mut stream);
-            if val != "OK" {
-                panic!("Expected a \"OK\" response from the master server")
-            }
+fn execute_command(
+    ctx: &ConnectionContext,
+    command: UnparsedCommandType,
+) -> Result<RedisMessageType, RedisMessageType> {
+    ctx.client().record_command(command.full_name());
+    middleware::before_execute(ctx, &command)?;
+
+    let is_write = command.is_write();
+    // held until the write is propagated, DEBUG PAUSE-WRITES waits for it
+    let _permit = match is_write {
+        true => Some(write_pause::acquire_client_write().ok_or_else(|| {
+            reply::error(
+                ErrorCode::Err,
+                "writes are paused by DEBUG PAUSE-WRITES, try again later",
+            )
+        })?),
+        false => None,
+    };
+    // reads skip copying the command, only writes are fed to the replicas
+    let message = is_write.then(|| command.to_message());
+    let limit = get_db().get_config().command_time_limit;
+    let limit = (!limit.is_zero()).then_some(limit);
+
+    // the keys stay locked until the write is propagated, so concurrent writes to the same
+    // keys reach the replicas in the order they were applied
+    let keys = command.write_keys();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    return get_db().with_locked_keys(&keys, |_| {
+        let response = deadline::with_budget(limit, || command.parse()?.execute(ctx))?;
+
+        if is_write {
+            persistence::record_write();
         }
-    }
-    debug!("Handshake 2/3 Successfully completed. 2/2 REPLCONF responses recieved.");
-
-    debug!("Handshake 3/3 Sending PSYNC to master");
-    {
-        let command = RedisMessageType::bulk_string_array(vec!["PSYNC", "?", "-1"].into());
-        stream
-        .write_all(command.encode().as_bytes())
-        .expect("Failed to write to stream. Should never happen!");
-
-        let val = read_simple_string_response(&mut stream);
-        if !val.starts_with("FULLRESYNC") {
-            panic!("Expected a \"FULLRESYNC ...\" response from the master server")
+        if let Some(message) = message {
+            let offset = master::propagate(&message);
+            ctx.client().record_write_offset(offset);
         }
-    }
-    debug!("Handshake 3/3 Successfully completed. PSYNC response recieved.")
+
+        return Ok(response);
+    });
 }
@@ -4,9 +4,16 @@ use anyhow::Error;
 
 use anyhow::{anyhow, Result};
 use dashmap::DashMap;
-use log::{trace};
+use log::trace;
 
-use crate::db::data_store::{DataUnit, Expiry};
+use crate::{
+    db::data_store::{DataUnit, Expiry},
+    utils::crc64::crc64,
+};
+
+const MAGIC_STRING: &str = "REDIS";
+const RDB_VERSION: &str = "0011";
+const REDIS_VERSION: &str = "7.2.0";
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RdbFile {
@@ -47,8 +54,6 @@ pub struct DatabaseSubSection {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct EndOfFile {}
 
-
-
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct KeyValueDataUnit {
     key: String,
@@ -57,10 +62,13 @@ pub struct KeyValueDataUnit {
 }
 
 impl RdbFile {
-    pub fn decode(input: Vec<u8>) -> Result<RdbFile> {
-        let s = input.as_slice();
+    pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<RdbFile> {
+        let s = input.as_ref();
         // println!("full file: {:?}", &s);
 
+        if s.len() < 9 {
+            return Err(anyhow!("Rdb file is too short to contain a header!"));
+        }
         let (raw_header, s) = s.split_at(9);
 
         let header = Header::decode(raw_header)?;
@@ -69,7 +77,7 @@ impl RdbFile {
 
         let (_raw_metadata, s) = s.split_at(metdata_size);
 
-        let db = Database::decode(s).unwrap().0;
+        let db = Database::decode(s)?.0;
         let eof = EndOfFile {};
 
         return Ok(RdbFile {
@@ -83,9 +91,91 @@ impl RdbFile {
     pub fn get_database(&self) -> &Database {
         return &self.db;
     }
+
+    /// The aux fields of the metadata section, e.g. `redis-ver`.
+    pub fn aux_fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        return self
+            .metadata
+            .subsections
+            .iter()
+            .map(|subsection| (subsection.key.as_str(), subsection.value.as_str()));
+    }
+
+    /// Encodes the values into a complete rdb file holding a single database.
+    pub fn encode<I: IntoIterator<Item = DataUnit>>(values: I) -> Vec<u8> {
+        return Self::encode_with_aux(values, &[]);
+    }
+
+    /// Like [`RdbFile::encode`], with additional aux fields written after `redis-ver`.
+    pub fn encode_with_aux<I: IntoIterator<Item = DataUnit>>(
+        values: I,
+        aux_fields: &[(&str, String)],
+    ) -> Vec<u8> {
+        let mut entries = Vec::new();
+        let mut size = 0;
+        let mut expires_size = 0;
+
+        for value in values {
+            let key_value_data_unit = KeyValueDataUnit::from_data_unit(&value);
+            if key_value_data_unit.expiry.is_some() {
+                expires_size += 1;
+            }
+            size += 1;
+            key_value_data_unit.encode_into(&mut entries);
+        }
+
+        let mut output = Vec::with_capacity(entries.len() + 64);
+        Header::current().encode_into(&mut output);
+        MetadataSubSection {
+            key: "redis-ver".to_string(),
+            value: REDIS_VERSION.to_string(),
+        }
+        .encode_into(&mut output);
+        for (key, value) in aux_fields {
+            MetadataSubSection {
+                key: key.to_string(),
+                value: value.clone(),
+            }
+            .encode_into(&mut output);
+        }
+
+        // like redis, empty databases are not written at all
+        if size > 0 {
+            DatabaseSubSectionHeader {
+                index: 0,
+                hash_table_size: size,
+                expiry_hash_table_size: expires_size,
+            }
+            .encode_into(&mut output);
+            output.extend_from_slice(&entries);
+        }
+
+        output.push(0xFF);
+        let checksum = crc64(&output);
+        output.extend_from_slice(&checksum.to_le_bytes());
+
+        trace!(
+            "encoded {} keys into a rdb file of {} bytes",
+            size,
+            output.len()
+        );
+        return output;
+    }
 }
 
 impl Header {
+    fn current() -> Header {
+        return Header {
+            magic_string: MAGIC_STRING.to_string(),
+            version: RDB_VERSION.to_string(),
+        };
+    }
+
+    fn encode_into(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self.magic_string.as_bytes());
+        output.extend_from_slice(self.version.as_bytes());
+    }
+
     pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Header> {
         let s = input.as_ref();
 
@@ -138,10 +228,16 @@ impl MetadataSection {
 }
 
 impl MetadataSubSection {
+    fn encode_into(&self, output: &mut Vec<u8>) {
+        output.push(0xFA);
+        encode_string(self.key.as_bytes(), output);
+        encode_string(self.value.as_bytes(), output);
+    }
+
     pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<(MetadataSubSection, usize)> {
         let s = input.as_ref();
 
-        if s[0] != 0xFA {
+        if s.first() != Some(&0xFA) {
             return Err(anyhow!("MetadataSubSection section must begin with 0xFA"));
         }
 
@@ -169,9 +265,10 @@ impl MetadataSubSection {
             index += 2;
             "no parse".to_string()
         } else {
-            let (value_length, parsed_bytes) = parse_length_encoding(&s[index..]).ok_or(
-                anyhow!("Unable to parse string length in metadata section!"),
-            )?;
+            let (value_length, parsed_bytes) =
+                parse_length_encoding(s.get(index..).unwrap_or_default()).ok_or(anyhow!(
+                    "Unable to parse string length in metadata section!"
+                ))?;
 
             index += parsed_bytes;
             let value_bytes = s
@@ -246,7 +343,9 @@ impl DatabaseSubSection {
         let (header, mut bytes_parsed) = DatabaseSubSectionHeader::decode(&input)?;
 
         let raw = input.as_ref();
-        let mut key_value_data_units = Vec::with_capacity(header.hash_table_size);
+        // the size is read from the file, every key value pair takes at least 3 bytes
+        let mut key_value_data_units =
+            Vec::with_capacity(header.hash_table_size.min(raw.len() / 3));
 
         for _ in 0..header.hash_table_size {
             let (data_unit, data_unit_bytes_parsed) = KeyValueDataUnit::decode(
@@ -276,6 +375,14 @@ pub struct DatabaseSubSectionHeader {
 }
 
 impl DatabaseSubSectionHeader {
+    fn encode_into(&self, output: &mut Vec<u8>) {
+        output.push(0xFE);
+        encode_length(self.index, output);
+        output.push(0xFB);
+        encode_length(self.hash_table_size, output);
+        encode_length(self.expiry_hash_table_size, output);
+    }
+
     pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<(DatabaseSubSectionHeader, usize), Error> {
         let bytes = input.as_ref();
 
@@ -305,7 +412,7 @@ impl DatabaseSubSectionHeader {
         }
 
         let (hash_table_size, parsed_bytes_hash_table_size) =
-            parse_length_encoding(&bytes[index_parsed_bytes + 2..])
+            parse_length_encoding(bytes.get(index_parsed_bytes + 2..).unwrap_or_default())
                 .ok_or(anyhow!("Expected value for hash table size!"))?;
 
         trace!(
@@ -314,9 +421,12 @@ impl DatabaseSubSectionHeader {
             &parsed_bytes_hash_table_size
         );
 
-        let (expiry_hash_table_size, parsed_bytes_expiry_hash_table_size) =
-            parse_length_encoding(&bytes[index_parsed_bytes + 2 + parsed_bytes_hash_table_size..])
-                .ok_or(anyhow!("Expected value for expiry hash table size!"))?;
+        let (expiry_hash_table_size, parsed_bytes_expiry_hash_table_size) = parse_length_encoding(
+            bytes
+                .get(index_parsed_bytes + 2 + parsed_bytes_hash_table_size..)
+                .unwrap_or_default(),
+        )
+        .ok_or(anyhow!("Expected value for expiry hash table size!"))?;
 
         trace!("header parsing - expiry_hash_table_size: {:?}, parsed_bytes_expiry_hash_table_size: {:?}", &expiry_hash_table_size, &parsed_bytes_expiry_hash_table_size);
 
@@ -347,13 +457,19 @@ impl KeyValueDataUnit {
                     // parses millis
                     data.get(1..9).ok_or(anyhow!("err"))?.try_into()?,
                 );
-                Some((UNIX_EPOCH + Duration::from_millis(ms), 9))
+                let expiry = UNIX_EPOCH
+                    .checked_add(Duration::from_millis(ms))
+                    .ok_or(anyhow!("expiry timestamp {}ms is out of range", ms))?;
+                Some((expiry, 9))
             }
             0xFD => {
                 // parses seconds
                 let seconds =
                     u32::from_le_bytes(data.get(1..5).ok_or(anyhow!("err"))?.try_into()?) as u64;
-                Some((UNIX_EPOCH + Duration::from_secs(seconds), 5))
+                let expiry = UNIX_EPOCH
+                    .checked_add(Duration::from_secs(seconds))
+                    .ok_or(anyhow!("expiry timestamp {}s is out of range", seconds))?;
+                Some((expiry, 5))
             }
             _ => None,
         };
@@ -363,39 +479,24 @@ impl KeyValueDataUnit {
             None => index,
         };
 
-        let key_value_data_unit = match data.get(index).unwrap() {
-            0x00 => {
-                index += 1;
-                let (key_data_len, bytes_parsed) =
-                    parse_length_encoding(data.get(index..).unwrap()).unwrap();
-                index += bytes_parsed;
-                let key_string_data_as_bytes =
-                    data.get(index..index + key_data_len).ok_or(anyhow!(
-                        "Data gave len {} for key but not enough bytes where present in the data!",
-                        { key_data_len }
-                    ))?;
-                let key = str::from_utf8(key_string_data_as_bytes)?;
-                index += key_data_len;
-
-                let (value_data_len, bytes_parsed) =
-                    parse_length_encoding(data.get(index..).unwrap()).unwrap();
-
-                index += bytes_parsed;
-                let value_string_data_as_bytes =
-                    data.get(index..index + value_data_len).ok_or(anyhow!(
-                    "Data gave len {} for value but not enough bytes where present in the data!",
-                    { value_data_len }
-                ))?;
-                let value = str::from_utf8(value_string_data_as_bytes)?;
-                index += value_data_len;
+        let value_type = *data
+            .get(index)
+            .ok_or(anyhow!("missing value type of the key value pair"))?;
+        index += 1;
 
-                KeyValueDataUnit {
-                    key: key.into(),
-                    value: value.into(),
-                    expiry: expire_timestamp.map(|(v, _size)| v),
-                }
-            }
-            _ => unimplemented!("Only Value type 'string' is implemented!"),
+        let (key, bytes_parsed) = decode_string(data.get(index..).unwrap_or_default())?;
+        index += bytes_parsed;
+
+        let (value, bytes_parsed) =
+            RdbValue::decode(value_type, data.get(index..).unwrap_or_default())?;
+        index += bytes_parsed;
+
+        let key_value_data_unit = match value {
+            RdbValue::String(value) => KeyValueDataUnit {
+                key,
+                value,
+                expiry: expire_timestamp.map(|(v, _size)| v),
+            },
         };
 
         trace!(
@@ -406,6 +507,30 @@ impl KeyValueDataUnit {
         return Ok((key_value_data_unit, index));
     }
 
+    fn from_data_unit(data_unit: &DataUnit) -> KeyValueDataUnit {
+        return KeyValueDataUnit {
+            key: data_unit.key.clone(),
+            value: data_unit.value.to_string(),
+            expiry: data_unit.get_expiry_timestamp(),
+        };
+    }
+
+    fn encode_into(&self, output: &mut Vec<u8>) {
+        if let Some(expiry) = self.expiry {
+            let millis = expiry
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or(Duration::ZERO)
+                .as_millis() as u64;
+            output.push(0xFC);
+            output.extend_from_slice(&millis.to_le_bytes());
+        }
+
+        let value = RdbValue::String(self.value.clone());
+        output.push(value.value_type());
+        encode_string(self.key.as_bytes(), output);
+        output.extend(value.encode());
+    }
+
     fn to_data_unit(&self) -> DataUnit {
         return DataUnit::new(
             self.key.clone(),
@@ -453,7 +578,8 @@ fn parse_length_encoding(buf: &[u8]) -> Option<(usize, usize)> {
                 0b00 => Some((LengthEncoding::StringEncoding(1), 1)),
                 0b01 => Some((LengthEncoding::StringEncoding(2), 1)),
                 0b10 => Some((LengthEncoding::StringEncoding(4), 1)),
-                0b11 => unimplemented!("LZF compressed string - not implemented"),
+                // LZF compressed strings are not supported
+                0b11 => None,
                 _ => unreachable!(),
             },
             _ => unreachable!(),
@@ -476,6 +602,122 @@ fn parse_length_encoding(buf: &[u8]) -> Option<(usize, usize)> {
     };
 }
 
+/// Writes the length encoding described here: https://rdb.fnordig.de/file_format.html#length-encoding
+fn encode_length(length: usize, output: &mut Vec<u8>) {
+    if length < 1 << 6 {
+        output.push(length as u8);
+    } else if length < 1 << 14 {
+        output.push(0x40 | (length >> 8) as u8);
+        output.push(length as u8);
+    } else {
+        output.push(0x80);
+        output.extend_from_slice(&(length as u32).to_be_bytes());
+    }
+}
+
+/// A value of the key value section, see https://rdb.fnordig.de/file_format.html#value-type
+///
+/// Only strings are supported as of now, new types are added here with their value type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RdbValue {
+    String(String),
+}
+
+impl RdbValue {
+    pub const fn value_type(&self) -> u8 {
+        return match self {
+            Self::String(_) => 0x00,
+        };
+    }
+
+    /// Encodes the value without the value type and the key.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        match self {
+            Self::String(value) => {
+                if !encode_int_string(value, &mut output) {
+                    encode_string(value.as_bytes(), &mut output);
+                }
+            }
+        }
+        return output;
+    }
+
+    /// Decodes a value of the given value type, returns the value and the bytes parsed.
+    pub fn decode<T: AsRef<[u8]>>(value_type: u8, input: T) -> Result<(RdbValue, usize)> {
+        return match value_type {
+            0x00 => decode_string(input.as_ref()).map(|(value, size)| (Self::String(value), size)),
+            other => Err(anyhow!("Value type {:#04x} is not supported", other)),
+        };
+    }
+}
+
+/// Decodes a length prefixed or integer encoded string.
+fn decode_string(input: &[u8]) -> Result<(String, usize)> {
+    let b0 = *input
+        .first()
+        .ok_or(anyhow!("missing bytes to parse a string"))?;
+
+    if b0 >> 6 == 0b11 {
+        let missing = || anyhow!("missing bytes to parse an integer encoded string");
+        let (value, bytes_parsed) = match b0 & 0x3F {
+            0 => (*input.get(1).ok_or_else(missing)? as i8 as i64, 2),
+            1 => (
+                i16::from_le_bytes(input.get(1..3).ok_or_else(missing)?.try_into()?) as i64,
+                3,
+            ),
+            2 => (
+                i32::from_le_bytes(input.get(1..5).ok_or_else(missing)?.try_into()?) as i64,
+                5,
+            ),
+            3 => return Err(anyhow!("LZF compressed strings are not supported")),
+            other => return Err(anyhow!("Unknown string encoding {}", other)),
+        };
+        return Ok((value.to_string(), bytes_parsed));
+    }
+
+    let (length, bytes_parsed) =
+        parse_length_encoding(input).ok_or(anyhow!("Unable to parse the string length"))?;
+    let bytes = input
+        .get(bytes_parsed..bytes_parsed + length)
+        .ok_or(anyhow!(
+            "Data gave len {} for a string but not enough bytes where present in the data!",
+            length
+        ))?;
+
+    return Ok((str::from_utf8(bytes)?.to_string(), bytes_parsed + length));
+}
+
+/// Writes strings holding a 32 bit integer in the integer encoding, like redis does.
+/// Returns false if the string can not be encoded as an integer.
+fn encode_int_string(value: &str, output: &mut Vec<u8>) -> bool {
+    // only canonical integers, "01" or "+1" would not survive the round trip
+    let int = match value.parse::<i64>() {
+        Ok(int) if int.to_string() == value => int,
+        _ => return false,
+    };
+
+    if let Ok(int) = i8::try_from(int) {
+        output.push(0xC0);
+        output.extend_from_slice(&int.to_le_bytes());
+    } else if let Ok(int) = i16::try_from(int) {
+        output.push(0xC1);
+        output.extend_from_slice(&int.to_le_bytes());
+    } else if let Ok(int) = i32::try_from(int) {
+        output.push(0xC2);
+        output.extend_from_slice(&int.to_le_bytes());
+    } else {
+        return false;
+    }
+    return true;
+}
+
+/// Writes a length prefixed string.
+fn encode_string(bytes: &[u8], output: &mut Vec<u8>) {
+    encode_length(bytes.len(), output);
+    output.extend_from_slice(bytes);
+}
+
 #[cfg(test)]
 mod test {
 
@@ -488,14 +730,14 @@ mod test {
         fn test_load_full_rdb_file() {
             #[rustfmt::skip]
             let input = vec![
-                82, 69, 68, 73, 83, 48, 48, 49, 49, 
-                250, 
-                9, 114, 101, 100, 105, 115, 45, 118, 101, 114, 
-                5, 55, 46, 50, 46, 48, 
+                82, 69, 68, 73, 83, 48, 48, 49, 49,
+                250,
+                9, 114, 101, 100, 105, 115, 45, 118, 101, 114,
+                5, 55, 46, 50, 46, 48,
                 // i have to parse based on key basis! not all values are strings..
-                250, 
-                10, 114, 101, 100, 105, 115, 45, 98, 105, 116, 115, 
-                192, 64, 
+                250,
+                10, 114, 101, 100, 105, 115, 45, 98, 105, 116, 115,
+                192, 64,
                 0xFE, 0x00, 0xFB, 0x02, 0x01, 0x00, 0x06, 0x66, 0x6F, 0x6F, 0x62, 0x61, 0x72, 0x06,
                 0x62, 0x61, 0x7A, 0x71, 0x75, 0x78, 0xFD, 0x52, 0xED, 0x2A, 0x66, 0x00, 0x03, 0x62,
                 0x61, 0x7A, 0x03, 0x71, 0x75, 0x78,
@@ -513,6 +755,193 @@ mod test {
                 result.db.subsections[0].key_value_data_units[0].key
             )
         }
+
+        #[test]
+        fn test_decode_malformed_rdb_file() {
+            assert!(RdbFile::decode(vec![]).is_err());
+            assert!(RdbFile::decode(b"REDIS".to_vec()).is_err());
+            assert!(RdbFile::decode(b"REDIS0011".to_vec()).is_err());
+
+            let mut input = b"REDIS0011".to_vec();
+            // huge hash table size without any data
+            input.extend_from_slice(&[0xFE, 0x00, 0xFB, 0x80, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+            assert!(RdbFile::decode(input).is_err());
+
+            let mut input = b"REDIS0011".to_vec();
+            // truncated database header
+            input.extend_from_slice(&[0xFE, 0x00, 0xFB]);
+            assert!(RdbFile::decode(input).is_err());
+
+            let mut input = b"REDIS0011".to_vec();
+            // LZF compressed metadata key
+            input.extend_from_slice(&[0xFA, 0xC3, 0x01, 0x01, b'a', 0xFF]);
+            assert!(RdbFile::decode(input).is_err());
+        }
+    }
+
+    #[cfg(test)]
+    mod test_encode_rdb_file {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        use crate::{
+            db::data_store::{DataUnit, Expiry},
+            parser::db_file::RdbFile,
+            utils::crc64::crc64,
+        };
+
+        #[test]
+        fn test_encode_empty_rdb_file() {
+            let encoded = RdbFile::encode(vec![]);
+
+            let result = RdbFile::decode(encoded.clone()).unwrap();
+
+            assert_eq!("REDIS", result.header.magic_string);
+            assert_eq!("0011", result.header.version);
+            assert_eq!(1, result.metadata.subsections.len());
+            assert!(result.db.subsections.is_empty());
+            assert_eq!(0xFF, encoded[encoded.len() - 9]);
+        }
+
+        #[test]
+        fn test_encode_aux_fields() {
+            let aux_fields = [("ctime", "1700000000".to_string())];
+            let encoded = RdbFile::encode_with_aux(vec![], &aux_fields);
+
+            let result = RdbFile::decode(encoded).unwrap();
+
+            assert_eq!(
+                vec![("redis-ver", "7.2.0"), ("ctime", "1700000000")],
+                result.aux_fields().collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn test_encode_checksum() {
+            let encoded = RdbFile::encode(vec![DataUnit::new("key", "value", None)]);
+
+            let (content, checksum) = encoded.split_at(encoded.len() - 8);
+
+            assert_eq!(crc64(content).to_le_bytes(), checksum);
+        }
+
+        #[test]
+        fn test_encode_decode_round_trip() {
+            let deadline = SystemTime::now() + Duration::from_secs(100);
+            let long_value = "x".repeat(20000);
+            let values = vec![
+                DataUnit::new("foo", "bar", None),
+                DataUnit::new("expiring", "value", Some(Expiry::Deadline(deadline))),
+                DataUnit::new("long", long_value.as_str(), None),
+            ];
+
+            let result = RdbFile::decode(RdbFile::encode(values)).unwrap();
+
+            let subsection = &result.db.subsections[0];
+            assert_eq!(3, subsection.header.hash_table_size);
+            assert_eq!(1, subsection.header.expiry_hash_table_size);
+
+            let units = &subsection.key_value_data_units;
+            assert_eq!("foo", units[0].key);
+            assert_eq!("bar", units[0].value);
+            assert!(units[0].expiry.is_none());
+            assert_eq!("expiring", units[1].key);
+            assert_eq!("value", units[1].value);
+            let millis = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_millis();
+            let drift = millis(units[1].expiry.unwrap()).abs_diff(millis(deadline));
+            assert!(drift <= 1, "expiry drifted by {}ms", drift);
+            assert_eq!(long_value, units[2].value);
+        }
+    }
+
+    #[cfg(test)]
+    mod test_rdb_value {
+        use rand::{distr::Alphanumeric, Rng};
+
+        use crate::parser::db_file::RdbValue;
+
+        fn string(value: &str) -> RdbValue {
+            return RdbValue::String(value.to_string());
+        }
+
+        fn assert_round_trip(value: &RdbValue) {
+            let encoded = value.encode();
+
+            let (decoded, bytes_parsed) = RdbValue::decode(value.value_type(), &encoded).unwrap();
+
+            assert_eq!(value, &decoded);
+            assert_eq!(encoded.len(), bytes_parsed);
+        }
+
+        fn random_string(rng: &mut impl Rng) -> String {
+            return match rng.random_range(0..6) {
+                0 => rng.random::<i8>().to_string(),
+                1 => rng.random::<i16>().to_string(),
+                2 => rng.random::<i32>().to_string(),
+                3 => rng.random::<i64>().to_string(),
+                // not canonical integers, must stay strings
+                4 => format!(
+                    "{}{}",
+                    ["0", "+", "-0", " "][rng.random_range(0..4)],
+                    rng.random::<u16>()
+                ),
+                _ => {
+                    let length = [0, 10, 63, 64, 16383, 16384, 20000][rng.random_range(0..7)];
+                    (&mut *rng)
+                        .sample_iter(Alphanumeric)
+                        .take(length)
+                        .map(char::from)
+                        .collect()
+                }
+            };
+        }
+
+        /// Generates a random value of every supported type, new types must be added here.
+        fn random_value(rng: &mut impl Rng) -> RdbValue {
+            return RdbValue::String(random_string(rng));
+        }
+
+        #[test]
+        fn test_encode_string_vectors() {
+            assert_eq!(vec![0x03, b'b', b'a', b'r'], string("bar").encode());
+            assert_eq!(vec![0x00], string("").encode());
+            assert_eq!(vec![0xC0, 0x01], string("1").encode());
+            assert_eq!(vec![0xC0, 0xFF], string("-1").encode());
+            assert_eq!(vec![0xC1, 0x2C, 0x01], string("300").encode());
+            assert_eq!(vec![0xC2, 0x70, 0x11, 0x01, 0x00], string("70000").encode());
+            assert_eq!(vec![0x02, b'0', b'1'], string("01").encode());
+            assert_eq!(11, string("2147483648").encode().len());
+        }
+
+        #[test]
+        fn test_decode_unsupported_value() {
+            assert!(RdbValue::decode(0x01, [0x00]).is_err());
+            assert!(RdbValue::decode(0x00, [0xC3, 0x00]).is_err());
+            assert!(RdbValue::decode(0x00, [0x05, b'a']).is_err());
+            assert!(RdbValue::decode(0x00, []).is_err());
+        }
+
+        #[test]
+        fn test_random_values_round_trip() {
+            let mut rng = rand::rng();
+            for _ in 0..2000 {
+                assert_round_trip(&random_value(&mut rng));
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test_encode_length {
+        use crate::parser::db_file::{encode_length, parse_length_encoding};
+
+        #[test]
+        fn test_encode_length_round_trip() {
+            for length in [0, 63, 64, 700, 16383, 16384, 17000, 0x72E7078F] {
+                let mut output = Vec::new();
+                encode_length(length, &mut output);
+
+                assert_eq!(Some((length, output.len())), parse_length_encoding(&output));
+            }
+        }
     }
 
     #[cfg(test)]
@@ -573,9 +1002,9 @@ mod test {
         }
 
         #[test]
-        #[should_panic]
         fn test_parse_string_length_encoding_0xc3() {
             let result = parse_length_encoding(vec![0xC3].as_slice());
+            assert_eq!(None, result);
         }
     }
 
@@ -605,8 +1034,8 @@ mod test {
         fn test_metadata_decode() {
             #[rustfmt::skip]
             let data = vec![
-                0xFA, 
-                0x09, 0x72, 0x65, 0x64, 0x69, 0x73, 0x2D, 0x76, 0x65, 0x72, 
+                0xFA,
+                0x09, 0x72, 0x65, 0x64, 0x69, 0x73, 0x2D, 0x76, 0x65, 0x72,
                 0x06, 0x36, 0x2E, 0x30, 0x2E, 0x31, 0x36, 0xFE, 0xDE, 0xAD, 0xBE, 0xEF, 0x00,
             ];
 
@@ -1,12 +1,80 @@
 use std::{collections::VecDeque, fmt::Display};
 
 use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
 
 use crate::consts::CRLF;
 
 pub type RedisDecodeResult = Result<(RedisMessageType, usize)>;
 
-#[derive(Debug, PartialEq, Eq)]
+/// Arrays nested deeper than this are rejected instead of overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 128;
+
+static PROTOCOL_LIMITS: OnceCell<ProtocolLimits> = OnceCell::new();
+
+/// Decode errors a connection has to tell apart, every other error is a malformed message.
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    /// The input ends inside the message, more data may complete it.
+    #[error("{0}")]
+    Incomplete(&'static str),
+    /// The message exceeds one of the [`ProtocolLimits`].
+    #[error("{0}")]
+    LimitExceeded(String),
+}
+
+/// How a connection continues after its input could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Keep the input and wait for more data.
+    NeedMoreData,
+    /// Drop the given amount of bytes, the next message starts after them.
+    Skip(usize),
+    /// The rest of the input can not be trusted, e.g. a client announcing a huge bulk string
+    /// sends its payload next. The connection has to be closed.
+    Close,
+}
+
+/// Upper bounds the decoder enforces on a single message, so a client can not make the
+/// server allocate arbitrary amounts of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    /// Longest bulk string in bytes (proto-max-bulk-len).
+    pub max_bulk_len: usize,
+    /// Most elements of a single array (proto-max-multibulk-len).
+    pub max_multibulk_len: usize,
+    /// Longest line of simple strings, errors, integers and length headers
+    /// (proto-max-inline-len).
+    pub max_inline_len: usize,
+    /// Most bytes of input a connection may buffer before its commands are handled
+    /// (client-query-buffer-limit).
+    pub max_query_buffer_len: usize,
+}
+
+impl Default for ProtocolLimits {
+    /// The limits of redis.
+    fn default() -> Self {
+        return Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: i32::MAX as usize,
+            max_inline_len: 64 * 1024,
+            max_query_buffer_len: 1024 * 1024 * 1024,
+        };
+    }
+}
+
+/// Sets the limits of the decoder. Must be called before the first message is decoded.
+pub fn init_protocol_limits(limits: ProtocolLimits) {
+    PROTOCOL_LIMITS
+        .set(limits)
+        .expect("Protocol limits are already set. Should never happen!");
+}
+
+pub fn protocol_limits() -> &'static ProtocolLimits {
+    return PROTOCOL_LIMITS.get_or_init(ProtocolLimits::default);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RedisMessageType {
     SimpleString(String),
     Error(String),
@@ -23,34 +91,60 @@ impl Display for RedisMessageType {
 }
 
 impl RedisMessageType {
-    pub fn encode(&self) -> String {
+    /// Encodes the message as RESP. Bulk strings are copied byte by byte, so every message
+    /// survives a roundtrip through [`RedisMessageType::decode`].
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        return out;
+    }
+
+    /// Appends the encoded message to `out`, nested arrays are written without buffers of
+    /// their own.
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
         match self {
-            Self::SimpleString(data) => format!("+{}{CRLF}", data),
-            Self::Error(data) => format!("-{}{CRLF}", data),
-            Self::BulkString(data) => format!("${}{CRLF}{}{CRLF}", data.len(), data),
-            Self::NullBulkString => format!("$-1{CRLF}"),
-            Self::Integer(data) => format!(":{}{CRLF}", data),
-            Self::Array(data) => format!("*{}{CRLF}{}", data.len(), encode_array_elements(data)),
+            Self::SimpleString(data) => write_line(out, b'+', data),
+            Self::Error(data) => write_line(out, b'-', data),
+            Self::BulkString(data) => {
+                write_line(out, b'$', data.len());
+                out.extend_from_slice(data.as_bytes());
+                out.extend_from_slice(CRLF.as_bytes());
+            }
+            Self::NullBulkString => write_line(out, b'$', -1),
+            Self::Integer(data) => write_line(out, b':', data),
+            Self::Array(data) => {
+                write_line(out, b'*', data.len());
+                for message in data {
+                    message.encode_into(out);
+                }
+            }
         }
     }
 
+    /// Decodes the first message of the input and returns it with the amount of bytes it
+    /// used. Malformed or incomplete input results in an error, never in a panic.
     pub fn decode<T: AsRef<str>>(input: T) -> RedisDecodeResult {
-        // let s = std::str::from_utf8(&input)?;
-        let s = input.as_ref();
+        return Self::decode_with_limits(input, protocol_limits());
+    }
 
-        let first_char = match s.chars().nth(0) {
-            Some(val) => val,
-            None => return Err(anyhow!("Redis message does not contain any chars!")),
-        };
+    /// Like [`RedisMessageType::decode`], a message exceeding one of the limits is an error
+    /// naming the limit.
+    pub fn decode_with_limits<T: AsRef<str>>(
+        input: T,
+        limits: &ProtocolLimits,
+    ) -> RedisDecodeResult {
+        return decode_nested(input.as_ref(), 0, limits);
+    }
 
-        match first_char {
-            '+' => parse_simple_string(s),
-            '-' => parse_error_string(s),
-            '$' => parse_bulk_string(s),
-            ':' => parse_integer(s),
-            '*' => parse_array(s),
-            _ => return Err(anyhow!("Unhandled first_char in redis data {}", first_char)),
-        }
+    /// Decides how to continue after `input` failed to decode with `err`. A malformed
+    /// message is skipped up to the start of the next command, so a single bad message does
+    /// not corrupt the rest of the stream.
+    pub fn recover(input: &str, err: &anyhow::Error) -> Recovery {
+        return match err.downcast_ref::<DecodeError>() {
+            Some(DecodeError::Incomplete(_)) => Recovery::NeedMoreData,
+            Some(DecodeError::LimitExceeded(_)) => Recovery::Close,
+            None => Recovery::Skip(next_command_start(input)),
+        };
     }
 
     pub fn as_string(&self) -> Option<String> {
@@ -77,7 +171,10 @@ impl RedisMessageType {
     }
 
     pub fn bulk_string_array<S: Into<String>>(values: Vec<S>) -> Self {
-        let value = values.into_iter().map(|v| RedisMessageType::bulk_string(v)).collect();
+        let value = values
+            .into_iter()
+            .map(|v| RedisMessageType::bulk_string(v))
+            .collect();
         return RedisMessageType::Array(value);
     }
 
@@ -105,35 +202,83 @@ impl RedisMessageType {
     }
 }
 
-fn encode_array_elements(data: &VecDeque<RedisMessageType>) -> String {
-    return data
-        .iter()
-        .map(|message| message.encode())
-        .collect::<Vec<String>>()
-        .concat();
+fn decode_nested(s: &str, depth: usize, limits: &ProtocolLimits) -> RedisDecodeResult {
+    // let s = std::str::from_utf8(&input)?;
+    let first_char = match s.chars().nth(0) {
+        Some(val) => val,
+        None => {
+            return Err(DecodeError::Incomplete("Redis message does not contain any chars!").into())
+        }
+    };
+
+    match first_char {
+        '+' => parse_simple_string(s, limits),
+        '-' => parse_error_string(s, limits),
+        '$' => parse_bulk_string(s, limits),
+        ':' => parse_integer(s, limits),
+        '*' => parse_array(s, depth, limits),
+        _ => return Err(anyhow!("Unhandled first_char in redis data {}", first_char)),
+    }
+}
+
+/// Commands are always arrays, so the next command is expected at the next line starting
+/// with a '*'. Without one the whole input is skipped.
+fn next_command_start(input: &str) -> usize {
+    return input
+        .get(1..)
+        .and_then(|rest| rest.find("\r\n*"))
+        .map_or(input.len(), |position| 1 + position + CRLF.len());
+}
+
+/// Appends a line of the given type, e.g. `+OK\r\n` or the length header of a bulk string.
+fn write_line<T: Display>(out: &mut Vec<u8>, type_byte: u8, value: T) {
+    out.push(type_byte);
+    out.extend_from_slice(value.to_string().as_bytes());
+    out.extend_from_slice(CRLF.as_bytes());
+}
+
+/// The header of the rdb file send on a full resync. The file follows as raw bytes, unlike a
+/// bulk string without a trailing CRLF.
+pub fn rdb_payload_header(len: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_line(&mut out, b'$', len);
+    return out;
 }
 
-fn parse_simple_string(s: &str) -> RedisDecodeResult {
-    let (value, _) = s[1..]
-        .split_once(CRLF)
-        .expect("Simple string must end on a CRLF");
+/// Splits off the line after the type char. Lines longer than the inline limit are rejected
+/// even if their CRLF has not been received yet.
+fn split_line<'a>(s: &'a str, limits: &ProtocolLimits) -> Option<Result<(&'a str, &'a str)>> {
+    let line = s[1..].split_once(CRLF);
+    let length = line.map_or(s.len() - 1, |(line, _)| line.len());
+    if length > limits.max_inline_len {
+        return Some(Err(DecodeError::LimitExceeded(format!(
+            "too big inline request (exceeds proto-max-inline-len {})",
+            limits.max_inline_len
+        ))
+        .into()));
+    }
+    return line.map(Ok);
+}
+
+fn parse_simple_string(s: &str, limits: &ProtocolLimits) -> RedisDecodeResult {
+    let (value, _) = split_line(s, limits)
+        .ok_or(DecodeError::Incomplete("Simple string must end on a CRLF"))??;
 
     let string = value.to_string();
 
     return Ok((RedisMessageType::SimpleString(string), value.len() + 3));
 }
 
-fn parse_error_string(s: &str) -> RedisDecodeResult {
-    let (value, _) = s[1..]
-        .split_once(CRLF)
-        .expect("Error string must end on a CRLF");
+fn parse_error_string(s: &str, limits: &ProtocolLimits) -> RedisDecodeResult {
+    let (value, _) = split_line(s, limits)
+        .ok_or(DecodeError::Incomplete("Error string must end on a CRLF"))??;
 
     let string = value.to_string();
 
     return Ok((RedisMessageType::Error(string), value.len() + 3));
 }
 
-fn parse_bulk_string(s: &str) -> RedisDecodeResult {
+fn parse_bulk_string(s: &str, limits: &ProtocolLimits) -> RedisDecodeResult {
     // let s = std::str::from_utf8(&input)?;
 
     // let start_byte = s
@@ -142,13 +287,36 @@ fn parse_bulk_string(s: &str) -> RedisDecodeResult {
     //     .map(|(idx, _)| idx)
     //     .unwrap_or(s.len());
 
-    let (length_str, value) = s[1..]
-        .split_once(CRLF)
-        .expect("Malformed Bulk String. Expected length and data element split by CRLF.");
+    let (length_str, value) = split_line(s, limits).ok_or(DecodeError::Incomplete(
+        "Malformed Bulk String. Expected length and data element split by CRLF.",
+    ))??;
+
+    // replies use a length of -1 for missing values
+    if length_str == "-1" {
+        return Ok((RedisMessageType::NullBulkString, length_str.len() + 3));
+    }
 
     let length = usize::from_str_radix(length_str, 10)?;
+    if length > limits.max_bulk_len {
+        return Err(DecodeError::LimitExceeded(format!(
+            "invalid bulk length (exceeds proto-max-bulk-len {})",
+            limits.max_bulk_len
+        ))
+        .into());
+    }
+
+    if value.len() < length + CRLF.len() {
+        return Err(DecodeError::Incomplete("Incomplete Bulk String.").into());
+    }
 
-    let string = value[0..length].to_string();
+    let string = value
+        .get(0..length)
+        .ok_or(anyhow!("Bulk String of {} bytes splits a char", length))?
+        .to_string();
+
+    if value.get(length..length + 2) != Some(CRLF) {
+        return Err(anyhow!("Bulk String must end on a CRLF"));
+    }
 
     return Ok((
         RedisMessageType::BulkString(string),
@@ -156,32 +324,48 @@ fn parse_bulk_string(s: &str) -> RedisDecodeResult {
     ));
 }
 
-fn parse_integer(s: &str) -> RedisDecodeResult {
+fn parse_integer(s: &str, limits: &ProtocolLimits) -> RedisDecodeResult {
     // let s = std::str::from_utf8(&input)?;
 
-    let (value_str, _) = s[1..]
-        .split_once(CRLF)
-        .expect("Malformed Bulk String. Expected length and data element split by CRLF.");
+    let (value_str, _) =
+        split_line(s, limits).ok_or(DecodeError::Incomplete("Integer must end on a CRLF"))??;
 
     let value = i64::from_str_radix(value_str, 10)?;
 
     return Ok((RedisMessageType::Integer(value), value_str.len() + 3));
 }
 
-fn parse_array(s: &str) -> RedisDecodeResult {
-    let (length_str, mut value) = s
-        .split_once(CRLF)
-        .expect("Malformed Array. Expected length and data element split by CRLF.");
+fn parse_array(s: &str, depth: usize, limits: &ProtocolLimits) -> RedisDecodeResult {
+    if depth >= MAX_NESTING_DEPTH {
+        return Err(anyhow!(
+            "Array nesting exceeds {} levels",
+            MAX_NESTING_DEPTH
+        ));
+    }
+
+    let (length_str, mut value) = split_line(s, limits).ok_or(DecodeError::Incomplete(
+        "Malformed Array. Expected length and data element split by CRLF.",
+    ))??;
 
-    let length = usize::from_str_radix(&length_str[1..], 10)?;
+    let length = usize::from_str_radix(length_str, 10)?;
+    if length > limits.max_multibulk_len {
+        return Err(DecodeError::LimitExceeded(format!(
+            "invalid multibulk length (exceeds proto-max-multibulk-len {})",
+            limits.max_multibulk_len
+        ))
+        .into());
+    }
 
-    let mut array = VecDeque::with_capacity(length);
+    // every element takes at least 3 bytes, don't trust the announced length
+    let mut array = VecDeque::with_capacity(length.min(value.len() / 3));
     let mut all_value_length = 0;
 
     for _ in 0..length {
-        let message_type = RedisMessageType::decode(value)?;
+        let message_type = decode_nested(value, depth + 1, limits)?;
         all_value_length += message_type.1;
-        value = &value[message_type.1..];
+        value = value
+            .get(message_type.1..)
+            .ok_or(anyhow!("Array element is longer than the message"))?;
         array.push_back(message_type.0);
     }
 
@@ -234,7 +418,7 @@ mod test {
             let input = RedisMessageType::SimpleString("Test".into());
             let expected = "+Test\r\n";
 
-            assert_eq!(expected, input.encode())
+            assert_eq!(expected.as_bytes(), input.encode())
         }
     }
 
@@ -267,7 +451,7 @@ mod test {
             let input = RedisMessageType::Error("Test".into());
             let expected = "-Test\r\n";
 
-            assert_eq!(expected, input.encode())
+            assert_eq!(expected.as_bytes(), input.encode())
         }
     }
 
@@ -296,12 +480,41 @@ mod test {
             assert_eq!(expected, result.0)
         }
 
+        #[test]
+        fn decode_null_string() {
+            let input = "$-1\r\n+OK\r\n";
+
+            let result = RedisMessageType::decode(input).unwrap();
+
+            assert_eq!(RedisMessageType::NullBulkString, result.0);
+            assert_eq!(5, result.1);
+        }
+
         #[test]
         fn encode() {
             let input = RedisMessageType::BulkString("Test".into());
             let expected = "$4\r\nTest\r\n";
 
-            assert_eq!(expected, input.encode())
+            assert_eq!(expected.as_bytes(), input.encode())
+        }
+
+        #[test]
+        fn decode_binary_string() {
+            let expected = RedisMessageType::BulkString("a\r\nb\0c".into());
+            let input = "$6\r\na\r\nb\0c\r\n";
+
+            let result = RedisMessageType::decode(input).unwrap();
+
+            assert_eq!(expected, result.0);
+            assert_eq!(input.len(), result.1);
+        }
+
+        #[test]
+        fn encode_binary_string() {
+            let input = RedisMessageType::BulkString("\0\r\n\r\n".into());
+            let expected = "$5\r\n\0\r\n\r\n\r\n";
+
+            assert_eq!(expected.as_bytes(), input.encode())
         }
     }
 
@@ -344,7 +557,7 @@ mod test {
             let input = RedisMessageType::Integer(123);
             let expected = ":123\r\n";
 
-            assert_eq!(expected, input.encode());
+            assert_eq!(expected.as_bytes(), input.encode());
         }
 
         #[test]
@@ -352,7 +565,7 @@ mod test {
             let input = RedisMessageType::Integer(-3);
             let expected = ":-3\r\n";
 
-            assert_eq!(expected, input.encode());
+            assert_eq!(expected.as_bytes(), input.encode());
         }
     }
 
@@ -392,6 +605,154 @@ mod test {
 
             let result = RedisMessageType::decode(input).unwrap();
             assert_eq!(expected, result.0);
+            assert_eq!(input.len(), result.1);
+        }
+
+        #[test]
+        fn decode_multiple_messages() {
+            let input = "*1\r\n$4\r\nPING\r\n*2\r\n$4\r\nECHO\r\n$3\r\nhey\r\n";
+
+            let (first, first_length) = RedisMessageType::decode(input).unwrap();
+            let (second, second_length) = RedisMessageType::decode(&input[first_length..]).unwrap();
+
+            assert_eq!(RedisMessageType::bulk_string_array(vec!["PING"]), first);
+            assert_eq!(
+                RedisMessageType::bulk_string_array(vec!["ECHO", "hey"]),
+                second
+            );
+            assert_eq!(input.len(), first_length + second_length);
+        }
+
+        #[test]
+        fn decode_truncated_element() {
+            // the bulk string is missing its trailing CRLF
+            assert!(RedisMessageType::decode("*2\r\n$4\r\nECHO").is_err());
+            assert!(RedisMessageType::decode("*2\r\n$4\r\nECHO\r\n$3\r\nhe").is_err());
+            assert!(RedisMessageType::decode("$4\r\nECHOxx").is_err());
+        }
+
+        #[test]
+        fn decode_huge_length() {
+            assert!(RedisMessageType::decode("*99999999999999999\r\n").is_err());
+            assert!(RedisMessageType::decode("$99999999999999999\r\nabc\r\n").is_err());
+        }
+
+        #[test]
+        fn encode_roundtrip() {
+            let message = RedisMessageType::Array(
+                vec![
+                    RedisMessageType::bulk_string("\r\n\0ü"),
+                    RedisMessageType::NullBulkString,
+                    RedisMessageType::Integer(-7),
+                    RedisMessageType::bulk_string_array(vec!["nested", ""]),
+                ]
+                .into(),
+            );
+
+            let encoded = message.encode();
+            let (decoded, length) =
+                RedisMessageType::decode(std::str::from_utf8(&encoded).unwrap()).unwrap();
+
+            assert_eq!(message, decoded);
+            assert_eq!(encoded.len(), length);
+        }
+
+        #[test]
+        fn rdb_payload_header_has_no_body() {
+            assert_eq!(b"$88\r\n".to_vec(), rdb_payload_header(88));
+        }
+
+        #[test]
+        fn decode_deeply_nested_array() {
+            let input = "*1\r\n".repeat(100_000);
+
+            assert!(RedisMessageType::decode(input).is_err());
+        }
+    }
+
+    #[cfg(test)]
+    mod test_recovery {
+        use super::*;
+
+        fn recover(input: &str) -> Recovery {
+            let err = RedisMessageType::decode(input).unwrap_err();
+            return RedisMessageType::recover(input, &err);
+        }
+
+        #[test]
+        fn recover_incomplete_message() {
+            assert_eq!(Recovery::NeedMoreData, recover(""));
+            assert_eq!(Recovery::NeedMoreData, recover("*2\r\n$4\r\nECHO\r\n"));
+            assert_eq!(Recovery::NeedMoreData, recover("*1\r\n$4\r\nPI"));
+            assert_eq!(Recovery::NeedMoreData, recover("+OK\r"));
+        }
+
+        #[test]
+        fn recover_malformed_message() {
+            let input = "*1\r\n$x\r\nPING\r\n*1\r\n$4\r\nPING\r\n";
+
+            let Recovery::Skip(length) = recover(input) else {
+                panic!("malformed message is not skipped");
+            };
+            assert_eq!(
+                RedisMessageType::bulk_string_array(vec!["PING"]),
+                RedisMessageType::decode(&input[length..]).unwrap().0
+            );
+
+            // without a following command everything is dropped
+            assert_eq!(Recovery::Skip(7), recover("hello\r\n"));
+        }
+
+        #[test]
+        fn recover_exceeded_limit() {
+            assert_eq!(Recovery::Close, recover("$99999999999999999\r\nabc\r\n"));
+        }
+    }
+
+    #[cfg(test)]
+    mod test_limits {
+        use super::*;
+
+        const LIMITS: ProtocolLimits = ProtocolLimits {
+            max_bulk_len: 4,
+            max_multibulk_len: 2,
+            max_inline_len: 8,
+            max_query_buffer_len: 64,
+        };
+
+        fn decode_error(input: &str) -> String {
+            return RedisMessageType::decode_with_limits(input, &LIMITS)
+                .unwrap_err()
+                .to_string();
+        }
+
+        #[test]
+        fn decode_within_limits() {
+            let input = "*2\r\n$4\r\nECHO\r\n+12345678\r\n";
+
+            let result = RedisMessageType::decode_with_limits(input, &LIMITS).unwrap();
+            assert_eq!(input.len(), result.1);
+        }
+
+        #[test]
+        fn decode_exceeding_limits() {
+            assert_eq!(
+                "invalid bulk length (exceeds proto-max-bulk-len 4)",
+                decode_error("$5\r\nhello\r\n")
+            );
+            assert_eq!(
+                "invalid multibulk length (exceeds proto-max-multibulk-len 2)",
+                decode_error("*3\r\n:1\r\n:2\r\n:3\r\n")
+            );
+            assert_eq!(
+                "too big inline request (exceeds proto-max-inline-len 8)",
+                decode_error("+123456789\r\n")
+            );
+            // the line is rejected before its CRLF arrives
+            assert_eq!(
+                "too big inline request (exceeds proto-max-inline-len 8)",
+                decode_error(":123456789")
+            );
         }
     }
 }
@@ -1,2 +1,2 @@
-pub mod messages;
 pub mod db_file;
+pub mod messages;
@@ -0,0 +1,97 @@
+//! Compression of the replication stream, negotiated with `REPLCONF capa lz4`.
+//!
+//! A replica offering the capability gets `FULLRESYNC <replid> <offset> lz4` as reply. After
+//! the rdb file, the propagated writes are then sent in frames: the amount of uncompressed
+//! bytes and the size of the lz4 block as u32 little endian, followed by the block. The
+//! replication offsets still count the uncompressed bytes.
+
+use crate::utils::lz4;
+
+/// The capability offered by the replica with `REPLCONF capa`.
+pub const CAPABILITY: &str = "lz4";
+
+const HEADER_LEN: usize = 8;
+/// Larger writes are split up. It bounds the memory a replica allocates for a single frame.
+const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Compresses the bytes into one or more frames.
+pub fn encode_frames(bytes: &[u8]) -> Vec<u8> {
+    let mut frames = Vec::new();
+    for chunk in bytes.chunks(MAX_FRAME_LEN) {
+        let block = lz4::compress(chunk);
+        frames.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        frames.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        frames.extend_from_slice(&block);
+    }
+    return frames;
+}
+
+/// Decompresses the complete frames at the start of `input` into `output`. Returns the amount
+/// of bytes consumed, the remaining bytes are the start of a frame not fully received yet.
+pub fn decode_frames(input: &[u8], output: &mut Vec<u8>) -> Result<usize, String> {
+    let mut position = 0;
+    while let Some(header) = input.get(position..position + HEADER_LEN) {
+        let raw_len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let block_len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if raw_len > MAX_FRAME_LEN || block_len > lz4_bound(MAX_FRAME_LEN) {
+            return Err(format!(
+                "frame of {} bytes exceeds the limit of {} bytes",
+                raw_len, MAX_FRAME_LEN
+            ));
+        }
+
+        let block_start = position + HEADER_LEN;
+        let block = match input.get(block_start..block_start + block_len) {
+            Some(block) => block,
+            None => break,
+        };
+        output.extend_from_slice(&lz4::decompress(block, raw_len)?);
+        position = block_start + block_len;
+    }
+    return Ok(position);
+}
+
+/// The largest block of incompressible input, every 255 literals take an extra length byte.
+fn lz4_bound(len: usize) -> usize {
+    return len + len / 255 + 16;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replication::compression::{decode_frames, encode_frames, MAX_FRAME_LEN};
+
+    #[test]
+    fn test_frames_round_trip() {
+        let command = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n";
+        let mut input = encode_frames(command);
+        input.extend(encode_frames(&command.repeat(100)));
+
+        let mut output = Vec::new();
+        assert_eq!(Ok(input.len()), decode_frames(&input, &mut output));
+        assert_eq!(command.repeat(101), output);
+    }
+
+    #[test]
+    fn test_incomplete_frame_is_kept() {
+        let input = encode_frames(b"*1\r\n$4\r\nPING\r\n");
+        for len in [0, 5, input.len() - 1] {
+            let mut output = Vec::new();
+            assert_eq!(Ok(0), decode_frames(&input[..len], &mut output));
+            assert!(output.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_large_writes_are_split() {
+        let input = vec![b'x'; MAX_FRAME_LEN * 2 + 1];
+        let frames = encode_frames(&input);
+
+        let mut output = Vec::new();
+        assert_eq!(Ok(frames.len()), decode_frames(&frames, &mut output));
+        assert_eq!(input, output);
+
+        let mut oversized = ((MAX_FRAME_LEN + 1) as u32).to_le_bytes().to_vec();
+        oversized.extend_from_slice(&[0; 4]);
+        assert!(decode_frames(&oversized, &mut output).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    io::{ErrorKind, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info, trace, warn};
+use once_cell::sync::Lazy;
+
+use crate::{
+    commands::{
+        context::{ConnectionContext, ConnectionKind},
+        psync::PsyncCommand,
+        traits::{Command, Unparsed},
+    },
+    consts::WRITE_TIMEOUT,
+    db::{data_store::get_db, persistence, write_pause},
+    parser::messages::{rdb_payload_header, Recovery, RedisMessageType},
+    read_message,
+    replication::compression,
+    utils::failpoint::{self, FailAction},
+};
+
+/// How often the acknowledged offsets of the replicas are checked while waiting for acks.
+const ACK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static REPLICAS: Lazy<RwLock<Vec<Arc<Replica>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// A replica connected to this master after a successful PSYNC.
+#[derive(Debug)]
+pub struct Replica {
+    addr: SocketAddr,
+    writer: Mutex<ReplicaWriter>,
+    acked_offset: AtomicU64,
+}
+
+/// The stream writes are propagated on. Until the replica received the snapshot of its full
+/// resync, the writes are buffered instead.
+#[derive(Debug)]
+struct ReplicaWriter {
+    stream: TcpStream,
+    pending: Option<Vec<u8>>,
+    /// Negotiated with `REPLCONF capa lz4`, writes are sent as lz4 frames.
+    compression: bool,
+}
+
+impl Replica {
+    fn new(addr: SocketAddr, stream: TcpStream, compression: bool) -> Self {
+        let writer = ReplicaWriter {
+            stream,
+            pending: Some(Vec::new()),
+            compression,
+        };
+        return Self {
+            addr,
+            writer: Mutex::new(writer),
+            acked_offset: AtomicU64::new(0),
+        };
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        return self.addr;
+    }
+
+    /// The last replication offset the replica has acknowledged via `REPLCONF ACK`.
+    pub fn acked_offset(&self) -> u64 {
+        return self.acked_offset.load(Ordering::SeqCst);
+    }
+
+    fn shutdown(&self) {
+        if let Ok(writer) = self.writer.lock() {
+            let _ = writer.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn lock_writer(&self) -> MutexGuard<'_, ReplicaWriter> {
+        return self
+            .writer
+            .lock()
+            .expect("Replica writer lock poisoned. Should never happen!");
+    }
+
+    fn write(&self, bytes: &[u8]) -> std::io::Result<()> {
+        let writer = &mut *self.lock_writer();
+        return match &mut writer.pending {
+            Some(pending) => {
+                pending.extend_from_slice(bytes);
+                Ok(())
+            }
+            None if writer.compression => {
+                writer.stream.write_all(&compression::encode_frames(bytes))
+            }
+            None => writer.stream.write_all(bytes),
+        };
+    }
+
+    /// Sends the writes buffered during the full resync, every later write is sent directly.
+    fn start_streaming(&self) -> std::io::Result<()> {
+        let mut writer = self.lock_writer();
+        if let Some(pending) = writer.pending.take() {
+            trace!(
+                "Send {} bytes of writes buffered during the full resync to replica {}",
+                pending.len(),
+                self.addr
+            );
+            if !writer.compression {
+                writer.stream.write_all(&pending)?;
+            } else if !pending.is_empty() {
+                writer
+                    .stream
+                    .write_all(&compression::encode_frames(&pending))?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Returns all replicas currently connected to this master.
+pub fn get_replicas() -> Vec<Arc<Replica>> {
+    return REPLICAS
+        .read()
+        .expect("Unable to read the replica list. Should never happen!")
+        .clone();
+}
+
+/// Amount of replicas that acknowledged at least the given replication offset.
+pub fn count_acked(offset: u64) -> usize {
+    return REPLICAS
+        .read()
+        .expect("Unable to read the replica list. Should never happen!")
+        .iter()
+        .filter(|replica| replica.acked_offset() >= offset)
+        .count();
+}
+
+/// Asks all replicas to report their offset with `REPLCONF GETACK *`. The request is part of
+/// the replication stream, so it advances the offset as well.
+pub fn request_acks() {
+    propagate(&RedisMessageType::bulk_string_array(vec![
+        "REPLCONF", "GETACK", "*",
+    ]));
+}
+
+/// Blocks until `num_replicas` replicas acknowledged `offset` or the timeout is over, `None`
+/// waits forever. Returns the amount of replicas that acknowledged the offset.
+///
+/// Waiting for the offset of a client's last write instead of the current offset gives the
+/// client read-your-writes on the replicas without waiting for the writes of other clients.
+pub fn wait_for_acks(num_replicas: usize, offset: u64, timeout: Option<Duration>) -> usize {
+    let mut acked = count_acked(offset);
+    if acked >= num_replicas {
+        return acked;
+    }
+
+    request_acks();
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    while acked < num_replicas && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        thread::sleep(ACK_POLL_INTERVAL);
+        acked = count_acked(offset);
+    }
+    return acked;
+}
+
+/// Takes over the connection after a PSYNC command was recieved.
+///
+/// The connection leaves the request / response loop for good: the full resync is send,
+/// the replica is registered for the propagation feed and the current thread becomes the
+/// ack reader until the replica disconnects. The client of the connection is listed as a
+/// replica link from then on.
+pub fn handle_psync(
+    mut stream: TcpStream,
+    ctx: &ConnectionContext,
+    command: Command<Unparsed, PsyncCommand>,
+) {
+    let peer = match stream.peer_addr() {
+        Ok(peer) => peer,
+        Err(err) => {
+            error!("Unable to get the address of the replica: {}", err);
+            return;
+        }
+    };
+
+    // also applies to the writer used for the propagation, it is a clone of this stream
+    if let Err(err) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+        error!(
+            "Unable to set the write timeout for replica {}: {}",
+            peer, err
+        );
+        return;
+    }
+
+    let command = match command.parse() {
+        Ok(command) => command,
+        Err(err) => {
+            // invalid psync command, the connection stays a normal client connection
+            warn!("Replica {} send an invalid PSYNC command", peer);
+            let _ = stream.write_all(&err.encode());
+            return;
+        }
+    };
+    let writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(err) => {
+            error!("Unable to clone the replica stream of {}: {}", peer, err);
+            return;
+        }
+    };
+
+    let (replica, response, rdb_file) = {
+        // the snapshot has to match the offset of the FULLRESYNC reply, so no write may change
+        // the dataset in between. Writes after it are buffered until the snapshot is sent.
+        let _hold = write_pause::hold();
+
+        let (replica, response) = {
+            // registers the replica at the offset of the reply, replication pings or
+            // evictions are propagated without a write permit
+            let mut replicas = REPLICAS
+                .write()
+                .expect("Unable to write the replica list. Should never happen!");
+
+            let response = match command.execute_with_context(ctx) {
+                Ok(response) => response,
+                Err(err) => {
+                    let _ = stream.write_all(&err.encode());
+                    return;
+                }
+            };
+            let compression = ctx.client().state().repl_compression;
+            let replica = Arc::new(Replica::new(peer, writer, compression));
+            replicas.push(Arc::clone(&replica));
+            (replica, response)
+        };
+
+        // the replica connects again and retries once the snapshot failed
+        match persistence::save_snapshot(get_db()) {
+            Ok(rdb_file) => (replica, response, rdb_file),
+            Err(err) => {
+                error!("Failed to take the snapshot for replica {}: {}", peer, err);
+                disconnect(&replica);
+                return;
+            }
+        }
+    };
+
+    let transfer =
+        send_full_resync(&mut stream, response, &rdb_file).and_then(|_| replica.start_streaming());
+    if let Err(err) = transfer {
+        error!(
+            "Failed to send the full resync to replica {}: {}",
+            peer, err
+        );
+        disconnect(&replica);
+        return;
+    }
+    ctx.client()
+        .update_state(|state| state.kind = ConnectionKind::ReplicaLink);
+    info!("Replica {} connected", peer);
+
+    read_acks(&mut stream, &replica);
+    disconnect(&replica);
+}
+
+fn send_full_resync(
+    stream: &mut TcpStream,
+    response: RedisMessageType,
+    rdb_file: &[u8],
+) -> std::io::Result<()> {
+    stream.write_all(&response.encode())?;
+    debug!("Send FULLRESYNC to replica, sending rdb file");
+
+    stream.write_all(&rdb_payload_header(rdb_file.len()))?;
+
+    let (first_half, second_half) = rdb_file.split_at(rdb_file.len() / 2);
+    stream.write_all(first_half)?;
+    match failpoint::eval("master-rdb-transfer") {
+        Some(FailAction::Drop) => return Ok(()),
+        Some(FailAction::Disconnect) => {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(ErrorKind::ConnectionAborted.into());
+        }
+        _ => (),
+    }
+    stream.write_all(second_half)?;
+    trace!("Send rdb file of {} bytes to replica", rdb_file.len());
+
+    return Ok(());
+}
+
+/// Reads the `REPLCONF ACK <offset>` messages of the replica until the connection closes.
+fn read_acks(stream: &mut TcpStream, replica: &Replica) {
+    loop {
+        let raw_message = match read_message(stream) {
+            Ok(raw_message) if raw_message.is_empty() => return,
+            Ok(raw_message) => raw_message,
+            Err(err) => {
+                match err.kind() {
+                    ErrorKind::BrokenPipe | ErrorKind::ConnectionReset => {
+                        info!("Pipe to replica {} broke", replica.addr)
+                    }
+                    _ => error!("Encounterd IO exception while connected to replica {}", err),
+                }
+                return;
+            }
+        };
+
+        let mut input = match str::from_utf8(&raw_message) {
+            Ok(input) => input,
+            Err(_) => {
+                warn!("Replica {} send a non utf8 message", replica.addr);
+                continue;
+            }
+        };
+
+        // several acks may be recieved with a single read
+        while !input.is_empty() {
+            let (message, length) = match RedisMessageType::decode(input) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    warn!(
+                        "Unable to decode message of replica {}: {}",
+                        replica.addr, err
+                    );
+                    match RedisMessageType::recover(input, &err) {
+                        Recovery::Skip(length) => {
+                            input = &input[length..];
+                            continue;
+                        }
+                        Recovery::NeedMoreData | Recovery::Close => break,
+                    }
+                }
+            };
+            input = &input[length.min(input.len())..];
+
+            match parse_ack(message) {
+                Some(offset) => {
+                    trace!("Replica {} acknowledged offset {}", replica.addr, offset);
+                    replica.acked_offset.store(offset, Ordering::SeqCst);
+                }
+                None => warn!("Replica {} send an unexpected message", replica.addr),
+            }
+        }
+    }
+}
+
+fn parse_ack(message: RedisMessageType) -> Option<u64> {
+    let mut args = match message {
+        RedisMessageType::Array(args) => args,
+        _ => return None,
+    };
+
+    let command = args.pop_front()?.bulk_string_value().ok()?;
+    let sub_command = args.pop_front()?.bulk_string_value().ok()?;
+    if !command.eq_ignore_ascii_case("REPLCONF") || !sub_command.eq_ignore_ascii_case("ACK") {
+        return None;
+    }
+
+    return args.pop_front()?.bulk_string_value().ok()?.parse().ok();
+}
+
+fn disconnect(replica: &Arc<Replica>) {
+    REPLICAS
+        .write()
+        .expect("Unable to write the replica list. Should never happen!")
+        .retain(|other| !Arc::ptr_eq(other, replica));
+
+    replica.shutdown();
+    info!("Replica {} disconnected", replica.addr);
+}
+
+/// Feeds a write command to all connected replicas and advances the replication offset.
+/// Returns the offset right after the command.
+pub fn propagate(command: &RedisMessageType) -> u64 {
+    let replicas = REPLICAS
+        .read()
+        .expect("Unable to read the replica list. Should never happen!");
+
+    let bytes = command.encode();
+    for replica in replicas.iter() {
+        match failpoint::eval("master-propagate") {
+            Some(FailAction::Drop) => continue,
+            Some(FailAction::Disconnect) => {
+                replica.shutdown();
+                continue;
+            }
+            _ => (),
+        }
+
+        if let Err(err) = replica.write(&bytes) {
+            // the frame may be written partially, so the replica can not continue the stream.
+            // Its ack reader notices the closed connection and cleans up.
+            warn!(
+                "Failed to propagate command to replica {}, disconnecting: {}",
+                replica.addr, err
+            );
+            replica.shutdown();
+        }
+    }
+
+    return get_db().add_master_repl_offset(bytes.len() as u128) as u64;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{parser::messages::RedisMessageType, replication::master::parse_ack};
+
+    #[test]
+    fn test_parse_ack() {
+        let message = RedisMessageType::bulk_string_array(vec!["REPLCONF", "ACK", "154"]);
+
+        assert_eq!(Some(154), parse_ack(message));
+    }
+
+    #[test]
+    fn test_parse_ack_case_insensitive() {
+        let message = RedisMessageType::bulk_string_array(vec!["replconf", "ack", "0"]);
+
+        assert_eq!(Some(0), parse_ack(message));
+    }
+
+    #[test]
+    fn test_parse_ack_invalid_message() {
+        let not_an_array = RedisMessageType::simple_string("OK");
+        let wrong_command = RedisMessageType::bulk_string_array(vec!["REPLCONF", "GETACK", "*"]);
+        let missing_offset = RedisMessageType::bulk_string_array(vec!["REPLCONF", "ACK"]);
+        let empty = RedisMessageType::Array(VecDeque::new());
+
+        assert_eq!(None, parse_ack(not_an_array));
+        assert_eq!(None, parse_ack(wrong_command));
+        assert_eq!(None, parse_ack(missing_offset));
+        assert_eq!(None, parse_ack(empty));
+    }
+}
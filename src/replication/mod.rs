@@ -0,0 +1,3 @@
+pub mod compression;
+pub mod master;
+pub mod slave;
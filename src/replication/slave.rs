@@ -0,0 +1,627 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::RwLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::{debug, error, info, trace, warn};
+use once_cell::sync::Lazy;
+
+use crate::{
+    commands::{
+        command::UnparsedCommandType,
+        context::{ConnectionContext, ConnectionKind},
+        middleware,
+    },
+    consts::CRLF,
+    db::{data_store::get_db, persistence, write_pause},
+    parser::{
+        db_file::RdbFile,
+        messages::{protocol_limits, Recovery, RedisMessageType},
+    },
+    read_message,
+    replication::compression,
+    utils::{
+        buffer_pool,
+        failpoint::{self, FailAction},
+    },
+};
+
+/// Delay before a broken link to the master is established again.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+static STATE: Lazy<RwLock<SlaveState>> = Lazy::new(|| RwLock::new(SlaveState::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    Up,
+    Down,
+}
+
+impl LinkStatus {
+    pub const fn name(&self) -> &'static str {
+        return match self {
+            Self::Up => "up",
+            Self::Down => "down",
+        };
+    }
+}
+
+/// State of the connection from this replica to its master.
+#[derive(Debug, Clone)]
+pub struct SlaveState {
+    pub link_status: LinkStatus,
+    /// Last time data was recieved from the master.
+    pub last_io: Option<Instant>,
+    /// Amount of replication stream bytes processed.
+    pub repl_offset: u64,
+}
+
+impl SlaveState {
+    fn new() -> Self {
+        return Self {
+            link_status: LinkStatus::Down,
+            last_io: None,
+            repl_offset: 0,
+        };
+    }
+
+    /// Seconds since the last interaction with the master, -1 if the link is down.
+    pub fn last_io_seconds_ago(&self) -> i64 {
+        return match (self.link_status, self.last_io) {
+            (LinkStatus::Up, Some(last_io)) => last_io.elapsed().as_secs() as i64,
+            _ => -1,
+        };
+    }
+}
+
+pub fn get_slave_state() -> SlaveState {
+    return STATE
+        .read()
+        .expect("Unable to read the slave state. Should never happen!")
+        .clone();
+}
+
+fn update_state<F: FnOnce(&mut SlaveState)>(f: F) {
+    let mut state = STATE
+        .write()
+        .expect("Unable to write the slave state. Should never happen!");
+    f(&mut state);
+}
+
+/// Keeps the replica connected to its master. A broken link is established again after
+/// [`RECONNECT_DELAY`], the host name of the master is resolved on every attempt, so a
+/// master moved by a DNS change is followed.
+pub fn connect_slave_to_master(master_host: String, master_port: u16) {
+    loop {
+        run_master_link(&master_host, master_port);
+        update_state(|state| state.link_status = LinkStatus::Down);
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+/// Connects to the master, runs the handshake and processes the replication stream until
+/// the link breaks.
+fn run_master_link(master_host: &str, master_port: u16) {
+    info!("Starting slave to master connection");
+    let stream = match connect_to_master(master_host, master_port) {
+        Ok(stream) => stream,
+        Err(err) => {
+            error!(
+                "Failed to connect to master {}:{}: {}",
+                master_host, master_port, err
+            );
+            return;
+        }
+    };
+
+    let master_addr = match stream.peer_addr() {
+        Ok(master_addr) => master_addr,
+        Err(err) => {
+            error!("Unable to get the address of the master: {}", err);
+            return;
+        }
+    };
+
+    let mut link = MasterLink::new(stream);
+    let offset = match repl_handshake(&mut link) {
+        Ok(offset) => offset,
+        Err(err) => {
+            error!("Replication handshake with master failed: {}", err);
+            return;
+        }
+    };
+
+    // acks are absolute offsets of the master, like the ones WAIT waits for
+    update_state(|state| {
+        state.link_status = LinkStatus::Up;
+        state.repl_offset = offset;
+    });
+    info!("Replication link to master {} is up", master_addr);
+
+    // the master is listed as a client, like in redis
+    let ctx = ConnectionContext::with_kind(master_addr, ConnectionKind::MasterLink);
+    if let Ok(socket) = link.stream.try_clone() {
+        ctx.client().attach_socket(socket);
+    }
+    if let Err(err) = process_replication_stream(&mut link, &ctx) {
+        warn!("Replication link to master broke: {}", err);
+    }
+}
+
+/// Resolves the master and connects to the first address accepting the connection. The
+/// addresses are never cached.
+fn connect_to_master(master_host: &str, master_port: u16) -> Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = (master_host, master_port).to_socket_addrs()?.collect();
+    debug!("Master {} resolved to {:?}", master_host, addrs);
+
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect(addr) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    return Err(match last_err {
+        Some(err) => err.into(),
+        None => anyhow!("{} did not resolve to any address", master_host),
+    });
+}
+
+/// Steps of the handshake with the master. A failed handshake names the step it broke at,
+/// e.g. a wrong `--masterauth` shows up as a failed AUTH instead of an unexpected reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandshakeStage {
+    Ping,
+    Auth,
+    ListeningPort,
+    Capa,
+    Psync,
+    RdbTransfer,
+}
+
+impl HandshakeStage {
+    const fn name(&self) -> &'static str {
+        return match self {
+            Self::Ping => "PING",
+            Self::Auth => "AUTH",
+            Self::ListeningPort => "REPLCONF listening-port",
+            Self::Capa => "REPLCONF capa",
+            Self::Psync => "PSYNC",
+            Self::RdbTransfer => "rdb transfer",
+        };
+    }
+
+    fn fail(self) -> impl FnOnce(anyhow::Error) -> anyhow::Error {
+        return move |err| anyhow!("{} failed: {}", self.name(), err);
+    }
+}
+
+/// Whether the error a master replies to PING asks for authentication. Like redis, the
+/// handshake goes on and authenticates in that case.
+fn is_auth_error(error: &str) -> bool {
+    return error.starts_with("NOAUTH")
+        || error.starts_with("NOPERM")
+        || error.starts_with("ERR operation not permitted");
+}
+
+/// Returns the offset of the master the replication stream starts at.
+fn repl_handshake(link: &mut MasterLink) -> Result<u64> {
+    let config = get_db().get_config();
+
+    debug!("Handshake 1/4 Sending ping to master");
+    link.send(RedisMessageType::bulk_string_array(vec!["PING"]))
+        .and_then(|_| link.read_message())
+        .and_then(|(reply, _)| match reply {
+            RedisMessageType::SimpleString(val) if val == "PONG" => Ok(()),
+            RedisMessageType::Error(err) if is_auth_error(&err) => Ok(()),
+            other => Err(anyhow!(
+                "Expected a \"PONG\" response from the master server, but got: {:?}",
+                other
+            )),
+        })
+        .map_err(HandshakeStage::Ping.fail())?;
+    debug!("Handshake 1/4 Successfully completed. PING response recieved.");
+
+    match &config.masterauth {
+        Some(password) => {
+            debug!("Handshake 2/4 Sending AUTH to master");
+            link.request(vec!["AUTH", password], |val| val == "OK", "OK")
+                .map_err(HandshakeStage::Auth.fail())?;
+            debug!("Handshake 2/4 Successfully completed. Authenticated at the master.");
+        }
+        None => debug!("Handshake 2/4 Skipped, there is no masterauth."),
+    }
+
+    debug!("Handshake 3/4 Sending replconf to master");
+    {
+        trace!("Sending replconf 1/2 listenport to master");
+        let listen_port = config.current_listening_port.to_string();
+        link.request(
+            vec!["REPLCONF", "listening-port", &listen_port],
+            |val| val == "OK",
+            "OK",
+        )
+        .map_err(HandshakeStage::ListeningPort.fail())?;
+
+        trace!("Sending replconf 2/2 capa to master");
+        let mut capa = vec!["REPLCONF", "capa", "psync2"];
+        if config.repl_compression {
+            capa.extend(["capa", compression::CAPABILITY]);
+        }
+        link.request(capa, |val| val == "OK", "OK")
+            .map_err(HandshakeStage::Capa.fail())?;
+    }
+    debug!("Handshake 3/4 Successfully completed. 2/2 REPLCONF responses recieved.");
+
+    debug!("Handshake 4/4 Sending PSYNC to master");
+    let reply = link
+        .request(
+            vec!["PSYNC", "?", "-1"],
+            |val| parse_fullresync(val).is_some(),
+            "FULLRESYNC <replid> <offset>",
+        )
+        .map_err(HandshakeStage::Psync.fail())?;
+    let (offset, compressed) = parse_fullresync(&reply)
+        .expect("The reply has been checked to contain an offset. Should never happen!");
+    let raw_rdb_file = link
+        .read_rdb_file()
+        .map_err(HandshakeStage::RdbTransfer.fail())?;
+    debug!("Handshake 4/4 Successfully completed. PSYNC response and rdb file recieved.");
+
+    // clients get -LOADING until the dataset of the master replaced the old one
+    let _loading = persistence::start_loading(raw_rdb_file.len() as u64);
+    let rdb_file = RdbFile::decode(&raw_rdb_file);
+    buffer_pool::give_back(raw_rdb_file);
+    get_db().load_rdb_file(&rdb_file.map_err(HandshakeStage::RdbTransfer.fail())?);
+
+    if compressed {
+        debug!("The replication stream of the master is lz4 compressed");
+        link.start_decompression()?;
+    }
+    return Ok(offset);
+}
+
+/// The offset of a `FULLRESYNC <replid> <offset> [lz4]` reply, the replication stream
+/// continues from there. `lz4` tells the stream is compressed, see [`compression`].
+fn parse_fullresync(reply: &str) -> Option<(u64, bool)> {
+    return match reply.split(' ').collect::<Vec<&str>>()[..] {
+        ["FULLRESYNC", _, offset] => offset.parse().ok().map(|offset| (offset, false)),
+        ["FULLRESYNC", _, offset, capability] if capability == compression::CAPABILITY => {
+            offset.parse().ok().map(|offset| (offset, true))
+        }
+        _ => None,
+    };
+}
+
+/// Applies the commands the master propagates. No replies are send except for
+/// `REPLCONF GETACK`, which is answered with the offset processed before it.
+fn process_replication_stream(link: &mut MasterLink, ctx: &ConnectionContext) -> Result<()> {
+    loop {
+        let (message, length) = link.read_message()?;
+
+        if is_getack(&message) {
+            let offset = get_slave_state().repl_offset;
+            trace!("Master requested ACK, answering with offset {}", offset);
+            match failpoint::eval("replica-before-ack") {
+                Some(FailAction::Drop) => (),
+                Some(FailAction::Disconnect) => bail!("Failpoint closed the link before the ACK"),
+                _ => link.send(RedisMessageType::bulk_string_array(vec![
+                    "REPLCONF".to_string(),
+                    "ACK".to_string(),
+                    offset.to_string(),
+                ]))?,
+            }
+        } else {
+            let result = match message {
+                RedisMessageType::Array(args) => apply_command(ctx, args),
+                other => Err(RedisMessageType::error(format!(
+                    "Expected an Array from the master, but got: {}",
+                    other
+                ))),
+            };
+
+            // the master link never gets a reply, only failures are logged
+            if let Err(err) = result {
+                warn!("Failed to apply command from master: {:?}", err);
+            }
+        }
+
+        update_state(|state| state.repl_offset += length as u64);
+    }
+}
+
+/// Runs a command of the master through the same steps as a client command. The middlewares
+/// know the master link by its connection kind, so e.g. read only replicas accept its writes.
+fn apply_command(
+    ctx: &ConnectionContext,
+    args: VecDeque<RedisMessageType>,
+) -> Result<RedisMessageType, RedisMessageType> {
+    let command = UnparsedCommandType::new(args)?;
+    ctx.client().record_command(command.full_name());
+    middleware::before_execute(ctx, &command)?;
+
+    let is_write = command.is_write();
+    // the link has a thread of its own and may not drop a write, so it waits without a limit
+    let _permit = is_write.then(write_pause::acquire_write);
+    let response = command.parse()?.execute(ctx)?;
+
+    if is_write {
+        persistence::record_write();
+    }
+    return Ok(response);
+}
+
+fn is_getack(message: &RedisMessageType) -> bool {
+    let args = match message {
+        RedisMessageType::Array(args) => args,
+        _ => return false,
+    };
+
+    let mut args = args.iter().map(|arg| arg.bulk_string_value());
+    return matches!(
+        (args.next(), args.next()),
+        (Some(Ok(command)), Some(Ok(sub_command)))
+            if command.eq_ignore_ascii_case("REPLCONF") && sub_command.eq_ignore_ascii_case("GETACK")
+    );
+}
+
+/// Buffered connection to the master. A single read may contain several messages
+/// (or the rdb file followed by propagated commands), so unconsumed bytes are kept.
+struct MasterLink {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    /// Received lz4 frames not complete yet, once the stream is compressed. `buffer` holds
+    /// the decompressed bytes.
+    compressed: Option<Vec<u8>>,
+}
+
+impl MasterLink {
+    fn new(stream: TcpStream) -> Self {
+        return Self {
+            stream,
+            buffer: Vec::new(),
+            compressed: None,
+        };
+    }
+
+    /// Everything after the rdb file is compressed, including the bytes already received.
+    fn start_decompression(&mut self) -> Result<()> {
+        self.compressed = Some(std::mem::take(&mut self.buffer));
+        return self.decompress();
+    }
+
+    fn decompress(&mut self) -> Result<()> {
+        if let Some(compressed) = &mut self.compressed {
+            let consumed = compression::decode_frames(compressed, &mut self.buffer)
+                .map_err(|err| anyhow!("Unable to decompress the replication stream: {}", err))?;
+            compressed.drain(..consumed);
+        }
+        return Ok(());
+    }
+
+    fn send(&mut self, message: RedisMessageType) -> Result<()> {
+        self.stream.write_all(&message.encode())?;
+        return Ok(());
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        let data = read_message(&mut self.stream)?;
+        if data.is_empty() {
+            bail!("Master closed the connection");
+        }
+
+        update_state(|state| state.last_io = Some(Instant::now()));
+        let buffer = self.compressed.as_mut().unwrap_or(&mut self.buffer);
+        buffer_pool::release(buffer);
+        buffer_pool::reserve(buffer, data.len());
+        buffer.extend_from_slice(&data);
+        buffer_pool::give_back(data);
+        return self.decompress();
+    }
+
+    /// Returns the next message and its length in bytes.
+    fn read_message(&mut self) -> Result<(RedisMessageType, usize)> {
+        return self.read_message_within(protocol_limits().max_query_buffer_len);
+    }
+
+    /// Like [`MasterLink::read_message`], the link is closed once more than `max_buffer_len`
+    /// bytes are buffered or the master sends bytes that are no utf8. The replica connects
+    /// again and gets a full resync, dropping bytes would corrupt the replication stream.
+    fn read_message_within(&mut self, max_buffer_len: usize) -> Result<(RedisMessageType, usize)> {
+        loop {
+            if self.buffer.len() > max_buffer_len {
+                bail!(
+                    "Master reached the max query buffer length of {} bytes",
+                    max_buffer_len
+                );
+            }
+
+            if !self.buffer.is_empty() {
+                // only the valid utf8 prefix can hold a complete message, the messages before
+                // invalid bytes are still applied
+                let (input, invalid) = match str::from_utf8(&self.buffer) {
+                    Ok(input) => (input, None),
+                    Err(err) => (
+                        str::from_utf8(&self.buffer[..err.valid_up_to()])
+                            .expect("The prefix is valid utf8. Should never happen!"),
+                        // the last char may be split between two reads
+                        err.error_len().map(|_| err),
+                    ),
+                };
+
+                match RedisMessageType::decode(input) {
+                    Ok((message, length)) => {
+                        self.buffer.drain(..length);
+                        return Ok((message, length));
+                    }
+                    Err(err) => match RedisMessageType::recover(input, &err) {
+                        Recovery::NeedMoreData => {
+                            if let Some(err) = invalid {
+                                bail!("Master sent a payload that is no utf8 ({})", err);
+                            }
+                        }
+                        Recovery::Skip(length) => {
+                            warn!(
+                                "Skipping {} malformed bytes send by the master: {}",
+                                length, err
+                            );
+                            self.buffer.drain(..length);
+                            continue;
+                        }
+                        Recovery::Close => bail!("Unable to decode message of the master: {}", err),
+                    },
+                }
+            }
+
+            self.fill()?;
+        }
+    }
+
+    fn expect_simple_string<F: Fn(&str) -> bool>(
+        &mut self,
+        check: F,
+        expected: &str,
+    ) -> Result<String> {
+        return match self.read_message()?.0 {
+            RedisMessageType::SimpleString(val) if check(&val) => Ok(val),
+            other => Err(anyhow!(
+                "Expected a \"{}\" response from the master server, but got: {:?}",
+                expected,
+                other
+            )),
+        };
+    }
+
+    /// Sends a command of the handshake and checks the simple string the master replies.
+    fn request<F: Fn(&str) -> bool>(
+        &mut self,
+        args: Vec<&str>,
+        check: F,
+        expected: &str,
+    ) -> Result<String> {
+        self.send(RedisMessageType::bulk_string_array(args))?;
+        return self.expect_simple_string(check, expected);
+    }
+
+    /// Reads the rdb file send on a full resync: `$<length>\r\n<bytes>` without a trailing CRLF.
+    fn read_rdb_file(&mut self) -> Result<Vec<u8>> {
+        loop {
+            let header_end = self
+                .buffer
+                .windows(CRLF.len())
+                .position(|window| window == CRLF.as_bytes());
+
+            if let Some(header_end) = header_end {
+                if self.buffer[0] != b'$' {
+                    bail!("Expected the rdb file to start with '$'");
+                }
+                let length: usize = str::from_utf8(&self.buffer[1..header_end])?.parse()?;
+                let start = header_end + CRLF.len();
+
+                if self.buffer.len() >= start + length {
+                    let mut rdb_file = buffer_pool::take(length);
+                    rdb_file.extend_from_slice(&self.buffer[start..start + length]);
+                    self.buffer.drain(..start + length);
+                    return Ok(rdb_file);
+                }
+            }
+
+            self.fill()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, time::Duration};
+
+    use crate::{
+        parser::messages::RedisMessageType,
+        replication::slave::{connect_to_master, is_getack, parse_fullresync, MasterLink},
+    };
+
+    /// A link to a master that sends nothing, `buffer` is what was received so far.
+    fn link_with_buffer(buffer: &[u8]) -> (MasterLink, TcpListener) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = connect_to_master("127.0.0.1", port).unwrap();
+        // a link waiting for more data fails the test instead of blocking it
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let mut link = MasterLink::new(stream);
+        link.buffer.extend_from_slice(buffer);
+        return (link, listener);
+    }
+
+    #[test]
+    fn test_parse_fullresync() {
+        assert_eq!(
+            Some((1234, false)),
+            parse_fullresync("FULLRESYNC 8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb 1234")
+        );
+        assert_eq!(
+            Some((0, true)),
+            parse_fullresync("FULLRESYNC 8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb 0 lz4")
+        );
+        assert_eq!(None, parse_fullresync("FULLRESYNC replid"));
+        assert_eq!(None, parse_fullresync("FULLRESYNC replid -1"));
+        assert_eq!(None, parse_fullresync("FULLRESYNC replid 0 zstd"));
+        assert_eq!(None, parse_fullresync("CONTINUE replid 0"));
+    }
+
+    #[test]
+    fn test_invalid_utf8_closes_the_link() {
+        let (mut link, _master) = link_with_buffer(b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nP\xffNG\r\n");
+
+        let (message, length) = link.read_message().unwrap();
+        assert_eq!(RedisMessageType::bulk_string_array(vec!["PING"]), message);
+        assert_eq!(14, length);
+
+        let err = link.read_message().unwrap_err();
+        assert!(err.to_string().contains("no utf8"), "{}", err);
+    }
+
+    #[test]
+    fn test_buffer_limit_closes_the_link() {
+        // an array announcing more elements than are ever sent
+        let (mut link, _master) = link_with_buffer(b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n");
+
+        let err = link.read_message_within(16).unwrap_err();
+        assert!(err.to_string().contains("max query buffer"), "{}", err);
+    }
+
+    #[test]
+    fn test_is_getack() {
+        let message = RedisMessageType::bulk_string_array(vec!["REPLCONF", "GETACK", "*"]);
+
+        assert!(is_getack(&message));
+    }
+
+    #[test]
+    fn test_is_not_getack() {
+        let ack = RedisMessageType::bulk_string_array(vec!["REPLCONF", "ACK", "0"]);
+        let set = RedisMessageType::bulk_string_array(vec!["SET", "foo", "bar"]);
+
+        assert!(!is_getack(&ack));
+        assert!(!is_getack(&set));
+        assert!(!is_getack(&RedisMessageType::simple_string("REPLCONF")));
+    }
+
+    #[test]
+    fn test_connect_to_master_by_host_name() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let stream = connect_to_master("localhost", port).unwrap();
+
+        assert_eq!(port, stream.peer_addr().unwrap().port());
+        assert!(connect_to_master("master.invalid", port).is_err());
+    }
+}
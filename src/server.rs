@@ -0,0 +1,190 @@
+//! Access to the server for embedders, without going through the RESP protocol.
+
+use std::{
+    fs,
+    io::{self, Write},
+    net::{Ipv4Addr, SocketAddr},
+    path::Path,
+    str,
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::info;
+
+use crate::{
+    commands::{command::UnparsedCommandType, context::ConnectionContext},
+    db::{
+        data_store::{get_db, init_db, DataStore, DataUnit, DbConfig},
+        persistence,
+    },
+    parser::messages::RedisMessageType,
+};
+
+/// Handle to the server of this process. The data store is global, so every handle sees the
+/// same keyspace.
+#[derive(Clone, Copy)]
+pub struct Server {
+    db: &'static DataStore,
+}
+
+impl Server {
+    /// Initializes the data store, e.g. with [`crate::utils::cli::Args::get_db_config`].
+    /// Can only be called once per process.
+    pub fn init(config: DbConfig) -> Self {
+        init_db(config);
+        return Self::get();
+    }
+
+    /// Handle to the already initialized server.
+    pub fn get() -> Self {
+        return Self { db: get_db() };
+    }
+
+    /// Iterates over the names of all keys that are not expired. Writes are not blocked
+    /// while iterating, the keys are copied shard by shard.
+    pub fn iter_keys(&self) -> impl Iterator<Item = String> {
+        return self.iter().map(|value| value.key);
+    }
+
+    /// Iterates over copies of all values that are not expired, see [`Server::iter_keys`].
+    pub fn iter(&self) -> impl Iterator<Item = DataUnit> {
+        return self.db.snapshot_iter();
+    }
+
+    /// Writes a snapshot of the keyspace as rdb file, the same file a replica receives on a
+    /// full resync, and counts as a save. Returns the amount of bytes written.
+    pub fn export_rdb<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let rdb_file = persistence::save_snapshot(self.db)?;
+        if let Err(err) = writer.write_all(&rdb_file) {
+            persistence::record_failed_save();
+            return Err(err);
+        }
+        return Ok(rdb_file.len());
+    }
+
+    /// Executes the RESP encoded commands of the file, see [`Server::preload`].
+    pub fn preload_file<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let path = path.as_ref();
+        let input = fs::read(path).map_err(|err| anyhow!("Unable to read {:?}: {}", path, err))?;
+        let executed = self.preload(&input)?;
+        info!("Preloaded {} commands from {:?}", executed, path);
+        return Ok(executed);
+    }
+
+    /// Executes RESP encoded commands one after another, like a client sending them, e.g.
+    /// to fill the keyspace with fixtures before accepting connections. Middlewares like
+    /// ACLs are skipped and nothing is propagated, replicas get the keys on their full
+    /// resync. Whitespace between commands is ignored.
+    ///
+    /// Stops at the first malformed or failing command, the commands before it stay
+    /// executed. Returns the amount of executed commands.
+    pub fn preload(&self, input: &[u8]) -> Result<usize> {
+        let input = str::from_utf8(input)
+            .map_err(|err| anyhow!("Only utf8 payloads are supported ({})", err))?;
+        let ctx = ConnectionContext::new(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+
+        let mut rest = input.trim_start();
+        let mut executed = 0;
+        while !rest.is_empty() {
+            let position = executed + 1;
+            let (message, length) = RedisMessageType::decode(rest)
+                .map_err(|err| anyhow!("Command {} is malformed: {}", position, err))?;
+            rest = rest[length..].trim_start();
+
+            let RedisMessageType::Array(args) = message else {
+                bail!("Command {} is a {}, not an Array", position, message);
+            };
+            let command = UnparsedCommandType::new(args)
+                .map_err(|err| anyhow!("Command {} failed: {}", position, describe(&err)))?;
+            let name = command.full_name();
+            let is_write = command.is_write();
+            command
+                .parse()
+                .and_then(|command| command.execute(&ctx))
+                .map_err(|err| {
+                    anyhow!("Command {} ({}) failed: {}", position, name, describe(&err))
+                })?;
+
+            if is_write {
+                persistence::record_write();
+            }
+            executed += 1;
+        }
+        return Ok(executed);
+    }
+}
+
+/// The message of an error reply.
+fn describe(reply: &RedisMessageType) -> String {
+    return reply.as_string().unwrap_or_else(|| reply.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        db::data_store::{init_test_db, DataUnit, Expiry},
+        parser::db_file::RdbFile,
+        server::Server,
+    };
+
+    #[test]
+    fn test_iter_keys() {
+        let db = init_test_db();
+        db.set("server_test:a", DataUnit::new("server_test:a", "1", None));
+        let expired = Some(Expiry::Ttl(Duration::ZERO));
+        db.set(
+            "server_test:expired",
+            DataUnit::new("server_test:expired", "1", expired),
+        );
+
+        let keys: Vec<String> = Server::get().iter_keys().collect();
+
+        assert!(keys.contains(&"server_test:a".to_string()));
+        assert!(!keys.contains(&"server_test:expired".to_string()));
+    }
+
+    #[test]
+    fn test_export_rdb() {
+        let db = init_test_db();
+        db.set(
+            "server_test:export",
+            DataUnit::new("server_test:export", "value", None),
+        );
+
+        let mut output = Vec::new();
+        let written = Server::get().export_rdb(&mut output).unwrap();
+
+        assert_eq!(output.len(), written);
+        let exported = RdbFile::decode(output).unwrap().get_database().to_dashmap();
+        assert_eq!("value", exported.get("server_test:export").unwrap().value);
+    }
+
+    #[test]
+    fn test_preload() {
+        let db = init_test_db();
+        let input = "*3\r\n$3\r\nSET\r\n$19\r\nserver_test:preload\r\n$5\r\nvalue\r\n\n\
+                     *2\r\n$3\r\nGET\r\n$19\r\nserver_test:preload\r\n\n";
+
+        assert_eq!(2, Server::get().preload(input.as_bytes()).unwrap());
+        assert_eq!("value", db.peek("server_test:preload").unwrap().value);
+        assert_eq!(0, Server::get().preload(b"\r\n").unwrap());
+    }
+
+    #[test]
+    fn test_preload_stops_at_failing_command() {
+        let db = init_test_db();
+        let input = "*3\r\n$3\r\nSET\r\n$20\r\nserver_test:preload2\r\n$1\r\n1\r\n\
+                     *1\r\n$3\r\nSET\r\n\
+                     *3\r\n$3\r\nSET\r\n$20\r\nserver_test:preload3\r\n$1\r\n1\r\n";
+
+        let err = Server::get().preload(input.as_bytes()).unwrap_err();
+
+        assert!(err.to_string().starts_with("Command 2 (set) failed: ERR"));
+        assert!(db.peek("server_test:preload2").is_some());
+        assert!(db.peek("server_test:preload3").is_none());
+        assert!(Server::get().preload(b"+OK\r\n").is_err());
+        assert!(Server::get().preload(b"*1\r\n$4\r\nPI").is_err());
+    }
+}
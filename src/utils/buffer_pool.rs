@@ -0,0 +1,243 @@
+//! Pool of large byte buffers, reused instead of allocated for every big message.
+//!
+//! The query buffers of the connections, the link to the master and the rdb loader need
+//! buffers of megabytes for a short time, e.g. while a large bulk string or an rdb file is
+//! received. Buffers are grouped in power of two size classes, a returned buffer serves the
+//! next request of its class. Small buffers are left to the allocator.
+
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Mutex,
+};
+
+use once_cell::sync::Lazy;
+
+/// Smallest pooled buffer, 16 KiB.
+const MIN_CLASS_BITS: u32 = 14;
+/// Largest pooled buffer, 64 MiB. Larger buffers are rare enough for the allocator.
+const MAX_CLASS_BITS: u32 = 26;
+const BUFFERS_PER_CLASS: usize = 4;
+/// Upper bound of the memory held by the pool.
+const MAX_POOLED_BYTES: usize = 64 * 1024 * 1024;
+
+static POOL: Lazy<BufferPool> = Lazy::new(BufferPool::new);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Requests served by a pooled buffer.
+    pub hits: u64,
+    /// Requests of a pooled size that had to allocate.
+    pub misses: u64,
+    pub returned: u64,
+    /// Buffers freed because their class or the pool was full.
+    pub discarded: u64,
+    pub pooled_bytes: usize,
+    /// The size and amount of pooled buffers of every class.
+    pub classes: Vec<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub struct BufferPool {
+    classes: Vec<Mutex<Vec<Vec<u8>>>>,
+    pooled_bytes: AtomicUsize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    returned: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        return Self {
+            classes: (MIN_CLASS_BITS..=MAX_CLASS_BITS)
+                .map(|_| Mutex::new(Vec::new()))
+                .collect(),
+            pooled_bytes: AtomicUsize::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            returned: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        };
+    }
+
+    fn class(&self, index: usize) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        return self.classes[index]
+            .lock()
+            .expect("Buffer pool lock poisoned. Should never happen!");
+    }
+
+    /// An empty buffer with a capacity of at least `min_capacity`.
+    pub fn take(&self, min_capacity: usize) -> Vec<u8> {
+        let bits = min_capacity.next_power_of_two().trailing_zeros();
+        if min_capacity < 1 << MIN_CLASS_BITS || bits > MAX_CLASS_BITS {
+            return Vec::with_capacity(min_capacity);
+        }
+
+        let index = (bits - MIN_CLASS_BITS) as usize;
+        if let Some(buffer) = self.class(index).pop() {
+            self.pooled_bytes
+                .fetch_sub(buffer.capacity(), Ordering::Relaxed);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return buffer;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        return Vec::with_capacity(1 << bits);
+    }
+
+    /// Keeps the buffer for a later [`BufferPool::take`], if it is large enough and there
+    /// is room for it.
+    pub fn give_back(&self, mut buffer: Vec<u8>) {
+        let capacity = buffer.capacity();
+        if capacity < 1 << MIN_CLASS_BITS {
+            return;
+        }
+
+        // a grown buffer belongs to the largest class it fully covers
+        let bits = (usize::BITS - 1 - capacity.leading_zeros()).min(MAX_CLASS_BITS);
+        let mut class = self.class((bits - MIN_CLASS_BITS) as usize);
+        let pooled = self.pooled_bytes.load(Ordering::Relaxed);
+        if class.len() >= BUFFERS_PER_CLASS || pooled + capacity > MAX_POOLED_BYTES {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        buffer.clear();
+        self.pooled_bytes.fetch_add(capacity, Ordering::Relaxed);
+        self.returned.fetch_add(1, Ordering::Relaxed);
+        class.push(buffer);
+    }
+
+    /// Makes room for `additional` bytes. Growing past the smallest class moves the content
+    /// into a pooled buffer and returns the old one.
+    pub fn reserve(&self, buffer: &mut Vec<u8>, additional: usize) {
+        let needed = buffer.len() + additional;
+        if needed <= buffer.capacity() {
+            return;
+        }
+        if needed < 1 << MIN_CLASS_BITS {
+            buffer.reserve(additional);
+            return;
+        }
+
+        // at least double like Vec does, so appending stays amortized constant
+        let mut grown = self.take(needed.max(buffer.capacity() * 2));
+        grown.extend_from_slice(buffer);
+        self.give_back(std::mem::replace(buffer, grown));
+    }
+
+    /// Returns the allocation of an empty buffer to the pool, so an idle owner does not
+    /// hold on to a large buffer.
+    pub fn release(&self, buffer: &mut Vec<u8>) {
+        if buffer.is_empty() && buffer.capacity() >= 1 << MIN_CLASS_BITS {
+            self.give_back(std::mem::take(buffer));
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let classes = (0..self.classes.len())
+            .map(|index| {
+                (
+                    1 << (MIN_CLASS_BITS as usize + index),
+                    self.class(index).len(),
+                )
+            })
+            .collect();
+        return PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            returned: self.returned.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+            pooled_bytes: self.pooled_bytes.load(Ordering::Relaxed),
+            classes,
+        };
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// See [`BufferPool::take`], of the pool shared by the whole server.
+pub fn take(min_capacity: usize) -> Vec<u8> {
+    return POOL.take(min_capacity);
+}
+
+pub fn give_back(buffer: Vec<u8>) {
+    POOL.give_back(buffer);
+}
+
+pub fn reserve(buffer: &mut Vec<u8>, additional: usize) {
+    POOL.reserve(buffer, additional);
+}
+
+pub fn release(buffer: &mut Vec<u8>) {
+    POOL.release(buffer);
+}
+
+pub fn stats() -> PoolStats {
+    return POOL.stats();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::buffer_pool::{BufferPool, BUFFERS_PER_CLASS};
+
+    #[test]
+    fn test_take_reuses_returned_buffer() {
+        let pool = BufferPool::new();
+        let mut buffer = pool.take(20_000);
+        assert_eq!(32 * 1024, buffer.capacity());
+        buffer.extend_from_slice(b"data");
+        let address = buffer.as_ptr();
+        pool.give_back(buffer);
+
+        let reused = pool.take(17_000);
+        assert_eq!(address, reused.as_ptr());
+        assert!(reused.is_empty());
+
+        let stats = pool.stats();
+        assert_eq!((1, 1, 1), (stats.hits, stats.misses, stats.returned));
+        assert_eq!(0, stats.pooled_bytes);
+    }
+
+    #[test]
+    fn test_small_and_huge_buffers_are_not_pooled() {
+        let pool = BufferPool::new();
+        pool.give_back(pool.take(100));
+        pool.give_back(Vec::with_capacity(1024));
+
+        let stats = pool.stats();
+        assert_eq!((0, 0, 0), (stats.hits, stats.misses, stats.returned));
+        assert_eq!(1 << 30, pool.take(1 << 30).capacity());
+    }
+
+    #[test]
+    fn test_full_class_discards() {
+        let pool = BufferPool::new();
+        for _ in 0..BUFFERS_PER_CLASS + 1 {
+            pool.give_back(Vec::with_capacity(16 * 1024));
+        }
+
+        let stats = pool.stats();
+        assert_eq!(BUFFERS_PER_CLASS as u64, stats.returned);
+        assert_eq!(1, stats.discarded);
+        assert_eq!((16 * 1024, BUFFERS_PER_CLASS), stats.classes[0]);
+    }
+
+    #[test]
+    fn test_reserve_and_release() {
+        let pool = BufferPool::new();
+        let mut buffer = b"start".to_vec();
+        pool.reserve(&mut buffer, 40_000);
+
+        assert_eq!(b"start", buffer.as_slice());
+        assert_eq!(64 * 1024, buffer.capacity());
+
+        buffer.clear();
+        pool.release(&mut buffer);
+        assert_eq!(0, buffer.capacity());
+        assert_eq!(64 * 1024, pool.stats().pooled_bytes);
+    }
+}
@@ -3,11 +3,59 @@ use std::{
     net::{IpAddr, Ipv4Addr},
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 
 use log::{trace, LevelFilter};
 
-use crate::{db::data_store::DbConfig, utils::logger::set_log_level};
+use crate::{
+    commands::{acl::AclRules, mode::ServerMode},
+    consts::DEFAULT_SHUTDOWN_TIMEOUT,
+    db::{
+        data_store::DbConfig,
+        eviction::{EvictionPolicy, DEFAULT_MAXMEMORY_SAMPLES},
+        storage::StorageBackend,
+    },
+    parser::messages::ProtocolLimits,
+    utils::{
+        ip_filter::{parse_cidr_list, Cidr, IpFilter},
+        logger::set_log_level,
+        units::parse_memory,
+    },
+};
+
+/// Arguments taking a value, `--help` and `--version` are handled before.
+const KNOWN_ARGS: [&str; 26] = [
+    "--port",
+    "--host",
+    "--threads",
+    "--log-level",
+    "--dir",
+    "--dbfilename",
+    "--replicaof",
+    "--acl-rules",
+    "--shards",
+    "--storage-backend",
+    "--proto-max-bulk-len",
+    "--proto-max-multibulk-len",
+    "--proto-max-inline-len",
+    "--client-query-buffer-limit",
+    "--maxmemory",
+    "--maxmemory-policy",
+    "--maxmemory-samples",
+    "--masterauth",
+    "--repl-compression",
+    "--command-time-limit",
+    "--shutdown-timeout",
+    "--cluster-enabled",
+    "--allow-ips",
+    "--deny-ips",
+    "--max-connections-per-ip",
+    "--preload",
+];
+
+/// Arguments without a value.
+const KNOWN_FLAGS: [&str; 1] = ["--sentinel"];
 
 pub struct Args {
     pub host: IpAddr,
@@ -17,6 +65,23 @@ pub struct Args {
     pub db_dir: PathBuf,
     pub db_filename: String,
     pub replica_connection: Option<(String, u16)>,
+    pub acl_rules: AclRules,
+    pub shards: Option<usize>,
+    pub storage_backend: StorageBackend,
+    pub protocol_limits: ProtocolLimits,
+    pub maxmemory: u64,
+    pub maxmemory_policy: EvictionPolicy,
+    pub maxmemory_samples: usize,
+    pub masterauth: Option<String>,
+    pub repl_compression: bool,
+    pub command_time_limit: Duration,
+    pub shutdown_timeout: Duration,
+    pub mode: ServerMode,
+    pub allow_ips: Vec<Cidr>,
+    pub deny_ips: Vec<Cidr>,
+    pub max_connections_per_ip: usize,
+    /// File of RESP encoded commands executed at startup, before connections are accepted.
+    pub preload: Option<PathBuf>,
 }
 
 impl Args {
@@ -36,10 +101,71 @@ impl Args {
             "  --dir <path>                    Specifies the db dir (default: /tmp/redis-files)"
         );
         println!("  --dbfilename <file>             Specifies the filename where redis will save its data (default: redis.rdb)");
-        println!("  --replicaof \"<host> <port>\"   Specified the redis server to be a replica of (default none)")
+        println!("  --replicaof \"<host> <port>\"   Specified the redis server to be a replica of (default none)");
+        println!("  --masterauth <password>         Specifies the password to authenticate with at the master (default: none)");
+        println!("  --repl-compression <yes|no>     Asks the master for an lz4 compressed replication stream (default: no)");
+        println!("  --allow-ips \"<ranges>\"        Specifies the only addresses allowed to connect, e.g. \"10.0.0.0/8 ::1\" (default: all)");
+        println!("  --deny-ips \"<ranges>\"         Specifies addresses not allowed to connect, takes precedence over --allow-ips (default: none)");
+        println!("  --max-connections-per-ip <num>  Specifies the most concurrent connections of a single address. 0 is no limit (default: 0)");
+        println!("  --acl-rules \"<rules>\"         Specifies the command categories of the default user, e.g. \"+@all -@dangerous\" (default: +@all)");
+        println!("  --shards <num>                  Specifies the number of keyspace shards, a power of two (default: 4 * cpu count)");
+        println!("  --storage-backend <name>        Specifies the map holding the keys, dashmap or hashmap (default: dashmap)");
+        println!("  --proto-max-bulk-len <bytes>    Specifies the longest bulk string a client may send, e.g. 512mb (default: 536870912)");
+        println!("  --proto-max-multibulk-len <num> Specifies the most elements of an array a client may send (default: 2147483647)");
+        println!("  --proto-max-inline-len <bytes>  Specifies the longest line of a message a client may send, e.g. 64kb (default: 65536)");
+        println!("  --client-query-buffer-limit <bytes>");
+        println!("                                  Specifies the most input a client may buffer, e.g. 1gb (default: 1073741824)");
+        println!("  --maxmemory <bytes>             Specifies the memory limit, e.g. 100mb. 0 is no limit (default: 0)");
+        println!("  --maxmemory-policy <name>       Specifies which keys are evicted above maxmemory, e.g. allkeys-lru (default: noeviction)");
+        println!("  --maxmemory-samples <num>       Specifies how many keys are sampled per eviction (default: 5)");
+        println!("  --command-time-limit <ms>       Specifies how long a command may run before it is cancelled. 0 is no limit (default: 0)");
+        println!("  --shutdown-timeout <seconds>    Specifies how long commands may take to finish on shutdown before connections are closed (default: 10)");
+        println!("  --preload <file.resp>           Specifies a file of RESP encoded commands executed at startup, e.g. to load fixtures");
+        println!("  --cluster-enabled <yes|no>      Runs the server as a single node cluster, enabling the CLUSTER command (default: no)");
+        println!("  --sentinel                      Runs the server in sentinel-lite mode, only monitoring commands are available");
+        println!("  --help, -h                      Prints this help");
+        println!("  --version, -v                   Prints the version");
+    }
+
+    fn print_version() {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     }
 
+    /// Parses the command line arguments. Invalid arguments are reported all at once
+    /// together with the usage, then the process exits with status 1. `--help` and
+    /// `--version` exit with status 0.
     pub fn parse() -> Args {
+        let raw_args: Vec<String> = std::env::args().skip(1).collect();
+        if raw_args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            Args::print_help();
+            std::process::exit(0);
+        }
+        if raw_args.iter().any(|arg| arg == "--version" || arg == "-v") {
+            Args::print_version();
+            std::process::exit(0);
+        }
+
+        let args = match Args::try_parse(raw_args.into_iter()) {
+            Ok(args) => args,
+            Err(errors) => {
+                eprintln!("Invalid configuration:");
+                for error in errors {
+                    eprintln!("  - {}", error);
+                }
+                eprintln!();
+                Args::print_help();
+                std::process::exit(1);
+            }
+        };
+
+        set_log_level(&args);
+        // create_file_if_missing(&args.db_dir.clone(), &args.db_filename);
+        return args;
+    }
+
+    /// Parses the arguments and collects every configuration error instead of stopping at
+    /// the first one.
+    pub fn try_parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, Vec<String>> {
         let mut port: u16 = 6379;
         let mut host: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
         let mut threads: u8 = 4;
@@ -47,102 +173,356 @@ impl Args {
         let mut db_dir = Path::new("/tmp/redis-files").to_path_buf();
         let mut db_filename = "redis.rdb".to_string();
         let mut replica_connection = None;
+        let mut acl_rules = AclRules::allow_all();
+        let mut shards = None;
+        let mut storage_backend = StorageBackend::DashMap;
+        let mut protocol_limits = ProtocolLimits::default();
+        let mut maxmemory = 0;
+        let mut maxmemory_policy = EvictionPolicy::NoEviction;
+        let mut maxmemory_samples = DEFAULT_MAXMEMORY_SAMPLES;
+        let mut masterauth = None;
+        let mut repl_compression = false;
+        let mut command_time_limit = Duration::ZERO;
+        let mut shutdown_timeout = DEFAULT_SHUTDOWN_TIMEOUT;
+        let mut cluster_enabled = false;
+        let mut sentinel = false;
+        let mut allow_ips = Vec::new();
+        let mut deny_ips = Vec::new();
+        let mut max_connections_per_ip = 0;
+        let mut preload = None;
 
-        let mut args = std::env::args().skip(1);
+        let mut errors = Vec::new();
         while let Some(arg) = args.next() {
-            match arg.as_str() {
-                "--help" => {
-                    Args::print_help();
-                    panic!("exit after help");
-                }
-                "--port" => {
-                    port = args
-                        .next()
-                        .expect("Port must be specified")
-                        .parse::<u16>()
-                        .expect("Failed to parse port");
+            if arg == "--sentinel" {
+                sentinel = true;
+                continue;
+            }
+            if !KNOWN_ARGS.contains(&arg.as_str()) {
+                errors.push(match suggest_arg(&arg) {
+                    Some(known) => format!("Unknown argument '{}', did you mean {}?", arg, known),
+                    None => format!("Unknown argument '{}'", arg),
+                });
+                continue;
+            }
+
+            let value = match args.next() {
+                Some(value) => value,
+                None => {
+                    errors.push(format!("{} requires a value", arg));
+                    continue;
                 }
-                "--host" => {
-                    let host_string = args.next().expect("Host Addr must be specified");
-
-                    host = match IpAddr::from_str(&host_string) {
-                        Ok(val) => val,
-                        Err(err) => {
-                            panic!("Given ip is neither ipv4 not ipv6: {}", err);
-                        }
+            };
+
+            let result = match arg.as_str() {
+                "--port" => parse_port(&value).map(|val| port = val),
+                "--host" => IpAddr::from_str(&value).map(|val| host = val).map_err(|_| {
+                    format!("Host '{}' is neither an ipv4 nor an ipv6 address", value)
+                }),
+                "--threads" => match value.parse::<u8>() {
+                    Ok(0) | Err(_) => Err(format!(
+                        "Thread count '{}' must be a number between 1 and 255",
+                        value
+                    )),
+                    Ok(val) => {
+                        threads = val;
+                        Ok(())
+                    }
+                },
+                "--log-level" => LevelFilter::from_str(&value)
+                    .map(|val| log_level = val)
+                    .map_err(|_| {
+                        format!(
+                            "Log level '{}' must be one of trace, debug, info, warn, error, off",
+                            value
+                        )
+                    }),
+                "--dir" => {
+                    let path = Path::new(&value);
+                    if path.is_absolute() {
+                        db_dir = path.to_path_buf();
+                        Ok(())
+                    } else {
+                        Err(format!("Dir '{}' must be an absolute path", value))
                     }
                 }
-                "--threads" => {
-                    threads = args
-                        .next()
-                        .expect("Thread count must be specified")
-                        .parse::<u8>()
-                        .expect("Failed to parse thread count");
+                "--dbfilename" => {
+                    db_filename = value;
+                    Ok(())
                 }
-                "--log-level" => {
-                    let raw = args.next().expect("Log Level must be specified");
-                    log_level = match LevelFilter::from_str(raw.as_str()) {
-                        Ok(val) => val,
-                        Err(err) => panic!("{}", err.to_string()),
-                    };
+                "--replicaof" => parse_replica_of(&value).map(|val| replica_connection = Some(val)),
+                "--masterauth" => {
+                    masterauth = Some(value);
+                    Ok(())
                 }
-                "--dir" => {
-                    let raw = args.next().expect("redis file dir must be specified");
-
-                    db_dir = Path::new(&raw).to_path_buf();
-                    if !db_dir.is_absolute() {
-                        panic!("Given dir path must be absolut!")
+                "--repl-compression" => match value.to_ascii_lowercase().as_str() {
+                    "yes" => {
+                        repl_compression = true;
+                        Ok(())
+                    }
+                    "no" => {
+                        repl_compression = false;
+                        Ok(())
+                    }
+                    _ => Err(format!("Repl compression '{}' must be yes or no", value)),
+                },
+                "--acl-rules" => AclRules::parse(value.trim_matches('"'))
+                    .map(|val| acl_rules = val)
+                    .map_err(|err| format!("Invalid ACL rules '{}': {}", value, err)),
+                "--shards" => match value.parse::<usize>() {
+                    Ok(amount) if amount >= 2 && amount.is_power_of_two() => {
+                        shards = Some(amount);
+                        Ok(())
+                    }
+                    _ => Err(format!(
+                        "Shard count '{}' must be a power of two greater than 1",
+                        value
+                    )),
+                },
+                "--storage-backend" => match StorageBackend::from_name(&value) {
+                    Some(backend) => {
+                        storage_backend = backend;
+                        Ok(())
                     }
+                    None => Err(format!(
+                        "Storage backend '{}' must be one of dashmap, hashmap",
+                        value
+                    )),
+                },
+                "--proto-max-bulk-len" => {
+                    parse_memory_limit(&arg, &value).map(|val| protocol_limits.max_bulk_len = val)
                 }
-                "--dbfilename" => {
-                    db_filename = args.next().expect("Redis file name must be specified");
+                "--proto-max-multibulk-len" => {
+                    parse_limit(&arg, &value).map(|val| protocol_limits.max_multibulk_len = val)
+                }
+                "--proto-max-inline-len" => {
+                    parse_memory_limit(&arg, &value).map(|val| protocol_limits.max_inline_len = val)
+                }
+                "--client-query-buffer-limit" => parse_memory_limit(&arg, &value)
+                    .map(|val| protocol_limits.max_query_buffer_len = val),
+                "--maxmemory" => parse_memory(&value)
+                    .map(|val| maxmemory = val)
+                    .map_err(|_| {
+                        format!("Maxmemory '{}' must be a memory value like 100mb", value)
+                    }),
+                "--maxmemory-policy" => EvictionPolicy::from_name(&value)
+                    .map(|policy| maxmemory_policy = policy)
+                    .map_err(|reason| format!("Maxmemory policy '{}': {}", value, reason)),
+                "--maxmemory-samples" => match value.parse::<usize>() {
+                    Ok(samples) if samples > 0 => {
+                        maxmemory_samples = samples;
+                        Ok(())
+                    }
+                    _ => Err(format!(
+                        "Maxmemory samples '{}' must be a positive number",
+                        value
+                    )),
+                },
+                "--command-time-limit" => value
+                    .parse::<u64>()
+                    .map(|millis| command_time_limit = Duration::from_millis(millis))
+                    .map_err(|_| {
+                        format!(
+                            "Command time limit '{}' must be a number of milliseconds",
+                            value
+                        )
+                    }),
+                "--shutdown-timeout" => value
+                    .parse::<u64>()
+                    .map(|secs| shutdown_timeout = Duration::from_secs(secs))
+                    .map_err(|_| {
+                        format!("Shutdown timeout '{}' must be a number of seconds", value)
+                    }),
+                "--cluster-enabled" => match value.to_ascii_lowercase().as_str() {
+                    "yes" => {
+                        cluster_enabled = true;
+                        Ok(())
+                    }
+                    "no" => {
+                        cluster_enabled = false;
+                        Ok(())
+                    }
+                    _ => Err(format!("Cluster enabled '{}' must be yes or no", value)),
+                },
+                "--allow-ips" => parse_cidr_list(&value)
+                    .map(|val| allow_ips = val)
+                    .map_err(|err| format!("Invalid --allow-ips: {}", err)),
+                "--deny-ips" => parse_cidr_list(&value)
+                    .map(|val| deny_ips = val)
+                    .map_err(|err| format!("Invalid --deny-ips: {}", err)),
+                "--max-connections-per-ip" => value
+                    .parse::<usize>()
+                    .map(|val| max_connections_per_ip = val)
+                    .map_err(|_| {
+                        format!(
+                            "Max connections per ip '{}' must be a number, 0 is no limit",
+                            value
+                        )
+                    }),
+                "--preload" => {
+                    let path = PathBuf::from(&value);
+                    if path.is_file() {
+                        preload = Some(path);
+                        Ok(())
+                    } else {
+                        Err(format!("Preload file '{}' does not exist", value))
+                    }
                 }
-                "--replicaof" => {
-                    let arg = args
-                        .next()
-                        .expect("Redis replication arg must be specified");
+                _ => unreachable!("all known arguments are handled"),
+            };
 
-                    let (host, port) = arg
-                        .trim_matches('"')
-                        .split_once(' ')
-                        .expect("Redis replication arg must be: \"<host> <port>\"");
+            if let Err(error) = result {
+                errors.push(error);
+            }
+        }
 
-                    let port = u16::from_str(port).unwrap();
+        // a bulk string longer than the buffer could never be received
+        if protocol_limits.max_query_buffer_len < protocol_limits.max_bulk_len {
+            errors.push(
+                "--client-query-buffer-limit must not be smaller than --proto-max-bulk-len"
+                    .to_string(),
+            );
+        }
 
-                    replica_connection = Some((host.to_owned(), port));
-                }
-                _ => {
-                    Args::print_help();
-                    panic!("Invalid argument")
-                }
+        let mode = match (cluster_enabled, sentinel) {
+            (false, false) => ServerMode::Standalone,
+            (true, false) => ServerMode::Cluster,
+            (false, true) => ServerMode::Sentinel,
+            (true, true) => {
+                errors.push("--sentinel can not be combined with --cluster-enabled".to_string());
+                ServerMode::Standalone
             }
+        };
+        // a sentinel holds no data, so there is nothing to replicate
+        if mode == ServerMode::Sentinel && replica_connection.is_some() {
+            errors.push("--sentinel can not be combined with --replicaof".to_string());
+        }
+        // the keys of a replica are replaced on its full resync
+        if preload.is_some() && replica_connection.is_some() {
+            errors.push("--preload can not be combined with --replicaof".to_string());
         }
 
-        let args = Args {
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        return Ok(Args {
             host,
             port,
             threads,
             log_level,
-            db_dir: db_dir.to_path_buf(),
+            db_dir,
             db_filename,
             replica_connection,
-        };
-
-        set_log_level(&args);
-        // create_file_if_missing(&args.db_dir.clone(), &args.db_filename);
-        return args;
+            acl_rules,
+            shards,
+            storage_backend,
+            protocol_limits,
+            maxmemory,
+            maxmemory_policy,
+            maxmemory_samples,
+            masterauth,
+            repl_compression,
+            command_time_limit,
+            shutdown_timeout,
+            mode,
+            allow_ips,
+            deny_ips,
+            max_connections_per_ip,
+            preload,
+        });
     }
 
     pub fn get_db_config(&self) -> DbConfig {
-        return DbConfig::new(
+        let mut db_config = DbConfig::new(
             self.db_dir.clone(),
             self.db_filename.clone(),
             self.replica_connection.clone(),
             self.port.clone(),
         );
+        db_config.shard_amount = self.shards;
+        db_config.storage_backend = self.storage_backend;
+        db_config.maxmemory = self.maxmemory;
+        db_config.maxmemory_policy = self.maxmemory_policy;
+        db_config.maxmemory_samples = self.maxmemory_samples;
+        db_config.masterauth = self.masterauth.clone();
+        db_config.repl_compression = self.repl_compression;
+        db_config.command_time_limit = self.command_time_limit;
+        db_config.shutdown_timeout = self.shutdown_timeout;
+        return db_config;
+    }
+
+    pub fn get_ip_filter(&self) -> IpFilter {
+        return IpFilter::new(
+            self.allow_ips.clone(),
+            self.deny_ips.clone(),
+            self.max_connections_per_ip,
+        );
     }
 }
 
+/// The known argument closest to a misspelled one, if it is close enough.
+fn suggest_arg(arg: &str) -> Option<&'static str> {
+    let max_distance = (arg.len() / 3).max(2);
+    return KNOWN_ARGS
+        .iter()
+        .chain(KNOWN_FLAGS.iter())
+        .map(|known| (edit_distance(arg, known), *known))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known);
+}
+
+/// Levenshtein distance of two ascii strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+        }
+        previous = current;
+    }
+    return previous[b.len()];
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    return value
+        .parse::<u16>()
+        .map_err(|_| format!("Port '{}' must be a number between 0 and 65535", value));
+}
+
+fn parse_limit(arg: &str, value: &str) -> Result<usize, String> {
+    return match value.parse::<usize>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(format!("{} '{}' must be a positive number", arg, value)),
+    };
+}
+
+/// Memory sizes like `512mb`, see [`parse_memory`].
+fn parse_memory_limit(arg: &str, value: &str) -> Result<usize, String> {
+    return match parse_memory(value).map(usize::try_from) {
+        Ok(Ok(limit)) if limit > 0 => Ok(limit),
+        _ => Err(format!(
+            "{} '{}' must be a positive memory value like 512mb",
+            arg, value
+        )),
+    };
+}
+
+/// Parses `"<host> <port>"`.
+fn parse_replica_of(value: &str) -> Result<(String, u16), String> {
+    return match value.trim_matches('"').split_once(' ') {
+        Some((host, port)) if !host.is_empty() => Ok((host.to_owned(), parse_port(port.trim())?)),
+        _ => Err(format!(
+            "Replicaof '{}' must be \"<host> <port>\", the port is missing",
+            value
+        )),
+    };
+}
+
 fn create_file_if_missing(root: &Path, filename: &str) {
     // Join root and filename into full path
     let full_path = root.join(filename);
@@ -167,6 +547,193 @@ mod tests {
 
     use super::*;
 
+    fn try_parse(args: Vec<&str>) -> Result<Args, Vec<String>> {
+        return Args::try_parse(args.into_iter().map(|arg| arg.to_string()));
+    }
+
+    #[test]
+    fn parse_valid_args() {
+        let args = try_parse(vec![
+            "--port",
+            "6380",
+            "--replicaof",
+            "localhost 6379",
+            "--dir",
+            "/tmp/redis",
+            "--shards",
+            "8",
+            "--storage-backend",
+            "hashmap",
+        ])
+        .ok()
+        .unwrap();
+
+        assert_eq!(6380, args.port);
+        assert_eq!(
+            Some(("localhost".to_string(), 6379)),
+            args.replica_connection
+        );
+        assert_eq!(PathBuf::from("/tmp/redis"), args.db_dir);
+        assert_eq!(Some(8), args.shards);
+        assert_eq!(StorageBackend::ShardedHashMap, args.storage_backend);
+    }
+
+    #[test]
+    fn parse_collects_all_errors() {
+        let errors = try_parse(vec![
+            "--port",
+            "70000",
+            "--dir",
+            "relative",
+            "--verbose",
+            "--replicaof",
+            "localhost",
+            "--threads",
+        ])
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                "Port '70000' must be a number between 0 and 65535",
+                "Dir 'relative' must be an absolute path",
+                "Unknown argument '--verbose'",
+                "Replicaof 'localhost' must be \"<host> <port>\", the port is missing",
+                "--threads requires a value",
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn parse_preload() {
+        let file = std::env::temp_dir().join(format!("preload-{}.resp", std::process::id()));
+        fs::write(&file, "*1\r\n$4\r\nPING\r\n").unwrap();
+        let path = file.to_str().unwrap();
+
+        assert_eq!(
+            Some(file.clone()),
+            try_parse(vec!["--preload", path]).ok().unwrap().preload
+        );
+        assert_eq!(
+            vec!["--preload can not be combined with --replicaof"],
+            try_parse(vec!["--preload", path, "--replicaof", "localhost 6379"])
+                .err()
+                .unwrap()
+        );
+        fs::remove_file(&file).unwrap();
+        assert!(try_parse(vec!["--preload", path]).is_err());
+    }
+
+    #[test]
+    fn parse_protocol_limits() {
+        let args = try_parse(vec!["--proto-max-bulk-len", "1kb", "--maxmemory", "2gb"])
+            .ok()
+            .unwrap();
+        assert_eq!(1024, args.protocol_limits.max_bulk_len);
+        assert_eq!(2 * 1024 * 1024 * 1024, args.maxmemory);
+        assert_eq!(
+            ProtocolLimits::default().max_inline_len,
+            args.protocol_limits.max_inline_len
+        );
+
+        assert_eq!(
+            vec!["--proto-max-inline-len '0' must be a positive memory value like 512mb"],
+            try_parse(vec!["--proto-max-inline-len", "0"])
+                .err()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_query_buffer_limit() {
+        let args = try_parse(vec![
+            "--client-query-buffer-limit",
+            "1mb",
+            "--proto-max-bulk-len",
+            "1mb",
+        ])
+        .ok()
+        .unwrap();
+        assert_eq!(1024 * 1024, args.protocol_limits.max_query_buffer_len);
+
+        assert_eq!(
+            vec!["--client-query-buffer-limit must not be smaller than --proto-max-bulk-len"],
+            try_parse(vec!["--client-query-buffer-limit", "1mb"])
+                .err()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_server_mode() {
+        assert_eq!(ServerMode::Standalone, try_parse(vec![]).ok().unwrap().mode);
+        assert_eq!(
+            ServerMode::Cluster,
+            try_parse(vec!["--cluster-enabled", "yes"])
+                .ok()
+                .unwrap()
+                .mode
+        );
+        assert_eq!(
+            ServerMode::Sentinel,
+            try_parse(vec!["--sentinel", "--port", "26379"])
+                .ok()
+                .unwrap()
+                .mode
+        );
+
+        assert_eq!(
+            vec![
+                "Cluster enabled 'maybe' must be yes or no",
+                "--sentinel can not be combined with --replicaof",
+            ],
+            try_parse(vec![
+                "--cluster-enabled",
+                "maybe",
+                "--sentinel",
+                "--replicaof",
+                "localhost 6379"
+            ])
+            .err()
+            .unwrap()
+        );
+        assert_eq!(
+            vec!["--sentinel can not be combined with --cluster-enabled"],
+            try_parse(vec!["--sentinel", "--cluster-enabled", "yes"])
+                .err()
+                .unwrap()
+        );
+        assert_eq!(Some("--sentinel"), suggest_arg("--sentinal"));
+    }
+
+    #[test]
+    fn parse_unknown_arg_suggestion() {
+        assert_eq!(
+            vec!["Unknown argument '--dbfilenme', did you mean --dbfilename?"],
+            try_parse(vec!["--dbfilenme", "dump.rdb"]).err().unwrap()[..1]
+        );
+        assert_eq!(Some("--replicaof"), suggest_arg("--replica-of"));
+        assert_eq!(Some("--port"), suggest_arg("-port"));
+        assert_eq!(None, suggest_arg("--verbose"));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("--dir", "--dir"));
+        assert_eq!(1, edit_distance("--dr", "--dir"));
+        assert_eq!(3, edit_distance("", "abc"));
+        assert_eq!(2, edit_distance("--prot", "--port"));
+    }
+
+    #[test]
+    fn parse_replica_of_invalid_port() {
+        assert_eq!(
+            Err("Port 'abc' must be a number between 0 and 65535".to_string()),
+            parse_replica_of("localhost abc")
+        );
+    }
+
     #[test]
     fn parse_ipv4_address() {
         let result = IpAddr::V4(Ipv4Addr::LOCALHOST);
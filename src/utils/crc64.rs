@@ -0,0 +1,36 @@
+/// Reflected form of the Jones polynomial 0xad93d23594c935a9 used by redis.
+const POLY: u64 = 0x95AC_9329_AC4B_C9B5;
+
+/// CRC64 (Jones) as used for the checksum at the end of rdb files.
+pub fn crc64(bytes: &[u8]) -> u64 {
+    let mut crc: u64 = 0;
+
+    for byte in bytes {
+        crc ^= *byte as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    return crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc64;
+
+    #[test]
+    fn test_check_value() {
+        // check value from the redis crc64 implementation
+        assert_eq!(0xe9c6d914c4b8d9ca, crc64(b"123456789"));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(0, crc64(&[]));
+    }
+}
@@ -0,0 +1,138 @@
+//! Time budget of the command running on the current thread.
+//!
+//! Long running commands check the budget cooperatively, a command over it stops and
+//! replies with an error instead of occupying its worker. Only commands that can stop
+//! without leaving changes half done check it, e.g. KEYS, so a cancelled command never
+//! changed the keyspace.
+
+use std::{
+    cell::Cell,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    commands::reply::{self, ErrorCode},
+    parser::messages::RedisMessageType,
+};
+
+/// Calls of [`Budget::tick`] between two looks at the clock.
+const CHECK_INTERVAL: u32 = 1024;
+
+/// Longest nap of [`sleep`], so a sleeping command notices its deadline in time.
+const SLEEP_SLICE: Duration = Duration::from_millis(10);
+
+thread_local! {
+    /// The deadline and the limit it was derived from, for the error message.
+    static DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+}
+
+/// Restores the deadline of the outer budget, even if the command panics.
+struct Reset(Option<(Instant, Duration)>);
+
+impl Drop for Reset {
+    fn drop(&mut self) {
+        DEADLINE.with(|deadline| deadline.set(self.0));
+    }
+}
+
+/// Runs `f` with a budget of `limit`, None runs it without one.
+pub fn with_budget<R, F: FnOnce() -> R>(limit: Option<Duration>, f: F) -> R {
+    let deadline = limit.map(|limit| (Instant::now() + limit, limit));
+    let _reset = Reset(DEADLINE.with(|current| current.replace(deadline)));
+    return f();
+}
+
+/// Fails once the budget of the current command is used up.
+pub fn check() -> Result<(), RedisMessageType> {
+    return match DEADLINE.with(Cell::get) {
+        Some((deadline, limit)) if Instant::now() >= deadline => Err(reply::error(
+            ErrorCode::Err,
+            format!(
+                "command cancelled after exceeding the time limit of {} ms",
+                limit.as_millis()
+            ),
+        )),
+        _ => Ok(()),
+    };
+}
+
+/// Sleeps for `duration`, but stops with an error once the budget is used up.
+pub fn sleep(duration: Duration) -> Result<(), RedisMessageType> {
+    let end = Instant::now() + duration;
+    loop {
+        check()?;
+        let remaining = end.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        thread::sleep(remaining.min(SLEEP_SLICE));
+    }
+}
+
+/// Checks the budget in a loop over many cheap steps, the clock is only read every
+/// [`CHECK_INTERVAL`] steps.
+#[derive(Debug, Default)]
+pub struct Budget {
+    ticks: u32,
+}
+
+impl Budget {
+    pub fn tick(&mut self) -> Result<(), RedisMessageType> {
+        self.ticks = self.ticks.wrapping_add(1);
+        if self.ticks % CHECK_INTERVAL != 0 {
+            return Ok(());
+        }
+        return check();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        parser::messages::RedisMessageType,
+        utils::deadline::{check, sleep, with_budget, Budget},
+    };
+
+    #[test]
+    fn test_budget_runs_out() {
+        let result = with_budget(Some(Duration::from_millis(20)), || {
+            assert_eq!(Ok(()), check());
+            return sleep(Duration::from_secs(5));
+        });
+
+        assert_eq!(
+            Err(RedisMessageType::error(
+                "ERR command cancelled after exceeding the time limit of 20 ms"
+            )),
+            result
+        );
+        assert_eq!(Ok(()), check(), "The budget ends with the command");
+    }
+
+    #[test]
+    fn test_without_budget() {
+        with_budget(None, || {
+            assert_eq!(Ok(()), sleep(Duration::from_millis(20)));
+        });
+    }
+
+    #[test]
+    fn test_nested_budget_is_restored() {
+        with_budget(Some(Duration::ZERO), || {
+            with_budget(None, || assert_eq!(Ok(()), check()));
+            assert!(check().is_err());
+        });
+    }
+
+    #[test]
+    fn test_tick_only_reads_the_clock_sometimes() {
+        with_budget(Some(Duration::ZERO), || {
+            let mut budget = Budget::default();
+            let failed = (0..2048).filter(|_| budget.tick().is_err()).count();
+            assert_eq!(2, failed);
+        });
+    }
+}
@@ -0,0 +1,122 @@
+//! Diagnostic snapshot of the whole server, logged on SIGUSR1.
+//!
+//! A hung server may not answer DEBUG or INFO any more, e.g. because all workers are busy.
+//! `kill -USR1 <pid>` still writes the snapshot to the log, the signal is handled on its own
+//! thread.
+
+use std::{fmt::Write, thread};
+
+use log::error;
+
+use crate::{
+    commands::{config, context::get_clients, info},
+    db::write_pause,
+    replication::master,
+    utils::thread_pool::PoolUsage,
+};
+
+/// Renders the state of the workers, clients, replicas and the config together with the
+/// INFO sections.
+pub fn snapshot(pool: &PoolUsage) -> String {
+    let mut dump = String::new();
+
+    let _ = writeln!(dump, "# Threads");
+    let _ = writeln!(dump, "workers:{}", pool.size());
+    let _ = writeln!(dump, "busy_workers:{}", pool.busy());
+    let _ = writeln!(dump, "writes_paused:{}", write_pause::is_paused());
+
+    let clients = get_clients();
+    let _ = writeln!(dump, "\n# Clients ({})", clients.len());
+    for client in clients {
+        let _ = writeln!(dump, "{}", client.describe());
+    }
+
+    let replicas = master::get_replicas();
+    let _ = writeln!(dump, "\n# Replicas ({})", replicas.len());
+    for replica in replicas {
+        let _ = writeln!(
+            dump,
+            "addr={} acked_offset={}",
+            replica.addr(),
+            replica.acked_offset()
+        );
+    }
+
+    let _ = writeln!(dump, "\n# Config");
+    for (name, value) in config::all_values() {
+        let _ = writeln!(dump, "{}:{}", name, value);
+    }
+
+    let _ = writeln!(dump);
+    dump.push_str(&info::render_all().replace("\r\n", "\n"));
+    return dump;
+}
+
+/// Logs a [`snapshot`] whenever the process receives SIGUSR1.
+#[cfg(unix)]
+pub fn dump_on_sigusr1(pool: PoolUsage) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let listener = move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                error!("Unable to listen for SIGUSR1: {}", err);
+                return;
+            }
+        };
+
+        runtime.block_on(async {
+            let mut signals = match signal(SignalKind::user_defined1()) {
+                Ok(signals) => signals,
+                Err(err) => {
+                    error!("Unable to listen for SIGUSR1: {}", err);
+                    return;
+                }
+            };
+
+            while signals.recv().await.is_some() {
+                // logged as an error, so it is not hidden by the default log level
+                error!("Received SIGUSR1, diagnostic dump:\n{}", snapshot(&pool));
+            }
+        });
+    };
+
+    if let Err(err) = thread::Builder::new()
+        .name("diagnostics".to_string())
+        .spawn(listener)
+    {
+        error!("Unable to spawn the diagnostics thread: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::data_store::init_test_db,
+        utils::{diagnostics::snapshot, thread_pool::ThreadPool},
+    };
+
+    #[test]
+    fn test_snapshot_sections() {
+        init_test_db();
+        let pool = ThreadPool::new(2);
+
+        let dump = snapshot(&pool.usage());
+
+        assert!(dump.starts_with("# Threads\nworkers:2\nbusy_workers:0\n"));
+        for section in [
+            "# Clients",
+            "# Replicas",
+            "# Config",
+            "# Server",
+            "# Keyspace",
+        ] {
+            assert!(dump.contains(section), "{} is missing", section);
+        }
+        assert!(dump.contains("\nreplica-read-only:yes\n"));
+    }
+}
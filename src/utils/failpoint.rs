@@ -0,0 +1,157 @@
+//! Failpoints inject faults at fixed points of the server, so replication edge cases can
+//! be tested deterministically.
+//!
+//! Only compiled with the `failpoints` feature, without it [`eval`] is always `None` and
+//! optimized away. Failpoints are set in tests with [`set`] or on startup with the
+//! `FAILPOINTS` environment variable, e.g. `FAILPOINTS="master-propagate=drop;replica-before-ack=delay(500)"`.
+//!
+//! Available failpoints:
+//! - `master-rdb-transfer`: after the first half of the rdb file is send to a replica
+//! - `master-propagate`: before a command is propagated to a replica
+//! - `replica-before-ack`: before a replica answers `REPLCONF GETACK`
+
+use std::{str::FromStr, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailAction {
+    /// Sleeps before continuing normally.
+    Delay(Duration),
+    /// Skips the data that would have been send.
+    Drop,
+    /// Closes the connection.
+    Disconnect,
+}
+
+impl FromStr for FailAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        return match value.trim().to_ascii_lowercase().as_str() {
+            "drop" => Ok(Self::Drop),
+            "disconnect" => Ok(Self::Disconnect),
+            other => other
+                .strip_prefix("delay(")
+                .and_then(|millis| millis.strip_suffix(')'))
+                .and_then(|millis| millis.parse::<u64>().ok())
+                .map(|millis| Self::Delay(Duration::from_millis(millis)))
+                .ok_or(format!("Invalid failpoint action '{}'", value)),
+        };
+    }
+}
+
+/// Parses `name=action` pairs separated by `;`.
+pub fn parse_failpoints(value: &str) -> Result<Vec<(String, FailAction)>, String> {
+    return value
+        .split(';')
+        .filter(|failpoint| !failpoint.trim().is_empty())
+        .map(|failpoint| {
+            let (name, action) = failpoint
+                .split_once('=')
+                .ok_or(format!("Failpoint '{}' must be name=action", failpoint))?;
+            return Ok((name.trim().to_string(), action.parse()?));
+        })
+        .collect();
+}
+
+#[cfg(feature = "failpoints")]
+mod registry {
+    use std::{collections::HashMap, sync::RwLock, thread};
+
+    use log::warn;
+    use once_cell::sync::Lazy;
+
+    use crate::utils::failpoint::{parse_failpoints, FailAction};
+
+    static FAILPOINTS: Lazy<RwLock<HashMap<String, FailAction>>> = Lazy::new(|| {
+        let failpoints = match std::env::var("FAILPOINTS") {
+            Ok(value) => parse_failpoints(&value).expect("Invalid FAILPOINTS variable"),
+            Err(_) => Vec::new(),
+        };
+        RwLock::new(failpoints.into_iter().collect())
+    });
+
+    pub fn set(name: &str, action: FailAction) {
+        FAILPOINTS
+            .write()
+            .expect("Unable to write the failpoints. Should never happen!")
+            .insert(name.to_string(), action);
+    }
+
+    pub fn remove(name: &str) {
+        FAILPOINTS
+            .write()
+            .expect("Unable to write the failpoints. Should never happen!")
+            .remove(name);
+    }
+
+    /// Returns the action of an active failpoint. Delays are applied right away.
+    pub fn eval(name: &str) -> Option<FailAction> {
+        let action = *FAILPOINTS
+            .read()
+            .expect("Unable to read the failpoints. Should never happen!")
+            .get(name)?;
+
+        warn!("Failpoint '{}' triggered: {:?}", name, action);
+        if let FailAction::Delay(delay) = action {
+            thread::sleep(delay);
+        }
+        return Some(action);
+    }
+}
+
+#[cfg(feature = "failpoints")]
+pub use registry::{eval, remove, set};
+
+#[cfg(not(feature = "failpoints"))]
+#[inline(always)]
+pub fn eval(_name: &str) -> Option<FailAction> {
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::utils::failpoint::{parse_failpoints, FailAction};
+
+    #[test]
+    fn test_parse_fail_action() {
+        assert_eq!(Ok(FailAction::Drop), "drop".parse());
+        assert_eq!(Ok(FailAction::Disconnect), " Disconnect ".parse());
+        assert_eq!(
+            Ok(FailAction::Delay(Duration::from_millis(250))),
+            "delay(250)".parse()
+        );
+        assert!("delay(abc)".parse::<FailAction>().is_err());
+        assert!("crash".parse::<FailAction>().is_err());
+    }
+
+    #[test]
+    fn test_parse_failpoints() {
+        assert_eq!(
+            Ok(vec![
+                ("master-propagate".to_string(), FailAction::Drop),
+                (
+                    "replica-before-ack".to_string(),
+                    FailAction::Delay(Duration::from_millis(5))
+                ),
+            ]),
+            parse_failpoints("master-propagate=drop; replica-before-ack=delay(5);")
+        );
+        assert!(parse_failpoints("master-propagate").is_err());
+    }
+
+    #[cfg(feature = "failpoints")]
+    #[test]
+    fn test_eval_failpoint() {
+        use crate::utils::failpoint::{eval, remove, set};
+
+        assert_eq!(None, eval("test-failpoint"));
+
+        set("test-failpoint", FailAction::Drop);
+        assert_eq!(Some(FailAction::Drop), eval("test-failpoint"));
+
+        remove("test-failpoint");
+        assert_eq!(None, eval("test-failpoint"));
+    }
+}
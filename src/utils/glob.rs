@@ -0,0 +1,148 @@
+/// Glob style matching as used by KEYS, see https://redis.io/docs/latest/commands/keys/
+///
+/// Supports `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and `\` to escape special chars. Like redis
+/// the matching is done on bytes.
+///
+/// The matcher never recurses. A `*` only remembers a single backtracking point, so the
+/// work is bounded by `pattern.len() * value.len()` steps, even for patterns like
+/// `*a*a*a*a*b` that make a recursive matcher take exponential time.
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let value = value.as_bytes();
+
+    let (mut p, mut v) = (0, 0);
+    // pattern index after the last star and the value index it is tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() {
+            if pattern[p] == b'*' {
+                while p < pattern.len() && pattern[p] == b'*' {
+                    p += 1;
+                }
+                if p == pattern.len() {
+                    return true;
+                }
+                backtrack = Some((p, v));
+                continue;
+            }
+
+            if let Some(consumed) = match_single(&pattern[p..], value[v]) {
+                p += consumed;
+                v += 1;
+                continue;
+            }
+        }
+
+        // let the last star match one more byte and try again
+        match backtrack {
+            Some((star_p, star_v)) => {
+                p = star_p;
+                v = star_v + 1;
+                backtrack = Some((star_p, star_v + 1));
+            }
+            None => return false,
+        }
+    }
+
+    return pattern[p..].iter().all(|&c| c == b'*');
+}
+
+/// Matches the first token of the pattern (anything but `*`) against a single byte.
+/// Returns the amount of pattern bytes the token is long if it matches.
+fn match_single(pattern: &[u8], c: u8) -> Option<usize> {
+    return match pattern[0] {
+        b'?' => Some(1),
+        b'[' => match_class(pattern, c),
+        // a trailing backslash is a literal backslash
+        b'\\' if pattern.len() > 1 => (pattern[1] == c).then_some(2),
+        literal => (literal == c).then_some(1),
+    };
+}
+
+/// Matches a `[...]` class. An unterminated class ends with the pattern.
+fn match_class(pattern: &[u8], c: u8) -> Option<usize> {
+    let mut index = 1;
+    let negate = pattern.get(index) == Some(&b'^');
+    if negate {
+        index += 1;
+    }
+
+    let mut matched = false;
+    while index < pattern.len() && pattern[index] != b']' {
+        if pattern[index] == b'\\' && index + 1 < pattern.len() {
+            matched |= pattern[index + 1] == c;
+            index += 2;
+        } else if index + 2 < pattern.len()
+            && pattern[index + 1] == b'-'
+            && pattern[index + 2] != b']'
+        {
+            let (start, end) = (pattern[index], pattern[index + 2]);
+            let (start, end) = (start.min(end), start.max(end));
+            matched |= (start..=end).contains(&c);
+            index += 3;
+        } else {
+            matched |= pattern[index] == c;
+            index += 1;
+        }
+    }
+
+    // include the closing bracket
+    let length = (index + 1).min(pattern.len());
+    return (matched != negate).then_some(length);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::utils::glob::glob_match;
+
+    #[test]
+    fn test_literal_and_wildcards() {
+        assert!(glob_match("foo", "foo"));
+        assert!(!glob_match("foo", "foobar"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("foo*", "foobar"));
+        assert!(glob_match("*bar", "foobar"));
+        assert!(glob_match("f*o*r", "foobar"));
+        assert!(!glob_match("f*o*z", "foobar"));
+        assert!(glob_match("h?llo", "hello"));
+        assert!(!glob_match("h?llo", "hllo"));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(glob_match("h[ae]llo", "hallo"));
+        assert!(!glob_match("h[ae]llo", "hillo"));
+        assert!(glob_match("h[^e]llo", "hallo"));
+        assert!(!glob_match("h[^e]llo", "hello"));
+        assert!(glob_match("h[a-b]llo", "hbllo"));
+        assert!(glob_match("h[b-a]llo", "hallo"));
+        assert!(glob_match("h[a-]", "h-"));
+        // like redis, an unterminated class ends with the pattern
+        assert!(glob_match("key[a", "keya"));
+        assert!(!glob_match("key[", "key["));
+        assert!(glob_match("a[\\]]", "a]"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(glob_match("a\\*", "a*"));
+        assert!(!glob_match("a\\*", "ab"));
+        assert!(glob_match("a\\?", "a?"));
+        assert!(glob_match("a\\", "a\\"));
+    }
+
+    #[test]
+    fn test_pathological_pattern_is_fast() {
+        let pattern = format!("{}b", "*a".repeat(50));
+        let value = "a".repeat(10000);
+
+        let start = Instant::now();
+        assert!(!glob_match(&pattern, &value));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
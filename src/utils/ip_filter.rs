@@ -0,0 +1,282 @@
+//! Checks applied to every connection when it is accepted, before it occupies a thread.
+//!
+//! Source addresses can be denied or allowed by CIDR ranges, and the concurrent
+//! connections of a single address can be limited. Useful when the server is reachable
+//! from a shared network.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::OnceCell;
+
+static IP_FILTER: OnceCell<IpFilter> = OnceCell::new();
+
+/// An address range like `10.0.0.0/8`, a plain address is a range of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        return match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => prefix_matches(
+                network.to_bits().into(),
+                ip.to_bits().into(),
+                self.prefix,
+                32,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(network.to_bits(), ip.to_bits(), self.prefix, 128)
+            }
+            _ => false,
+        };
+    }
+}
+
+fn prefix_matches(network: u128, ip: u128, prefix: u8, bits: u8) -> bool {
+    let host_bits = u32::from(bits - prefix);
+    return network.checked_shr(host_bits).unwrap_or(0) == ip.checked_shr(host_bits).unwrap_or(0);
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is neither an ip address nor a CIDR range", value);
+        let (network, prefix) = match value.split_once('/') {
+            Some((network, prefix)) => (network, Some(prefix)),
+            None => (value, None),
+        };
+
+        let network = IpAddr::from_str(network)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(invalid)?,
+            None => bits,
+        };
+        return Ok(Self { network, prefix });
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}/{}", self.network, self.prefix);
+    }
+}
+
+/// Parses a list of ranges separated by spaces or commas.
+pub fn parse_cidr_list(value: &str) -> Result<Vec<Cidr>, String> {
+    return value
+        .trim_matches('"')
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|cidr| !cidr.is_empty())
+        .map(Cidr::from_str)
+        .collect();
+}
+
+/// Why a connection was refused, sent to the client before it is closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    Denied(IpAddr),
+    TooManyConnections(IpAddr, usize),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::Denied(ip) => write!(f, "connections from {} are not allowed", ip),
+            Self::TooManyConnections(ip, limit) => write!(
+                f,
+                "max number of {} connections per ip reached by {}",
+                limit, ip
+            ),
+        };
+    }
+}
+
+/// Counts a connection of its address until it is dropped.
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    ip: IpAddr,
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut connections = lock(&self.connections);
+        if let Some(count) = connections.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+fn lock(
+    connections: &Mutex<HashMap<IpAddr, usize>>,
+) -> std::sync::MutexGuard<'_, HashMap<IpAddr, usize>> {
+    return connections
+        .lock()
+        .expect("Connection count lock poisoned. Should never happen!");
+}
+
+#[derive(Debug, Default)]
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+    /// 0 is no limit.
+    max_connections_per_ip: usize,
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl IpFilter {
+    /// An empty allow list allows every address, the deny list takes precedence.
+    pub fn new(allow: Vec<Cidr>, deny: Vec<Cidr>, max_connections_per_ip: usize) -> Self {
+        return Self {
+            allow,
+            deny,
+            max_connections_per_ip,
+            connections: Arc::default(),
+        };
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return false;
+        }
+        return self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip));
+    }
+
+    /// Checks a new connection from `ip`, it counts against the limit of its address as
+    /// long as the permit lives.
+    pub fn admit(&self, ip: IpAddr) -> Result<ConnectionPermit, Rejection> {
+        let ip = ip.to_canonical();
+        if !self.is_allowed(ip) {
+            return Err(Rejection::Denied(ip));
+        }
+
+        let mut connections = lock(&self.connections);
+        let count = connections.entry(ip).or_insert(0);
+        if self.max_connections_per_ip > 0 && *count >= self.max_connections_per_ip {
+            return Err(Rejection::TooManyConnections(
+                ip,
+                self.max_connections_per_ip,
+            ));
+        }
+        *count += 1;
+
+        return Ok(ConnectionPermit {
+            ip,
+            connections: Arc::clone(&self.connections),
+        });
+    }
+
+    /// Open connections of `ip`.
+    pub fn connections(&self, ip: IpAddr) -> usize {
+        return lock(&self.connections)
+            .get(&ip.to_canonical())
+            .copied()
+            .unwrap_or(0);
+    }
+}
+
+/// Sets the filter of the listener. Must be called before the first client connects.
+pub fn init_ip_filter(filter: IpFilter) {
+    IP_FILTER
+        .set(filter)
+        .expect("IP filter is already set. Should never happen!");
+}
+
+/// See [`IpFilter::admit`], of the filter of the listener.
+pub fn admit(ip: IpAddr) -> Result<ConnectionPermit, Rejection> {
+    return IP_FILTER.get_or_init(IpFilter::default).admit(ip);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::utils::ip_filter::{parse_cidr_list, Cidr, IpFilter, Rejection};
+
+    fn ip(value: &str) -> IpAddr {
+        return value.parse().unwrap();
+    }
+
+    #[test]
+    fn test_cidr() {
+        let cidr: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert!(cidr.contains(ip("10.1.255.3")));
+        assert!(!cidr.contains(ip("10.2.0.1")));
+        assert!(cidr.contains(ip("::ffff:10.1.0.1")));
+        assert!(!cidr.contains(ip("::1")));
+
+        assert!("0.0.0.0/0".parse::<Cidr>().unwrap().contains(ip("8.8.8.8")));
+        assert!("fd00::/8".parse::<Cidr>().unwrap().contains(ip("fd12::1")));
+        assert_eq!(
+            "127.0.0.1/32",
+            "127.0.0.1".parse::<Cidr>().unwrap().to_string()
+        );
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("localhost".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_cidr_list() {
+        assert_eq!(2, parse_cidr_list("10.0.0.0/8, ::1").unwrap().len());
+        assert_eq!(
+            Err("'10.0.0.0/x' is neither an ip address nor a CIDR range".to_string()),
+            parse_cidr_list("\"127.0.0.1 10.0.0.0/x\"")
+        );
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let filter = IpFilter::new(
+            parse_cidr_list("10.0.0.0/8").unwrap(),
+            parse_cidr_list("10.0.0.66").unwrap(),
+            0,
+        );
+        assert!(filter.is_allowed(ip("10.3.4.5")));
+        assert!(!filter.is_allowed(ip("10.0.0.66")));
+        assert!(!filter.is_allowed(ip("192.168.0.1")));
+        assert_eq!(
+            Rejection::Denied(ip("10.0.0.66")),
+            filter.admit(ip("::ffff:10.0.0.66")).unwrap_err()
+        );
+
+        assert!(IpFilter::default().is_allowed(ip("192.168.0.1")));
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let filter = IpFilter::new(vec![], vec![], 2);
+        let first = filter.admit(ip("127.0.0.1")).unwrap();
+        let _second = filter.admit(ip("127.0.0.1")).unwrap();
+        let _other = filter.admit(ip("127.0.0.2")).unwrap();
+
+        assert_eq!(
+            Rejection::TooManyConnections(ip("127.0.0.1"), 2),
+            filter.admit(ip("127.0.0.1")).unwrap_err()
+        );
+        assert_eq!(2, filter.connections(ip("127.0.0.1")));
+
+        drop(first);
+        assert_eq!(1, filter.connections(ip("127.0.0.1")));
+        assert!(filter.admit(ip("127.0.0.1")).is_ok());
+    }
+}
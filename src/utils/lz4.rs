@@ -0,0 +1,220 @@
+//! Compression in the LZ4 block format, used for the replication stream.
+//!
+//! A block is a series of sequences: a token with the amount of literals and the length of
+//! the match, the literals, and the offset of the match into the bytes already decoded. The
+//! last sequence only holds literals. The compressor is a greedy single pass over a hash
+//! table of 4 byte prefixes, fast rather than small.
+
+/// Shortest match, the match length of a token is stored without it.
+const MIN_MATCH: usize = 4;
+/// The last match starts at least this many bytes before the end of the input.
+const MFLIMIT: usize = 12;
+/// The last bytes of the input are always literals.
+const LAST_LITERALS: usize = 5;
+/// Matches are at most this far back, the offset is stored in 2 bytes.
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_LOG: u32 = 12;
+
+fn read_u32(input: &[u8], position: usize) -> u32 {
+    let bytes = input[position..position + 4]
+        .try_into()
+        .expect("The slice has 4 bytes. Should never happen!");
+    return u32::from_le_bytes(bytes);
+}
+
+fn hash(sequence: u32) -> usize {
+    return (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize;
+}
+
+/// Lengths of 15 and more continue in bytes of 255 until a smaller byte.
+fn write_length(output: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        output.push(255);
+        length -= 255;
+    }
+    output.push(length as u8);
+}
+
+fn write_literals(output: &mut Vec<u8>, token: u8, literals: &[u8]) {
+    output.push(((literals.len().min(15) as u8) << 4) | token);
+    if literals.len() >= 15 {
+        write_length(output, literals.len() - 15);
+    }
+    output.extend_from_slice(literals);
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], offset: usize, length: usize) {
+    let length = length - MIN_MATCH;
+    write_literals(output, length.min(15) as u8, literals);
+    output.extend_from_slice(&(offset as u16).to_le_bytes());
+    if length >= 15 {
+        write_length(output, length - 15);
+    }
+}
+
+/// Compresses `input` into a single block.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 16);
+    // position + 1 of the last sequence with the hash, 0 is empty
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut position = 0;
+
+    if input.len() > MFLIMIT {
+        let match_limit = input.len() - MFLIMIT;
+        let extend_limit = input.len() - LAST_LITERALS;
+
+        while position < match_limit {
+            let sequence = read_u32(input, position);
+            let slot = hash(sequence);
+            let candidate = table[slot];
+            table[slot] = position + 1;
+
+            let is_match = candidate > 0
+                && position - (candidate - 1) <= MAX_OFFSET
+                && read_u32(input, candidate - 1) == sequence;
+            if !is_match {
+                position += 1;
+                continue;
+            }
+
+            let candidate = candidate - 1;
+            let mut length = MIN_MATCH;
+            while position + length < extend_limit
+                && input[candidate + length] == input[position + length]
+            {
+                length += 1;
+            }
+
+            write_sequence(
+                &mut output,
+                &input[anchor..position],
+                position - candidate,
+                length,
+            );
+            position += length;
+            anchor = position;
+        }
+    }
+
+    write_literals(&mut output, 0, &input[anchor..]);
+    return output;
+}
+
+fn read_length(block: &[u8], position: &mut usize) -> Result<usize, String> {
+    let mut length = 0;
+    loop {
+        let byte = *block.get(*position).ok_or("block ends inside a length")?;
+        *position += 1;
+        length += byte as usize;
+        if byte != 255 {
+            return Ok(length);
+        }
+    }
+}
+
+/// Decompresses a block into the `length` bytes it was compressed from.
+pub fn decompress(block: &[u8], length: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(length);
+    let mut position = 0;
+
+    loop {
+        let token = *block.get(position).ok_or("block ends before a token")?;
+        position += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(block, &mut position)?;
+        }
+        let literals_end = position + literals;
+        if literals_end > block.len() || output.len() + literals > length {
+            return Err("literals exceed the block".to_string());
+        }
+        output.extend_from_slice(&block[position..literals_end]);
+        position = literals_end;
+
+        // the last sequence has no match
+        if position == block.len() {
+            break;
+        }
+
+        let offset = match block.get(position..position + 2) {
+            Some(offset) => u16::from_le_bytes([offset[0], offset[1]]) as usize,
+            None => return Err("block ends inside an offset".to_string()),
+        };
+        position += 2;
+        if offset == 0 || offset > output.len() {
+            return Err(format!("offset {} is out of range", offset));
+        }
+
+        let mut match_length = (token & 15) as usize + MIN_MATCH;
+        if token & 15 == 15 {
+            match_length += read_length(block, &mut position)?;
+        }
+        if output.len() + match_length > length {
+            return Err("match exceeds the block".to_string());
+        }
+        // the match may overlap the bytes it produces, e.g. a run of a single byte
+        let start = output.len() - offset;
+        for index in start..start + match_length {
+            output.push(output[index]);
+        }
+    }
+
+    if output.len() != length {
+        return Err(format!(
+            "block holds {} bytes instead of {}",
+            output.len(),
+            length
+        ));
+    }
+    return Ok(output);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::lz4::{compress, decompress};
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let block = compress(input);
+        assert_eq!(Ok(input.to_vec()), decompress(&block, input.len()));
+        return block;
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n");
+
+        let noise: Vec<u8> = (0..10_000u32)
+            .map(|index| (index.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        round_trip(&noise);
+    }
+
+    #[test]
+    fn test_repetitions_are_compressed() {
+        let command = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n";
+        let input = command.repeat(1000);
+
+        let block = round_trip(&input);
+        assert!(block.len() * 20 < input.len(), "{} bytes", block.len());
+
+        // long runs overlap the bytes they copy
+        let block = round_trip(&[b'x'; 100_000]);
+        assert!(block.len() < 1000, "{} bytes", block.len());
+    }
+
+    #[test]
+    fn test_decompress_invalid_block() {
+        assert!(decompress(&[], 0).is_err());
+        // literal length beyond the block
+        assert!(decompress(&[0x50, b'a'], 5).is_err());
+        // offset before the start
+        assert!(decompress(&[0x10, b'a', 0x02, 0x00], 5).is_err());
+        // more bytes than announced
+        assert!(decompress(&compress(b"abcdef"), 3).is_err());
+        assert!(decompress(&compress(b"abcdef"), 10).is_err());
+    }
+}
@@ -1,3 +1,15 @@
+pub mod buffer_pool;
 pub mod cli;
+pub mod crc64;
+pub mod deadline;
+pub mod diagnostics;
+pub mod failpoint;
+pub mod glob;
+pub mod ip_filter;
 pub mod logger;
+pub mod lz4;
+pub mod platform;
+pub mod random;
+pub mod shutdown;
 pub mod thread_pool;
+pub mod units;
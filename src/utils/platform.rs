@@ -0,0 +1,117 @@
+//! Metrics of the process read from the operating system, so callers don't depend on the
+//! platform. Platforms without an implementation report no metrics.
+
+use std::time::Duration;
+
+/// Time spent on the cpu, in user space and in the kernel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuTime {
+    pub user: Duration,
+    pub sys: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuUsage {
+    /// All threads of the server.
+    pub process: CpuTime,
+    /// Terminated child processes. The server does not fork, so this stays zero for now.
+    pub children: CpuTime,
+}
+
+/// The cpu time used so far, None if the platform is not supported.
+pub fn cpu_usage() -> Option<CpuUsage> {
+    return sys::cpu_usage();
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::{os::raw::c_long, time::Duration};
+
+    use crate::utils::platform::{CpuTime, CpuUsage};
+
+    const RUSAGE_SELF: i32 = 0;
+    const RUSAGE_CHILDREN: i32 = -1;
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        tv_usec: c_long,
+    }
+
+    /// `struct rusage` of `<sys/resource.h>`, only the times are read.
+    #[repr(C)]
+    struct Rusage {
+        ru_utime: Timeval,
+        ru_stime: Timeval,
+        ru_other: [c_long; 14],
+    }
+
+    extern "C" {
+        fn getrusage(who: i32, usage: *mut Rusage) -> i32;
+    }
+
+    fn to_duration(time: &Timeval) -> Duration {
+        return Duration::from_secs(time.tv_sec.max(0) as u64)
+            + Duration::from_micros(time.tv_usec.max(0) as u64);
+    }
+
+    fn cpu_time(who: i32) -> Option<CpuTime> {
+        let mut usage = Rusage {
+            ru_utime: Timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            ru_stime: Timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            ru_other: [0; 14],
+        };
+        // SAFETY: getrusage only writes into the struct, which has the layout of the C one.
+        if unsafe { getrusage(who, &mut usage) } != 0 {
+            return None;
+        }
+        return Some(CpuTime {
+            user: to_duration(&usage.ru_utime),
+            sys: to_duration(&usage.ru_stime),
+        });
+    }
+
+    pub fn cpu_usage() -> Option<CpuUsage> {
+        return Some(CpuUsage {
+            process: cpu_time(RUSAGE_SELF)?,
+            children: cpu_time(RUSAGE_CHILDREN)?,
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use crate::utils::platform::CpuUsage;
+
+    pub fn cpu_usage() -> Option<CpuUsage> {
+        return None;
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::utils::platform::cpu_usage;
+
+    #[test]
+    fn test_cpu_usage_grows() {
+        let before = cpu_usage().unwrap();
+
+        // busy enough to be measured by the scheduler
+        let started = Instant::now();
+        let mut counter: u64 = 0;
+        while started.elapsed() < Duration::from_millis(50) {
+            counter = std::hint::black_box(counter.wrapping_add(1));
+        }
+
+        let after = cpu_usage().unwrap();
+        assert!(after.process.user + after.process.sys > before.process.user + before.process.sys);
+    }
+}
@@ -0,0 +1,72 @@
+//! The randomness of the server, e.g. replication ids and the sampling of keys. A seeded
+//! source makes them deterministic, so tests of features built on them can assert exact
+//! values.
+
+use std::{ops::Range, sync::Mutex};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ID_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+#[derive(Debug)]
+pub struct RandomSource {
+    rng: Mutex<StdRng>,
+}
+
+impl RandomSource {
+    /// A source seeded with `seed`, or from the operating system if there is none.
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        return Self {
+            rng: Mutex::new(rng),
+        };
+    }
+
+    /// A random number of the range, which must not be empty.
+    pub fn range(&self, range: Range<usize>) -> usize {
+        return self
+            .rng
+            .lock()
+            .expect("Random source lock poisoned. Should never happen!")
+            .random_range(range);
+    }
+
+    /// A random id of lowercase letters and digits, like the replication id of redis.
+    pub fn id(&self, len: usize) -> String {
+        let mut rng = self
+            .rng
+            .lock()
+            .expect("Random source lock poisoned. Should never happen!");
+        return (0..len)
+            .map(|_| ID_CHARSET[rng.random_range(0..ID_CHARSET.len())] as char)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::random::RandomSource;
+
+    #[test]
+    fn test_seeded_source_is_deterministic() {
+        let first = RandomSource::new(Some(42));
+        let second = RandomSource::new(Some(42));
+
+        assert_eq!(first.id(40), second.id(40));
+        assert_eq!(first.range(0..1000), second.range(0..1000));
+        assert_ne!(first.id(40), RandomSource::new(Some(43)).id(40));
+    }
+
+    #[test]
+    fn test_id_charset() {
+        let id = RandomSource::new(None).id(40);
+
+        assert_eq!(40, id.len());
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+    }
+}
@@ -0,0 +1,139 @@
+//! Shutting the server down, on SHUTDOWN or SIGTERM.
+//!
+//! Clients are stopped from sending more commands, commands in flight get the shutdown
+//! timeout to finish and write their reply. Connections still open afterwards are closed
+//! by force and the process exits, even if a worker is wedged and never finishes its job.
+
+use std::{
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+
+use crate::{
+    commands::context::{get_clients, ConnectionKind},
+    db::data_store::get_db,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+pub fn is_shutting_down() -> bool {
+    return SHUTTING_DOWN.load(Ordering::SeqCst);
+}
+
+/// Starts shutting down on a thread of its own, the process exits once the connections
+/// are closed or `timeout` passed. Fails if the server is already shutting down.
+pub fn request(timeout: Duration) -> Result<(), String> {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        return Err("the server is already shutting down".to_string());
+    }
+
+    let spawned = thread::Builder::new()
+        .name("shutdown".to_string())
+        .spawn(move || run(timeout));
+    if let Err(err) = spawned {
+        // without the thread nothing would ever close the connections
+        error!("Unable to start shutting down, exiting now: {}", err);
+        process::exit(0);
+    }
+    return Ok(());
+}
+
+fn run(timeout: Duration) -> ! {
+    info!(
+        "Shutting down, connections get {}ms to finish their commands",
+        timeout.as_millis()
+    );
+    for client in get_clients() {
+        match client.kind() {
+            // the reply to a command in flight can still be written
+            ConnectionKind::Client => client.stop_reading(),
+            // a replication link never ends by itself
+            ConnectionKind::MasterLink | ConnectionKind::ReplicaLink => client.kill(false),
+        }
+    }
+
+    let deadline = Instant::now() + timeout;
+    let remaining = loop {
+        let remaining: Vec<_> = get_clients()
+            .into_iter()
+            .filter(|client| client.kind() == ConnectionKind::Client)
+            .collect();
+        if remaining.is_empty() || Instant::now() >= deadline {
+            break remaining;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    if !remaining.is_empty() {
+        warn!(
+            "Shutdown timeout reached, force closing {} connections",
+            remaining.len()
+        );
+        for client in remaining {
+            warn!("Abandoning {}", client.describe());
+            client.kill(false);
+        }
+    }
+
+    info!("Redis is now ready to exit, bye bye...");
+    process::exit(0);
+}
+
+/// Shuts the server down with the configured shutdown timeout when the process receives
+/// SIGTERM or SIGINT. A second signal while shutting down exits right away.
+#[cfg(unix)]
+pub fn shutdown_on_signals() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let listener = || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                error!("Unable to listen for SIGTERM: {}", err);
+                return;
+            }
+        };
+
+        runtime.block_on(async {
+            let (mut terminate, mut interrupt) = match (
+                signal(SignalKind::terminate()),
+                signal(SignalKind::interrupt()),
+            ) {
+                (Ok(terminate), Ok(interrupt)) => (terminate, interrupt),
+                (Err(err), _) | (_, Err(err)) => {
+                    error!("Unable to listen for SIGTERM: {}", err);
+                    return;
+                }
+            };
+
+            loop {
+                let name = tokio::select! {
+                    _ = terminate.recv() => "SIGTERM",
+                    _ = interrupt.recv() => "SIGINT",
+                };
+                if is_shutting_down() {
+                    warn!("Received {} while shutting down, exiting now", name);
+                    process::exit(0);
+                }
+                warn!("Received {}, scheduling shutdown", name);
+                let _ = request(get_db().get_config().shutdown_timeout);
+            }
+        });
+    };
+
+    if let Err(err) = thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(listener)
+    {
+        error!("Unable to spawn the signal thread: {}", err);
+    }
+}
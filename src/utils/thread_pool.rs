@@ -1,9 +1,13 @@
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
 };
 
-use log::trace;
+use log::{error, trace};
 
 struct Worker {
     #[allow(dead_code)]
@@ -13,7 +17,7 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>, busy: Arc<AtomicUsize>) -> Self {
         let thread_name = format!("worker-{}", id);
 
         let thread = thread::Builder::new()
@@ -23,7 +27,13 @@ impl Worker {
 
                 trace!("Worker {id} got a job; executing.");
 
-                job();
+                busy.fetch_add(1, Ordering::SeqCst);
+                // a panicking job must not take the worker with it, the pool would shrink
+                // with every panic until it accepts no connections at all
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    error!("Worker {id} recovered from a panicking job");
+                }
+                busy.fetch_sub(1, Ordering::SeqCst);
 
                 trace!("Worker {id} completed job; Giving worker back into pool.")
             })
@@ -39,6 +49,25 @@ pub struct ThreadPool {
     #[allow(dead_code)]
     workers: Vec<Worker>,
     sender: mpsc::Sender<Job>,
+    usage: PoolUsage,
+}
+
+/// How many workers of a pool are executing a job. Every connection occupies a worker for
+/// as long as it is open, so a pool without idle workers accepts no new connections.
+#[derive(Debug, Clone)]
+pub struct PoolUsage {
+    size: usize,
+    busy: Arc<AtomicUsize>,
+}
+
+impl PoolUsage {
+    pub fn size(&self) -> usize {
+        return self.size;
+    }
+
+    pub fn busy(&self) -> usize {
+        return self.busy.load(Ordering::SeqCst);
+    }
 }
 
 impl ThreadPool {
@@ -48,12 +77,22 @@ impl ThreadPool {
         let mut workers = Vec::with_capacity(size);
         let (sender, receiver) = mpsc::channel();
         let receiver = Arc::new(Mutex::new(receiver));
+        let busy = Arc::new(AtomicUsize::new(0));
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)))
+            workers.push(Worker::new(id, Arc::clone(&receiver), Arc::clone(&busy)))
+        }
+
+        let usage = PoolUsage { size, busy };
+        Self {
+            workers,
+            sender,
+            usage,
         }
+    }
 
-        Self { workers, sender }
+    pub fn usage(&self) -> PoolUsage {
+        return self.usage.clone();
     }
 
     pub fn execute<F>(&self, f: F)
@@ -64,3 +103,21 @@ impl ThreadPool {
         self.sender.send(job).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use crate::utils::thread_pool::ThreadPool;
+
+    #[test]
+    fn test_worker_survives_panicking_job() {
+        let pool = ThreadPool::new(1);
+        pool.execute(|| panic!("job failed"));
+
+        let (sender, receiver) = mpsc::channel();
+        pool.execute(move || sender.send(()).unwrap());
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+//! Typed config values, parsed the way redis reads them from CONFIG SET and the command
+//! line. The errors are the reasons redis gives, callers add the name of the parameter.
+
+/// Parses a memory size like `100mb`. Units are case insensitive, `k`, `m` and `g` are
+/// powers of 1000 and `kb`, `mb` and `gb` powers of 1024, like in redis.
+pub fn parse_memory(value: &str) -> Result<u64, String> {
+    let lower = value.to_ascii_lowercase();
+    let digits_end = lower
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(digits_end);
+
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+
+    return number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or("argument must be a memory value".to_string());
+}
+
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    return match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    };
+}
+
+pub const fn format_bool(value: bool) -> &'static str {
+    return if value { "yes" } else { "no" };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::units::{format_bool, parse_bool, parse_memory};
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(Ok(100), parse_memory("100"));
+        assert_eq!(Ok(100), parse_memory("100b"));
+        assert_eq!(Ok(2000), parse_memory("2k"));
+        assert_eq!(Ok(2048), parse_memory("2kb"));
+        assert_eq!(Ok(100 * 1024 * 1024), parse_memory("100mb"));
+        assert_eq!(Ok(2 * 1024 * 1024 * 1024), parse_memory("2GB"));
+        assert_eq!(Ok(3_000_000_000), parse_memory("3g"));
+
+        for invalid in [
+            "",
+            "mb",
+            "-1",
+            "1.5mb",
+            "10 mb",
+            "10tb",
+            "18446744073709551615gb",
+        ] {
+            assert_eq!(
+                Err("argument must be a memory value".to_string()),
+                parse_memory(invalid),
+                "'{}' is no memory value",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        assert_eq!(Ok(true), parse_bool("yes"));
+        assert_eq!(Ok(false), parse_bool("NO"));
+        assert!(parse_bool("true").is_err());
+        assert_eq!("yes", format_bool(parse_bool("Yes").unwrap()));
+    }
+}
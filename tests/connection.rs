@@ -0,0 +1,373 @@
+#![allow(clippy::needless_return)]
+
+// not every test crate uses all of the support
+#[allow(dead_code)]
+mod support;
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use redis_starter_rust::{
+    db::data_store::DataUnit,
+    parser::{db_file::RdbFile, messages::RedisMessageType},
+};
+use support::{wait_until, Server, TIMEOUT};
+
+#[test]
+fn test_query_buffer_limit_closes_connection() {
+    const LIMIT: usize = 1024 * 1024;
+    let server = Server::start(&[
+        "--client-query-buffer-limit",
+        "1mb",
+        "--proto-max-bulk-len",
+        "1mb",
+    ]);
+
+    // every element fits the limits, the whole array does not
+    let header = format!("*2\r\n${}\r\n", LIMIT);
+    let mut input = header.into_bytes();
+    input.resize(LIMIT + 1, b'a');
+
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port)).expect("Unable to connect");
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    stream.write_all(&input).expect("Unable to send the input");
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .expect("The server has to close the connection");
+    assert_eq!(
+        "-ERR Protocol error: query buffer limit of 1048576 bytes exceeded\r\n",
+        reply
+    );
+
+    let mut client = server.client();
+    assert_eq!(Some("PONG".into()), client.request_string(&["PING"]));
+}
+
+#[test]
+fn test_panicking_command_closes_connection() {
+    let server = Server::start(&[]);
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port)).expect("Unable to connect");
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+
+    stream
+        .write_all(b"*2\r\n$5\r\nDEBUG\r\n$5\r\nPANIC\r\n")
+        .expect("Unable to send the command");
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .expect("The server has to close the connection");
+    assert_eq!("-ERR internal error\r\n", reply);
+
+    let mut client = server.client();
+    assert_eq!(Some("PONG".into()), client.request_string(&["PING"]));
+}
+
+#[test]
+fn test_panic_with_locked_key_keeps_the_key_usable() {
+    let server = Server::start(&[]);
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port)).expect("Unable to connect");
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+
+    stream
+        .write_all(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nPANIC\r\n$3\r\nkey\r\n")
+        .expect("Unable to send the command");
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .expect("The server has to close the connection");
+    assert_eq!("-ERR internal error\r\n", reply);
+
+    // the lock of the key was poisoned by the panic
+    let mut client = server.client();
+    assert_eq!(
+        Some("OK".into()),
+        client.request_string(&["SET", "key", "value"])
+    );
+    assert_eq!(Some("value".into()), client.request_string(&["GET", "key"]));
+}
+
+#[test]
+fn test_client_kill_closes_connection() {
+    let server = Server::start(&[]);
+    let mut killer = server.client();
+
+    let mut victim = TcpStream::connect(("127.0.0.1", server.port)).expect("Unable to connect");
+    victim.set_read_timeout(Some(TIMEOUT)).unwrap();
+    victim.write_all(b"*1\r\n$4\r\nPING\r\n").unwrap();
+    let mut pong = [0; 7];
+    victim.read_exact(&mut pong).unwrap();
+
+    assert_eq!(
+        RedisMessageType::Integer(1),
+        killer.request(&["CLIENT", "KILL", "TYPE", "normal"])
+    );
+
+    let mut rest = String::new();
+    victim
+        .read_to_string(&mut rest)
+        .expect("The server has to close the connection");
+    assert_eq!("", rest);
+    assert_eq!(Some("PONG".into()), killer.request_string(&["PING"]));
+}
+
+#[test]
+fn test_command_time_limit_cancels_command() {
+    let server = Server::start(&["--command-time-limit", "50"]);
+    let mut client = server.client();
+
+    assert_eq!(
+        RedisMessageType::error("ERR command cancelled after exceeding the time limit of 50 ms"),
+        client.request(&["DEBUG", "SLEEP", "5"])
+    );
+    assert_eq!(Some("PONG".into()), client.request_string(&["PING"]));
+
+    client.request(&["CONFIG", "SET", "command-time-limit", "0"]);
+    assert_eq!(
+        RedisMessageType::simple_string("OK"),
+        client.request(&["DEBUG", "SLEEP", "0.1"])
+    );
+}
+
+#[test]
+fn test_server_modes_select_commands() {
+    let cluster = Server::start(&["--cluster-enabled", "yes"]);
+    let mut client = cluster.client();
+    assert_eq!(
+        RedisMessageType::Integer(12182),
+        client.request(&["CLUSTER", "KEYSLOT", "foo"])
+    );
+    assert_eq!(
+        Some("OK".into()),
+        client.request_string(&["SET", "foo", "1"])
+    );
+    assert_eq!(
+        Some("cluster".into()),
+        client.info_field("server", "redis_mode")
+    );
+
+    let sentinel = Server::start(&["--sentinel"]);
+    let mut client = sentinel.client();
+    assert_eq!(Some("PONG".into()), client.request_string(&["PING"]));
+    assert_eq!(
+        Some("sentinel".into()),
+        client.info_field("server", "redis_mode")
+    );
+    assert_eq!(
+        RedisMessageType::error("ERR unknown command 'GET', with args beginning with: 'foo' "),
+        client.request(&["GET", "foo"])
+    );
+    assert_eq!(
+        RedisMessageType::error("ERR unknown command 'CLUSTER', with args beginning with: 'INFO' "),
+        client.request(&["CLUSTER", "INFO"])
+    );
+}
+
+/// Connects and returns what the server sends without being asked within a short time,
+/// which is the reason if the connection is refused and nothing if it is accepted.
+fn connect_unprompted(port: u16) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("Unable to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    let mut received = Vec::new();
+    // a refused connection is closed, an accepted one times out
+    let _ = stream.read_to_end(&mut received);
+    return (stream, String::from_utf8(received).unwrap());
+}
+
+#[test]
+fn test_connections_per_ip_are_limited() {
+    let server = Server::start(&["--max-connections-per-ip", "1"]);
+    // the probe connections of the start may still be counted for a moment
+    let mut client = None;
+    wait_until("the probe connections to be closed", || {
+        let (stream, received) = connect_unprompted(server.port);
+        client = received.is_empty().then_some(stream);
+        return client.is_some();
+    });
+
+    assert_eq!(
+        "-ERR max number of 1 connections per ip reached by 127.0.0.1\r\n",
+        connect_unprompted(server.port).1
+    );
+
+    drop(client);
+    // the released slot is taken by the connection that noticed it
+    let mut admitted = None;
+    wait_until("the closed connection to be released", || {
+        let (stream, received) = connect_unprompted(server.port);
+        admitted = received.is_empty().then_some(stream);
+        return admitted.is_some();
+    });
+    let mut stream = admitted.unwrap();
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").unwrap();
+    let mut pong = [0; 7];
+    stream.read_exact(&mut pong).unwrap();
+    assert_eq!(b"+PONG\r\n", &pong);
+}
+
+#[test]
+fn test_denied_ips_are_refused() {
+    let server = Server::start(&["--allow-ips", "10.0.0.0/8", "--deny-ips", "10.0.0.1"]);
+    assert_eq!(
+        "-ERR connections from 127.0.0.1 are not allowed\r\n",
+        connect_unprompted(server.port).1
+    );
+}
+
+#[test]
+fn test_rdb_is_loaded_in_the_background() {
+    const KEYS: usize = 20_000;
+    let rdb_file = RdbFile::encode(
+        (0..KEYS).map(|index| DataUnit::new(format!("key:{}", index), "value".to_string(), None)),
+    );
+    let server = Server::start_with_rdb(Some(&rdb_file), &[]);
+    let mut client = server.client();
+
+    // until the file is loaded only inspecting the server works
+    let loading = RedisMessageType::error("LOADING Redis is loading the dataset in memory");
+    wait_until("the db file to be loaded", || {
+        let reply = client.request(&["GET", "key:0"]);
+        if reply == loading {
+            assert!(client
+                .info_field("persistence", "loading_loaded_perc")
+                .is_some());
+            return false;
+        }
+        assert_eq!(RedisMessageType::bulk_string("value"), reply);
+        return true;
+    });
+
+    assert_eq!(
+        Some("0".into()),
+        client.info_field("persistence", "loading")
+    );
+    assert_eq!(
+        Some(KEYS.to_string()),
+        client.info_field("persistence", "rdb_last_load_keys_loaded")
+    );
+}
+
+#[test]
+fn test_preload_applies_fixtures_before_accepting_connections() {
+    let rdb_file =
+        RdbFile::encode(["from_rdb", "fixture"].map(|key| DataUnit::new(key, "rdb", None)));
+    let mut fixtures = Vec::new();
+    for command in [
+        vec!["SET", "fixture", "preloaded"],
+        vec!["RENAME", "from_rdb", "renamed"],
+    ] {
+        fixtures.extend(RedisMessageType::bulk_string_array(command).encode());
+    }
+    let path = std::env::temp_dir().join(format!("fixtures-{}.resp", std::process::id()));
+    std::fs::write(&path, fixtures).expect("Unable to write the fixtures");
+
+    let server = Server::start_with_rdb(Some(&rdb_file), &["--preload", path.to_str().unwrap()]);
+    let mut client = server.client();
+    let _ = std::fs::remove_file(&path);
+
+    // the first command is already answered with the fixtures, never with -LOADING
+    assert_eq!(
+        RedisMessageType::bulk_string("preloaded"),
+        client.request(&["GET", "fixture"])
+    );
+    assert_eq!(
+        RedisMessageType::bulk_string("rdb"),
+        client.request(&["GET", "renamed"])
+    );
+}
+
+#[test]
+fn test_paused_writes_leave_threads_to_resume() {
+    let threads = 2;
+    let server = Server::start(&["--threads", &threads.to_string()]);
+    let mut pauser = server.client();
+    assert_eq!(
+        RedisMessageType::simple_string("OK"),
+        pauser.request(&["DEBUG", "PAUSE-WRITES", "1"])
+    );
+    drop(pauser);
+
+    // as many writers as threads, each one blocks the thread serving it while it waits
+    let writers: Vec<_> = (0..threads)
+        .map(|index| {
+            let mut writer = server.client();
+            thread::spawn(move || {
+                let reply = writer.request(&["SET", &format!("key{}", index), "value"]);
+                (writer, reply)
+            })
+        })
+        .collect();
+    for writer in writers {
+        let (writer, reply) = writer.join().unwrap();
+        assert_eq!(
+            RedisMessageType::error("ERR writes are paused by DEBUG PAUSE-WRITES, try again later"),
+            reply
+        );
+        drop(writer);
+    }
+
+    let mut client = server.client();
+    assert_eq!(
+        RedisMessageType::simple_string("OK"),
+        client.request(&["DEBUG", "PAUSE-WRITES", "0"])
+    );
+    assert_eq!(
+        RedisMessageType::simple_string("OK"),
+        client.request(&["SET", "key0", "value"])
+    );
+}
+
+#[test]
+fn test_shutdown_force_closes_stuck_connections() {
+    let mut server = Server::start(&["--shutdown-timeout", "1"]);
+    let mut idle = server.client();
+    assert_eq!(
+        RedisMessageType::simple_string("PONG"),
+        idle.request(&["PING"])
+    );
+
+    // a worker wedged in a command that takes longer than the shutdown timeout
+    let mut stuck = TcpStream::connect(("127.0.0.1", server.port)).expect("Unable to connect");
+    stuck.set_read_timeout(Some(TIMEOUT)).unwrap();
+    stuck
+        .write_all(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$2\r\n30\r\n")
+        .unwrap();
+    wait_until("the sleep to start", || {
+        idle.request_string(&["CLIENT", "LIST"])
+            .is_some_and(|clients| clients.contains(" cmd=debug "))
+    });
+
+    let mut client = server.client();
+    assert_eq!(
+        RedisMessageType::simple_string("OK"),
+        client.request(&["SHUTDOWN"])
+    );
+
+    assert!(server.wait_for_exit(TIMEOUT).success());
+    let mut reply = Vec::new();
+    assert!(stuck.read_to_end(&mut reply).is_ok());
+    assert!(reply.is_empty(), "The stuck command was answered");
+}
+
+#[test]
+fn test_sigterm_shuts_down() {
+    let mut server = Server::start(&[]);
+    let mut client = server.client();
+    assert_eq!(
+        RedisMessageType::simple_string("PONG"),
+        client.request(&["PING"])
+    );
+
+    server.signal("TERM");
+
+    // an idle client is no command in flight, so there is no need to wait for the timeout
+    assert!(server.wait_for_exit(TIMEOUT).success());
+}
@@ -0,0 +1,333 @@
+#![allow(clippy::needless_return)]
+
+// not every test crate uses all of the support
+#[allow(dead_code)]
+mod support;
+
+use redis_starter_rust::{
+    db::data_store::DataUnit,
+    parser::{db_file::RdbFile, messages::RedisMessageType},
+};
+use support::{wait_until, Client, FakeMaster, ReplicaPair, Server};
+
+#[test]
+fn test_writes_converge() {
+    let pair = ReplicaPair::start();
+    let mut master = pair.master.client();
+
+    for (key, value) in [("a", "1"), ("b", "2"), ("c", "3")] {
+        assert_eq!(
+            Some("OK".into()),
+            master.request_string(&["SET", key, value])
+        );
+    }
+    master.request(&["RENAME", "c", "d"]);
+
+    pair.assert_acknowledged(&mut master);
+    pair.assert_converged(&["a", "b", "c", "d"]);
+    assert_eq!(
+        RedisMessageType::NullBulkString,
+        pair.replica.client().request(&["GET", "c"])
+    );
+}
+
+#[test]
+fn test_flush_is_propagated() {
+    let pair = ReplicaPair::start();
+    let mut master = pair.master.client();
+    master.request(&["SET", "a", "1"]);
+    master.request(&["SET", "b", "2"]);
+    pair.assert_converged(&["a", "b"]);
+
+    assert_eq!(Some("OK".into()), master.request_string(&["FLUSHALL"]));
+    master.request(&["SET", "c", "3"]);
+    assert_eq!(
+        Some("OK".into()),
+        master.request_string(&["FLUSHDB", "ASYNC"])
+    );
+
+    pair.assert_acknowledged(&mut master);
+    pair.assert_converged(&["a", "b", "c"]);
+    assert_eq!(
+        RedisMessageType::NullBulkString,
+        pair.replica.client().request(&["GET", "a"])
+    );
+}
+
+#[test]
+fn test_evictions_are_propagated() {
+    let pair = ReplicaPair::start();
+    let mut master = pair.master.client();
+    master.request(&["SET", "a", "1"]);
+    master.request(&["SET", "b", "2"]);
+    pair.assert_converged(&["a", "b"]);
+
+    // every key has to go to fit into a single byte
+    master.request(&["CONFIG", "SET", "maxmemory-policy", "allkeys-random"]);
+    master.request(&["CONFIG", "SET", "maxmemory", "1"]);
+    assert_eq!(Some("OK".into()), master.request_string(&["SET", "c", "3"]));
+    assert_eq!(Some("2".into()), master.info_field("stats", "evicted_keys"));
+
+    pair.assert_acknowledged(&mut master);
+    let mut replica = pair.replica.client();
+    for key in ["a", "b"] {
+        assert_eq!(
+            RedisMessageType::NullBulkString,
+            replica.request(&["GET", key])
+        );
+    }
+    assert_eq!(Some("3".into()), replica.request_string(&["GET", "c"]));
+}
+
+#[test]
+fn test_replica_rejects_writes() {
+    let pair = ReplicaPair::start();
+
+    let reply = pair.replica.client().request(&["SET", "key", "value"]);
+    assert_eq!(
+        RedisMessageType::error("READONLY You can't write against a read only replica."),
+        reply
+    );
+}
+
+#[test]
+fn test_replica_reports_read_only() {
+    let pair = ReplicaPair::start();
+    let mut replica = pair.replica.client();
+    assert_eq!(
+        Some("1".into()),
+        replica.info_field("replication", "slave_read_only")
+    );
+
+    replica.request(&["CONFIG", "SET", "replica-read-only", "no"]);
+    assert_eq!(
+        Some("0".into()),
+        replica.info_field("replication", "slave_read_only")
+    );
+    assert_eq!(
+        Some("OK".into()),
+        replica.request_string(&["SET", "key", "value"])
+    );
+}
+
+#[test]
+fn test_offsets_match_after_acknowledgement() {
+    let pair = ReplicaPair::start();
+    let mut master = pair.master.client();
+    master.request(&["SET", "key", "value"]);
+    pair.assert_acknowledged(&mut master);
+
+    let offset = |client: &mut Client, field: &str| -> u64 {
+        let value = client.info_field("replication", field);
+        return value.and_then(|value| value.parse().ok()).unwrap_or(0);
+    };
+    let master_offset = offset(&mut master, "master_repl_offset");
+    assert!(master_offset > 0);
+
+    let mut replica = pair.replica.client();
+    support::wait_until("the replica offset to catch up", || {
+        offset(&mut replica, "slave_repl_offset") >= master_offset
+    });
+}
+
+#[test]
+fn test_replica_attached_after_writes_acknowledges() {
+    let master = Server::start(&[]);
+    let mut client = master.client();
+    client.request(&["SET", "a", "1"]);
+
+    let replica = Server::start(&["--replicaof", &format!("127.0.0.1 {}", master.port)]);
+    let mut replica_client = replica.client();
+    wait_until("the replica to connect to the master", || {
+        replica_client.info_field("replication", "master_link_status") == Some("up".into())
+    });
+    client.request(&["SET", "b", "2"]);
+
+    // the acks of the replica count from the offset of the master, not from 0
+    assert_eq!(
+        RedisMessageType::Integer(1),
+        client.request(&["WAIT", "1", "2000"])
+    );
+    wait_until("the replica offset to match the master", || {
+        replica_client.info_field("replication", "slave_repl_offset")
+            == client.info_field("replication", "master_repl_offset")
+    });
+}
+
+#[test]
+fn test_compressed_replication_stream() {
+    let master = Server::start(&[]);
+    let mut client = master.client();
+    client.request(&["SET", "a", "1"]);
+
+    let replica = Server::start(&[
+        "--replicaof",
+        &format!("127.0.0.1 {}", master.port),
+        "--repl-compression",
+        "yes",
+    ]);
+    let mut replica_client = replica.client();
+    wait_until("the replica to connect to the master", || {
+        replica_client.info_field("replication", "master_link_status") == Some("up".into())
+    });
+
+    let value = "x".repeat(10_000);
+    for key in ["b", "c", "d"] {
+        client.request(&["SET", key, &value]);
+    }
+    client.request(&["DEL", "a"]);
+
+    // offsets count the uncompressed bytes on both sides
+    assert_eq!(
+        RedisMessageType::Integer(1),
+        client.request(&["WAIT", "1", "2000"])
+    );
+    wait_until("the replica offset to match the master", || {
+        replica_client.info_field("replication", "slave_repl_offset")
+            == client.info_field("replication", "master_repl_offset")
+    });
+    assert_eq!(
+        RedisMessageType::NullBulkString,
+        replica_client.request(&["GET", "a"])
+    );
+    for key in ["b", "c", "d"] {
+        assert_eq!(
+            Some(value.clone()),
+            replica_client.request_string(&["GET", key])
+        );
+    }
+}
+
+#[test]
+fn test_replica_takes_over_the_dataset_history() {
+    let pair = ReplicaPair::start();
+    let mut master = pair.master.client();
+    for key in ["a", "b", "c"] {
+        master.request(&["SET", key, "1"]);
+    }
+    pair.assert_acknowledged(&mut master);
+
+    let mut replica = pair.replica.client();
+    // the full resync is a save of the master, the replica continues its write history
+    assert_eq!(
+        Some("1".into()),
+        master.info_field("persistence", "rdb_saves")
+    );
+    for field in ["total_writes", "dataset_created_time"] {
+        assert_eq!(
+            master.info_field("persistence", field),
+            replica.info_field("persistence", field),
+            "{} differs",
+            field
+        );
+    }
+}
+
+#[test]
+fn test_replica_authenticates_at_the_master() {
+    let master = FakeMaster::bind();
+    let replica = Server::start(&[
+        "--replicaof",
+        &format!("127.0.0.1 {}", master.port),
+        "--masterauth",
+        "secret",
+    ]);
+
+    // a rejected password fails the handshake, the replica tries again
+    let mut link = master.accept();
+    assert_eq!(vec!["PING"], link.receive());
+    link.send_raw(b"-NOAUTH Authentication required.\r\n");
+    assert_eq!(vec!["AUTH", "secret"], link.receive());
+    link.send_raw(b"-WRONGPASS invalid username-password pair or user is disabled.\r\n");
+
+    let mut link = master.accept();
+    assert_eq!(vec!["PING"], link.receive());
+    link.send_raw(b"-NOAUTH Authentication required.\r\n");
+    assert_eq!(vec!["AUTH", "secret"], link.receive());
+    link.send_raw(b"+OK\r\n");
+    let listening_port = replica.port.to_string();
+    assert_eq!(
+        vec!["REPLCONF", "listening-port", listening_port.as_str()],
+        link.receive()
+    );
+    link.send_raw(b"+OK\r\n");
+    assert_eq!(vec!["REPLCONF", "capa", "psync2"], link.receive());
+    link.send_raw(b"+OK\r\n");
+    assert_eq!(vec!["PSYNC", "?", "-1"], link.receive());
+
+    let rdb_file = RdbFile::encode(vec![DataUnit::new("key", "value", None)]);
+    let repl_id = "8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb";
+    link.send_raw(format!("+FULLRESYNC {} 0\r\n${}\r\n", repl_id, rdb_file.len()).as_bytes());
+    link.send_raw(&rdb_file);
+
+    let mut client = replica.client();
+    wait_until("the replica to load the rdb file", || {
+        client.info_field("replication", "master_link_status") == Some("up".into())
+    });
+    assert_eq!(Some("value".into()), client.request_string(&["GET", "key"]));
+}
+
+#[test]
+fn test_writes_during_full_resync_reach_the_replica() {
+    let master = Server::start(&[]);
+    let mut client = master.client();
+    client.request(&["SET", "a", "1"]);
+    client.request(&["DEBUG", "SLEEP-AFTER-FORK-SECONDS", "0.5"]);
+
+    let replica = Server::start(&["--replicaof", &format!("127.0.0.1 {}", master.port)]);
+    wait_until("the snapshot to be in progress", || {
+        client.info_field("persistence", "rdb_bgsave_in_progress") == Some("1".into())
+    });
+    // waits for the snapshot, then it is buffered until the snapshot is transferred
+    assert_eq!(Some("OK".into()), client.request_string(&["SET", "b", "2"]));
+    client.request(&["DEBUG", "SLEEP-AFTER-FORK-SECONDS", "0"]);
+
+    let mut replica_client = replica.client();
+    for (key, value) in [("a", "1"), ("b", "2")] {
+        wait_until(&format!("key '{}' to reach the replica", key), || {
+            replica_client.request_string(&["GET", key]) == Some(value.into())
+        });
+    }
+
+    // counting writes is not idempotent, a write both in the snapshot and in the buffer
+    // would be counted twice by the replica
+    assert_eq!(
+        RedisMessageType::Integer(1),
+        client.request(&["WAIT", "1", "2000"])
+    );
+    assert_eq!(
+        client.info_field("persistence", "total_writes"),
+        replica_client.info_field("persistence", "total_writes")
+    );
+}
+
+#[test]
+fn test_failed_snapshot_is_retried_by_the_replica() {
+    let master = Server::start(&[]);
+    let mut client = master.client();
+    client.request(&["SET", "a", "1"]);
+    assert_eq!(
+        Some("OK".into()),
+        client.request_string(&["DEBUG", "SLEEP-AFTER-FORK-SECONDS", "0.5"])
+    );
+    client.request(&["DEBUG", "FAIL-SAVES", "1"]);
+
+    let replica = Server::start(&["--replicaof", &format!("127.0.0.1 {}", master.port)]);
+    wait_until("the snapshot to be in progress", || {
+        client.info_field("persistence", "rdb_bgsave_in_progress") == Some("1".into())
+    });
+    wait_until("the snapshot to fail", || {
+        client.info_field("persistence", "rdb_last_bgsave_status") == Some("err".into())
+    });
+
+    client.request(&["DEBUG", "SLEEP-AFTER-FORK-SECONDS", "0"]);
+    client.request(&["DEBUG", "FAIL-SAVES", "0"]);
+    let mut replica_client = replica.client();
+    wait_until("the replica to retry the full resync", || {
+        replica_client.request_string(&["GET", "a"]) == Some("1".into())
+    });
+    assert_eq!(
+        Some("ok".into()),
+        client.info_field("persistence", "rdb_last_bgsave_status")
+    );
+}
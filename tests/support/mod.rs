@@ -0,0 +1,299 @@
+//! Support for the integration tests.
+//!
+//! The data store and the config of a server are global, so only one server fits into a
+//! process. The tests therefore run the server binary as child processes and talk to them
+//! over TCP, using the RESP types of the library.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use redis_starter_rust::parser::messages::RedisMessageType;
+
+/// How long to wait for servers to start, connect or converge before failing the test.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Every connection, including the one of a replica, occupies a thread of the server, so
+/// tests holding several clients need more than the default.
+const SERVER_THREADS: &str = "16";
+
+/// Polls `condition` until it holds, panics naming `what` once [`TIMEOUT`] passed.
+pub fn wait_until<F: FnMut() -> bool>(what: &str, mut condition: F) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !condition() {
+        if Instant::now() >= deadline {
+            panic!("Timed out waiting for {}", what);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// A port nothing listens on right now. Another process may take it before the server
+/// binds it, which is unlikely enough for tests.
+fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Unable to bind a free port");
+    return listener
+        .local_addr()
+        .expect("Bound listener has an address. Should never happen!")
+        .port();
+}
+
+/// A server running as child process, killed when dropped.
+pub struct Server {
+    pub port: u16,
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Server {
+    /// Starts the server on a free port with an empty data directory and waits until it
+    /// accepts connections.
+    pub fn start(extra_args: &[&str]) -> Self {
+        return Self::start_with_rdb(None, extra_args);
+    }
+
+    /// Like [`Server::start`], with `rdb_file` as db file of the data directory.
+    pub fn start_with_rdb(rdb_file: Option<&[u8]>, extra_args: &[&str]) -> Self {
+        let port = free_port();
+        let dir = std::env::temp_dir().join(format!("redis-it-{}-{}", std::process::id(), port));
+        fs::create_dir_all(&dir).expect("Unable to create the data directory of the server");
+        if let Some(rdb_file) = rdb_file {
+            fs::write(dir.join("redis.rdb"), rdb_file).expect("Unable to write the db file");
+        }
+
+        let child = Command::new(env!("CARGO_BIN_EXE_redis-starter-rust"))
+            .args(["--port", &port.to_string()])
+            .args(["--dir", dir.to_str().expect("Temp dir is valid utf-8")])
+            .args(["--threads", SERVER_THREADS])
+            .args(extra_args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Unable to start the server binary");
+
+        let server = Self { port, child, dir };
+        wait_until("the server to accept connections", || {
+            TcpStream::connect(("127.0.0.1", port)).is_ok()
+        });
+        return server;
+    }
+
+    pub fn client(&self) -> Client {
+        return Client::connect(self.port);
+    }
+
+    /// Sends a signal like `TERM` to the server process.
+    pub fn signal(&self, signal: &str) {
+        let status = Command::new("kill")
+            .args([&format!("-{}", signal), &self.child.id().to_string()])
+            .status()
+            .expect("Unable to run kill");
+        assert!(
+            status.success(),
+            "Unable to send SIG{} to the server",
+            signal
+        );
+    }
+
+    /// Waits until the server process exited, panics if it still runs after `timeout`.
+    pub fn wait_for_exit(&mut self, timeout: Duration) -> ExitStatus {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self
+                .child
+                .try_wait()
+                .expect("Unable to wait for the server")
+            {
+                return status;
+            }
+            if Instant::now() >= deadline {
+                panic!("Server did not exit within {:?}", timeout);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A blocking connection sending one command at a time.
+pub struct Client {
+    stream: TcpStream,
+    buffer: String,
+}
+
+impl Client {
+    pub fn connect(port: u16) -> Self {
+        let stream = TcpStream::connect(("127.0.0.1", port)).expect("Unable to connect");
+        return Self::from_stream(stream);
+    }
+
+    fn from_stream(stream: TcpStream) -> Self {
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .expect("Timeout is not zero. Should never happen!");
+        return Self {
+            stream,
+            buffer: String::new(),
+        };
+    }
+
+    /// Sends the command and returns its reply.
+    pub fn request(&mut self, args: &[&str]) -> RedisMessageType {
+        let command = RedisMessageType::Array(
+            args.iter()
+                .map(|arg| RedisMessageType::bulk_string(*arg))
+                .collect::<VecDeque<_>>(),
+        );
+        self.stream
+            .write_all(&command.encode())
+            .expect("Unable to send the command");
+        return self.read_message();
+    }
+
+    fn read_message(&mut self) -> RedisMessageType {
+        let mut chunk = [0; 4096];
+        loop {
+            if let Ok((reply, length)) = RedisMessageType::decode(&self.buffer) {
+                self.buffer.drain(..length);
+                return reply;
+            }
+
+            let read = self
+                .stream
+                .read(&mut chunk)
+                .expect("No message from the peer");
+            assert!(read > 0, "Peer closed the connection");
+            self.buffer
+                .push_str(std::str::from_utf8(&chunk[..read]).expect("Reply is utf-8"));
+        }
+    }
+
+    /// Reads the next command of the peer, for the master side of a replication link.
+    pub fn receive(&mut self) -> Vec<String> {
+        let RedisMessageType::Array(args) = self.read_message() else {
+            panic!("Expected a command of the peer");
+        };
+        return args
+            .iter()
+            .map(|arg| arg.bulk_string_value().expect("Arguments are bulk strings"))
+            .collect();
+    }
+
+    /// Sends raw bytes, e.g. replies or an rdb file that is no RESP message.
+    pub fn send_raw(&mut self, data: &[u8]) {
+        self.stream
+            .write_all(data)
+            .expect("Unable to send the data");
+    }
+
+    /// Like [`Client::request`] for commands replying with a string.
+    pub fn request_string(&mut self, args: &[&str]) -> Option<String> {
+        return self.request(args).as_string();
+    }
+
+    /// A field of INFO, e.g. `master_repl_offset` of the `replication` section.
+    pub fn info_field(&mut self, section: &str, field: &str) -> Option<String> {
+        let info = self.request_string(&["INFO", section])?;
+        return info.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            return (name == field).then(|| value.to_string());
+        });
+    }
+}
+
+/// A master with one replica connected to it.
+pub struct ReplicaPair {
+    pub master: Server,
+    pub replica: Server,
+}
+
+impl ReplicaPair {
+    /// Starts both servers and waits until the replica finished the handshake.
+    pub fn start() -> Self {
+        let master = Server::start(&[]);
+        let replica = Server::start(&["--replicaof", &format!("127.0.0.1 {}", master.port)]);
+
+        let pair = Self { master, replica };
+        let mut replica = pair.replica.client();
+        wait_until("the replica to connect to the master", || {
+            replica.info_field("replication", "master_link_status") == Some("up".into())
+        });
+        return pair;
+    }
+
+    /// Waits until the replica has the same values for all keys as the master.
+    pub fn assert_converged(&self, keys: &[&str]) {
+        let mut master = self.master.client();
+        let mut replica = self.replica.client();
+
+        for key in keys {
+            let expected = master.request(&["GET", key]);
+            wait_until(&format!("key '{}' to converge", key), || {
+                replica.request(&["GET", key]) == expected
+            });
+        }
+    }
+
+    /// Waits until the replica acknowledged the writes sent by `writer`, a client of the
+    /// master.
+    pub fn assert_acknowledged(&self, writer: &mut Client) {
+        let reply = writer.request(&["WAIT", "1", &TIMEOUT.as_millis().to_string()]);
+        assert_eq!(
+            RedisMessageType::Integer(1),
+            reply,
+            "The replica did not acknowledge the writes"
+        );
+    }
+}
+
+/// The master side of a replication link, driven by the test to check the handshake of a
+/// replica step by step.
+pub struct FakeMaster {
+    pub port: u16,
+    listener: TcpListener,
+}
+
+impl FakeMaster {
+    pub fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Unable to bind a free port");
+        let port = listener
+            .local_addr()
+            .expect("Bound listener has an address. Should never happen!")
+            .port();
+        listener
+            .set_nonblocking(true)
+            .expect("Unable to make the listener non blocking");
+        return Self { port, listener };
+    }
+
+    /// Waits for the next connection of the replica.
+    pub fn accept(&self) -> Client {
+        let mut stream = None;
+        wait_until("the replica to connect", || {
+            stream = self.listener.accept().ok().map(|(stream, _)| stream);
+            return stream.is_some();
+        });
+
+        let stream = stream.expect("Accepted above. Should never happen!");
+        stream
+            .set_nonblocking(false)
+            .expect("Unable to make the link blocking");
+        return Client::from_stream(stream);
+    }
+}